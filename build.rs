@@ -0,0 +1,57 @@
+//! Build metadata embedded in the binary and shown by `cosmo version`.
+
+use std::{
+    path::Path,
+    process::Command,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+fn main() {
+    let git_sha = Command::new("git")
+        .args(["rev-parse", "--short=12", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|sha| sha.trim().to_string())
+        .unwrap_or_default();
+    println!("cargo:rustc-env=COSMO_GIT_SHA={git_sha}");
+
+    // Reproducible builds set the date themselves
+    let timestamp = std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.parse().ok())
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_secs())
+        });
+    println!("cargo:rustc-env=COSMO_BUILD_DATE={}", date(timestamp));
+
+    let target = std::env::var("TARGET").unwrap_or_default();
+    println!("cargo:rustc-env=COSMO_TARGET={target}");
+
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+    // A missing path would rerun the script at each build
+    for path in [".git/HEAD", ".git/refs"] {
+        if Path::new(path).exists() {
+            println!("cargo:rerun-if-changed={path}");
+        }
+    }
+}
+
+/// `YYYY-MM-DD` of the unix `timestamp`, in UTC.
+fn date(timestamp: u64) -> String {
+    // Days to civil date, from http://howardhinnant.github.io/date_algorithms.html
+    let days = (timestamp / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{year:04}-{month:02}-{day:02}")
+}
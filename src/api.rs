@@ -1,39 +1,350 @@
 use async_trait::async_trait;
+use futures::stream::BoxStream;
+use reqwest::header::HeaderMap;
 use semver::Version;
-use serde::Deserialize;
-use std::{fmt::Display, path::Path};
+use serde::{Deserialize, Serialize};
+use std::{fmt::Display, net::SocketAddr, path::Path, time::Duration};
+use url::Url;
 use uuid::Uuid;
 
 use crate::{
-    cli::Analysis,
+    cli::{Analysis, ProjectFilter},
     services::{
         apikey_service::ApiKeyData,
         organization_service::OrganizationData,
-        project_service::{Project, ProjectAnalysis},
+        project_service::{
+            Project, ProjectAnalysis, ProjectCreated, ProjectStatus, RescanJob, UploadValidationDTO,
+        },
+        usage_service::Usage,
+        webhook_service::{WebhookCreateDTO, WebhookData},
     },
 };
 
+mod cache;
+mod download;
+mod events;
 mod http_server;
+mod offline_server;
+mod registry;
+mod remote;
+mod unix_socket;
+mod upload;
+mod user_agent;
 
-pub use http_server::HttpApiServer;
+pub use cache::{CacheKey, CachedResponse, ResponseCache, Validators};
+pub use download::PlainClient;
+pub use http_server::{HttpApiServer, HttpApiServerOptions, SUPPORTED_API_VERSIONS};
+pub use offline_server::OfflineApiServer;
+pub use registry::{
+    docker_credentials, pull as pull_image, ImageReference, Platform, PulledImage,
+    RegistryCredentials,
+};
+pub use upload::file_sha256;
+pub(crate) use upload::hex;
+
+/// Statuses of a project pushed by the server as they change.
+pub type StatusEvents = BoxStream<'static, Result<ProjectStatus, ApiServerError>>;
 
 #[derive(Debug, Deserialize)]
 pub struct LatestCliVersion {
     pub version: Version,
+    #[serde(default)]
     pub changelog: String,
+    /// Binaries of the release, one per platform
+    #[serde(default)]
+    pub artifacts: Vec<ReleaseArtifact>,
+}
+
+/// Binary of a release for a platform, downloaded by `self-update`.
+#[derive(Debug, Deserialize)]
+pub struct ReleaseArtifact {
+    /// OS and architecture, e.g. `linux-x86_64` or `windows-x86_64`
+    pub platform: String,
+    pub url: Url,
+    /// Hex encoded SHA-256 of the binary
+    pub sha256: String,
+    /// Base64 Ed25519 signature of the binary
+    pub signature: Option<String>,
+}
+
+/// Versions reported by the api server.
+#[derive(Debug, Serialize)]
+pub struct ServerVersion {
+    /// Version of the server, when it tells it
+    pub version: Option<String>,
+    /// Versions of the api offered by the server
+    pub api_versions: Vec<u32>,
+    /// Versions of the api deprecated by the server
+    pub deprecated_api_versions: Vec<u32>,
+}
+
+/// Outcome of the checks of `ping`, with latencies in milliseconds.
+#[derive(Debug, Serialize)]
+pub struct PingReport {
+    /// Address of the api server
+    pub address: String,
+    /// Addresses the server resolves to, empty when going through a proxy
+    pub resolved: Vec<SocketAddr>,
+    /// Time to resolve the server, unless going through a proxy
+    pub dns_ms: Option<u128>,
+    /// Time to open a TCP connection, unless going through a proxy
+    pub tcp_ms: Option<u128>,
+    /// Round trip of an unauthenticated request
+    pub reachability_ms: u128,
+    /// Round trip of an authenticated request
+    pub authentication_ms: u128,
+}
+
+/// Options of a firmware upload.
+#[derive(Debug, Clone, Default)]
+pub struct UploadOptions {
+    /// Use a resumable upload session, continuing a previous one if any
+    pub resume: bool,
+    /// Gzip the firmware on the fly, unless it is already compressed
+    pub compress: bool,
+    /// Name of the uploaded file, by default the one of the firmware
+    pub filename: Option<String>,
+    /// Maximum size of a firmware read from stdin, whose size isn't known
+    /// before reading it
+    pub max_size: Option<u64>,
+    /// Hide the progress bar, e.g. of uploads running concurrently
+    pub hide_progress: bool,
+}
+
+/// Firmware at a remote url, the source of `create --from-url`.
+#[derive(Debug, Clone)]
+pub struct RemoteFirmware {
+    pub url: Url,
+    /// Headers of the requests of the firmware, e.g. its credentials
+    pub headers: HeaderMap,
+    /// Stream the firmware through the cli, instead of letting the server
+    /// fetch it with the headers
+    pub stream: bool,
+}
+
+/// Error response of the api.
+#[derive(Debug, Clone)]
+pub struct ApiErrorResponse {
+    /// Method of the failed request
+    pub method: String,
+    /// Path of the failed request
+    pub path: String,
+    /// Http status code
+    pub status: u16,
+    /// Machine readable error code, if sent by the server
+    pub code: Option<String>,
+    /// Error message, the raw body when not in the JSON error envelope
+    pub message: String,
+    /// Additional details, if sent by the server
+    pub details: Option<serde_json::Value>,
+    /// Raw body of the response
+    pub body: String,
+    /// Id of the request, the one of the server when it sent it
+    pub request_id: Option<String>,
+}
+
+impl ApiErrorResponse {
+    /// Parse the error envelope of the response to `method` `path`, either
+    /// `{"code": .., "message": .., "details": ..}` or the same nested in
+    /// an `error` object.
+    pub fn parse(method: &str, path: &str, status: u16, body: String) -> Self {
+        let json = serde_json::from_str::<serde_json::Value>(&body).unwrap_or_default();
+        let envelope = match json.get("error") {
+            Some(error) if error.is_object() => error,
+            _ => &json,
+        };
+
+        let code = match envelope.get("code") {
+            Some(serde_json::Value::String(code)) => Some(code.clone()),
+            Some(serde_json::Value::Number(code)) => Some(code.to_string()),
+            _ => None,
+        };
+        let message = ["message", "detail", "error"]
+            .iter()
+            .find_map(|key| envelope.get(key).and_then(|m| m.as_str()))
+            .map(str::to_string)
+            .unwrap_or_else(|| body.trim().to_string());
+        let details = envelope.get("details").cloned();
+
+        Self {
+            method: method.to_string(),
+            path: path.to_string(),
+            status,
+            code,
+            message,
+            details,
+            body,
+            request_id: None,
+        }
+    }
+
+    /// Whether the request was refused because a quota of the account is
+    /// reached, told by its error code, e.g. `QUOTA_EXCEEDED`.
+    pub fn is_quota_exceeded(&self) -> bool {
+        self.code
+            .as_deref()
+            .is_some_and(|code| code.to_lowercase().contains("quota"))
+    }
+}
+
+impl Display for ApiErrorResponse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} ({} {}, status {}",
+            self.message, self.method, self.path, self.status
+        )?;
+        if let Some(code) = &self.code {
+            write!(f, ", code {}", code)?;
+        }
+        if let Some(request_id) = &self.request_id {
+            write!(f, ", request id {}", request_id)?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// Why the api rejected a request, with the remedy shown to the user.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthError {
+    /// The api key is past its expiry date
+    ExpiredToken,
+    /// The api key is unknown or was revoked
+    InvalidCredentials,
+    /// The api key is valid but has no access to the resource
+    PermissionDenied,
+    /// The account of the api key is locked or disabled
+    AccountLocked,
+}
+
+impl AuthError {
+    /// Kind of the rejection `response`, from its status and the code and
+    /// message of its error payload.
+    fn from_response(response: &ApiErrorResponse) -> Self {
+        let text = format!(
+            "{} {}",
+            response.code.as_deref().unwrap_or_default(),
+            response.message
+        )
+        .to_lowercase();
+        let mentions = |words: &[&str]| words.iter().any(|word| text.contains(word));
+
+        if response.status == 423 || mentions(&["locked", "disabled", "suspended"]) {
+            Self::AccountLocked
+        } else if mentions(&["expired"]) {
+            Self::ExpiredToken
+        } else if response.status == 403 {
+            Self::PermissionDenied
+        } else {
+            Self::InvalidCredentials
+        }
+    }
+}
+
+impl Display for AuthError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ExpiredToken => write!(
+                f,
+                "Api key expired, create a new one from the web application and save it with `cosmo setup`"
+            ),
+            Self::InvalidCredentials => write!(
+                f,
+                "Unauthorized, check your api key or save a new one with `cosmo setup`"
+            ),
+            Self::PermissionDenied => write!(
+                f,
+                "Forbidden, ask an administrator of the organization for access"
+            ),
+            Self::AccountLocked => write!(
+                f,
+                "Account locked, contact an administrator of the organization"
+            ),
+        }
+    }
 }
 
 #[derive(Debug)]
 pub enum ApiServerError {
     HttpRequestError(reqwest::Error),
+    Timeout(reqwest::Error),
+    RetriesExhausted {
+        attempts: u32,
+        error: Box<ApiServerError>,
+    },
     RequestError(String),
     ResponseError(String),
-    ApiError(String),
+    ResponseTooLarge {
+        limit: u64,
+    },
+    ApiError(Box<ApiErrorResponse>),
+    NotFound(Box<ApiErrorResponse>),
+    Auth {
+        kind: AuthError,
+        response: Box<ApiErrorResponse>,
+    },
+    Conflict(Box<ApiErrorResponse>),
+    RateLimited(Box<ApiErrorResponse>),
+    ClientIdentityError(String),
+    NotCached(String),
+    DnsError(String),
+    ConnectError(String),
+    TlsError(String),
+    UnsupportedApiVersion {
+        server: Vec<u32>,
+        cli: Vec<u32>,
+    },
+    IntegrityMismatch {
+        expected: String,
+        actual: String,
+    },
+    DownloadIntegrityMismatch {
+        expected: String,
+        actual: String,
+    },
+}
+
+impl ApiServerError {
+    /// Error for an error response of the api, by status code.
+    pub fn from_response(response: ApiErrorResponse) -> Self {
+        let status = response.status;
+        // Quotas are often refused with 403, not a matter of credentials
+        if matches!(status, 401 | 403 | 423) && !response.is_quota_exceeded() {
+            return Self::Auth {
+                kind: AuthError::from_response(&response),
+                response: Box::new(response),
+            };
+        }
+        let response = Box::new(response);
+        match status {
+            404 => Self::NotFound(response),
+            409 => Self::Conflict(response),
+            429 => Self::RateLimited(response),
+            _ => Self::ApiError(response),
+        }
+    }
+
+    /// Error response of the api that caused the error, if any.
+    pub fn response(&self) -> Option<&ApiErrorResponse> {
+        match self {
+            Self::ApiError(response)
+            | Self::NotFound(response)
+            | Self::Auth { response, .. }
+            | Self::Conflict(response)
+            | Self::RateLimited(response) => Some(response.as_ref()),
+            Self::RetriesExhausted { error, .. } => error.response(),
+            _ => None,
+        }
+    }
 }
 
 impl From<reqwest::Error> for ApiServerError {
     fn from(err: reqwest::Error) -> Self {
-        Self::HttpRequestError(err)
+        if err.is_timeout() {
+            Self::Timeout(err)
+        } else {
+            Self::HttpRequestError(err)
+        }
     }
 }
 
@@ -41,11 +352,64 @@ impl Display for ApiServerError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::ApiError(response) => write!(f, "Error from server: {}", response),
+            Self::NotFound(response) => write!(f, "Not found: {}", response),
+            Self::Auth { kind, response } => write!(f, "{}: {}", kind, response),
+            Self::Conflict(response) => write!(f, "Conflict: {}", response),
+            Self::RateLimited(response) => write!(f, "Rate limited: {}", response),
             Self::HttpRequestError(err) => {
                 write!(f, "Error with http request. Reason: {}", err)
             }
+            Self::Timeout(err) => write!(f, "Request timed out. Reason: {}", err),
+            Self::RetriesExhausted { attempts, error } => {
+                write!(f, "{} (after {} attempts)", error, attempts)
+            }
             Self::RequestError(err) => write!(f, "Error with the request: {}", err),
             Self::ResponseError(err) => write!(f, "Error with the response: {}", err),
+            Self::ResponseTooLarge { limit } => write!(
+                f,
+                "Response larger than {} bytes, request less results per page or raise --max-response-size",
+                limit
+            ),
+            Self::ClientIdentityError(err) => write!(f, "Invalid client certificate: {}", err),
+            Self::DnsError(err) => write!(f, "Unable to resolve the server: {}", err),
+            Self::ConnectError(err) => write!(f, "Unable to connect to the server: {}", err),
+            Self::TlsError(err) => write!(f, "TLS handshake failed: {}", err),
+            Self::UnsupportedApiVersion { server, cli } => {
+                let versions = |versions: &[u32]| {
+                    versions
+                        .iter()
+                        .map(|version| format!("v{}", version))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                };
+                let hint = if server.iter().max() > cli.iter().max() {
+                    "update the cli"
+                } else {
+                    "use an older cli or update the server"
+                };
+                write!(
+                    f,
+                    "The server supports api {} while this cli supports api {}: {}",
+                    versions(server),
+                    versions(cli),
+                    hint
+                )
+            }
+            Self::NotCached(call) => write!(
+                f,
+                "No cached response for {}, run the command once without --offline",
+                call
+            ),
+            Self::IntegrityMismatch { expected, actual } => write!(
+                f,
+                "Firmware corrupted during upload: local SHA-256 {}, server SHA-256 {}",
+                expected, actual
+            ),
+            Self::DownloadIntegrityMismatch { expected, actual } => write!(
+                f,
+                "Firmware corrupted during download: server SHA-256 {}, downloaded SHA-256 {}",
+                expected, actual
+            ),
         }
     }
 }
@@ -54,35 +418,111 @@ impl std::error::Error for ApiServerError {}
 #[async_trait]
 pub trait ApiServer {
     fn address(&self) -> &str;
+    async fn ping(&self) -> Result<PingReport, ApiServerError>;
+    /// Versions of the server and of its api, without the api key.
+    async fn server_version(&self) -> Result<ServerVersion, ApiServerError>;
     async fn updates_check(&self) -> Result<LatestCliVersion, ApiServerError>;
+    #[allow(clippy::too_many_arguments)]
     async fn create(
-        &mut self,
+        &self,
         fw_filepath: &str,
         fw_type: &str,
         fw_subtype: &str,
         name: &str,
         description: Option<&str>,
         organization: Option<&str>,
-    ) -> Result<Uuid, ApiServerError>;
+        tags: &[String],
+        options: &UploadOptions,
+    ) -> Result<ProjectCreated, ApiServerError>;
+    /// Create a project from the firmware at a remote url, fetched by the
+    /// server when it can and else streamed through the cli.
+    #[allow(clippy::too_many_arguments)]
+    async fn create_from_url(
+        &self,
+        firmware: &RemoteFirmware,
+        fw_type: &str,
+        fw_subtype: &str,
+        name: &str,
+        description: Option<&str>,
+        organization: Option<&str>,
+        tags: &[String],
+        options: &UploadOptions,
+    ) -> Result<ProjectCreated, ApiServerError>;
     async fn overview(&mut self, project_id: &Uuid) -> Result<serde_json::Value, ApiServerError>;
+    async fn status(&mut self, project_id: &Uuid) -> Result<ProjectStatus, ApiServerError>;
+    /// Statuses of a project as they change, pushed by the server for up to
+    /// `timeout` with server-sent events, `None` when it doesn't offer them.
+    async fn status_events(
+        &self,
+        project_id: &Uuid,
+        timeout: Duration,
+    ) -> Result<Option<StatusEvents>, ApiServerError>;
     async fn analysis(
-        &mut self,
+        &self,
         project_id: &Uuid,
         analysis: &Analysis,
         page: i32,
         per_page: i32,
     ) -> Result<ProjectAnalysis, ApiServerError>;
-    async fn delete(&mut self, project_id: &Uuid) -> Result<(), ApiServerError>;
-    async fn report(&mut self, project_id: &Uuid, savepath: &Path) -> Result<(), ApiServerError>;
+    async fn delete(&self, project_id: &Uuid) -> Result<(), ApiServerError>;
+    async fn update(
+        &self,
+        project_id: &Uuid,
+        name: Option<&str>,
+        description: Option<&str>,
+    ) -> Result<(), ApiServerError>;
+    async fn rescan(&self, project_id: &Uuid) -> Result<RescanJob, ApiServerError>;
+    async fn tag(
+        &self,
+        project_id: &Uuid,
+        add: &[String],
+        remove: &[String],
+    ) -> Result<Vec<String>, ApiServerError>;
+    /// Stream the PDF report of a project to `output_path`, returning its size.
+    async fn report_pdf(
+        &mut self,
+        project_id: &Uuid,
+        output_path: &Path,
+    ) -> Result<u64, ApiServerError>;
+    async fn fetch_firmware(
+        &mut self,
+        project_id: &Uuid,
+        output_path: &Path,
+    ) -> Result<u64, ApiServerError>;
+    /// Ask the server whether it would accept the upload of a firmware,
+    /// `false` when it doesn't validate uploads.
+    async fn validate_upload(
+        &self,
+        validation: &UploadValidationDTO<'_>,
+    ) -> Result<bool, ApiServerError>;
     async fn list_projects(&mut self) -> Result<Vec<Project>, ApiServerError>;
+    async fn list_projects_paged(
+        &mut self,
+        page: u32,
+        per_page: u32,
+        filter: &ProjectFilter,
+    ) -> Result<Vec<Project>, ApiServerError>;
     async fn organization_create(
         &mut self,
         name: &str,
         description: &str,
     ) -> Result<(), ApiServerError>;
-    async fn organization_list(&mut self) -> Result<Vec<OrganizationData>, ApiServerError>;
+    async fn organization_list(&self) -> Result<Vec<OrganizationData>, ApiServerError>;
     async fn organization_delete(&mut self, id: &Uuid) -> Result<(), ApiServerError>;
     async fn apikey_create(&mut self) -> Result<ApiKeyData, ApiServerError>;
-    async fn apikey_list(&mut self) -> Result<Option<ApiKeyData>, ApiServerError>;
+    async fn apikey_list(&self) -> Result<Vec<ApiKeyData>, ApiServerError>;
     async fn apikey_delete(&mut self) -> Result<(), ApiServerError>;
+    /// Replace the api key in use with a new one, used by the following
+    /// requests. The previous key is deleted by its id once the new one
+    /// works, and kept when the server allows a single key per account.
+    async fn apikey_rotate(&mut self) -> Result<ApiKeyData, ApiServerError>;
+    /// Usage of the account against the limits of its plan.
+    async fn usage(&self) -> Result<Usage, ApiServerError>;
+    /// Webhooks of the account, only the ones of `project` when given.
+    async fn webhooks(&self, project: Option<&Uuid>) -> Result<Vec<WebhookData>, ApiServerError>;
+    async fn webhook_create(
+        &self,
+        webhook: &WebhookCreateDTO<'_>,
+    ) -> Result<WebhookData, ApiServerError>;
+    async fn webhook_delete(&self, id: &Uuid) -> Result<(), ApiServerError>;
 }
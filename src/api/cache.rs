@@ -0,0 +1,222 @@
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Utc};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::cli::{Analysis, ProjectFilter};
+
+use super::upload;
+
+const RESPONSES_CACHE_DIR: &str = "cosmo-cli/responses";
+const PROFILES_CACHE_DIR: &str = "cosmo-cli/profiles";
+
+/// Response of the api cached for offline use and conditional requests.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CachedResponse<T> {
+    /// When the response was received
+    pub cached_at: DateTime<Utc>,
+    /// Validators to revalidate the response with the server
+    #[serde(default)]
+    pub validators: Validators,
+    pub response: T,
+}
+
+/// `ETag` and `Last-Modified` of a response.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Validators {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+impl Validators {
+    /// Validators sent by the server in the response `headers`.
+    pub fn from_headers(headers: &reqwest::header::HeaderMap) -> Self {
+        let header = |name| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        };
+
+        Self {
+            etag: header(reqwest::header::ETAG),
+            last_modified: header(reqwest::header::LAST_MODIFIED),
+        }
+    }
+}
+
+/// Api call whose response is cached.
+#[derive(Debug, Clone, Copy)]
+pub enum CacheKey<'a> {
+    /// Versions of the api offered by the server
+    ApiVersions,
+    Projects,
+    ProjectsPage {
+        page: u32,
+        per_page: u32,
+        filter: &'a ProjectFilter,
+    },
+    Organizations,
+    OrganizationProjects(&'a Uuid),
+    Overview(&'a Uuid),
+    Analysis {
+        project_id: &'a Uuid,
+        analysis: &'a Analysis,
+        page: i32,
+        per_page: i32,
+    },
+}
+
+impl CacheKey<'_> {
+    /// Path of the cache entry, relative to the cache directory.
+    fn path(&self) -> PathBuf {
+        match self {
+            Self::ApiVersions => PathBuf::from("versions.json"),
+            Self::Projects => PathBuf::from("projects.json"),
+            Self::ProjectsPage {
+                page,
+                per_page,
+                filter,
+            } => {
+                let query = filter.query();
+                if query.is_empty() {
+                    PathBuf::from(format!("projects-{}-{}.json", page, per_page))
+                } else {
+                    // Filtered lists are cached apart, by hash of the filters
+                    let digest = Sha256::digest(format!("{:?}", query));
+                    PathBuf::from(format!(
+                        "projects-{}-{}-{}.json",
+                        page,
+                        per_page,
+                        &upload::hex(&digest)[..16]
+                    ))
+                }
+            }
+            Self::Organizations => PathBuf::from("organizations.json"),
+            Self::OrganizationProjects(organization_id) => Path::new("organizations")
+                .join(organization_id.to_string())
+                .join("projects.json"),
+            Self::Overview(project_id) => Path::new(&project_id.to_string()).join("overview.json"),
+            Self::Analysis {
+                project_id,
+                analysis,
+                page,
+                per_page,
+            } => Path::new(&project_id.to_string())
+                .join(format!("{}-{}-{}.json", analysis, page, per_page)),
+        }
+    }
+}
+
+impl std::fmt::Display for CacheKey<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ApiVersions => write!(f, "the api versions"),
+            Self::Projects => write!(f, "the project list"),
+            Self::ProjectsPage {
+                page,
+                per_page,
+                filter,
+            } => {
+                write!(f, "the project list (page {}, {} per page", page, per_page)?;
+                for (name, value) in filter.query() {
+                    write!(f, ", {} {}", name, value)?;
+                }
+                write!(f, ")")
+            }
+            Self::Organizations => write!(f, "the organization list"),
+            Self::OrganizationProjects(organization_id) => {
+                write!(f, "the projects of organization {}", organization_id)
+            }
+            Self::Overview(project_id) => write!(f, "the overview of project {}", project_id),
+            Self::Analysis {
+                project_id,
+                analysis,
+                page,
+                per_page,
+            } => write!(
+                f,
+                "the {} analysis of project {} (page {}, {} per page)",
+                analysis, project_id, page, per_page
+            ),
+        }
+    }
+}
+
+/// Local cache of the last responses of the api, one JSON file per call.
+#[derive(Debug, Clone)]
+pub struct ResponseCache {
+    dir: PathBuf,
+}
+
+impl ResponseCache {
+    /// Cache in the user cache directory of the OS, if any.
+    pub fn new() -> Option<Self> {
+        dirs::cache_dir().map(|dir| Self::with_dir(dir.join(RESPONSES_CACHE_DIR)))
+    }
+
+    /// Cache of the named `profile`, apart from the others so that the
+    /// responses of different servers never mix.
+    pub fn for_profile(profile: &str) -> Option<Self> {
+        dirs::cache_dir()
+            .map(|dir| Self::with_dir(dir.join(PROFILES_CACHE_DIR).join(profile).join("responses")))
+    }
+
+    /// Cache in `dir`.
+    pub fn with_dir(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Atomically persist the response of the call `key`, so that a crash
+    /// never leaves a truncated entry behind.
+    pub fn store<T: Serialize>(
+        &self,
+        key: &CacheKey,
+        response: &T,
+        validators: Validators,
+    ) -> std::io::Result<()> {
+        let path = self.dir.join(key.path());
+        std::fs::create_dir_all(path.parent().expect("cache entry should have a parent"))?;
+
+        let entry = CachedResponse {
+            cached_at: Utc::now(),
+            validators,
+            response,
+        };
+        let tmp = path.with_extension(format!("json.{}.tmp", std::process::id()));
+        let mut file = std::fs::File::create(&tmp)?;
+        file.write_all(&serde_json::to_vec(&entry)?)?;
+        file.sync_all()?;
+        std::fs::rename(tmp, path)
+    }
+
+    /// Load the cached response of the call `key`, if any.
+    pub fn load<T: DeserializeOwned>(
+        &self,
+        key: &CacheKey,
+    ) -> std::io::Result<Option<CachedResponse<T>>> {
+        let data = match std::fs::read(self.dir.join(key.path())) {
+            Ok(data) => data,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        Ok(Some(serde_json::from_slice(&data)?))
+    }
+
+    /// Remove all the cached responses.
+    pub fn clear(&self) -> std::io::Result<()> {
+        match std::fs::remove_dir_all(&self.dir) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        }
+    }
+}
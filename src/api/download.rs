@@ -0,0 +1,232 @@
+use std::{
+    io::IsTerminal,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use futures::StreamExt;
+use indicatif::{HumanBytes, HumanDuration, ProgressBar, ProgressStyle};
+use reqwest::{header::USER_AGENT, StatusCode};
+use sha2::{Digest, Sha256};
+use tokio::io::AsyncWriteExt;
+use url::Url;
+
+use super::{
+    http_server::{load_ca_bundle, proxy_of, CLI_PLATFORM_USER_AGENT, CLI_USER_AGENT},
+    upload, ApiServerError, HttpApiServerOptions,
+};
+
+/// Header with the hex encoded SHA-256 of a downloaded file, if any
+const X_CHECKSUM_SHA256: &str = "X-Checksum-SHA256";
+
+/// Http client of the downloads outside of the api of Cosmo, the release
+/// binaries and the images of the registries.
+///
+/// It sends neither the api key nor the extra headers of the api, and always
+/// verifies the certificates of the servers, whatever `--insecure`.
+#[derive(Debug, Clone)]
+pub struct PlainClient {
+    pub(crate) client: reqwest::Client,
+    pub(crate) user_agent: &'static str,
+    /// Maximum time of a download
+    pub(crate) timeout: Duration,
+    pub(crate) progress: bool,
+}
+
+impl PlainClient {
+    /// Build the client with the proxy, the additional root certificates,
+    /// the timeouts and the progress of the api ones.
+    pub fn new(options: &HttpApiServerOptions) -> Result<Self, ApiServerError> {
+        let mut builder = reqwest::Client::builder().connect_timeout(options.connect_timeout);
+        if let Some(proxy) = &options.proxy {
+            builder = builder.proxy(proxy_of(proxy)?);
+        }
+        if let Some(ca_cert) = &options.ca_cert {
+            for cert in load_ca_bundle(ca_cert)? {
+                builder = builder.add_root_certificate(cert);
+            }
+        }
+
+        Ok(Self {
+            client: builder.build()?,
+            user_agent: if options.platform_user_agent {
+                &CLI_PLATFORM_USER_AGENT
+            } else {
+                &CLI_USER_AGENT
+            },
+            timeout: options.upload_timeout,
+            progress: options.progress,
+        })
+    }
+
+    /// Download the file at `url` to `output_path`, returning its size.
+    pub async fn download(&self, url: &Url, output_path: &Path) -> Result<u64, ApiServerError> {
+        let response = self
+            .client
+            .get(url.clone())
+            .header(USER_AGENT, self.user_agent)
+            .timeout(self.timeout)
+            .send()
+            .await?;
+
+        if response.status() != StatusCode::OK {
+            return Err(ApiServerError::ResponseError(format!(
+                "Unexpected status {} downloading {}",
+                response.status(),
+                url
+            )));
+        }
+        save_response(response, output_path, self.progress).await
+    }
+}
+
+/// Progress of a download, rendered on stderr.
+struct DownloadProgress {
+    bar: ProgressBar,
+    start: Instant,
+}
+
+impl DownloadProgress {
+    /// Create the progress bar for a download of `size` bytes, a spinner
+    /// when the size is unknown.
+    ///
+    /// The bar is hidden when `enabled` is false or stderr is not a terminal.
+    fn new(size: Option<u64>, enabled: bool) -> Self {
+        let bar = if !enabled || !std::io::stderr().is_terminal() {
+            ProgressBar::hidden()
+        } else if let Some(size) = size {
+            ProgressBar::new(size).with_style(
+                ProgressStyle::with_template(
+                    "[{elapsed_precise}] [{wide_bar}] {bytes}/{total_bytes} ({percent}%) {binary_bytes_per_sec} ETA {eta}",
+                )
+                .expect("valid progress template")
+                .progress_chars("=> "),
+            )
+        } else {
+            ProgressBar::new_spinner().with_style(
+                ProgressStyle::with_template(
+                    "[{elapsed_precise}] {spinner} {bytes} {binary_bytes_per_sec}",
+                )
+                .expect("valid progress template"),
+            )
+        };
+
+        Self {
+            bar,
+            start: Instant::now(),
+        }
+    }
+
+    /// Replace the bar with a summary line of the downloaded bytes.
+    fn finish(&self) {
+        if self.bar.is_hidden() {
+            return;
+        }
+
+        self.bar
+            .set_style(ProgressStyle::with_template("{msg}").expect("valid template"));
+        self.bar.finish_with_message(format!(
+            "Downloaded {} in {}",
+            HumanBytes(self.bar.position()),
+            HumanDuration(self.start.elapsed())
+        ));
+    }
+}
+
+/// Stream the body of `response` to `path`, verifying its length and, when
+/// sent by the server, its SHA-256. Returns the size of the file.
+///
+/// The body is written to a temporary file next to `path`, renamed only once
+/// verified, so a failed download never leaves a partial file at `path`.
+pub(crate) async fn save_response(
+    response: reqwest::Response,
+    path: &Path,
+    progress: bool,
+) -> Result<u64, ApiServerError> {
+    let expected_len = response.content_length();
+    let expected_sha256 = response
+        .headers()
+        .get(X_CHECKSUM_SHA256)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.trim().to_ascii_lowercase());
+
+    let progress = DownloadProgress::new(expected_len, progress);
+    let tmp = partial_path(path);
+
+    let saved = save_body(response, &tmp, &progress)
+        .await
+        .and_then(|(len, sha256)| {
+            if let Some(expected_len) = expected_len.filter(|expected| *expected != len) {
+                return Err(ApiServerError::ResponseError(format!(
+                    "Incomplete download: received {} of {} bytes",
+                    len, expected_len
+                )));
+            }
+            if let Some(expected) = expected_sha256.filter(|expected| *expected != sha256) {
+                return Err(ApiServerError::DownloadIntegrityMismatch {
+                    expected,
+                    actual: sha256,
+                });
+            }
+            Ok(len)
+        });
+
+    let saved = saved.and_then(|len| {
+        std::fs::rename(&tmp, path).map_err(|err| write_error(path, err))?;
+        Ok(len)
+    });
+
+    match saved {
+        Ok(len) => {
+            progress.finish();
+            Ok(len)
+        }
+        Err(err) => {
+            progress.bar.finish_and_clear();
+            let _ = std::fs::remove_file(&tmp);
+            Err(err)
+        }
+    }
+}
+
+/// Write the body to `path`, returning its size and hex encoded SHA-256.
+async fn save_body(
+    response: reqwest::Response,
+    path: &Path,
+    progress: &DownloadProgress,
+) -> Result<(u64, String), ApiServerError> {
+    let mut file = tokio::fs::File::create(path)
+        .await
+        .map_err(|err| write_error(path, err))?;
+    let mut hasher = Sha256::new();
+    let mut len = 0;
+
+    let mut body = response.bytes_stream();
+    while let Some(chunk) = body.next().await {
+        let chunk = chunk?;
+        hasher.update(&chunk);
+        file.write_all(&chunk)
+            .await
+            .map_err(|err| write_error(path, err))?;
+        len += chunk.len() as u64;
+        progress.bar.set_position(len);
+    }
+    file.flush().await.map_err(|err| write_error(path, err))?;
+
+    Ok((len, upload::hex(&hasher.finalize())))
+}
+
+/// Temporary file of the download to `path`.
+fn partial_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".part");
+    path.with_file_name(name)
+}
+
+fn write_error(path: &Path, err: std::io::Error) -> ApiServerError {
+    ApiServerError::RequestError(format!(
+        "Error writing data to file: {}. Reason: {}",
+        path.display(),
+        err
+    ))
+}
@@ -0,0 +1,77 @@
+//! Server-sent events pushing the status of a project as it changes,
+//! followed by `project watch` instead of polling when the server offers
+//! them.
+
+use futures::{Stream, StreamExt};
+
+use super::{ApiServerError, StatusEvents};
+
+/// Whether `response` is a stream of server-sent events.
+pub(crate) fn is_event_stream(response: &reqwest::Response) -> bool {
+    response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/event-stream"))
+}
+
+/// Statuses sent as the data of the events of `response`, the events
+/// without data, like keep-alive comments, being skipped.
+pub(crate) fn statuses(response: reqwest::Response) -> StatusEvents {
+    events(response.bytes_stream().boxed())
+        .map(|data| {
+            let data = data?;
+            serde_json::from_str(&data).map_err(|err| {
+                ApiServerError::ResponseError(format!("invalid status event `{}`: {}", data, err))
+            })
+        })
+        .boxed()
+}
+
+/// Data of the events read from `body`, until it ends.
+fn events<S, B>(body: S) -> impl Stream<Item = Result<String, ApiServerError>>
+where
+    S: Stream<Item = Result<B, reqwest::Error>> + Send + Unpin,
+    B: AsRef<[u8]>,
+{
+    futures::stream::unfold((body, Vec::new()), |(mut body, mut buffer)| async move {
+        loop {
+            if let Some(end) = event_end(&buffer) {
+                let event: Vec<u8> = buffer.drain(..end).collect();
+                match data(&String::from_utf8_lossy(&event)) {
+                    Some(data) => return Some((Ok(data), (body, buffer))),
+                    None => continue,
+                }
+            }
+            match body.next().await? {
+                Ok(chunk) => buffer.extend_from_slice(chunk.as_ref()),
+                Err(err) => return Some((Err(err.into()), (body, buffer))),
+            }
+        }
+    })
+}
+
+/// End of the first event of `buffer`, after the blank line closing it.
+fn event_end(buffer: &[u8]) -> Option<usize> {
+    let end = |separator: &[u8]| {
+        buffer
+            .windows(separator.len())
+            .position(|window| window == separator)
+            .map(|start| start + separator.len())
+    };
+    [end(b"\n\n"), end(b"\r\n\r\n")].into_iter().flatten().min()
+}
+
+/// Data of `event`, its `data:` lines joined, `None` when it has none.
+fn data(event: &str) -> Option<String> {
+    let lines: Vec<&str> = event
+        .lines()
+        .filter_map(|line| line.strip_prefix("data"))
+        .filter_map(|value| match value.strip_prefix(':') {
+            Some(value) => Some(value.strip_prefix(' ').unwrap_or(value)),
+            // A bare `data` field is an empty line of data
+            None => value.is_empty().then_some(""),
+        })
+        .collect();
+    (!lines.is_empty()).then(|| lines.join("\n"))
+}
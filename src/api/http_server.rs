@@ -1,95 +1,1604 @@
 use async_trait::async_trait;
+use futures::StreamExt;
+use indicatif::HumanBytes;
 use lazy_static::lazy_static;
-use reqwest::header::USER_AGENT;
-use std::{collections::HashMap, fs::File, io::Write, path::Path};
+use rand::Rng;
+use reqwest::{
+    header::{
+        HeaderMap, HeaderValue, ACCEPT, AUTHORIZATION, CONTENT_ENCODING, CONTENT_LENGTH,
+        CONTENT_RANGE, CONTENT_TYPE, IF_MODIFIED_SINCE, IF_NONE_MATCH, RETRY_AFTER, USER_AGENT,
+    },
+    Method, ResponseBuilderExt, StatusCode,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+use tokio_util::io::{StreamReader, SyncIoBridge};
+use tracing::Instrument;
+use url::Url;
 use uuid::Uuid;
 
 use crate::{
-    cli::Analysis,
+    cli::{Analysis, ProjectFilter},
+    logging,
     services::{
         apikey_service::ApiKeyData,
         organization_service::OrganizationData,
-        project_service::{Project, ProjectAnalysis, ProjectIdDTO},
+        project_service::{
+            Project, ProjectAnalysis, ProjectCreated, ProjectIdDTO, ProjectStatus, ProjectTagsDTO,
+            ProjectTagsUpdateDTO, ProjectUpdateDTO, RescanJob, UploadValidationDTO,
+        },
+        usage_service::Usage,
+        webhook_service::{WebhookCreateDTO, WebhookData},
     },
 };
 
-use super::{ApiServer, ApiServerError, LatestCliVersion};
+use super::{
+    cache::{CacheKey, ResponseCache, Validators},
+    download, events, remote, unix_socket,
+    upload::{self, Throttle, UploadDigest, UploadProgress, UploadState},
+    user_agent, ApiErrorResponse, ApiServer, ApiServerError, LatestCliVersion, PingReport,
+    RemoteFirmware, ServerVersion, StatusEvents, UploadOptions,
+};
 
 lazy_static! {
     pub static ref CLI_USER_AGENT: String = format!("ExeinCosmoCLI/{}", crate::version());
+    /// User agent with the platform too, e.g. `ExeinCosmoCLI/0.4.0 (linux; x86_64; ubuntu-22.04)`
+    pub static ref CLI_PLATFORM_USER_AGENT: String =
+        format!("{} ({})", *CLI_USER_AGENT, user_agent::platform());
 }
 
 const X_API_KEY: &str = "X-API-KEY";
+const X_REQUEST_ID: &str = "X-Request-Id";
+const X_INVOCATION_ID: &str = "X-Invocation-Id";
+
+const PDF_CONTENT_TYPE: &str = "application/pdf";
+
+const DEPRECATION: &str = "Deprecation";
+const SUNSET: &str = "Sunset";
+
+// Routes of the api, relative to the prefix of its version
+const PROJECT_ROUTE: &str = "/projects";
+const ORGANIZATION_ROUTE: &str = "/organizations";
+const APIKEY_ROUTE: &str = "/api_key";
+const UPLOADS_ROUTE: &str = "/uploads";
+const USAGE_ROUTE: &str = "/account/usage";
+const WEBHOOK_ROUTE: &str = "/webhooks";
 
-const PROJECT_ROUTE_V1: &str = "/api/v1/projects";
-const ORGANIZATION_ROUTE_V1: &str = "/api/v1/organizations";
-const APIKEY_ROUTE_V1: &str = "/api/v1/api_key";
+const VERSIONS_ROUTE: &str = "/api/versions";
 const UPDATES_ROUTE: &str = "/api/updates_check";
 
+/// Versions of the api supported by the cli
+pub const SUPPORTED_API_VERSIONS: [u32; 1] = [1];
+
+/// Size of the chunks of resumable uploads, unless chosen by the server
+const RESUMABLE_CHUNK_SIZE: u64 = 8 * 1024 * 1024;
+
+/// How long the versions of the api offered by a server are reused
+const VERSIONS_CACHE_TTL_HOURS: i64 = 24;
+
+#[derive(Serialize, Deserialize)]
+struct ApiVersionsDTO {
+    versions: Vec<u32>,
+    #[serde(default)]
+    deprecated: Vec<u32>,
+    #[serde(default)]
+    server_version: Option<String>,
+}
+
+/// Versions of the api cached with the server offering them, as the cache
+/// may be shared by several servers.
+#[derive(Serialize, Deserialize)]
+struct CachedApiVersions {
+    server: String,
+    versions: ApiVersionsDTO,
+}
+
+#[derive(Serialize)]
+struct UploadSessionRequest<'a> {
+    name: &'a str,
+    r#type: &'a str,
+    subtype: &'a str,
+    description: Option<&'a str>,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    tags: &'a [String],
+    filename: &'a str,
+    size: u64,
+}
+
+#[derive(Deserialize)]
+struct UploadSessionDTO {
+    upload_id: Uuid,
+    chunk_size: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct UploadOffsetDTO {
+    offset: u64,
+}
+
+#[derive(Serialize)]
+struct UploadCompleteRequest<'a> {
+    sha256: &'a str,
+}
+
+#[derive(Serialize)]
+struct ProjectFetchRequest<'a> {
+    name: &'a str,
+    r#type: &'a str,
+    subtype: &'a str,
+    description: Option<&'a str>,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    tags: &'a [String],
+    url: &'a str,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    headers: BTreeMap<&'a str, String>,
+}
+
+/// Options used to build the http client of [HttpApiServer].
+#[derive(Debug, Clone)]
+pub struct HttpApiServerOptions {
+    /// Maximum time to establish a connection
+    pub connect_timeout: Duration,
+    /// Maximum time for a whole request, from connect to the end of the body
+    pub timeout: Duration,
+    /// Same as `timeout` but only for firmware uploads and downloads
+    pub upload_timeout: Duration,
+    /// Maximum bandwidth of firmware uploads, in bytes per second
+    pub upload_limit: Option<u64>,
+    /// Number of retries for transient failures
+    pub retries: u32,
+    /// Wait and retry when the server responds with 429 Too Many Requests
+    pub rate_limit_wait: bool,
+    /// Proxy for all the requests, `http://`, `https://` or `socks5://`.
+    /// When not set, `HTTP_PROXY`, `HTTPS_PROXY` and `NO_PROXY` are used.
+    pub proxy: Option<String>,
+    /// PEM bundle with additional root certificates to trust
+    pub ca_cert: Option<PathBuf>,
+    /// Client certificate for mutual TLS, PEM or PKCS#12
+    pub client_cert: Option<PathBuf>,
+    /// Private key of a PEM client certificate
+    pub client_key: Option<PathBuf>,
+    /// Passphrase of a PKCS#12 client certificate
+    pub client_cert_password: Option<String>,
+    /// Skip verification of the server certificate
+    pub insecure: bool,
+    /// Show a progress bar during uploads
+    pub progress: bool,
+    /// Include the platform of the cli in the user agent
+    pub platform_user_agent: bool,
+    /// Extra headers sent with every request
+    pub headers: HeaderMap,
+    /// Allow `headers` to replace the Authorization, User-Agent and api
+    /// key headers
+    pub override_headers: bool,
+    /// Maximum size of the JSON responses, in bytes
+    pub max_response_size: u64,
+    /// Cache where the responses are stored for `--offline` and
+    /// revalidated with conditional requests
+    pub cache: Option<ResponseCache>,
+}
+
+impl Default for HttpApiServerOptions {
+    fn default() -> Self {
+        Self {
+            connect_timeout: Duration::from_secs(10),
+            timeout: Duration::from_secs(60),
+            upload_timeout: Duration::from_secs(3600),
+            upload_limit: None,
+            retries: 3,
+            rate_limit_wait: true,
+            proxy: None,
+            ca_cert: None,
+            client_cert: None,
+            client_key: None,
+            client_cert_password: None,
+            insecure: false,
+            progress: true,
+            platform_user_agent: true,
+            headers: HeaderMap::new(),
+            override_headers: false,
+            max_response_size: DEFAULT_MAX_RESPONSE_SIZE,
+            cache: ResponseCache::new(),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct HttpApiServer {
-    address: String,
+    address: Url,
+    endpoint: Url,
     apikey: String,
+    client: reqwest::Client,
+    user_agent: &'static str,
+    headers: HeaderMap,
+    invocation_id: Uuid,
+    connect_timeout: Duration,
+    proxied: bool,
+    api_version: tokio::sync::OnceCell<u32>,
+    deprecation_warned: AtomicBool,
+    upload_timeout: Duration,
+    upload_limit: Option<u64>,
+    /// Shared by the concurrent uploads, which split the limit
+    throttle: Option<Throttle>,
+    retries: u32,
+    rate_limit_wait: bool,
+    progress: bool,
+    max_response_size: u64,
+    cache: Option<ResponseCache>,
+    created_project: Mutex<Option<Uuid>>,
 }
 
+/// Default maximum size of the JSON responses
+const DEFAULT_MAX_RESPONSE_SIZE: u64 = 256 * 1024 * 1024;
+
+/// Environment variables of the proxies used by the http client
+const PROXY_ENV_VARS: [&str; 6] = [
+    "HTTP_PROXY",
+    "http_proxy",
+    "HTTPS_PROXY",
+    "https_proxy",
+    "ALL_PROXY",
+    "all_proxy",
+];
+
+/// Maximum length of the response bodies logged at trace level
+const TRACE_BODY_MAX_LEN: usize = 16 * 1024;
+
+/// Maximum number of retries after a 429 response
+const RATE_LIMIT_MAX_RETRIES: u32 = 5;
+/// Upper bound of the time waited after a 429 response
+const RATE_LIMIT_MAX_DELAY: Duration = Duration::from_secs(300);
+
+/// Base delay of the exponential backoff between retries
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+/// Upper bound of the delay between retries
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(10);
+
 impl HttpApiServer {
-    pub async fn new(address: String, apikey: String) -> Self {
-        Self { address, apikey }
+    /// Build the server for the api at `address`.
+    ///
+    /// The address can include a path prefix, e.g.
+    /// `https://security.example.com/exein`, prepended to every route, or
+    /// be a unix socket like `unix:///var/run/cosmo.sock`.
+    pub async fn new(
+        address: Url,
+        apikey: String,
+        options: HttpApiServerOptions,
+    ) -> Result<Self, ApiServerError> {
+        let address = base_url(address)?;
+        let endpoint = address.clone();
+        let unix_socket = (address.scheme() == "unix").then(|| PathBuf::from(address.path()));
+
+        if unix_socket.is_some()
+            && (options.insecure || options.ca_cert.is_some() || options.client_cert.is_some())
+        {
+            return Err(ApiServerError::RequestError(
+                "TLS is not available over a unix socket, --insecure, --ca-cert and --client-cert can't be used"
+                    .to_string(),
+            ));
+        }
+
+        if !options.override_headers {
+            let reserved = [AUTHORIZATION.as_str(), USER_AGENT.as_str(), X_API_KEY];
+            if let Some(name) = options.headers.keys().find(|name| {
+                reserved
+                    .iter()
+                    .any(|r| name.as_str().eq_ignore_ascii_case(r))
+            }) {
+                return Err(ApiServerError::RequestError(format!(
+                    "The header {} is set by the cli, pass --override-headers to replace it",
+                    name
+                )));
+            }
+        }
+
+        if options.insecure {
+            let production = Url::parse(crate::COSMO_API_SERVER)
+                .ok()
+                .and_then(|url| url.host_str().map(str::to_string));
+
+            if address.host_str().map(str::to_string) == production {
+                return Err(ApiServerError::RequestError(
+                    "TLS verification can't be disabled for the production server".to_string(),
+                ));
+            }
+
+            tracing::warn!(
+                "TLS certificate verification is DISABLED: the connection to {} is not secure",
+                address
+            );
+        }
+
+        let mut builder = reqwest::Client::builder()
+            .connect_timeout(options.connect_timeout)
+            .timeout(options.timeout)
+            .danger_accept_invalid_certs(options.insecure);
+
+        if let Some(proxy) = &options.proxy {
+            builder = builder.proxy(proxy_of(proxy)?);
+        }
+
+        if let Some(ca_cert) = &options.ca_cert {
+            for cert in load_ca_bundle(ca_cert)? {
+                builder = builder.add_root_certificate(cert);
+            }
+        }
+
+        if let Some(client_cert) = &options.client_cert {
+            let identity = load_identity(
+                client_cert,
+                options.client_key.as_deref(),
+                options.client_cert_password.as_deref(),
+            )?;
+            builder = builder.identity(identity);
+        } else if options.client_key.is_some() {
+            return Err(ApiServerError::ClientIdentityError(
+                "a client key requires a client certificate".to_string(),
+            ));
+        }
+
+        // Requests to unix sockets go through a loopback bridge, which
+        // only accepts one request per connection
+        let address = match unix_socket {
+            Some(socket) => {
+                builder = builder.no_proxy().pool_max_idle_per_host(0);
+                unix_socket::bridge(socket).await?
+            }
+            None => address,
+        };
+
+        let client = builder.build().map_err(|err| match &options.client_cert {
+            // The identity is validated against its key only when building
+            Some(client_cert) if err.is_builder() => ApiServerError::ClientIdentityError(format!(
+                "unable to use client certificate {}. Reason: {}",
+                client_cert.display(),
+                err
+            )),
+            _ => ApiServerError::from(err),
+        })?;
+
+        let mut server = Self::with_client(address, apikey, client)?;
+        server.endpoint = endpoint;
+        server.connect_timeout = options.connect_timeout;
+        if !options.platform_user_agent {
+            server.user_agent = &CLI_USER_AGENT;
+        }
+        server.proxied |= options.proxy.is_some();
+        server.upload_timeout = options.upload_timeout;
+        server.upload_limit = options.upload_limit;
+        server.throttle = options.upload_limit.map(Throttle::new);
+        server.retries = options.retries;
+        server.rate_limit_wait = options.rate_limit_wait;
+        server.progress = options.progress;
+        server.max_response_size = options.max_response_size;
+        server.cache = options.cache;
+        // Extra headers can carry credentials, keep them out of the logs
+        server.headers = options.headers;
+        for value in server.headers.values_mut() {
+            value.set_sensitive(true);
+        }
+        Ok(server)
+    }
+
+    /// Build the server on top of a pre-configured client, so that
+    /// timeouts, proxies and the like can be injected by the caller.
+    ///
+    /// The client is shared by every request, keeping the connection pool
+    /// and TLS sessions alive between calls.
+    pub fn with_client(
+        address: Url,
+        apikey: String,
+        client: reqwest::Client,
+    ) -> Result<Self, ApiServerError> {
+        let address = base_url(address)?;
+        let defaults = HttpApiServerOptions::default();
+        Ok(Self {
+            endpoint: address.clone(),
+            address,
+            apikey,
+            client,
+            user_agent: &CLI_PLATFORM_USER_AGENT,
+            headers: HeaderMap::new(),
+            invocation_id: Uuid::new_v4(),
+            connect_timeout: defaults.connect_timeout,
+            proxied: PROXY_ENV_VARS
+                .iter()
+                .any(|var| std::env::var_os(var).is_some()),
+            api_version: tokio::sync::OnceCell::new(),
+            deprecation_warned: AtomicBool::new(false),
+            upload_timeout: defaults.upload_timeout,
+            upload_limit: defaults.upload_limit,
+            throttle: None,
+            retries: defaults.retries,
+            rate_limit_wait: defaults.rate_limit_wait,
+            progress: defaults.progress,
+            max_response_size: defaults.max_response_size,
+            cache: defaults.cache,
+            created_project: Mutex::new(None),
+        })
+    }
+
+    /// Url of the api `route`, relative to the path of the server address.
+    fn url(&self, route: &str) -> Url {
+        let mut url = self.address.clone();
+        let path = format!("{}{}", url.path().trim_end_matches('/'), route);
+        url.set_path(&path);
+        url
+    }
+
+    /// Id sent with all the requests of this server, identifying the
+    /// invocation of the cli.
+    pub fn invocation_id(&self) -> Uuid {
+        self.invocation_id
+    }
+
+    /// Id of the project created by the last `create`, also when it failed
+    /// after the server created the project.
+    pub fn created_project(&self) -> Option<Uuid> {
+        *self.created_project.lock().expect("created project lock")
+    }
+
+    /// Remember `project_id` as the project created by the last `create`.
+    fn set_created_project(&self, project_id: Option<Uuid>) {
+        *self.created_project.lock().expect("created project lock") = project_id;
+    }
+
+    /// Path of the api `route` in the version negotiated with the server.
+    async fn route(&self, route: &str) -> Result<String, ApiServerError> {
+        Ok(format!("/api/v{}{}", self.api_version(true).await?, route))
+    }
+
+    /// Newest version of the api supported by both the server and the cli,
+    /// discovered with the first request that needs it unless cached, with
+    /// retries when `retry`.
+    async fn api_version(&self, retry: bool) -> Result<u32, ApiServerError> {
+        self.api_version
+            .get_or_try_init(|| async {
+                let versions = match self.cached_versions() {
+                    Some(versions) => versions,
+                    None => {
+                        let versions = self.versions(retry).await?;
+                        let cached = CachedApiVersions {
+                            server: self.endpoint.to_string(),
+                            versions,
+                        };
+                        self.cache_response(CacheKey::ApiVersions, &cached, Validators::default());
+                        cached.versions
+                    }
+                };
+                let version = versions
+                    .versions
+                    .iter()
+                    .filter(|version| SUPPORTED_API_VERSIONS.contains(version))
+                    .max()
+                    .copied()
+                    .ok_or_else(|| ApiServerError::UnsupportedApiVersion {
+                        server: versions.versions.clone(),
+                        cli: SUPPORTED_API_VERSIONS.to_vec(),
+                    })?;
+
+                tracing::debug!("Using api v{}", version);
+                if versions.deprecated.contains(&version) {
+                    self.warn_deprecated(None);
+                }
+                Ok(version)
+            })
+            .await
+            .copied()
+    }
+
+    /// Versions of the api of this server cached by a previous invocation,
+    /// unless expired.
+    fn cached_versions(&self) -> Option<ApiVersionsDTO> {
+        let cached = self
+            .cache
+            .as_ref()?
+            .load::<CachedApiVersions>(&CacheKey::ApiVersions)
+            .map_err(|err| tracing::debug!("Ignoring the cached api versions. Reason: {}", err))
+            .ok()??;
+
+        let fresh = chrono::Utc::now() - cached.cached_at
+            < chrono::Duration::hours(VERSIONS_CACHE_TTL_HOURS);
+        (fresh && cached.response.server == self.endpoint.as_str())
+            .then_some(cached.response.versions)
+    }
+
+    /// Versions of the api offered by the server, with retries when `retry`.
+    async fn versions(&self, retry: bool) -> Result<ApiVersionsDTO, ApiServerError> {
+        let response = if retry {
+            self.send(|| self.request(VERSIONS_ROUTE, Method::GET))
+                .await?
+        } else {
+            let request = self.request(VERSIONS_ROUTE, Method::GET).build()?;
+            self.execute(request).await.map_err(connect_error)?
+        };
+
+        match response.status() {
+            StatusCode::OK => self.json::<ApiVersionsDTO>(response).await,
+            // Servers predating the discovery only have the first version
+            StatusCode::NOT_FOUND => Ok(ApiVersionsDTO {
+                versions: vec![1],
+                deprecated: vec![],
+                server_version: None,
+            }),
+            _ => Err(api_error(&Method::GET, response).await),
+        }
+    }
+
+    /// Warn, once per invocation, that the server deprecated the api used.
+    fn warn_deprecated(&self, sunset: Option<&str>) {
+        if self.deprecation_warned.swap(true, Ordering::Relaxed) {
+            return;
+        }
+
+        match sunset {
+            Some(sunset) => tracing::warn!(
+                "The api used by this cli is deprecated and will be removed on {}, update the cli",
+                sunset
+            ),
+            None => tracing::warn!("The api used by this cli is deprecated, update the cli"),
+        }
     }
 
     fn request(&self, path: &str, method: reqwest::Method) -> reqwest::RequestBuilder {
-        let url = format!("{}{}", self.address, path);
+        let url = self.url(path);
 
-        reqwest::Client::new()
+        self.client
             .request(method, url)
-            .header(USER_AGENT, &*CLI_USER_AGENT)
+            .header(USER_AGENT, self.user_agent)
+            .header(X_REQUEST_ID, Uuid::new_v4().to_string())
+            .header(X_INVOCATION_ID, self.invocation_id.to_string())
+            .headers(self.headers.clone())
     }
 
-    async fn authenticated_request(
-        &mut self,
+    /// Delete the api key `id` of the account, authenticated with the key in
+    /// use.
+    async fn apikey_delete_id(&self, id: &Uuid) -> Result<(), ApiServerError> {
+        let path = format!("{}/{}", self.route(APIKEY_ROUTE).await?, id);
+        let response = self
+            .send(|| self.authenticated_request(&path, reqwest::Method::DELETE, None))
+            .await?;
+
+        if response.status() == reqwest::StatusCode::OK {
+            Ok(())
+        } else {
+            Err(api_error(&Method::DELETE, response).await)
+        }
+    }
+
+    fn authenticated_request(
+        &self,
         path: &str,
         method: reqwest::Method,
         query: Option<&[(&str, &String)]>,
-    ) -> Result<reqwest::RequestBuilder, ApiServerError> {
-        let req = self
-            .request(path, method)
-            .query(&query)
-            .header(X_API_KEY, &self.apikey);
+    ) -> reqwest::RequestBuilder {
+        let request = self.request(path, method).query(&query);
 
-        Ok(req)
+        // An api key header passed with --override-headers replaces the configured one
+        if self.headers.contains_key(X_API_KEY) {
+            request
+        } else {
+            request.header(X_API_KEY, &self.apikey)
+        }
+    }
+
+    /// Send the request built by `build`, retrying transient failures with
+    /// exponential backoff.
+    ///
+    /// The request is rebuilt for every attempt, so bodies that can't be
+    /// cloned (like multipart streams) can be retried too. Idempotent requests
+    /// are retried on 502, 503, 504 and on connection errors. The others only
+    /// when the server didn't receive the body: connection refused or 503.
+    ///
+    /// Responses with 429 Too Many Requests are retried after the time
+    /// indicated by the `Retry-After` header, unless disabled by the options.
+    async fn send<F>(&self, build: F) -> Result<reqwest::Response, ApiServerError>
+    where
+        F: Fn() -> reqwest::RequestBuilder + Send + Sync,
+    {
+        let mut attempt = 1;
+        let mut rate_limited = 0;
+
+        loop {
+            let request = build().build()?;
+            let method = request.method().clone();
+            let url = request.url().path().to_string();
+            let idempotent = matches!(method, Method::GET | Method::HEAD | Method::DELETE);
+
+            let outcome = self.execute(request).await;
+
+            if let Ok(response) = &outcome {
+                if response.status() == StatusCode::TOO_MANY_REQUESTS
+                    && self.rate_limit_wait
+                    && rate_limited < RATE_LIMIT_MAX_RETRIES
+                {
+                    rate_limited += 1;
+                    let delay = retry_after(response)
+                        .unwrap_or_else(|| retry_delay(rate_limited))
+                        .min(RATE_LIMIT_MAX_DELAY);
+                    tracing::warn!("rate limited, retrying in {}s", delay.as_secs());
+                    tokio::time::sleep(delay).await;
+                    continue;
+                }
+            }
+
+            let retryable = match &outcome {
+                Ok(response) => match response.status() {
+                    StatusCode::SERVICE_UNAVAILABLE => true,
+                    StatusCode::BAD_GATEWAY | StatusCode::GATEWAY_TIMEOUT => idempotent,
+                    _ => false,
+                },
+                Err(err) => {
+                    err.is_connect() || (idempotent && (err.is_timeout() || err.is_request()))
+                }
+            };
+
+            if !retryable {
+                return outcome.map_err(ApiServerError::from);
+            }
+
+            if attempt > self.retries {
+                let error = match outcome {
+                    Ok(response) => api_error(&method, response).await,
+                    Err(err) => ApiServerError::from(err),
+                };
+
+                return Err(if attempt > 1 {
+                    ApiServerError::RetriesExhausted {
+                        attempts: attempt,
+                        error: Box::new(error),
+                    }
+                } else {
+                    error
+                });
+            }
+
+            let delay = retry_delay(attempt);
+            match &outcome {
+                Ok(response) => tracing::debug!(
+                    "{} {} failed with status {}, retrying in {:?}",
+                    method,
+                    url,
+                    response.status(),
+                    delay
+                ),
+                Err(err) => tracing::debug!(
+                    "{} {} failed: {}, retrying in {:?}",
+                    method,
+                    url,
+                    err,
+                    delay
+                ),
+            }
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
     }
 }
 
+impl HttpApiServer {
+    /// Execute `request` in a span with its method and path, recording the
+    /// status and the duration of the call.
+    async fn execute(&self, request: reqwest::Request) -> reqwest::Result<reqwest::Response> {
+        let span = tracing::debug_span!(
+            "request",
+            method = %request.method(),
+            path = request.url().path(),
+            status = tracing::field::Empty,
+            duration_ms = tracing::field::Empty,
+        );
+        self.execute_traced(request).instrument(span).await
+    }
+
+    /// Execute `request`, tracing it in the logs.
+    ///
+    /// Method, url and status are logged at debug level, headers and
+    /// response body at trace level, the secrets of the body redacted.
+    async fn execute_traced(
+        &self,
+        request: reqwest::Request,
+    ) -> reqwest::Result<reqwest::Response> {
+        let method = request.method().clone();
+        let url = request.url().clone();
+
+        tracing::debug!("--> {} {}", method, url);
+        if tracing::enabled!(tracing::Level::TRACE) {
+            log_headers("-->", request.headers());
+        }
+
+        let request_id = request.headers().get(X_REQUEST_ID).cloned();
+
+        let start = Instant::now();
+        let response = self.client.execute(request).await;
+        let elapsed = start.elapsed();
+        let span = tracing::Span::current();
+        span.record(
+            "duration_ms",
+            u64::try_from(elapsed.as_millis()).unwrap_or(u64::MAX),
+        );
+
+        let mut response = match response {
+            Ok(response) => response,
+            Err(err) => {
+                tracing::debug!("<-- {} {} failed in {:?}: {}", method, url, elapsed, err);
+                return Err(err);
+            }
+        };
+
+        span.record("status", response.status().as_u16());
+
+        let headers = response.headers();
+        if headers.contains_key(DEPRECATION) || headers.contains_key(SUNSET) {
+            let sunset = headers.get(SUNSET).and_then(|sunset| sunset.to_str().ok());
+            self.warn_deprecated(sunset);
+        }
+
+        // Keep track of the id of the request, unless the server sent its own
+        if let Some(request_id) = request_id {
+            let headers = response.headers_mut();
+            if !headers.contains_key(X_REQUEST_ID) {
+                headers.insert(X_REQUEST_ID, request_id);
+            }
+        }
+
+        tracing::debug!(
+            "<-- {} {} {} ({:?})",
+            response.status(),
+            method,
+            url,
+            elapsed
+        );
+        if !tracing::enabled!(tracing::Level::TRACE) {
+            return Ok(response);
+        }
+
+        log_headers("<--", response.headers());
+
+        // Read the body to log it, then rebuild the response around it
+        let status = response.status();
+        let version = response.version();
+        let headers = response.headers().clone();
+        let response_url = response.url().clone();
+        let body = response.bytes().await?;
+
+        match std::str::from_utf8(&body).map(logging::redact_json) {
+            Ok(text) if text.len() > TRACE_BODY_MAX_LEN => {
+                let end = (0..=TRACE_BODY_MAX_LEN)
+                    .rev()
+                    .find(|i| text.is_char_boundary(*i))
+                    .unwrap_or(0);
+                tracing::trace!("<-- {}... ({} bytes)", &text[..end], body.len())
+            }
+            Ok(text) => tracing::trace!("<-- {}", text),
+            Err(_) => tracing::trace!("<-- ({} bytes of binary data)", body.len()),
+        }
+
+        let mut rebuilt = http::Response::builder()
+            .status(status)
+            .version(version)
+            .url(response_url);
+        if let Some(rebuilt_headers) = rebuilt.headers_mut() {
+            *rebuilt_headers = headers;
+        }
+        Ok(rebuilt
+            .body(body)
+            .expect("response rebuilt from valid parts")
+            .into())
+    }
+
+    /// Deserialize the JSON body of `response` while it is received,
+    /// failing if it is larger than the maximum response size.
+    async fn json<T>(&self, response: reqwest::Response) -> Result<T, ApiServerError>
+    where
+        T: DeserializeOwned + Send + 'static,
+    {
+        let limit = self.max_response_size;
+        let too_large = || ApiServerError::ResponseTooLarge { limit };
+
+        if response.content_length().is_some_and(|len| len > limit) {
+            return Err(too_large());
+        }
+
+        let exceeded = Arc::new(AtomicBool::new(false));
+        let mut received = 0;
+        let stream_exceeded = exceeded.clone();
+        let body = response.bytes_stream().map(move |chunk| {
+            let chunk = chunk.map_err(std::io::Error::other)?;
+            received += chunk.len() as u64;
+            if received > limit {
+                stream_exceeded.store(true, Ordering::Relaxed);
+                return Err(std::io::Error::other("response too large"));
+            }
+            Ok(chunk)
+        });
+
+        // serde_json reads synchronously, so parse on a blocking thread
+        let reader = SyncIoBridge::new(StreamReader::new(body));
+        let parsed = tokio::task::spawn_blocking(move || {
+            serde_json::from_reader::<_, T>(std::io::BufReader::new(reader))
+        })
+        .await
+        .map_err(|err| ApiServerError::ResponseError(err.to_string()))?;
+
+        parsed.map_err(|err| {
+            if exceeded.load(Ordering::Relaxed) {
+                too_large()
+            } else {
+                ApiServerError::ResponseError(format!("Invalid JSON response. Reason: {}", err))
+            }
+        })
+    }
+
+    /// Store `response` in the cache, if any. Failures only lose the
+    /// offline copy, so they are not reported to the caller.
+    fn cache_response<T: Serialize>(&self, key: CacheKey, response: &T, validators: Validators) {
+        if let Some(cache) = &self.cache {
+            if let Err(err) = cache.store(&key, response, validators) {
+                tracing::debug!("Error caching {}. Reason: {}", key, err);
+            }
+        }
+    }
+
+    /// GET the JSON response at `path` with `query`, cached as `key`.
+    ///
+    /// When the cached response has an `ETag` or `Last-Modified`, the
+    /// request is conditional and on 304 Not Modified the cached response
+    /// is returned without downloading it again.
+    async fn get_cached<T>(
+        &self,
+        path: &str,
+        query: Option<&[(&str, &String)]>,
+        key: CacheKey<'_>,
+    ) -> Result<T, ApiServerError>
+    where
+        T: Serialize + DeserializeOwned + Send + 'static,
+    {
+        let cached = self.cache.as_ref().and_then(|cache| {
+            cache
+                .load::<T>(&key)
+                .map_err(|err| tracing::debug!("Ignoring cached {}. Reason: {}", key, err))
+                .ok()
+                .flatten()
+        });
+        let validators = cached.as_ref().map(|cached| &cached.validators);
+
+        let response = self
+            .send(|| {
+                let mut request = self.authenticated_request(path, Method::GET, query);
+                if let Some(etag) = validators.and_then(|v| v.etag.as_ref()) {
+                    request = request.header(IF_NONE_MATCH, etag);
+                }
+                if let Some(last_modified) = validators.and_then(|v| v.last_modified.as_ref()) {
+                    request = request.header(IF_MODIFIED_SINCE, last_modified);
+                }
+                request
+            })
+            .await?;
+
+        match (response.status(), cached) {
+            (StatusCode::NOT_MODIFIED, Some(cached)) => {
+                tracing::debug!("Using the cached response for {}, not modified", key);
+                Ok(cached.response)
+            }
+            (StatusCode::OK, _) => {
+                let validators = Validators::from_headers(response.headers());
+                let res = self.json::<T>(response).await?;
+                self.cache_response(key, &res, validators);
+                Ok(res)
+            }
+            _ => Err(api_error(&Method::GET, response).await),
+        }
+    }
+
+    /// Organization of a new project, the built-in one unless given.
+    ///
+    /// Both requests fail at once with a rejected api key, instead of after
+    /// uploading the whole firmware.
+    async fn upload_organization(
+        &self,
+        organization: Option<&str>,
+    ) -> Result<String, ApiServerError> {
+        match organization {
+            Some(o) => {
+                self.apikey_list().await?;
+                Ok(o.to_string())
+            }
+            None => Ok(self
+                .organization_list()
+                .await?
+                .into_iter()
+                .find(|s| s.built_in)
+                .ok_or_else(|| ApiServerError::RequestError("No organization found".to_string()))?
+                .id
+                .to_string()),
+        }
+    }
+
+    /// Create a project from the firmware read from stdin, streamed into
+    /// the upload. Stdin is read once, so the upload isn't retried after
+    /// reading started.
+    #[allow(clippy::too_many_arguments)]
+    async fn create_from_stdin(
+        &self,
+        fw_type: &str,
+        fw_subtype: &str,
+        name: &str,
+        description: Option<&str>,
+        organization: Option<&str>,
+        tags: &[String],
+        options: &UploadOptions,
+    ) -> Result<ProjectCreated, ApiServerError> {
+        let org_id = self.upload_organization(organization).await?;
+        let fw_filename = options
+            .filename
+            .clone()
+            .unwrap_or_else(|| stdin_filename(name));
+        tracing::info!("Streaming {} from stdin", fw_filename);
+
+        let progress = UploadProgress::new(None, self.progress && !options.hide_progress)
+            .with_limit(self.upload_limit);
+        let digest = UploadDigest::default();
+        let throttle = self.throttle.clone();
+        let compress = options.compress && !upload::is_compressed(Path::new(&fw_filename));
+
+        let stdin = Mutex::new(Some(upload::stdin_stream(options.max_size)));
+        let form = || {
+            let body = match stdin.lock().expect("stdin lock").take() {
+                Some(stream) => {
+                    upload::stream_body(stream, &progress, &digest, compress, throttle.as_ref())
+                }
+                None => reqwest::Body::wrap_stream(futures::stream::iter([Err::<Vec<u8>, _>(
+                    std::io::Error::other("stdin already read, pipe the firmware again"),
+                )])),
+            };
+            let part = match compress {
+                true => {
+                    let mut headers = HeaderMap::new();
+                    headers.insert(CONTENT_ENCODING, HeaderValue::from_static("gzip"));
+                    reqwest::multipart::Part::stream(body).headers(headers)
+                }
+                false => reqwest::multipart::Part::stream(body),
+            }
+            .file_name(fw_filename.clone());
+            project_form(part, &digest, name, fw_type, fw_subtype, description, tags)
+        };
+
+        let path = format!(
+            "{}/{}/projects",
+            self.route(ORGANIZATION_ROUTE).await?,
+            org_id
+        );
+        let response = self
+            .send(|| {
+                self.authenticated_request(&path, Method::POST, None)
+                    .timeout(self.upload_timeout)
+                    .multipart(form())
+            })
+            .await
+            .inspect_err(|_| progress.abandon())?;
+        progress.finish();
+
+        if response.status() == StatusCode::OK {
+            let dto = self.json::<ProjectIdDTO>(response).await?;
+            self.set_created_project(Some(dto.id));
+            let sha256 = digest.hex();
+            verify_digest(&dto, &sha256)?;
+            Ok(ProjectCreated { id: dto.id, sha256 })
+        } else {
+            Err(api_error(&Method::POST, response).await)
+        }
+    }
+
+    /// Upload the firmware through a resumable session, continuing the
+    /// previous one for the same file if any.
+    ///
+    /// Returns `None` if the server doesn't support resumable uploads.
+    #[allow(clippy::too_many_arguments)]
+    async fn create_resumable(
+        &self,
+        org_id: &str,
+        fw_path: &Path,
+        fw_filename: &str,
+        fw_type: &str,
+        fw_subtype: &str,
+        name: &str,
+        description: Option<&str>,
+        tags: &[String],
+        progress: &UploadProgress,
+        throttle: Option<&Throttle>,
+    ) -> Result<Option<ProjectCreated>, ApiServerError> {
+        let file_error = |err: std::io::Error| {
+            ApiServerError::RequestError(format!(
+                "Error reading file {}. Reason: {}",
+                fw_path.display(),
+                err
+            ))
+        };
+        let (size, modified) = UploadState::fingerprint(fw_path).map_err(file_error)?;
+
+        // Continue the previous session, if the server still knows it
+        let mut state = None;
+        if let Some(previous) = UploadState::load(fw_path) {
+            let path = format!(
+                "{}/{}",
+                self.route(UPLOADS_ROUTE).await?,
+                previous.upload_id
+            );
+            let response = self
+                .send(|| self.authenticated_request(&path, Method::GET, None))
+                .await?;
+
+            if response.status() == StatusCode::OK {
+                let dto = self.json::<UploadOffsetDTO>(response).await?;
+                tracing::info!(
+                    "Resuming upload {} from {}",
+                    previous.upload_id,
+                    HumanBytes(dto.offset)
+                );
+                state = Some(UploadState {
+                    offset: dto.offset,
+                    ..previous
+                });
+            } else {
+                tracing::info!("Previous upload session expired, starting a new one");
+            }
+        }
+
+        let mut state = match state {
+            Some(state) => state,
+            None => {
+                let path = format!(
+                    "{}/{}/uploads",
+                    self.route(ORGANIZATION_ROUTE).await?,
+                    org_id
+                );
+                let session = UploadSessionRequest {
+                    name,
+                    r#type: fw_type,
+                    subtype: fw_subtype,
+                    description,
+                    tags,
+                    filename: fw_filename,
+                    size,
+                };
+
+                let response = self
+                    .send(|| {
+                        self.authenticated_request(&path, Method::POST, None)
+                            .json(&session)
+                    })
+                    .await?;
+
+                match response.status() {
+                    StatusCode::OK | StatusCode::CREATED => {}
+                    StatusCode::NOT_FOUND
+                    | StatusCode::METHOD_NOT_ALLOWED
+                    | StatusCode::NOT_IMPLEMENTED => return Ok(None),
+                    _ => return Err(api_error(&Method::POST, response).await),
+                }
+
+                let dto = self.json::<UploadSessionDTO>(response).await?;
+                UploadState {
+                    upload_id: dto.upload_id,
+                    size,
+                    modified,
+                    offset: 0,
+                    chunk_size: dto.chunk_size.unwrap_or(RESUMABLE_CHUNK_SIZE).max(1),
+                }
+            }
+        };
+
+        let save_state = |state: &UploadState| {
+            if let Err(err) = state.save(fw_path) {
+                tracing::warn!("Unable to save the upload state: {}", err);
+            }
+        };
+        save_state(&state);
+
+        // The part uploaded by a previous run still has to be hashed
+        let digest = UploadDigest::default();
+        let mut hashed = 0;
+        while hashed < state.offset {
+            let chunk =
+                upload::read_chunk(fw_path, hashed, state.chunk_size.min(state.offset - hashed))
+                    .await
+                    .map_err(file_error)?;
+            if chunk.is_empty() {
+                break;
+            }
+            digest.update(&chunk);
+            hashed += chunk.len() as u64;
+        }
+
+        let path = format!("{}/{}", self.route(UPLOADS_ROUTE).await?, state.upload_id);
+        progress.set_position(state.offset);
+
+        while state.offset < size {
+            let chunk = upload::read_chunk(fw_path, state.offset, state.chunk_size)
+                .await
+                .map_err(file_error)?;
+            let end = state.offset + chunk.len() as u64 - 1;
+            let content_range = format!("bytes {}-{}/{}", state.offset, end, size);
+
+            let response = self
+                .send(|| {
+                    self.authenticated_request(&path, Method::PUT, None)
+                        .header(CONTENT_RANGE, &content_range)
+                        .header(CONTENT_LENGTH, chunk.len())
+                        .body(match throttle {
+                            Some(throttle) => throttle.body(chunk.clone()),
+                            None => chunk.clone().into(),
+                        })
+                })
+                .await?;
+
+            if !response.status().is_success() {
+                return Err(api_error(&Method::PUT, response).await);
+            }
+
+            let dto = self.json::<UploadOffsetDTO>(response).await?;
+            // Only the part accepted by the server is part of the file
+            let accepted = dto
+                .offset
+                .saturating_sub(state.offset)
+                .min(chunk.len() as u64);
+            digest.update(&chunk[..accepted as usize]);
+            state.offset = dto.offset;
+            save_state(&state);
+            progress.set_position(state.offset);
+        }
+
+        let sha256 = digest.hex();
+        let complete = UploadCompleteRequest { sha256: &sha256 };
+        let path = format!("{}/complete", path);
+        let response = self
+            .send(|| {
+                self.authenticated_request(&path, Method::POST, None)
+                    .json(&complete)
+            })
+            .await?;
+
+        if response.status() == StatusCode::OK {
+            let dto = self.json::<ProjectIdDTO>(response).await?;
+            self.set_created_project(Some(dto.id));
+            UploadState::remove(fw_path);
+            verify_digest(&dto, &sha256)?;
+            Ok(Some(ProjectCreated { id: dto.id, sha256 }))
+        } else {
+            Err(api_error(&Method::POST, response).await)
+        }
+    }
+}
+
+/// Log `headers` at trace level, redacting the credentials.
+fn log_headers(direction: &str, headers: &HeaderMap) {
+    const REDACTED: &[&str] = &[
+        "x-api-key",
+        "authorization",
+        "proxy-authorization",
+        "cookie",
+        "set-cookie",
+    ];
+
+    for (name, value) in headers {
+        if REDACTED.contains(&name.as_str()) || value.is_sensitive() {
+            tracing::trace!("{} {}: <redacted>", direction, name);
+        } else {
+            tracing::trace!(
+                "{} {}: {}",
+                direction,
+                name,
+                value.to_str().unwrap_or("<binary>")
+            );
+        }
+    }
+}
+
+/// Id of the request of `response`, the one sent by the server if any.
+fn request_id(response: &reqwest::Response) -> Option<String> {
+    response
+        .headers()
+        .get(X_REQUEST_ID)
+        .and_then(|id| id.to_str().ok())
+        .map(str::to_string)
+}
+
+/// Error for the error `response` to a `method` request.
+async fn api_error(method: &Method, response: reqwest::Response) -> ApiServerError {
+    let path = response.url().path().to_string();
+    let status = response.status().as_u16();
+    let request_id = request_id(&response);
+
+    match response.text().await {
+        Ok(body) => {
+            let mut response = ApiErrorResponse::parse(method.as_str(), &path, status, body);
+            response.request_id = request_id;
+            ApiServerError::from_response(response)
+        }
+        Err(err) => ApiServerError::from(err),
+    }
+}
+
+/// Classify the failure of a request that didn't get a response.
+///
+/// The cause is only reported as text by the http client, so the chain of
+/// its sources is searched for the stage that failed.
+fn connect_error(err: reqwest::Error) -> ApiServerError {
+    if err.is_timeout() || !err.is_connect() {
+        return ApiServerError::from(err);
+    }
+
+    let mut causes = Vec::new();
+    let mut source = std::error::Error::source(&err);
+    while let Some(cause) = source {
+        causes.push(cause.to_string());
+        source = cause.source();
+    }
+    let reason = causes.last().cloned().unwrap_or_else(|| err.to_string());
+    let chain = causes.join(": ").to_lowercase();
+
+    if chain.contains("dns error") || chain.contains("failed to lookup address") {
+        ApiServerError::DnsError(reason)
+    } else if ["ssl", "tls", "certificate", "handshake"]
+        .iter()
+        .any(|tls| chain.contains(tls))
+    {
+        ApiServerError::TlsError(reason)
+    } else {
+        ApiServerError::ConnectError(reason)
+    }
+}
+
+/// Check that `address` can be used as the base url of the api.
+fn base_url(address: Url) -> Result<Url, ApiServerError> {
+    let invalid = |reason: &str| {
+        ApiServerError::RequestError(format!("Invalid api server {}: {}", address, reason))
+    };
+
+    if address.scheme() == "unix" {
+        if address.has_host() || matches!(address.path(), "" | "/") {
+            return Err(invalid(
+                "expected a socket path like unix:///var/run/cosmo.sock",
+            ));
+        }
+        if address.query().is_some() || address.fragment().is_some() {
+            return Err(invalid("query and fragment are not allowed"));
+        }
+        return Ok(address);
+    }
+
+    if !matches!(address.scheme(), "http" | "https") {
+        return Err(invalid("the scheme must be http, https or unix"));
+    }
+    if address.cannot_be_a_base() || address.host().is_none() {
+        return Err(invalid("missing host"));
+    }
+    if address.query().is_some() || address.fragment().is_some() {
+        return Err(invalid("query and fragment are not allowed"));
+    }
+
+    Ok(address)
+}
+
+/// Check the digest of the firmware received by the server, if it sent one,
+/// against the local one.
+fn verify_digest(dto: &ProjectIdDTO, sha256: &str) -> Result<(), ApiServerError> {
+    match &dto.sha256 {
+        Some(received) if !received.eq_ignore_ascii_case(sha256) => {
+            Err(ApiServerError::IntegrityMismatch {
+                expected: sha256.to_string(),
+                actual: received.to_string(),
+            })
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Form creating a project, with the firmware sent by `part` and its
+/// SHA-256 computed by `digest` while sending it.
+fn project_form(
+    part: reqwest::multipart::Part,
+    digest: &UploadDigest,
+    name: &str,
+    fw_type: &str,
+    fw_subtype: &str,
+    description: Option<&str>,
+    tags: &[String],
+) -> reqwest::multipart::Form {
+    let mut form = reqwest::multipart::Form::new()
+        .text("name", name.to_string())
+        .text("type", fw_type.to_string())
+        .text("subtype", fw_subtype.to_string())
+        .part("file", part)
+        .part("sha256", digest.part());
+    for tag in tags {
+        form = form.text("tags", tag.clone());
+    }
+    match description {
+        Some(descr) => form.text("description", descr.to_string()),
+        None => form,
+    }
+}
+
+/// Name of a firmware read from stdin, after the project `name`.
+fn stdin_filename(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(
+            |c| match c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') {
+                true => c,
+                false => '_',
+            },
+        )
+        .collect();
+    format!("{}.bin", name.trim_matches(['.', '_']))
+}
+
+/// Proxy of all the requests, but the hosts of `NO_PROXY`.
+pub(super) fn proxy_of(proxy: &str) -> Result<reqwest::Proxy, ApiServerError> {
+    let all = reqwest::Proxy::all(proxy).map_err(|err| {
+        ApiServerError::RequestError(format!("Invalid proxy {}. Reason: {}", proxy, err))
+    })?;
+    Ok(all.no_proxy(reqwest::NoProxy::from_env()))
+}
+
+/// Load all the certificates contained in a PEM bundle.
+pub(super) fn load_ca_bundle(path: &Path) -> Result<Vec<reqwest::Certificate>, ApiServerError> {
+    const PEM_BEGIN: &str = "-----BEGIN CERTIFICATE-----";
+    const PEM_END: &str = "-----END CERTIFICATE-----";
+
+    let bundle = std::fs::read_to_string(path).map_err(|err| {
+        ApiServerError::RequestError(format!(
+            "Error reading CA certificate {}. Reason: {}",
+            path.display(),
+            err
+        ))
+    })?;
+
+    let certs = bundle
+        .split_inclusive(PEM_END)
+        .filter(|pem| pem.contains(PEM_BEGIN))
+        .map(|pem| {
+            reqwest::Certificate::from_pem(pem.as_bytes()).map_err(|err| {
+                ApiServerError::RequestError(format!(
+                    "Invalid CA certificate in {}. Reason: {}",
+                    path.display(),
+                    err
+                ))
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if certs.is_empty() {
+        return Err(ApiServerError::RequestError(format!(
+            "No certificate found in {}",
+            path.display()
+        )));
+    }
+
+    Ok(certs)
+}
+
+/// Load the client identity for mutual TLS.
+///
+/// With a key the certificate is read as PEM, otherwise as PKCS#12 protected
+/// by the optional passphrase.
+fn load_identity(
+    cert: &Path,
+    key: Option<&Path>,
+    password: Option<&str>,
+) -> Result<reqwest::Identity, ApiServerError> {
+    let read = |path: &Path| {
+        std::fs::read(path).map_err(|err| {
+            ApiServerError::ClientIdentityError(format!(
+                "error reading {}. Reason: {}",
+                path.display(),
+                err
+            ))
+        })
+    };
+
+    let cert_bytes = read(cert)?;
+
+    match key {
+        Some(key) => {
+            let key_bytes = read(key)?;
+            reqwest::Identity::from_pkcs8_pem(&cert_bytes, &key_bytes).map_err(|err| {
+                ApiServerError::ClientIdentityError(format!(
+                    "certificate {} and key {} are invalid or don't match. Reason: {}",
+                    cert.display(),
+                    key.display(),
+                    err
+                ))
+            })
+        }
+        None => {
+            if cert_bytes.starts_with(b"-----BEGIN") {
+                return Err(ApiServerError::ClientIdentityError(format!(
+                    "{} is a PEM certificate, the private key must be provided",
+                    cert.display()
+                )));
+            }
+
+            reqwest::Identity::from_pkcs12_der(&cert_bytes, password.unwrap_or_default()).map_err(
+                |err| {
+                    ApiServerError::ClientIdentityError(format!(
+                        "wrong passphrase or invalid PKCS#12 file {}. Reason: {}",
+                        cert.display(),
+                        err
+                    ))
+                },
+            )
+        }
+    }
+}
+
+/// Parse the `Retry-After` header, both in the delay-seconds and in the
+/// HTTP-date form.
+fn retry_after(response: &reqwest::Response) -> Option<Duration> {
+    let value = response.headers().get(RETRY_AFTER)?.to_str().ok()?.trim();
+
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    let delay = date.with_timezone(&chrono::Utc) - chrono::Utc::now();
+    Some(delay.to_std().unwrap_or(Duration::ZERO))
+}
+
+/// Exponential backoff with jitter for the given attempt, starting from 1.
+pub(super) fn retry_delay(attempt: u32) -> Duration {
+    let backoff = RETRY_BASE_DELAY
+        .saturating_mul(2u32.saturating_pow(attempt - 1))
+        .min(RETRY_MAX_DELAY);
+    let jitter = rand::thread_rng().gen_range(0..=backoff.as_millis() as u64 / 2);
+    backoff + Duration::from_millis(jitter)
+}
+
 #[async_trait]
 impl ApiServer for HttpApiServer {
     fn address(&self) -> &str {
-        &self.address
+        self.endpoint.as_str()
+    }
+    /// Check the server step by step: name resolution and TCP connection,
+    /// skipped when going through a proxy or a unix socket, then an
+    /// unauthenticated and an authenticated request, each sent once without
+    /// retries.
+    async fn ping(&self) -> Result<PingReport, ApiServerError> {
+        let host = self.address.host_str().expect("server address has a host");
+        let port = self
+            .address
+            .port_or_known_default()
+            .expect("http urls have a default port");
+
+        let mut resolved = Vec::new();
+        let (mut dns_ms, mut tcp_ms) = (None, None);
+
+        if self.proxied || self.endpoint.scheme() == "unix" {
+            tracing::info!("Not connecting directly, skipping DNS and TCP checks");
+        } else {
+            let start = Instant::now();
+            resolved =
+                tokio::time::timeout(self.connect_timeout, tokio::net::lookup_host((host, port)))
+                    .await
+                    .map_err(|_| ApiServerError::DnsError(format!("resolving {} timed out", host)))?
+                    .map_err(|err| ApiServerError::DnsError(format!("{}: {}", host, err)))?
+                    .collect();
+            dns_ms = Some(start.elapsed().as_millis());
+
+            let start = Instant::now();
+            let mut last_error = None;
+            for addr in &resolved {
+                match tokio::time::timeout(
+                    self.connect_timeout,
+                    tokio::net::TcpStream::connect(addr),
+                )
+                .await
+                {
+                    Ok(Ok(_)) => {
+                        tcp_ms = Some(start.elapsed().as_millis());
+                        break;
+                    }
+                    Ok(Err(err)) => last_error = Some(format!("{}: {}", addr, err)),
+                    Err(_) => last_error = Some(format!("{}: timed out", addr)),
+                }
+            }
+            if tcp_ms.is_none() {
+                return Err(ApiServerError::ConnectError(
+                    last_error.unwrap_or_else(|| format!("no address found for {}", host)),
+                ));
+            }
+        }
+
+        // Any response proves that the server is reachable
+        let request = self.request(UPDATES_ROUTE, Method::GET).build()?;
+        let start = Instant::now();
+        self.execute(request).await.map_err(connect_error)?;
+        let reachability_ms = start.elapsed().as_millis();
+
+        // The discovery of the api version, when not cached, is sent once too
+        self.api_version(false).await?;
+        let path = self.route(ORGANIZATION_ROUTE).await?;
+        let request = self
+            .authenticated_request(&path, Method::GET, None)
+            .build()?;
+        let start = Instant::now();
+        let response = self.execute(request).await.map_err(connect_error)?;
+        let authentication_ms = start.elapsed().as_millis();
+        if !response.status().is_success() {
+            return Err(api_error(&Method::GET, response).await);
+        }
+
+        Ok(PingReport {
+            address: self.endpoint.to_string(),
+            resolved,
+            dns_ms,
+            tcp_ms,
+            reachability_ms,
+            authentication_ms,
+        })
+    }
+
+    async fn server_version(&self) -> Result<ServerVersion, ApiServerError> {
+        let versions = self.versions(true).await?;
+
+        Ok(ServerVersion {
+            version: versions.server_version,
+            api_versions: versions.versions,
+            deprecated_api_versions: versions.deprecated,
+        })
     }
+
     async fn updates_check(&self) -> Result<LatestCliVersion, ApiServerError> {
         let response = self
-            .request(UPDATES_ROUTE, reqwest::Method::GET)
-            .send()
+            .send(|| self.request(UPDATES_ROUTE, reqwest::Method::GET))
             .await?;
         let response_status = response.status();
 
         if response_status == reqwest::StatusCode::OK {
-            let latest_version = response.json::<LatestCliVersion>().await?;
+            let latest_version = self.json::<LatestCliVersion>(response).await?;
             Ok(latest_version)
         } else {
-            let body = response.text().await?;
-            Err(ApiServerError::ApiError(body))
+            Err(api_error(&Method::GET, response).await)
         }
     }
 
     async fn create(
-        &mut self,
+        &self,
         fw_filepath: &str,
         fw_type: &str,
         fw_subtype: &str,
         name: &str,
         description: Option<&str>,
         organization: Option<&str>,
-    ) -> Result<Uuid, ApiServerError> {
+        tags: &[String],
+        options: &UploadOptions,
+    ) -> Result<ProjectCreated, ApiServerError> {
+        self.set_created_project(None);
+
+        if fw_filepath == "-" {
+            return self
+                .create_from_stdin(
+                    fw_type,
+                    fw_subtype,
+                    name,
+                    description,
+                    organization,
+                    tags,
+                    options,
+                )
+                .await;
+        }
+
         let path = Path::new(&fw_filepath);
         if !path.exists() || path.is_dir() {
             return Err(ApiServerError::RequestError(format!(
@@ -97,10 +1606,14 @@ impl ApiServer for HttpApiServer {
                 path.display()
             )));
         }
-        let fw_filename = path
-            .file_name()
-            .and_then(|s| s.to_str())
-            .map(|s| s.to_string())
+        let fw_filename = options
+            .filename
+            .clone()
+            .or_else(|| {
+                path.file_name()
+                    .and_then(|s| s.to_str())
+                    .map(|s| s.to_string())
+            })
             .ok_or_else(|| {
                 ApiServerError::RequestError(format!(
                     "Problem with image filename: {}",
@@ -109,185 +1622,547 @@ impl ApiServer for HttpApiServer {
             })?;
 
         // Prepare the file data
-        let bytes = crate::read_bytes_from_file(fw_filepath).unwrap(); //TODO: unwrap?
-        let part = reqwest::multipart::Part::bytes(bytes).file_name(fw_filename);
+        let fw_size = std::fs::metadata(path)
+            .map_err(|err| {
+                ApiServerError::RequestError(format!(
+                    "Error reading file {}. Reason: {}",
+                    path.display(),
+                    err
+                ))
+            })?
+            .len();
 
-        // Create the form
-        let mut form = reqwest::multipart::Form::new()
-            .text("name", name.to_string())
-            .text("type", fw_type.to_string())
-            .text("subtype", fw_subtype.to_string())
-            .part("file", part);
+        let progress = UploadProgress::new(Some(fw_size), self.progress && !options.hide_progress)
+            .with_limit(self.upload_limit);
+        let digest = UploadDigest::default();
+        let throttle = self.throttle.clone();
 
-        if let Some(descr) = description {
-            form = form.text("description", descr.to_string());
+        let compress = options.compress && !upload::is_compressed(path);
+        if options.compress && !compress {
+            tracing::info!("{} is already compressed, uploading as is", path.display());
         }
 
-        let org_id = match organization {
-            Some(o) => o.to_string(),
-            None => self
-                .organization_list()
-                .await?
-                .into_iter()
-                .find(|s| s.built_in)
-                .ok_or_else(|| ApiServerError::RequestError("No organization found".to_string()))?
-                .id
-                .to_string(),
+        // Create the form, rebuilt for each attempt. The file is streamed
+        // from disk so memory usage doesn't depend on its size.
+        let form = || {
+            progress.restart();
+            digest.reset();
+            let body = upload::file_body(path, &progress, &digest, compress, throttle.as_ref());
+            let part = if compress {
+                let mut headers = HeaderMap::new();
+                headers.insert(CONTENT_ENCODING, HeaderValue::from_static("gzip"));
+                reqwest::multipart::Part::stream(body).headers(headers)
+            } else {
+                reqwest::multipart::Part::stream_with_length(body, fw_size)
+            }
+            .file_name(fw_filename.clone());
+            project_form(part, &digest, name, fw_type, fw_subtype, description, tags)
         };
 
-        let path = format!("{}/{}/projects", ORGANIZATION_ROUTE_V1, org_id).to_string();
+        let org_id = self.upload_organization(organization).await?;
+
+        if options.resume {
+            if compress {
+                tracing::warn!("Compression is not supported by resumable uploads");
+            }
+
+            let project_created = self
+                .create_resumable(
+                    &org_id,
+                    path,
+                    &fw_filename,
+                    fw_type,
+                    fw_subtype,
+                    name,
+                    description,
+                    tags,
+                    &progress,
+                    throttle.as_ref(),
+                )
+                .await
+                .inspect_err(|_| progress.abandon())?;
+
+            if let Some(project_created) = project_created {
+                progress.finish();
+                return Ok(project_created);
+            }
+
+            tracing::info!("Resumable uploads not supported by the server, uploading in one go");
+        }
+
+        let path = format!(
+            "{}/{}/projects",
+            self.route(ORGANIZATION_ROUTE).await?,
+            org_id
+        );
 
         let response = self
-            .authenticated_request(&path, reqwest::Method::POST, None)
-            .await?
-            .multipart(form)
-            .send()
-            .await?;
+            .send(|| {
+                self.authenticated_request(&path, reqwest::Method::POST, None)
+                    .timeout(self.upload_timeout)
+                    .multipart(form())
+            })
+            .await
+            .inspect_err(|_| progress.abandon())?;
+        progress.finish();
 
         let response_status = response.status();
 
         if response_status == reqwest::StatusCode::OK {
-            let dto = response.json::<ProjectIdDTO>().await?;
-            Ok(dto.id)
+            let dto = self.json::<ProjectIdDTO>(response).await?;
+            self.set_created_project(Some(dto.id));
+            let sha256 = digest.hex();
+            verify_digest(&dto, &sha256)?;
+            Ok(ProjectCreated { id: dto.id, sha256 })
         } else {
-            let body = response.text().await?;
-            Err(ApiServerError::ApiError(body))
+            Err(api_error(&Method::POST, response).await)
+        }
+    }
+
+    async fn create_from_url(
+        &self,
+        firmware: &RemoteFirmware,
+        fw_type: &str,
+        fw_subtype: &str,
+        name: &str,
+        description: Option<&str>,
+        organization: Option<&str>,
+        tags: &[String],
+        options: &UploadOptions,
+    ) -> Result<ProjectCreated, ApiServerError> {
+        self.set_created_project(None);
+        let org_id = self.upload_organization(organization).await?;
+        let url = remote::redacted(&firmware.url);
+
+        // The server fetching the firmware itself saves the round trip
+        if !firmware.stream {
+            let path = format!(
+                "{}/{}/projects/fetch",
+                self.route(ORGANIZATION_ROUTE).await?,
+                org_id
+            );
+            let fetch = ProjectFetchRequest {
+                name,
+                r#type: fw_type,
+                subtype: fw_subtype,
+                description,
+                tags,
+                url: firmware.url.as_str(),
+                headers: firmware
+                    .headers
+                    .iter()
+                    .map(|(name, value)| {
+                        (
+                            name.as_str(),
+                            String::from_utf8_lossy(value.as_bytes()).into_owned(),
+                        )
+                    })
+                    .collect(),
+            };
+            let response = self
+                .send(|| {
+                    self.authenticated_request(&path, Method::POST, None)
+                        .json(&fetch)
+                })
+                .await?;
+
+            match response.status() {
+                StatusCode::OK | StatusCode::CREATED | StatusCode::ACCEPTED => {
+                    let dto = self.json::<ProjectIdDTO>(response).await?;
+                    self.set_created_project(Some(dto.id));
+                    tracing::info!("The server is fetching {}", url);
+                    return Ok(ProjectCreated {
+                        id: dto.id,
+                        sha256: dto
+                            .sha256
+                            .unwrap_or_else(|| "computed by the server once fetched".to_string()),
+                    });
+                }
+                StatusCode::NOT_FOUND
+                | StatusCode::METHOD_NOT_ALLOWED
+                | StatusCode::NOT_IMPLEMENTED => {
+                    tracing::info!("The server doesn't fetch urls, streaming the firmware");
+                }
+                _ => return Err(api_error(&Method::POST, response).await),
+            }
+        }
+
+        let source = Arc::new(remote::RemoteSource::new(
+            self.client.clone(),
+            firmware.clone(),
+            self.user_agent,
+            self.upload_timeout,
+            self.retries,
+        ));
+        let response = self.send(|| source.request(0, None)).await?;
+        source.check(&response)?;
+        let fw_filename = source.filename(&response);
+        let fw_size = response.content_length();
+        tracing::info!(
+            "Streaming {} ({}) from {}",
+            fw_filename,
+            fw_size.map_or("unknown size".to_string(), |size| HumanBytes(size)
+                .to_string()),
+            url
+        );
+
+        let progress = UploadProgress::new(fw_size, self.progress && !options.hide_progress)
+            .with_limit(self.upload_limit);
+        let digest = UploadDigest::default();
+        let throttle = self.throttle.clone();
+        let compress = options.compress && !upload::is_compressed(Path::new(&fw_filename));
+
+        // The response already received is streamed by the first attempt,
+        // the retries download the firmware again
+        let first = Mutex::new(Some(response));
+        let form = || {
+            progress.restart();
+            digest.reset();
+            let stream = source.stream(first.lock().expect("response lock").take());
+            let body = upload::stream_body(stream, &progress, &digest, compress, throttle.as_ref());
+            let part = match (compress, fw_size) {
+                (true, _) => {
+                    let mut headers = HeaderMap::new();
+                    headers.insert(CONTENT_ENCODING, HeaderValue::from_static("gzip"));
+                    reqwest::multipart::Part::stream(body).headers(headers)
+                }
+                (false, Some(size)) => reqwest::multipart::Part::stream_with_length(body, size),
+                (false, None) => reqwest::multipart::Part::stream(body),
+            }
+            .file_name(fw_filename.clone());
+            project_form(part, &digest, name, fw_type, fw_subtype, description, tags)
+        };
+
+        let path = format!(
+            "{}/{}/projects",
+            self.route(ORGANIZATION_ROUTE).await?,
+            org_id
+        );
+        let response = self
+            .send(|| {
+                self.authenticated_request(&path, Method::POST, None)
+                    .timeout(self.upload_timeout)
+                    .multipart(form())
+            })
+            .await
+            .inspect_err(|_| progress.abandon())?;
+        progress.finish();
+
+        if response.status() == StatusCode::OK {
+            let dto = self.json::<ProjectIdDTO>(response).await?;
+            self.set_created_project(Some(dto.id));
+            let sha256 = digest.hex();
+            verify_digest(&dto, &sha256)?;
+            Ok(ProjectCreated { id: dto.id, sha256 })
+        } else {
+            Err(api_error(&Method::POST, response).await)
         }
     }
 
     async fn overview(&mut self, project_id: &Uuid) -> Result<serde_json::Value, ApiServerError> {
-        let path = format!("{}/{}/overview", PROJECT_ROUTE_V1, project_id).to_string();
+        let path = format!(
+            "{}/{}/overview",
+            self.route(PROJECT_ROUTE).await?,
+            project_id
+        );
+
+        self.get_cached(&path, None, CacheKey::Overview(project_id))
+            .await
+    }
+
+    async fn report_pdf(
+        &mut self,
+        project_id: &Uuid,
+        output_path: &Path,
+    ) -> Result<u64, ApiServerError> {
+        let path = format!("{}/{}/report", self.route(PROJECT_ROUTE).await?, project_id);
+
+        let response = self
+            .send(|| {
+                self.authenticated_request(&path, reqwest::Method::GET, None)
+                    .header(ACCEPT, PDF_CONTENT_TYPE)
+                    .timeout(self.upload_timeout)
+            })
+            .await?;
+
+        if response.status() != reqwest::StatusCode::OK {
+            return Err(api_error(&Method::GET, response).await);
+        }
+
+        // Nothing is written when the server answers with something else
+        let content_type = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default();
+        let mime = content_type.split(';').next().unwrap_or_default().trim();
+        if !mime.eq_ignore_ascii_case(PDF_CONTENT_TYPE) {
+            return Err(ApiServerError::ResponseError(format!(
+                "Expected a PDF report, received content type '{}'",
+                content_type
+            )));
+        }
+
+        download::save_response(response, output_path, self.progress).await
+    }
+
+    async fn fetch_firmware(
+        &mut self,
+        project_id: &Uuid,
+        output_path: &Path,
+    ) -> Result<u64, ApiServerError> {
+        let path = format!(
+            "{}/{}/firmware",
+            self.route(PROJECT_ROUTE).await?,
+            project_id
+        );
 
         let response = self
-            .authenticated_request(&path, reqwest::Method::GET, None)
-            .await?
-            .send()
+            .send(|| {
+                self.authenticated_request(&path, reqwest::Method::GET, None)
+                    .timeout(self.upload_timeout)
+            })
             .await?;
+
         if response.status() == reqwest::StatusCode::OK {
-            let overview = response.json().await?;
-            Ok(overview)
+            download::save_response(response, output_path, self.progress).await
         } else {
-            let body = response.text().await?;
-            Err(ApiServerError::ApiError(body))
+            Err(api_error(&Method::GET, response).await)
         }
     }
 
-    async fn report(&mut self, project_id: &Uuid, savepath: &Path) -> Result<(), ApiServerError> {
-        let path = format!("{}/{}/report", PROJECT_ROUTE_V1, project_id).to_string();
+    async fn status(&mut self, project_id: &Uuid) -> Result<ProjectStatus, ApiServerError> {
+        let path = format!("{}/{}/status", self.route(PROJECT_ROUTE).await?, project_id);
 
         let response = self
-            .authenticated_request(&path, reqwest::Method::GET, None)
-            .await?
-            .send()
+            .send(|| self.authenticated_request(&path, reqwest::Method::GET, None))
             .await?;
 
-        let status = response.status();
-
-        if status == reqwest::StatusCode::OK {
-            let bytes = response.bytes().await?;
-            let mut f = File::create(savepath).map_err(|err| {
-                ApiServerError::RequestError(format!(
-                    "Error creating file to: {}. Reason: {}",
-                    savepath.display(),
-                    err
-                ))
-            })?;
-            f.write_all(bytes.as_ref()).map_err(|err| {
-                ApiServerError::RequestError(format!(
-                    "Error writing data to file: {}. Reason: {}",
-                    savepath.display(),
-                    err
-                ))
-            })?;
-            Ok(())
+        if response.status() == reqwest::StatusCode::OK {
+            self.json(response).await
         } else {
-            let body = response.text().await?;
+            Err(api_error(&Method::GET, response).await)
+        }
+    }
+
+    async fn status_events(
+        &self,
+        project_id: &Uuid,
+        timeout: Duration,
+    ) -> Result<Option<StatusEvents>, ApiServerError> {
+        let path = format!(
+            "{}/{}/status/events",
+            self.route(PROJECT_ROUTE).await?,
+            project_id
+        );
 
-            Err(ApiServerError::ApiError(body))
+        let response = self
+            .send(|| {
+                self.authenticated_request(&path, reqwest::Method::GET, None)
+                    .header(ACCEPT, "text/event-stream")
+                    .timeout(timeout)
+            })
+            .await?;
+
+        match response.status() {
+            StatusCode::OK if events::is_event_stream(&response) => {
+                Ok(Some(events::statuses(response)))
+            }
+            // Servers without events, the status being polled instead
+            StatusCode::OK
+            | StatusCode::NOT_FOUND
+            | StatusCode::METHOD_NOT_ALLOWED
+            | StatusCode::NOT_ACCEPTABLE
+            | StatusCode::NOT_IMPLEMENTED => Ok(None),
+            _ => Err(api_error(&Method::GET, response).await),
         }
     }
 
     async fn analysis(
-        &mut self,
+        &self,
         project_id: &Uuid,
         analysis: &Analysis,
         page: i32,
         per_page: i32,
     ) -> Result<ProjectAnalysis, ApiServerError> {
-        let path = format!("{}/{}/analysis/{}", PROJECT_ROUTE_V1, project_id, analysis).to_string();
+        let path = format!(
+            "{}/{}/analysis/{}",
+            self.route(PROJECT_ROUTE).await?,
+            project_id,
+            analysis
+        );
         let query = [
             ("page", &page.to_string()),
             ("per_page", &per_page.to_string()),
         ];
         let response = self
-            .authenticated_request(&path, reqwest::Method::GET, Some(&query))
-            .await?
-            .send()
+            .send(|| self.authenticated_request(&path, reqwest::Method::GET, Some(&query)))
             .await?;
         if response.status() == reqwest::StatusCode::OK {
-            let res = response.json().await?;
+            let res = self.json(response).await?;
+            self.cache_response(
+                CacheKey::Analysis {
+                    project_id,
+                    analysis,
+                    page,
+                    per_page,
+                },
+                &res,
+                Validators::default(),
+            );
             Ok(res)
         } else {
-            let body = response.text().await?;
-            Err(ApiServerError::ApiError(body))
+            Err(api_error(&Method::GET, response).await)
         }
     }
 
-    async fn delete(&mut self, project_id: &Uuid) -> Result<(), ApiServerError> {
-        let path = format!("{}/{}", PROJECT_ROUTE_V1, project_id).to_string();
+    async fn delete(&self, project_id: &Uuid) -> Result<(), ApiServerError> {
+        let path = format!("{}/{}", self.route(PROJECT_ROUTE).await?, project_id);
 
         let response = self
-            .authenticated_request(&path, reqwest::Method::DELETE, None)
-            .await?
-            .send()
+            .send(|| self.authenticated_request(&path, reqwest::Method::DELETE, None))
             .await?;
         if response.status() == reqwest::StatusCode::OK {
             Ok(())
         } else {
-            let body = response.text().await?;
-            Err(ApiServerError::ApiError(body))
+            Err(api_error(&Method::DELETE, response).await)
         }
     }
 
-    async fn list_projects(&mut self) -> Result<Vec<Project>, ApiServerError> {
-        let organizations = self.organization_list().await?;
+    async fn update(
+        &self,
+        project_id: &Uuid,
+        name: Option<&str>,
+        description: Option<&str>,
+    ) -> Result<(), ApiServerError> {
+        let path = format!("{}/{}", self.route(PROJECT_ROUTE).await?, project_id);
+        let update = ProjectUpdateDTO { name, description };
 
-        let mut projects: Vec<Project> = vec![];
-        for o in organizations {
-            let path = format!("{}/{}/projects", ORGANIZATION_ROUTE_V1, o.id).to_string();
+        let response = self
+            .send(|| {
+                self.authenticated_request(&path, Method::PATCH, None)
+                    .json(&update)
+            })
+            .await?;
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(api_error(&Method::PATCH, response).await)
+        }
+    }
 
-            let response = self
-                .authenticated_request(&path, reqwest::Method::GET, None)
-                .await?
-                .send()
-                .await?;
+    async fn validate_upload(
+        &self,
+        validation: &UploadValidationDTO<'_>,
+    ) -> Result<bool, ApiServerError> {
+        let path = format!("{}/validate", self.route(PROJECT_ROUTE).await?);
 
-            if response.status() == reqwest::StatusCode::OK {
-                let current_projects = response.json::<Vec<Project>>().await?;
-                let current_projects = current_projects.into_iter().map(|mut x| {
-                    x.organization_name = Some(o.name.clone());
-                    x
-                });
-                projects.extend(current_projects);
-            }
+        let response = self
+            .send(|| {
+                self.authenticated_request(&path, Method::POST, None)
+                    .json(validation)
+            })
+            .await?;
+        match response.status() {
+            status if status.is_success() => Ok(true),
+            // Servers without the validation
+            StatusCode::NOT_FOUND
+            | StatusCode::METHOD_NOT_ALLOWED
+            | StatusCode::NOT_IMPLEMENTED => Ok(false),
+            _ => Err(api_error(&Method::POST, response).await),
         }
-        Ok(projects)
     }
 
-    async fn organization_list(&mut self) -> Result<Vec<OrganizationData>, ApiServerError> {
+    async fn rescan(&self, project_id: &Uuid) -> Result<RescanJob, ApiServerError> {
+        let path = format!("{}/{}/rescan", self.route(PROJECT_ROUTE).await?, project_id);
+
         let response = self
-            .authenticated_request(ORGANIZATION_ROUTE_V1, reqwest::Method::GET, None)
-            .await?
-            .send()
+            .send(|| self.authenticated_request(&path, Method::POST, None))
             .await?;
+        if response.status().is_success() {
+            self.json::<RescanJob>(response).await
+        } else {
+            Err(api_error(&Method::POST, response).await)
+        }
+    }
 
-        if response.status() == reqwest::StatusCode::OK {
-            let orgs: Vec<OrganizationData> = response.json::<Vec<OrganizationData>>().await?;
-            Ok(orgs)
+    async fn tag(
+        &self,
+        project_id: &Uuid,
+        add: &[String],
+        remove: &[String],
+    ) -> Result<Vec<String>, ApiServerError> {
+        let path = format!("{}/{}/tags", self.route(PROJECT_ROUTE).await?, project_id);
+        let update = ProjectTagsUpdateDTO { add, remove };
+
+        let response = self
+            .send(|| {
+                self.authenticated_request(&path, Method::PATCH, None)
+                    .json(&update)
+            })
+            .await?;
+        if response.status().is_success() {
+            Ok(self.json::<ProjectTagsDTO>(response).await?.tags)
         } else {
-            let body = response.text().await?;
-            Err(ApiServerError::ApiError(body))
+            Err(api_error(&Method::PATCH, response).await)
+        }
+    }
+
+    async fn list_projects(&mut self) -> Result<Vec<Project>, ApiServerError> {
+        let organizations = self.organization_list().await?;
+
+        let mut projects: Vec<Project> = vec![];
+        for o in organizations {
+            let path = format!(
+                "{}/{}/projects",
+                self.route(ORGANIZATION_ROUTE).await?,
+                o.id
+            );
+
+            let current_projects = match self
+                .get_cached::<Vec<Project>>(&path, None, CacheKey::OrganizationProjects(&o.id))
+                .await
+            {
+                Ok(current_projects) => current_projects,
+                // Organizations whose projects can't be listed are skipped
+                Err(err) if err.response().is_some() => continue,
+                Err(err) => return Err(err),
+            };
+            let current_projects = current_projects.into_iter().map(|mut x| {
+                x.organization_name = Some(o.name.clone());
+                x
+            });
+            projects.extend(current_projects);
         }
+        self.cache_response(CacheKey::Projects, &projects, Validators::default());
+        Ok(projects)
+    }
+
+    async fn list_projects_paged(
+        &mut self,
+        page: u32,
+        per_page: u32,
+        filter: &ProjectFilter,
+    ) -> Result<Vec<Project>, ApiServerError> {
+        let path = self.route(PROJECT_ROUTE).await?;
+        let mut query = vec![
+            ("page", page.to_string()),
+            ("per_page", per_page.to_string()),
+        ];
+        query.extend(filter.query());
+        let query: Vec<_> = query.iter().map(|(name, value)| (*name, value)).collect();
+
+        let key = CacheKey::ProjectsPage {
+            page,
+            per_page,
+            filter,
+        };
+        self.get_cached(&path, Some(&query), key).await
+    }
+
+    async fn organization_list(&self) -> Result<Vec<OrganizationData>, ApiServerError> {
+        let path = self.route(ORGANIZATION_ROUTE).await?;
+        self.get_cached(&path, None, CacheKey::Organizations).await
     }
 
     async fn organization_create(
@@ -300,88 +2175,206 @@ impl ApiServer for HttpApiServer {
         form.insert("name", name.to_string());
         form.insert("description", description.to_string());
 
+        let path = self.route(ORGANIZATION_ROUTE).await?;
         let response = self
-            .authenticated_request(ORGANIZATION_ROUTE_V1, reqwest::Method::POST, None)
-            .await?
-            .json(&form)
-            .send()
+            .send(|| {
+                self.authenticated_request(&path, reqwest::Method::POST, None)
+                    .json(&form)
+            })
             .await?;
 
         if response.status() == reqwest::StatusCode::CREATED {
             Ok(())
         } else {
-            let body = response.text().await?;
-            Err(ApiServerError::ApiError(body))
+            Err(api_error(&Method::POST, response).await)
         }
     }
 
     async fn organization_delete(&mut self, id: &Uuid) -> Result<(), ApiServerError> {
-        let path = format!("{}/{}", ORGANIZATION_ROUTE_V1, id).to_string();
+        let path = format!("{}/{}", self.route(ORGANIZATION_ROUTE).await?, id);
 
         let response = self
-            .authenticated_request(&path, reqwest::Method::DELETE, None)
-            .await?
-            .send()
+            .send(|| self.authenticated_request(&path, reqwest::Method::DELETE, None))
             .await?;
 
         if response.status() == reqwest::StatusCode::NO_CONTENT {
             Ok(())
         } else {
-            let body = response.text().await?;
-            Err(ApiServerError::ApiError(body))
+            Err(api_error(&Method::DELETE, response).await)
         }
     }
 
     async fn apikey_create(&mut self) -> Result<ApiKeyData, ApiServerError> {
+        let path = self.route(APIKEY_ROUTE).await?;
         let response = self
-            .authenticated_request(APIKEY_ROUTE_V1, reqwest::Method::POST, None)
-            .await?
-            .send()
+            .send(|| self.authenticated_request(&path, reqwest::Method::POST, None))
             .await?;
 
         if response.status() == reqwest::StatusCode::OK {
-            let apikey = response.json().await?;
+            let apikey = self.json(response).await?;
             Ok(apikey)
         } else if response.status() == reqwest::StatusCode::BAD_REQUEST {
-            Err(ApiServerError::ApiError(
+            let path = response.url().path().to_string();
+            let mut error = ApiErrorResponse::parse(
+                Method::POST.as_str(),
+                &path,
+                StatusCode::BAD_REQUEST.as_u16(),
                 "API key already present!".to_string(),
-            ))
+            );
+            error.request_id = request_id(&response);
+            Err(ApiServerError::Conflict(Box::new(error)))
         } else {
-            let body = response.text().await?;
-            Err(ApiServerError::ApiError(body))
+            Err(api_error(&Method::POST, response).await)
         }
     }
 
-    async fn apikey_list(&mut self) -> Result<Option<ApiKeyData>, ApiServerError> {
+    async fn apikey_list(&self) -> Result<Vec<ApiKeyData>, ApiServerError> {
+        let path = self.route(APIKEY_ROUTE).await?;
         let response = self
-            .authenticated_request(APIKEY_ROUTE_V1, reqwest::Method::GET, None)
-            .await?
-            .send()
+            .send(|| self.authenticated_request(&path, reqwest::Method::GET, None))
             .await?;
 
         if response.status() == reqwest::StatusCode::OK {
-            let apikey = response.json().await?;
-            Ok(Some(apikey))
+            // A single key for now, the list of them should accounts get several
+            #[derive(Deserialize)]
+            #[serde(untagged)]
+            enum ApiKeys {
+                One(ApiKeyData),
+                Many(Vec<ApiKeyData>),
+            }
+            Ok(match self.json(response).await? {
+                ApiKeys::One(apikey) => vec![apikey],
+                ApiKeys::Many(apikeys) => apikeys,
+            })
         } else if response.status() == reqwest::StatusCode::NO_CONTENT {
-            Ok(None)
+            Ok(vec![])
         } else {
-            let body = response.text().await?;
-            Err(ApiServerError::ApiError(body))
+            Err(api_error(&Method::GET, response).await)
         }
     }
 
     async fn apikey_delete(&mut self) -> Result<(), ApiServerError> {
+        let path = self.route(APIKEY_ROUTE).await?;
         let response = self
-            .authenticated_request(APIKEY_ROUTE_V1, reqwest::Method::DELETE, None)
-            .await?
-            .send()
+            .send(|| self.authenticated_request(&path, reqwest::Method::DELETE, None))
             .await?;
 
         if response.status() == reqwest::StatusCode::OK {
             Ok(())
         } else {
-            let body = response.text().await?;
-            Err(ApiServerError::ApiError(body))
+            Err(api_error(&Method::DELETE, response).await)
+        }
+    }
+
+    async fn apikey_rotate(&mut self) -> Result<ApiKeyData, ApiServerError> {
+        let previous = self.apikey.clone();
+        let apikey = match self.apikey_create().await {
+            Ok(apikey) => apikey,
+            // Replacing the single key would leave the account without one
+            Err(ApiServerError::Conflict(_)) => {
+                return Err(ApiServerError::RequestError(
+                    "The server allows a single api key per account, which can't be rotated without leaving the account without a key. The api key is unchanged, replace it from the web application".to_string(),
+                ))
+            }
+            Err(err) => return Err(err),
+        };
+
+        // The new key must work before the previous one is deleted
+        self.apikey = apikey.api_key.to_string();
+        let listed = match self.apikey_list().await {
+            Ok(listed) => listed,
+            Err(err) => {
+                self.apikey = previous;
+                return Err(ApiServerError::RequestError(format!(
+                    "The new api key was created but is rejected by the server, the previous one is kept and still valid. Reason: {}",
+                    err
+                )));
+            }
+        };
+
+        // Deleted by its id, with the new key
+        let previous_id = listed
+            .iter()
+            .find(|listed| listed.api_key.to_string() == previous)
+            .map(ApiKeyData::id)
+            .or_else(|| previous.parse().ok());
+        let deleted = match previous_id {
+            Some(id) => self.apikey_delete_id(&id).await,
+            None => Err(ApiServerError::RequestError(
+                "the previous api key has no id".to_string(),
+            )),
+        };
+        if let Err(err) = deleted {
+            tracing::warn!(
+                "The new api key was created but the previous one couldn't be deleted, it is still valid: {}",
+                err
+            );
+        }
+
+        Ok(apikey)
+    }
+
+    async fn usage(&self) -> Result<Usage, ApiServerError> {
+        let path = self.route(USAGE_ROUTE).await?;
+        let response = self
+            .send(|| self.authenticated_request(&path, reqwest::Method::GET, None))
+            .await?;
+
+        if response.status() == reqwest::StatusCode::OK {
+            self.json(response).await
+        } else {
+            Err(api_error(&Method::GET, response).await)
+        }
+    }
+
+    async fn webhooks(&self, project: Option<&Uuid>) -> Result<Vec<WebhookData>, ApiServerError> {
+        let path = self.route(WEBHOOK_ROUTE).await?;
+        let project = project.map(Uuid::to_string);
+        let query = project.as_ref().map(|project| [("project_id", project)]);
+        let response = self
+            .send(|| {
+                self.authenticated_request(
+                    &path,
+                    reqwest::Method::GET,
+                    query.as_ref().map(|query| query.as_slice()),
+                )
+            })
+            .await?;
+
+        match response.status() {
+            reqwest::StatusCode::OK => self.json(response).await,
+            reqwest::StatusCode::NO_CONTENT => Ok(vec![]),
+            _ => Err(api_error(&Method::GET, response).await),
+        }
+    }
+
+    async fn webhook_create(
+        &self,
+        webhook: &WebhookCreateDTO<'_>,
+    ) -> Result<WebhookData, ApiServerError> {
+        let path = self.route(WEBHOOK_ROUTE).await?;
+        let response = self
+            .send(|| {
+                self.authenticated_request(&path, reqwest::Method::POST, None)
+                    .json(webhook)
+            })
+            .await?;
+
+        match response.status() {
+            reqwest::StatusCode::OK | reqwest::StatusCode::CREATED => self.json(response).await,
+            _ => Err(api_error(&Method::POST, response).await),
+        }
+    }
+
+    async fn webhook_delete(&self, id: &Uuid) -> Result<(), ApiServerError> {
+        let path = format!("{}/{}", self.route(WEBHOOK_ROUTE).await?, id);
+        let response = self
+            .send(|| self.authenticated_request(&path, reqwest::Method::DELETE, None))
+            .await?;
+
+        match response.status() {
+            reqwest::StatusCode::OK | reqwest::StatusCode::NO_CONTENT => Ok(()),
+            _ => Err(api_error(&Method::DELETE, response).await),
         }
     }
 }
@@ -0,0 +1,266 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use std::{path::Path, sync::Mutex, time::Duration};
+use uuid::Uuid;
+
+use crate::{
+    cli::{Analysis, ProjectFilter},
+    services::{
+        apikey_service::ApiKeyData,
+        organization_service::OrganizationData,
+        project_service::{
+            Project, ProjectAnalysis, ProjectCreated, ProjectStatus, RescanJob, UploadValidationDTO,
+        },
+        usage_service::Usage,
+        webhook_service::{WebhookCreateDTO, WebhookData},
+    },
+};
+
+use super::{
+    cache::{CacheKey, ResponseCache},
+    ApiServer, ApiServerError, LatestCliVersion, PingReport, RemoteFirmware, ServerVersion,
+    StatusEvents, UploadOptions,
+};
+
+/// Server answering from the responses cached by previous calls, without
+/// making any request.
+#[derive(Debug)]
+pub struct OfflineApiServer {
+    cache: ResponseCache,
+    cached_at: Mutex<Option<DateTime<Utc>>>,
+}
+
+impl OfflineApiServer {
+    pub fn new(cache: ResponseCache) -> Self {
+        Self {
+            cache,
+            cached_at: Mutex::new(None),
+        }
+    }
+
+    /// When the oldest of the served responses was cached, if any.
+    pub fn cached_at(&self) -> Option<DateTime<Utc>> {
+        *self.cached_at.lock().expect("cached_at lock")
+    }
+
+    fn load<T: DeserializeOwned>(&self, key: CacheKey<'_>) -> Result<T, ApiServerError> {
+        let cached = self
+            .cache
+            .load::<T>(&key)
+            .map_err(|err| {
+                ApiServerError::ResponseError(format!(
+                    "Invalid cached response for {}. Reason: {}",
+                    key, err
+                ))
+            })?
+            .ok_or_else(|| ApiServerError::NotCached(key.to_string()))?;
+
+        // Show the oldest of the responses served
+        let mut cached_at = self.cached_at.lock().expect("cached_at lock");
+        *cached_at = Some(cached_at.map_or(cached.cached_at, |at| at.min(cached.cached_at)));
+        Ok(cached.response)
+    }
+}
+
+fn unavailable<T>(operation: &str) -> Result<T, ApiServerError> {
+    Err(ApiServerError::RequestError(format!(
+        "{} is not available with --offline",
+        operation
+    )))
+}
+
+#[async_trait]
+impl ApiServer for OfflineApiServer {
+    fn address(&self) -> &str {
+        "offline"
+    }
+
+    async fn ping(&self) -> Result<PingReport, ApiServerError> {
+        unavailable("Pinging the server")
+    }
+
+    async fn server_version(&self) -> Result<ServerVersion, ApiServerError> {
+        unavailable("Querying the server version")
+    }
+
+    async fn updates_check(&self) -> Result<LatestCliVersion, ApiServerError> {
+        unavailable("Checking for updates")
+    }
+
+    async fn create(
+        &self,
+        _fw_filepath: &str,
+        _fw_type: &str,
+        _fw_subtype: &str,
+        _name: &str,
+        _description: Option<&str>,
+        _organization: Option<&str>,
+        _tags: &[String],
+        _options: &UploadOptions,
+    ) -> Result<ProjectCreated, ApiServerError> {
+        unavailable("Creating a project")
+    }
+
+    async fn create_from_url(
+        &self,
+        _firmware: &RemoteFirmware,
+        _fw_type: &str,
+        _fw_subtype: &str,
+        _name: &str,
+        _description: Option<&str>,
+        _organization: Option<&str>,
+        _tags: &[String],
+        _options: &UploadOptions,
+    ) -> Result<ProjectCreated, ApiServerError> {
+        unavailable("Creating a project")
+    }
+
+    async fn overview(&mut self, project_id: &Uuid) -> Result<serde_json::Value, ApiServerError> {
+        self.load(CacheKey::Overview(project_id))
+    }
+
+    async fn status(&mut self, _project_id: &Uuid) -> Result<ProjectStatus, ApiServerError> {
+        unavailable("Checking the status of a project")
+    }
+
+    async fn status_events(
+        &self,
+        _project_id: &Uuid,
+        _timeout: Duration,
+    ) -> Result<Option<StatusEvents>, ApiServerError> {
+        unavailable("Watching a project")
+    }
+
+    async fn analysis(
+        &self,
+        project_id: &Uuid,
+        analysis: &Analysis,
+        page: i32,
+        per_page: i32,
+    ) -> Result<ProjectAnalysis, ApiServerError> {
+        self.load(CacheKey::Analysis {
+            project_id,
+            analysis,
+            page,
+            per_page,
+        })
+    }
+
+    async fn delete(&self, _project_id: &Uuid) -> Result<(), ApiServerError> {
+        unavailable("Deleting a project")
+    }
+
+    async fn update(
+        &self,
+        _project_id: &Uuid,
+        _name: Option<&str>,
+        _description: Option<&str>,
+    ) -> Result<(), ApiServerError> {
+        unavailable("Editing a project")
+    }
+
+    async fn rescan(&self, _project_id: &Uuid) -> Result<RescanJob, ApiServerError> {
+        unavailable("Rescanning a project")
+    }
+
+    async fn tag(
+        &self,
+        _project_id: &Uuid,
+        _add: &[String],
+        _remove: &[String],
+    ) -> Result<Vec<String>, ApiServerError> {
+        unavailable("Tagging a project")
+    }
+
+    async fn report_pdf(
+        &mut self,
+        _project_id: &Uuid,
+        _output_path: &Path,
+    ) -> Result<u64, ApiServerError> {
+        unavailable("Downloading a report")
+    }
+
+    async fn fetch_firmware(
+        &mut self,
+        _project_id: &Uuid,
+        _output_path: &Path,
+    ) -> Result<u64, ApiServerError> {
+        unavailable("Downloading a firmware")
+    }
+
+    async fn validate_upload(
+        &self,
+        _validation: &UploadValidationDTO<'_>,
+    ) -> Result<bool, ApiServerError> {
+        unavailable("Validating an upload")
+    }
+
+    async fn list_projects(&mut self) -> Result<Vec<Project>, ApiServerError> {
+        self.load(CacheKey::Projects)
+    }
+
+    async fn list_projects_paged(
+        &mut self,
+        page: u32,
+        per_page: u32,
+        filter: &ProjectFilter,
+    ) -> Result<Vec<Project>, ApiServerError> {
+        self.load(CacheKey::ProjectsPage {
+            page,
+            per_page,
+            filter,
+        })
+    }
+
+    async fn organization_create(
+        &mut self,
+        _name: &str,
+        _description: &str,
+    ) -> Result<(), ApiServerError> {
+        unavailable("Creating an organization")
+    }
+
+    async fn organization_list(&self) -> Result<Vec<OrganizationData>, ApiServerError> {
+        unavailable("Listing organizations")
+    }
+
+    async fn organization_delete(&mut self, _id: &Uuid) -> Result<(), ApiServerError> {
+        unavailable("Deleting an organization")
+    }
+
+    async fn apikey_create(&mut self) -> Result<ApiKeyData, ApiServerError> {
+        unavailable("Creating an api key")
+    }
+
+    async fn apikey_list(&self) -> Result<Vec<ApiKeyData>, ApiServerError> {
+        unavailable("Listing api keys")
+    }
+
+    async fn apikey_delete(&mut self) -> Result<(), ApiServerError> {
+        unavailable("Deleting an api key")
+    }
+
+    async fn apikey_rotate(&mut self) -> Result<ApiKeyData, ApiServerError> {
+        unavailable("Rotating an api key")
+    }
+
+    async fn usage(&self) -> Result<Usage, ApiServerError> {
+        unavailable("Showing the usage of the account")
+    }
+
+    async fn webhooks(&self, _project: Option<&Uuid>) -> Result<Vec<WebhookData>, ApiServerError> {
+        unavailable("Listing webhooks")
+    }
+
+    async fn webhook_create(
+        &self,
+        _webhook: &WebhookCreateDTO<'_>,
+    ) -> Result<WebhookData, ApiServerError> {
+        unavailable("Adding a webhook")
+    }
+
+    async fn webhook_delete(&self, _id: &Uuid) -> Result<(), ApiServerError> {
+        unavailable("Removing a webhook")
+    }
+}
@@ -0,0 +1,882 @@
+//! Images pulled from container registries through their HTTP API v2,
+//! without a docker daemon, for `create --docker-image`.
+
+use std::{
+    fmt,
+    io::IsTerminal,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    str::FromStr,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use futures::{StreamExt, TryStreamExt};
+use indicatif::{HumanBytes, ProgressBar, ProgressStyle};
+use reqwest::{
+    header::{ACCEPT, AUTHORIZATION, CONTENT_TYPE, USER_AGENT, WWW_AUTHENTICATE},
+    StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::{io::AsyncWriteExt, sync::RwLock};
+
+use super::{ApiServerError, PlainClient};
+
+/// Registry of the images without one, Docker Hub
+const DOCKER_HUB: &str = "docker.io";
+/// Host of the api of Docker Hub
+const DOCKER_HUB_API: &str = "registry-1.docker.io";
+/// Key of the credentials of Docker Hub in the docker configuration
+const DOCKER_HUB_AUTH_KEY: &str = "https://index.docker.io/v1/";
+
+const MANIFEST_LIST: &str = "application/vnd.docker.distribution.manifest.list.v2+json";
+const OCI_INDEX: &str = "application/vnd.oci.image.index.v1+json";
+const MANIFEST_V2: &str = "application/vnd.docker.distribution.manifest.v2+json";
+const OCI_MANIFEST: &str = "application/vnd.oci.image.manifest.v1+json";
+
+/// Layers downloaded at the same time
+const CONCURRENT_LAYERS: usize = 4;
+
+/// Reference of an image in a registry, e.g. `registry.example.com/app:1.2.3`,
+/// `alpine` or `ghcr.io/org/app@sha256:...`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageReference {
+    /// Host of the registry, with its port if any
+    pub registry: String,
+    pub repository: String,
+    /// Tag, `latest` unless given
+    pub tag: Option<String>,
+    /// Digest of the manifest, `sha256:...`
+    pub digest: Option<String>,
+}
+
+impl FromStr for ImageReference {
+    type Err = String;
+
+    fn from_str(image: &str) -> Result<Self, Self::Err> {
+        let invalid = |reason: &str| format!("invalid image `{image}`, {reason}");
+
+        let (name, digest) = match image.split_once('@') {
+            Some((name, digest)) => {
+                blob_hex(digest).map_err(|_| invalid("expected a sha256 digest after @"))?;
+                (name, Some(digest.to_string()))
+            }
+            None => (image, None),
+        };
+        // The tag follows the last `:` after the last `/`, the other ones are ports
+        let (name, tag) = match name.rsplit_once(':') {
+            Some((repository, tag)) if !tag.contains('/') => (repository, Some(tag.to_string())),
+            _ => (name, None),
+        };
+        if let Some(tag) = &tag {
+            let valid = !tag.is_empty()
+                && tag.len() <= 128
+                && !tag.starts_with(['.', '-'])
+                && tag
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-'));
+            if !valid {
+                return Err(invalid(
+                    "expected a tag of letters, digits, `_`, `.` and `-`",
+                ));
+            }
+        }
+
+        let (registry, repository) = match name.split_once('/') {
+            Some((host, repository)) if host.contains(['.', ':']) || host == "localhost" => {
+                (host.to_string(), repository.to_string())
+            }
+            _ => (DOCKER_HUB.to_string(), name.to_string()),
+        };
+        let repository = match registry == DOCKER_HUB && !repository.contains('/') {
+            true => format!("library/{repository}"),
+            false => repository,
+        };
+        let valid = !repository.is_empty()
+            && repository.split('/').all(|component| {
+                !component.is_empty()
+                    && component.chars().all(|c| {
+                        c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '.' | '_' | '-')
+                    })
+            });
+        if !valid {
+            return Err(invalid(
+                "expected e.g. registry.example.com/app:1.2.3, names being lowercase",
+            ));
+        }
+
+        Ok(Self {
+            registry,
+            repository,
+            tag: tag.or_else(|| digest.is_none().then(|| "latest".to_string())),
+            digest,
+        })
+    }
+}
+
+impl fmt::Display for ImageReference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.registry, self.repository)?;
+        if let Some(tag) = &self.tag {
+            write!(f, ":{}", tag)?;
+        }
+        if let Some(digest) = &self.digest {
+            write!(f, "@{}", digest)?;
+        }
+        Ok(())
+    }
+}
+
+impl ImageReference {
+    /// Tag or digest of the manifest
+    fn reference(&self) -> &str {
+        self.digest
+            .as_deref()
+            .or(self.tag.as_deref())
+            .unwrap_or("latest")
+    }
+
+    /// Base url of the api of the registry. Registries on localhost are
+    /// reached over http, as docker does.
+    fn api_url(&self) -> String {
+        let host = match self.registry.as_str() {
+            DOCKER_HUB => DOCKER_HUB_API,
+            host => host,
+        };
+        let local = ["localhost", "127.0.0.1", "[::1]"]
+            .iter()
+            .any(|local| host == *local || host.starts_with(&format!("{local}:")));
+        match local {
+            true => format!("http://{host}/v2/{}", self.repository),
+            false => format!("https://{host}/v2/{}", self.repository),
+        }
+    }
+
+    /// Name of the archive of the image, e.g. `app_1.2.3`.
+    pub fn file_name(&self) -> String {
+        let name = self.repository.rsplit('/').next().unwrap_or("image");
+        match &self.tag {
+            Some(tag) => format!("{name}_{tag}"),
+            None => name.to_string(),
+        }
+    }
+
+    /// Name and tag in the `docker save` manifest, e.g. `app:1.2.3`.
+    fn repo_tag(&self) -> Option<String> {
+        let name = match self.registry.as_str() {
+            DOCKER_HUB => self
+                .repository
+                .strip_prefix("library/")
+                .unwrap_or(&self.repository)
+                .to_string(),
+            registry => format!("{}/{}", registry, self.repository),
+        };
+        self.tag.as_ref().map(|tag| format!("{name}:{tag}"))
+    }
+}
+
+/// Platform of an image, e.g. `linux/arm64` or `linux/arm/v7`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Platform {
+    pub os: String,
+    pub architecture: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub variant: Option<String>,
+}
+
+impl FromStr for Platform {
+    type Err = String;
+
+    fn from_str(platform: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = platform.split('/').collect();
+        match parts[..] {
+            [os, architecture] | [os, architecture, _]
+                if !os.is_empty() && !architecture.is_empty() =>
+            {
+                Ok(Self {
+                    os: os.to_string(),
+                    architecture: architecture.to_string(),
+                    variant: parts.get(2).map(|variant| variant.to_string()),
+                })
+            }
+            _ => Err(format!(
+                "invalid platform `{platform}`, expected e.g. linux/arm64 or linux/arm/v7"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.os, self.architecture)?;
+        match &self.variant {
+            Some(variant) => write!(f, "/{}", variant),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Platform {
+    /// Linux on the architecture of this machine, the default of docker.
+    pub fn current() -> Self {
+        let architecture = match std::env::consts::ARCH {
+            "x86_64" => "amd64",
+            "aarch64" => "arm64",
+            "x86" => "386",
+            "powerpc64" => "ppc64le",
+            arch => arch,
+        };
+        Self {
+            os: "linux".to_string(),
+            architecture: architecture.to_string(),
+            variant: None,
+        }
+    }
+
+    /// Whether an image for `platform` runs on this one, variants only
+    /// compared when given.
+    fn matches(&self, platform: &Platform) -> bool {
+        self.os == platform.os
+            && self.architecture == platform.architecture
+            && (self.variant.is_none() || self.variant == platform.variant)
+    }
+}
+
+/// Credentials of a registry.
+#[derive(Clone)]
+pub struct RegistryCredentials {
+    pub username: String,
+    pub password: String,
+}
+
+impl fmt::Debug for RegistryCredentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RegistryCredentials")
+            .field("username", &self.username)
+            .field("password", &"<redacted>")
+            .finish()
+    }
+}
+
+/// Image pulled into a directory.
+#[derive(Debug)]
+pub struct PulledImage {
+    /// Digest of the manifest of the image
+    pub digest: String,
+    /// Platform chosen in a multi-platform image
+    pub platform: Option<Platform>,
+    pub layers: usize,
+    /// Size of the configuration and of the layers
+    pub size: u64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Manifest {
+    media_type: Option<String>,
+    /// Manifests of the platforms of an index
+    #[serde(default)]
+    manifests: Vec<Descriptor>,
+    config: Option<Descriptor>,
+    #[serde(default)]
+    layers: Vec<Descriptor>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Descriptor {
+    media_type: String,
+    digest: String,
+    size: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    platform: Option<Platform>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    annotations: Option<serde_json::Map<String, serde_json::Value>>,
+}
+
+/// Entry of the `manifest.json` of `docker save`.
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct SaveManifest {
+    config: String,
+    repo_tags: Vec<String>,
+    layers: Vec<String>,
+}
+
+/// Error response of a registry.
+#[derive(Deserialize)]
+struct RegistryErrors {
+    errors: Vec<RegistryError>,
+}
+
+#[derive(Deserialize)]
+struct RegistryError {
+    code: String,
+    #[serde(default)]
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    token: Option<String>,
+    access_token: Option<String>,
+}
+
+/// Client of the api of the registry of an image, authenticated on demand.
+struct Registry<'a> {
+    client: &'a PlainClient,
+    image: &'a ImageReference,
+    credentials: Option<&'a RegistryCredentials>,
+    authorization: RwLock<Option<String>>,
+}
+
+/// Pull the manifest, configuration and layers of `image` for `platform`
+/// into `dir`, as both an OCI image layout and a `docker save` archive.
+pub async fn pull(
+    client: &PlainClient,
+    image: &ImageReference,
+    platform: &Platform,
+    credentials: Option<&RegistryCredentials>,
+    dir: &Path,
+) -> Result<PulledImage, ApiServerError> {
+    let registry = Registry {
+        client,
+        image,
+        credentials,
+        authorization: RwLock::new(None),
+    };
+
+    let (mut bytes, mut media_type) = registry.manifest(image.reference()).await?;
+    let mut digest = format!("sha256:{}", super::hex(&Sha256::digest(&bytes)));
+    if image
+        .digest
+        .as_ref()
+        .is_some_and(|expected| *expected != digest)
+    {
+        return Err(ApiServerError::DownloadIntegrityMismatch {
+            expected: image.digest.clone().unwrap_or_default(),
+            actual: digest,
+        });
+    }
+
+    // Multi-platform images list a manifest per platform
+    let mut chosen = None;
+    if matches!(media_type.as_str(), MANIFEST_LIST | OCI_INDEX) {
+        let index: Manifest = parse(&bytes)?;
+        let descriptor = index
+            .manifests
+            .iter()
+            .find(|manifest| {
+                manifest
+                    .platform
+                    .as_ref()
+                    .is_some_and(|candidate| platform.matches(candidate))
+            })
+            .ok_or_else(|| {
+                let available: Vec<String> = index
+                    .manifests
+                    .iter()
+                    .filter_map(|manifest| manifest.platform.as_ref())
+                    .filter(|platform| platform.os != "unknown")
+                    .map(Platform::to_string)
+                    .collect();
+                ApiServerError::RequestError(format!(
+                    "No {} image of {}, pass --platform with one of {}",
+                    platform,
+                    image,
+                    available.join(", ")
+                ))
+            })?;
+        chosen = descriptor.platform.clone();
+        (bytes, media_type) = registry.manifest(&descriptor.digest).await?;
+        digest = format!("sha256:{}", super::hex(&Sha256::digest(&bytes)));
+        if digest != descriptor.digest {
+            return Err(ApiServerError::DownloadIntegrityMismatch {
+                expected: descriptor.digest.clone(),
+                actual: digest,
+            });
+        }
+    }
+    if !matches!(media_type.as_str(), MANIFEST_V2 | OCI_MANIFEST) {
+        return Err(ApiServerError::ResponseError(format!(
+            "Unsupported manifest {} of {}",
+            media_type, image
+        )));
+    }
+    let manifest: Manifest = parse(&bytes)?;
+    let config = manifest.config.clone().ok_or_else(|| {
+        ApiServerError::ResponseError(format!("No configuration in the manifest of {}", image))
+    })?;
+
+    let blobs = dir.join("blobs").join("sha256");
+    tokio::fs::create_dir_all(&blobs)
+        .await
+        .map_err(write_error(&blobs))?;
+    let manifest_path = blobs.join(blob_hex(&digest)?);
+    tokio::fs::write(&manifest_path, &bytes)
+        .await
+        .map_err(write_error(&manifest_path))?;
+
+    let size = config.size + manifest.layers.iter().map(|layer| layer.size).sum::<u64>();
+    let progress = PullProgress::new(size, manifest.layers.len(), client.progress);
+    let (registry, blobs, progress) = (&registry, &blobs, &progress);
+    let layers = manifest.layers.iter().cloned().map(|layer| (layer, true));
+    futures::stream::iter([(config.clone(), false)].into_iter().chain(layers))
+        .map(|(descriptor, layer)| async move {
+            registry.blob(descriptor, blobs, progress).await?;
+            if layer {
+                progress.layer_done();
+            }
+            Ok::<_, ApiServerError>(())
+        })
+        .buffer_unordered(CONCURRENT_LAYERS)
+        .try_collect::<Vec<_>>()
+        .await
+        .inspect_err(|_| progress.bar.finish_and_clear())?;
+    progress.bar.finish_and_clear();
+
+    // The layout of `docker save` since docker 25, read by the older ones too
+    let mut annotations = serde_json::Map::new();
+    if let Some(tag) = &image.tag {
+        annotations.insert(
+            "io.containerd.image.name".to_string(),
+            image.to_string().into(),
+        );
+        annotations.insert(
+            "org.opencontainers.image.ref.name".to_string(),
+            tag.clone().into(),
+        );
+    }
+    let index = serde_json::json!({
+        "schemaVersion": 2,
+        "mediaType": OCI_INDEX,
+        "manifests": [Descriptor {
+            media_type: media_type.clone(),
+            digest: digest.clone(),
+            size: bytes.len() as u64,
+            platform: chosen.clone(),
+            annotations: Some(annotations).filter(|annotations| !annotations.is_empty()),
+        }],
+    });
+    let blob_path = |descriptor: &Descriptor| {
+        blob_hex(&descriptor.digest).map(|hex| format!("blobs/sha256/{hex}"))
+    };
+    let save = [SaveManifest {
+        config: blob_path(&config)?,
+        repo_tags: image.repo_tag().into_iter().collect(),
+        layers: manifest
+            .layers
+            .iter()
+            .map(blob_path)
+            .collect::<Result<_, _>>()?,
+    }];
+    for (name, content) in [
+        (
+            "oci-layout",
+            r#"{"imageLayoutVersion":"1.0.0"}"#.to_string(),
+        ),
+        ("index.json", index.to_string()),
+        (
+            "manifest.json",
+            serde_json::to_string(&save).unwrap_or_default(),
+        ),
+    ] {
+        let path = dir.join(name);
+        tokio::fs::write(&path, content)
+            .await
+            .map_err(write_error(&path))?;
+    }
+
+    Ok(PulledImage {
+        digest,
+        platform: chosen,
+        layers: manifest.layers.len(),
+        size,
+    })
+}
+
+impl Registry<'_> {
+    /// Manifest `reference`, a tag or a digest, with its media type.
+    async fn manifest(&self, reference: &str) -> Result<(Vec<u8>, String), ApiServerError> {
+        let accept = [MANIFEST_LIST, OCI_INDEX, MANIFEST_V2, OCI_MANIFEST].join(", ");
+        let response = self
+            .get(&format!("manifests/{}", reference), Some(&accept))
+            .await?;
+        let header_type = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| {
+                value
+                    .split(';')
+                    .next()
+                    .unwrap_or_default()
+                    .trim()
+                    .to_string()
+            });
+        let bytes = response.bytes().await?.to_vec();
+
+        // The media type of the body prevails over the header
+        let media_type = serde_json::from_slice::<Manifest>(&bytes)
+            .ok()
+            .and_then(|manifest| manifest.media_type)
+            .or(header_type)
+            .unwrap_or_default();
+        Ok((bytes, media_type))
+    }
+
+    /// Download the blob of `descriptor` into `dir`, named after its digest
+    /// once verified.
+    async fn blob(
+        &self,
+        descriptor: Descriptor,
+        dir: &Path,
+        progress: &PullProgress,
+    ) -> Result<(), ApiServerError> {
+        let path = dir.join(blob_hex(&descriptor.digest)?);
+        let partial = path.with_extension("partial");
+        let response = self
+            .get(&format!("blobs/{}", descriptor.digest), None)
+            .await?;
+
+        let mut file = tokio::fs::File::create(&partial)
+            .await
+            .map_err(write_error(&partial))?;
+        let mut hasher = Sha256::new();
+        let mut size = 0;
+        let mut body = response.bytes_stream();
+        while let Some(chunk) = body.next().await {
+            let chunk = chunk?;
+            hasher.update(&chunk);
+            size += chunk.len() as u64;
+            progress.bar.inc(chunk.len() as u64);
+            file.write_all(&chunk)
+                .await
+                .map_err(write_error(&partial))?;
+        }
+        file.flush().await.map_err(write_error(&partial))?;
+
+        let digest = format!("sha256:{}", super::hex(&hasher.finalize()));
+        if digest != descriptor.digest || size != descriptor.size {
+            let _ = tokio::fs::remove_file(&partial).await;
+            return Err(ApiServerError::DownloadIntegrityMismatch {
+                expected: descriptor.digest,
+                actual: digest,
+            });
+        }
+        tokio::fs::rename(&partial, &path)
+            .await
+            .map_err(write_error(&path))?;
+        Ok(())
+    }
+
+    /// Get `path` of the repository, authenticating when the registry asks
+    /// to.
+    async fn get(
+        &self,
+        path: &str,
+        accept: Option<&str>,
+    ) -> Result<reqwest::Response, ApiServerError> {
+        let url = format!("{}/{}", self.image.api_url(), path);
+        let mut authenticated = false;
+        loop {
+            let mut request = self
+                .client
+                .client
+                .get(&url)
+                .header(USER_AGENT, self.client.user_agent)
+                .timeout(self.client.timeout);
+            if let Some(accept) = accept {
+                request = request.header(ACCEPT, accept);
+            }
+            if let Some(authorization) = self.authorization.read().await.as_deref() {
+                request = request.header(AUTHORIZATION, authorization);
+            }
+            let response = request.send().await?;
+
+            match response.status() {
+                StatusCode::OK => return Ok(response),
+                StatusCode::UNAUTHORIZED if !authenticated => {
+                    let challenge = response
+                        .headers()
+                        .get(WWW_AUTHENTICATE)
+                        .and_then(|value| value.to_str().ok())
+                        .unwrap_or_default()
+                        .to_string();
+                    self.authenticate(&challenge).await?;
+                    authenticated = true;
+                }
+                status => return Err(self.error(status, response).await),
+            }
+        }
+    }
+
+    /// Authorize the next requests as asked by the `challenge` of the
+    /// registry, with a bearer token from its token service or with the
+    /// credentials.
+    async fn authenticate(&self, challenge: &str) -> Result<(), ApiServerError> {
+        let (scheme, params) = challenge.split_once(' ').unwrap_or((challenge, ""));
+        let params = challenge_params(params);
+        let param = |name: &str| {
+            params
+                .iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                .map(|(_, value)| value.as_str())
+        };
+
+        let authorization = if scheme.eq_ignore_ascii_case("basic") {
+            let credentials = self.credentials.ok_or_else(|| {
+                ApiServerError::RequestError(format!(
+                    "{} needs credentials, pass --registry-user or run `docker login {}`",
+                    self.image.registry, self.image.registry
+                ))
+            })?;
+            let basic = format!("{}:{}", credentials.username, credentials.password);
+            format!("Basic {}", STANDARD.encode(basic))
+        } else if scheme.eq_ignore_ascii_case("bearer") {
+            let realm = param("realm").ok_or_else(|| {
+                ApiServerError::ResponseError(format!(
+                    "No token service given by {}",
+                    self.image.registry
+                ))
+            })?;
+            let scope = format!("repository:{}:pull", self.image.repository);
+            let mut query = vec![("scope", param("scope").unwrap_or(&scope))];
+            if let Some(service) = param("service") {
+                query.push(("service", service));
+            }
+            let mut request = self
+                .client
+                .client
+                .get(realm)
+                .header(USER_AGENT, self.client.user_agent)
+                .query(&query);
+            if let Some(credentials) = self.credentials {
+                request = request.basic_auth(&credentials.username, Some(&credentials.password));
+            }
+            let response = request.send().await?;
+            if !response.status().is_success() {
+                return Err(self.error(response.status(), response).await);
+            }
+            let token: TokenResponse = response.json().await?;
+            let token = token.token.or(token.access_token).ok_or_else(|| {
+                ApiServerError::ResponseError(format!("No token in the response of {}", realm))
+            })?;
+            format!("Bearer {}", token)
+        } else {
+            return Err(ApiServerError::ResponseError(format!(
+                "Unsupported authentication `{}` of {}",
+                scheme, self.image.registry
+            )));
+        };
+
+        *self.authorization.write().await = Some(authorization);
+        Ok(())
+    }
+
+    /// Error for the error `response` of the registry.
+    async fn error(&self, status: StatusCode, response: reqwest::Response) -> ApiServerError {
+        let detail = response
+            .json::<RegistryErrors>()
+            .await
+            .ok()
+            .and_then(|errors| errors.errors.into_iter().next())
+            .map(|error| match error.message.is_empty() {
+                true => error.code,
+                false => format!("{}: {}", error.code, error.message),
+            })
+            .unwrap_or_else(|| status.to_string());
+        let hint = match status {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN if self.credentials.is_none() => {
+                format!(
+                    ", pass --registry-user or run `docker login {}`",
+                    self.image.registry
+                )
+            }
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+                ", check the credentials of the registry".to_string()
+            }
+            _ => String::new(),
+        };
+        ApiServerError::RequestError(format!("Error pulling {}: {}{}", self.image, detail, hint))
+    }
+}
+
+/// Parameters of an authentication challenge, e.g.
+/// `realm="https://auth.docker.io/token",service="registry.docker.io"`.
+fn challenge_params(params: &str) -> Vec<(String, String)> {
+    let mut parsed = vec![];
+    let mut rest = params.trim();
+    while let Some((key, value)) = rest.split_once('=') {
+        let key = key.trim().trim_start_matches(',').trim().to_string();
+        let value = value.trim_start();
+        let (value, next) = match value.strip_prefix('"') {
+            Some(quoted) => {
+                let end = quoted.find('"').unwrap_or(quoted.len());
+                (&quoted[..end], quoted.get(end + 1..).unwrap_or_default())
+            }
+            None => {
+                let end = value.find(',').unwrap_or(value.len());
+                (&value[..end], &value[end..])
+            }
+        };
+        parsed.push((key, value.to_string()));
+        rest = next;
+    }
+    parsed
+}
+
+/// Hex of a `sha256:` digest, the name of its blob.
+fn blob_hex(digest: &str) -> Result<&str, ApiServerError> {
+    digest
+        .strip_prefix("sha256:")
+        .filter(|hex| hex.len() == 64 && hex.bytes().all(|b| b.is_ascii_hexdigit()))
+        .ok_or_else(|| ApiServerError::ResponseError(format!("Unsupported digest {}", digest)))
+}
+
+fn parse<T: serde::de::DeserializeOwned>(bytes: &[u8]) -> Result<T, ApiServerError> {
+    serde_json::from_slice(bytes)
+        .map_err(|err| ApiServerError::ResponseError(format!("Invalid manifest: {}", err)))
+}
+
+fn write_error(path: &Path) -> impl Fn(std::io::Error) -> ApiServerError + '_ {
+    move |err| {
+        ApiServerError::RequestError(format!("Error writing {}. Reason: {}", path.display(), err))
+    }
+}
+
+/// Progress of the layers pulled, all of them on one bar.
+struct PullProgress {
+    bar: ProgressBar,
+    layers: usize,
+    done: AtomicUsize,
+}
+
+impl PullProgress {
+    /// The bar is hidden when `enabled` is false or stderr is not a terminal.
+    fn new(size: u64, layers: usize, enabled: bool) -> Self {
+        let bar = match enabled && std::io::stderr().is_terminal() {
+            true => ProgressBar::new(size).with_style(
+                ProgressStyle::with_template(
+                    "[{elapsed_precise}] [{wide_bar}] {bytes}/{total_bytes} {binary_bytes_per_sec} ETA {eta} {msg}",
+                )
+                .expect("valid progress template")
+                .progress_chars("=> "),
+            ),
+            false => ProgressBar::hidden(),
+        };
+        let progress = Self {
+            bar,
+            layers,
+            done: AtomicUsize::new(0),
+        };
+        progress.bar.set_message(format!("0/{} layers", layers));
+        progress
+    }
+
+    /// Count a layer as pulled.
+    fn layer_done(&self) {
+        let done = self.done.fetch_add(1, Ordering::Relaxed) + 1;
+        self.bar
+            .set_message(format!("{}/{} layers", done, self.layers));
+    }
+}
+
+/// Credentials of `registry` saved by `docker login`, in the docker
+/// configuration or in the credential helper it names.
+pub fn docker_credentials(registry: &str) -> Option<RegistryCredentials> {
+    let path = match std::env::var_os("DOCKER_CONFIG") {
+        Some(dir) => PathBuf::from(dir).join("config.json"),
+        None => dirs::home_dir()?.join(".docker").join("config.json"),
+    };
+    let config: serde_json::Value = serde_json::from_slice(&std::fs::read(path).ok()?).ok()?;
+    let key = match registry {
+        DOCKER_HUB => DOCKER_HUB_AUTH_KEY,
+        registry => registry,
+    };
+
+    let helper = config
+        .get("credHelpers")
+        .and_then(|helpers| helpers.get(key))
+        .or_else(|| config.get("credsStore"))
+        .and_then(|helper| helper.as_str());
+    if let Some(helper) = helper {
+        if let Some(credentials) = credential_helper(helper, key) {
+            return Some(credentials);
+        }
+    }
+
+    // Keys are hosts, or urls of the hosts
+    let host = |key: &str| {
+        key.trim_start_matches("https://")
+            .trim_start_matches("http://")
+            .split('/')
+            .next()
+            .unwrap_or_default()
+            .to_string()
+    };
+    let (_, auth) = config
+        .get("auths")?
+        .as_object()?
+        .iter()
+        .find(|(candidate, _)| host(candidate) == host(key))?;
+    let auth = STANDARD.decode(auth.get("auth")?.as_str()?).ok()?;
+    let (username, password) = std::str::from_utf8(&auth).ok()?.split_once(':')?;
+    Some(RegistryCredentials {
+        username: username.to_string(),
+        password: password.to_string(),
+    })
+}
+
+/// Credentials of `registry` from the `docker-credential-<helper>` program.
+fn credential_helper(helper: &str, registry: &str) -> Option<RegistryCredentials> {
+    #[derive(Deserialize)]
+    #[serde(rename_all = "PascalCase")]
+    struct HelperCredentials {
+        username: String,
+        secret: String,
+    }
+
+    let mut child = Command::new(format!("docker-credential-{helper}"))
+        .arg("get")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .inspect_err(|err| tracing::debug!("docker-credential-{} not run: {}", helper, err))
+        .ok()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        use std::io::Write;
+        let _ = stdin.write_all(registry.as_bytes());
+    }
+    let output = child.wait_with_output().ok()?;
+    if !output.status.success() {
+        tracing::debug!(
+            "No credentials of {} in docker-credential-{}",
+            registry,
+            helper
+        );
+        return None;
+    }
+    let credentials: HelperCredentials = serde_json::from_slice(&output.stdout).ok()?;
+    tracing::debug!(
+        "Credentials of {} from docker-credential-{}",
+        registry,
+        helper
+    );
+    Some(RegistryCredentials {
+        username: credentials.username,
+        password: credentials.secret,
+    })
+}
+
+impl fmt::Display for PulledImage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} layers, {}", self.layers, HumanBytes(self.size))?;
+        if let Some(platform) = &self.platform {
+            write!(f, ", {}", platform)?;
+        }
+        write!(f, ", {}", self.digest)
+    }
+}
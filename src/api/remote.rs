@@ -0,0 +1,251 @@
+//! Firmwares streamed from a remote url into their upload, without being
+//! written to disk, for `create --from-url`.
+
+use std::{io, sync::Arc, time::Duration};
+
+use bytes::Bytes;
+use futures::{channel::mpsc, SinkExt, Stream, StreamExt};
+use reqwest::{
+    header::{
+        HeaderValue, CONTENT_DISPOSITION, CONTENT_RANGE, ETAG, IF_RANGE, LAST_MODIFIED, RANGE,
+        USER_AGENT,
+    },
+    StatusCode,
+};
+
+use super::{http_server, ApiServerError, RemoteFirmware};
+
+/// Chunks downloaded ahead of the upload
+const BUFFERED_CHUNKS: usize = 16;
+
+/// Firmware at a remote url, downloaded again from where it stopped when the
+/// connection drops.
+#[derive(Debug, Clone)]
+pub(crate) struct RemoteSource {
+    client: reqwest::Client,
+    firmware: RemoteFirmware,
+    user_agent: &'static str,
+    timeout: Duration,
+    retries: u32,
+}
+
+impl RemoteSource {
+    pub(crate) fn new(
+        client: reqwest::Client,
+        firmware: RemoteFirmware,
+        user_agent: &'static str,
+        timeout: Duration,
+        retries: u32,
+    ) -> Self {
+        Self {
+            client,
+            firmware,
+            user_agent,
+            timeout,
+            retries,
+        }
+    }
+
+    /// Request of the firmware, from `offset` when not 0 as long as it is
+    /// the one identified by `validator`. Redirects are followed, the
+    /// credentials being dropped when they lead to another host.
+    pub(crate) fn request(
+        &self,
+        offset: u64,
+        validator: Option<&HeaderValue>,
+    ) -> reqwest::RequestBuilder {
+        let request = self
+            .client
+            .get(self.firmware.url.clone())
+            .header(USER_AGENT, self.user_agent)
+            .headers(self.firmware.headers.clone())
+            .timeout(self.timeout);
+        match (offset, validator) {
+            (0, _) => request,
+            (offset, Some(validator)) => request
+                .header(RANGE, format!("bytes={}-", offset))
+                .header(IF_RANGE, validator),
+            (offset, None) => request.header(RANGE, format!("bytes={}-", offset)),
+        }
+    }
+
+    /// Check the response to the first request of the firmware.
+    pub(crate) fn check(&self, response: &reqwest::Response) -> Result<(), ApiServerError> {
+        let status = response.status();
+        if status == StatusCode::OK {
+            return Ok(());
+        }
+        let hint = match status {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+                ", pass its credentials with --source-header"
+            }
+            _ => "",
+        };
+        Err(ApiServerError::RequestError(format!(
+            "Error downloading {}: {}{}",
+            redacted(&self.firmware.url),
+            status,
+            hint
+        )))
+    }
+
+    /// Name of the firmware, from the `Content-Disposition` of `response`
+    /// or else from the url.
+    pub(crate) fn filename(&self, response: &reqwest::Response) -> String {
+        let disposition = response
+            .headers()
+            .get(CONTENT_DISPOSITION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| {
+                value.split(';').find_map(|param| {
+                    let (key, value) = param.trim().split_once('=')?;
+                    (key.trim().eq_ignore_ascii_case("filename"))
+                        .then(|| value.trim().trim_matches('"'))
+                })
+            });
+        let from_url = |url: &url::Url| {
+            url.path_segments()
+                .and_then(|mut segments| segments.rfind(|segment| !segment.is_empty()))
+                .map(str::to_string)
+        };
+        disposition
+            .map(str::to_string)
+            .or_else(|| from_url(&self.firmware.url))
+            .or_else(|| from_url(response.url()))
+            .map(|name| {
+                name.rsplit(['/', '\\'])
+                    .next()
+                    .unwrap_or_default()
+                    .to_string()
+            })
+            .filter(|name| !name.is_empty() && name != "." && name != "..")
+            .unwrap_or_else(|| "firmware".to_string())
+    }
+
+    /// Stream of the firmware, read from `response` when given and else
+    /// from a new request. Interrupted downloads continue where they stopped
+    /// when the server supports ranges, up to the number of retries.
+    pub(crate) fn stream(
+        self: &Arc<Self>,
+        response: Option<reqwest::Response>,
+    ) -> impl Stream<Item = io::Result<Bytes>> + Send + Sync + 'static {
+        let (mut sender, receiver) = mpsc::channel(BUFFERED_CHUNKS);
+        let source = self.clone();
+        tokio::spawn(async move {
+            if let Err(err) = source.forward(response, &mut sender).await {
+                let _ = sender.send(Err(err)).await;
+            }
+        });
+        receiver
+    }
+
+    /// Send the chunks of the firmware to `sender`, until the end of the
+    /// firmware or until the upload stops reading them.
+    async fn forward(
+        &self,
+        mut response: Option<reqwest::Response>,
+        sender: &mut mpsc::Sender<io::Result<Bytes>>,
+    ) -> io::Result<()> {
+        let mut received = 0;
+        let mut validator = None;
+        let mut attempt = 0;
+
+        loop {
+            let outcome = match response.take() {
+                Some(response) => Ok(response),
+                None => self.reconnect(received, validator.as_ref()).await,
+            };
+            let error = match outcome {
+                Ok(response) => {
+                    if received == 0 {
+                        validator = strong_validator(&response);
+                    }
+                    let mut body = response.bytes_stream();
+                    loop {
+                        match body.next().await {
+                            Some(Ok(chunk)) => {
+                                received += chunk.len() as u64;
+                                attempt = 0;
+                                if sender.send(Ok(chunk)).await.is_err() {
+                                    return Ok(());
+                                }
+                            }
+                            Some(Err(err)) => break err.to_string(),
+                            None => return Ok(()),
+                        }
+                    }
+                }
+                Err(err) => err,
+            };
+
+            attempt += 1;
+            if attempt > self.retries {
+                return Err(io::Error::other(format!(
+                    "error downloading {}: {}",
+                    redacted(&self.firmware.url),
+                    error
+                )));
+            }
+            let delay = http_server::retry_delay(attempt);
+            tracing::warn!(
+                "Download of {} interrupted at {} bytes: {}, resuming in {}s",
+                redacted(&self.firmware.url),
+                received,
+                error,
+                delay.as_secs()
+            );
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    /// Request the firmware again from `offset`, which the server must send
+    /// as a range of the same firmware.
+    async fn reconnect(
+        &self,
+        offset: u64,
+        validator: Option<&HeaderValue>,
+    ) -> Result<reqwest::Response, String> {
+        let response = self
+            .request(offset, validator)
+            .send()
+            .await
+            .map_err(|err| err.to_string())?;
+        match response.status() {
+            StatusCode::OK if offset == 0 => Ok(response),
+            StatusCode::PARTIAL_CONTENT if range_start(&response) == Some(offset) => Ok(response),
+            StatusCode::OK => Err(
+                "the server can't resume it, or the firmware changed since the download started"
+                    .to_string(),
+            ),
+            status => Err(format!("status {}", status)),
+        }
+    }
+}
+
+/// Validator of the firmware sent by `response`, telling whether it changed
+/// between two requests: its strong ETag, else its modification date.
+fn strong_validator(response: &reqwest::Response) -> Option<HeaderValue> {
+    let headers = response.headers();
+    headers
+        .get(ETAG)
+        .filter(|etag| !etag.as_bytes().starts_with(b"W/"))
+        .or_else(|| headers.get(LAST_MODIFIED))
+        .cloned()
+}
+
+/// First byte of the range sent by `response`, from `Content-Range: bytes
+/// <start>-<end>/<size>`.
+fn range_start(response: &reqwest::Response) -> Option<u64> {
+    let range = response.headers().get(CONTENT_RANGE)?.to_str().ok()?;
+    let (start, _) = range.strip_prefix("bytes ")?.split_once('-')?;
+    start.trim().parse().ok()
+}
+
+/// `url` without its password, to be logged.
+pub(crate) fn redacted(url: &url::Url) -> String {
+    let mut url = url.clone();
+    if url.password().is_some() {
+        let _ = url.set_password(Some("***"));
+    }
+    url.to_string()
+}
@@ -0,0 +1,137 @@
+use std::path::PathBuf;
+
+use url::Url;
+
+use super::ApiServerError;
+
+/// Serve the api at the unix `socket` on a loopback port, returning the
+/// base url to use in its place.
+///
+/// The http client only speaks TCP, so every connection to the port is
+/// forwarded to the socket. Only the requests with a random path prefix,
+/// known by this process only, are forwarded: other local users can't use
+/// the port to reach the socket.
+#[cfg(unix)]
+pub(crate) async fn bridge(socket: PathBuf) -> Result<Url, ApiServerError> {
+    use std::{net::Ipv4Addr, sync::Arc};
+    use tokio::net::{TcpListener, UnixStream};
+    use uuid::Uuid;
+
+    let socket_error = |err: std::io::Error| {
+        ApiServerError::ConnectError(format!("unix socket {}: {}", socket.display(), err))
+    };
+    UnixStream::connect(&socket).await.map_err(socket_error)?;
+
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+        .await
+        .map_err(|err| {
+            ApiServerError::RequestError(format!("Unable to bridge unix socket: {}", err))
+        })?;
+    let port = listener
+        .local_addr()
+        .map_err(|err| {
+            ApiServerError::RequestError(format!("Unable to bridge unix socket: {}", err))
+        })?
+        .port();
+    let prefix: Arc<str> = format!("/{}", Uuid::new_v4().simple()).into();
+    let base = Url::parse(&format!("http://127.0.0.1:{}{}", port, prefix)).expect("valid url");
+
+    tracing::debug!("Bridging unix socket {} on port {}", socket.display(), port);
+
+    let socket = Arc::new(socket);
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let socket = socket.clone();
+            let prefix = prefix.clone();
+            tokio::spawn(async move {
+                if let Err(err) = forward(stream, &socket, &prefix).await {
+                    tracing::debug!("Error forwarding to unix socket: {}", err);
+                }
+            });
+        }
+    });
+
+    Ok(base)
+}
+
+#[cfg(not(unix))]
+pub(crate) async fn bridge(socket: PathBuf) -> Result<Url, ApiServerError> {
+    Err(ApiServerError::RequestError(format!(
+        "Unix sockets are not supported on this platform: {}",
+        socket.display()
+    )))
+}
+
+/// Maximum length of the request line of a forwarded request
+#[cfg(unix)]
+const MAX_REQUEST_LINE: u64 = 64 * 1024;
+
+/// Forward the connection `client` to the unix `socket`, removing `prefix`
+/// from the path of its request.
+///
+/// Connections are not reused by the client, so only their first request
+/// line has to be rewritten.
+#[cfg(unix)]
+async fn forward(
+    client: tokio::net::TcpStream,
+    socket: &std::path::Path,
+    prefix: &str,
+) -> std::io::Result<()> {
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+        net::UnixStream,
+    };
+
+    let mut reader = BufReader::new(client);
+    let mut line = Vec::new();
+    (&mut reader)
+        .take(MAX_REQUEST_LINE)
+        .read_until(b'\n', &mut line)
+        .await?;
+
+    // e.g. `GET /<prefix>/api/v1/projects HTTP/1.1`
+    let request_line = String::from_utf8_lossy(&line);
+    let rewritten = request_line.split_once(' ').and_then(|(method, rest)| {
+        let rest = rest.strip_prefix(prefix)?;
+        match rest.chars().next() {
+            Some('/') => Some(format!("{} {}", method, rest)),
+            Some('?' | ' ') => Some(format!("{} /{}", method, rest)),
+            _ => None,
+        }
+    });
+
+    let Some(rewritten) = rewritten else {
+        let mut client = reader.into_inner();
+        return client
+            .write_all(b"HTTP/1.1 403 Forbidden\r\ncontent-length: 0\r\nconnection: close\r\n\r\n")
+            .await;
+    };
+
+    let mut server = match UnixStream::connect(socket).await {
+        Ok(server) => server,
+        Err(err) => {
+            let body = format!(
+                "Unable to connect to unix socket {}: {}",
+                socket.display(),
+                err
+            );
+            let mut client = reader.into_inner();
+            return client
+                .write_all(
+                    format!(
+                        "HTTP/1.1 502 Bad Gateway\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                        body.len(),
+                        body
+                    )
+                    .as_bytes(),
+                )
+                .await;
+        }
+    };
+
+    server.write_all(rewritten.as_bytes()).await?;
+    server.write_all(reader.buffer()).await?;
+    let mut client = reader.into_inner();
+    tokio::io::copy_bidirectional(&mut client, &mut server).await?;
+    Ok(())
+}
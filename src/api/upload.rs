@@ -0,0 +1,436 @@
+use std::{
+    io::{IsTerminal, SeekFrom},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant, UNIX_EPOCH},
+};
+
+use async_compression::tokio::bufread::GzipEncoder;
+use bytes::Bytes;
+use futures::{Stream, StreamExt, TryStreamExt};
+use indicatif::{HumanBytes, HumanDuration, ProgressBar, ProgressStyle};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio_util::io::{ReaderStream, StreamReader};
+use uuid::Uuid;
+
+const UPLOADS_CACHE_DIR: &str = "cosmo-cli/uploads";
+
+/// Progress of a firmware upload, rendered on stderr.
+#[derive(Debug, Clone)]
+pub(crate) struct UploadProgress {
+    bar: ProgressBar,
+    start: Instant,
+    compressed: Arc<AtomicU64>,
+    limit: Option<u64>,
+}
+
+impl UploadProgress {
+    /// Create the progress bar for an upload of `size` bytes, a spinner when
+    /// the size is unknown.
+    ///
+    /// The bar is hidden when `enabled` is false or stderr is not a terminal.
+    pub(crate) fn new(size: Option<u64>, enabled: bool) -> Self {
+        let bar = if !enabled || !std::io::stderr().is_terminal() {
+            ProgressBar::hidden()
+        } else if let Some(size) = size {
+            ProgressBar::new(size).with_style(
+                ProgressStyle::with_template(
+                    "[{elapsed_precise}] [{wide_bar}] {bytes}/{total_bytes} ({percent}%) {binary_bytes_per_sec} ETA {eta} {msg}",
+                )
+                .expect("valid progress template")
+                .progress_chars("=> "),
+            )
+        } else {
+            ProgressBar::new_spinner().with_style(
+                ProgressStyle::with_template(
+                    "[{elapsed_precise}] {spinner} {bytes} {binary_bytes_per_sec} {msg}",
+                )
+                .expect("valid progress template"),
+            )
+        };
+
+        Self {
+            bar,
+            start: Instant::now(),
+            compressed: Arc::new(AtomicU64::new(0)),
+            limit: None,
+        }
+    }
+
+    /// Show the bandwidth limit of the upload, if any, next to its rate.
+    pub(crate) fn with_limit(mut self, limit: Option<u64>) -> Self {
+        self.limit = limit;
+        self.set_message();
+        self
+    }
+
+    fn set_message(&self) {
+        let mut msg = Vec::new();
+        if let Some(limit) = self.limit {
+            msg.push(format!("(limited to {}/s)", HumanBytes(limit)));
+        }
+        match self.compressed.load(Ordering::Relaxed) {
+            0 => {}
+            compressed => msg.push(format!("(compressed {})", HumanBytes(compressed))),
+        }
+        self.bar.set_message(msg.join(" "));
+    }
+
+    /// Restart the progress from zero, e.g. when an upload is retried.
+    pub(crate) fn restart(&self) {
+        self.bar.reset();
+        self.compressed.store(0, Ordering::Relaxed);
+        self.set_message();
+    }
+
+    fn inc(&self, bytes: u64) {
+        self.bar.inc(bytes)
+    }
+
+    fn inc_compressed(&self, bytes: u64) {
+        self.compressed.fetch_add(bytes, Ordering::Relaxed);
+        self.set_message();
+    }
+
+    pub(crate) fn set_position(&self, bytes: u64) {
+        self.bar.set_position(bytes)
+    }
+
+    /// Replace the bar with a summary line of the uploaded bytes.
+    pub(crate) fn finish(&self) {
+        if self.bar.is_hidden() {
+            return;
+        }
+
+        self.bar
+            .set_style(ProgressStyle::with_template("{msg}").expect("valid template"));
+        let compressed = match self.compressed.load(Ordering::Relaxed) {
+            0 => String::new(),
+            compressed => format!(" (compressed {})", HumanBytes(compressed)),
+        };
+        self.bar.finish_with_message(format!(
+            "Uploaded {}{} in {}",
+            HumanBytes(self.bar.position()),
+            compressed,
+            HumanDuration(self.start.elapsed())
+        ));
+    }
+
+    /// Remove the bar without a summary, e.g. when the upload failed.
+    pub(crate) fn abandon(&self) {
+        self.bar.finish_and_clear();
+    }
+}
+
+/// Token bucket limiting the upload bandwidth, shared by all the bodies of
+/// the uploads running at the same time.
+#[derive(Debug, Clone)]
+pub(crate) struct Throttle {
+    rate: u64,
+    bucket: Arc<Mutex<Bucket>>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    refilled: Instant,
+}
+
+impl Throttle {
+    /// Limit the upload to `rate` bytes per second.
+    pub(crate) fn new(rate: u64) -> Self {
+        Self {
+            rate: rate.max(1),
+            bucket: Arc::new(Mutex::new(Bucket {
+                tokens: 0.0,
+                refilled: Instant::now(),
+            })),
+        }
+    }
+
+    /// Take `bytes` from the bucket, returning how long to wait before
+    /// sending them. The bucket holds at most one second worth of bytes.
+    fn take(&self, bytes: usize) -> Duration {
+        let rate = self.rate as f64;
+        let mut bucket = self.bucket.lock().expect("throttle lock");
+
+        let now = Instant::now();
+        let refill = now.duration_since(bucket.refilled).as_secs_f64() * rate;
+        bucket.tokens = (bucket.tokens + refill).min(rate) - bytes as f64;
+        bucket.refilled = now;
+
+        if bucket.tokens < 0.0 {
+            Duration::from_secs_f64(-bucket.tokens / rate)
+        } else {
+            Duration::ZERO
+        }
+    }
+
+    /// Delay the chunks of `stream` to respect the limit.
+    pub(crate) fn stream<S, B, E>(self, stream: S) -> impl Stream<Item = Result<B, E>>
+    where
+        S: Stream<Item = Result<B, E>>,
+        B: AsRef<[u8]>,
+    {
+        stream.then(move |chunk| {
+            let delay = match &chunk {
+                Ok(chunk) => self.take(chunk.as_ref().len()),
+                Err(_) => Duration::ZERO,
+            };
+            async move {
+                if !delay.is_zero() {
+                    tokio::time::sleep(delay).await;
+                }
+                chunk
+            }
+        })
+    }
+
+    /// Body sending `data` within the limit.
+    pub(crate) fn body(&self, data: Vec<u8>) -> reqwest::Body {
+        const PIECE_SIZE: usize = 64 * 1024;
+
+        let pieces: Vec<Result<Vec<u8>, std::io::Error>> = data
+            .chunks(PIECE_SIZE)
+            .map(|piece| Ok(piece.to_vec()))
+            .collect();
+        reqwest::Body::wrap_stream(self.clone().stream(futures::stream::iter(pieces)))
+    }
+}
+
+/// SHA-256 of the uploaded firmware, computed while it is read.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct UploadDigest(Arc<Mutex<Sha256>>);
+
+impl UploadDigest {
+    pub(crate) fn update(&self, data: &[u8]) {
+        self.0.lock().expect("digest lock").update(data)
+    }
+
+    /// Restart the digest from zero, e.g. when an upload is retried.
+    pub(crate) fn reset(&self) {
+        self.0.lock().expect("digest lock").reset()
+    }
+
+    /// Hex encoded digest of the data read so far.
+    pub(crate) fn hex(&self) -> String {
+        hex(&self.0.lock().expect("digest lock").clone().finalize())
+    }
+
+    /// Form field with the digest, evaluated only once the parts before it
+    /// have been sent.
+    pub(crate) fn part(&self) -> reqwest::multipart::Part {
+        let digest = self.clone();
+        let value = futures::stream::once(async move { Ok::<_, std::io::Error>(digest.hex()) });
+        // Hex encoded SHA-256 digests are always 64 characters long
+        reqwest::multipart::Part::stream_with_length(reqwest::Body::wrap_stream(value), 64)
+    }
+}
+
+/// Hex encoded SHA-256 of the file at `path`.
+pub async fn file_sha256(path: &Path) -> std::io::Result<String> {
+    let file = tokio::fs::File::open(path).await?;
+    let mut hasher = Sha256::new();
+    ReaderStream::new(file)
+        .try_for_each(|chunk| {
+            hasher.update(&chunk);
+            futures::future::ready(Ok(()))
+        })
+        .await?;
+    Ok(hex(&hasher.finalize()))
+}
+
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Body streaming the file at `path`, reporting the bytes read to `progress`
+/// and `digest`.
+///
+/// With `compress` the file is gzipped on the fly, the digest is still the one
+/// of the original file. The `throttle` applies to the bytes actually sent.
+/// Errors opening the file are reported by the body itself, failing the
+/// request.
+pub(crate) fn file_body(
+    path: &Path,
+    progress: &UploadProgress,
+    digest: &UploadDigest,
+    compress: bool,
+    throttle: Option<&Throttle>,
+) -> reqwest::Body {
+    let file = match std::fs::File::open(path) {
+        Ok(file) => tokio::fs::File::from_std(file),
+        Err(err) => {
+            return reqwest::Body::wrap_stream(futures::stream::iter([Err::<Vec<u8>, _>(err)]))
+        }
+    };
+    stream_body(
+        ReaderStream::new(file),
+        progress,
+        digest,
+        compress,
+        throttle,
+    )
+}
+
+/// Stream of the firmware read from stdin, failing once more than
+/// `max_size` bytes are read.
+pub(crate) fn stdin_stream(
+    max_size: Option<u64>,
+) -> impl Stream<Item = std::io::Result<Bytes>> + Send + Sync + 'static {
+    let mut read = 0;
+    ReaderStream::new(tokio::io::stdin()).map(move |chunk| {
+        let chunk = chunk?;
+        read += chunk.len() as u64;
+        match max_size {
+            Some(max_size) if read > max_size => Err(std::io::Error::other(format!(
+                "the firmware read from stdin exceeds the maximum size of {}, raise it with --max-size",
+                HumanBytes(max_size)
+            ))),
+            _ => Ok(chunk),
+        }
+    })
+}
+
+/// Body sending the firmware read from `stream`, as [file_body] does.
+pub(crate) fn stream_body<S>(
+    stream: S,
+    progress: &UploadProgress,
+    digest: &UploadDigest,
+    compress: bool,
+    throttle: Option<&Throttle>,
+) -> reqwest::Body
+where
+    S: Stream<Item = std::io::Result<Bytes>> + Send + Sync + 'static,
+{
+    let read_progress = progress.clone();
+    let digest = digest.clone();
+    let raw = stream.inspect_ok(move |chunk| {
+        read_progress.inc(chunk.len() as u64);
+        digest.update(chunk);
+    });
+
+    if compress {
+        let encoder = GzipEncoder::new(StreamReader::new(raw));
+        let sent_progress = progress.clone();
+        let compressed = ReaderStream::new(encoder)
+            .inspect_ok(move |chunk| sent_progress.inc_compressed(chunk.len() as u64));
+        match throttle {
+            Some(throttle) => reqwest::Body::wrap_stream(throttle.clone().stream(compressed)),
+            None => reqwest::Body::wrap_stream(compressed),
+        }
+    } else {
+        match throttle {
+            Some(throttle) => reqwest::Body::wrap_stream(throttle.clone().stream(raw)),
+            None => reqwest::Body::wrap_stream(raw),
+        }
+    }
+}
+
+/// Whether the file name suggests a content that is already compressed,
+/// so that gzipping it again would only waste time.
+pub(crate) fn is_compressed(path: &Path) -> bool {
+    const COMPRESSED_EXTENSIONS: &[&str] = &[
+        "gz", "tgz", "xz", "txz", "zst", "bz2", "lz4", "lzma", "zip", "7z", "squashfs",
+    ];
+
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| COMPRESSED_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+/// Local state of a resumable upload, used to continue it after a failure.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct UploadState {
+    pub(crate) upload_id: Uuid,
+    pub(crate) size: u64,
+    pub(crate) modified: Option<u64>,
+    pub(crate) offset: u64,
+    pub(crate) chunk_size: u64,
+}
+
+impl UploadState {
+    /// Location of the state file for the firmware at `file`.
+    fn state_path(file: &Path) -> Option<PathBuf> {
+        let file = file.canonicalize().ok()?;
+        dirs::cache_dir().map(|dir| dir.join(UPLOADS_CACHE_DIR).join(state_file_name(&file)))
+    }
+
+    /// Size and modification time identifying the content of `file`.
+    pub(crate) fn fingerprint(file: &Path) -> std::io::Result<(u64, Option<u64>)> {
+        let metadata = std::fs::metadata(file)?;
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map(|duration| duration.as_secs());
+        Ok((metadata.len(), modified))
+    }
+
+    /// Load the state of a previous upload of `file`, if its content
+    /// didn't change in the meantime.
+    pub(crate) fn load(file: &Path) -> Option<Self> {
+        let data = std::fs::read(Self::state_path(file)?).ok()?;
+        let state: Self = serde_json::from_slice(&data).ok()?;
+
+        match Self::fingerprint(file) {
+            Ok((size, modified)) if size == state.size && modified == state.modified => Some(state),
+            _ => None,
+        }
+    }
+
+    /// Atomically persist the state of the upload of `file`.
+    pub(crate) fn save(&self, file: &Path) -> std::io::Result<()> {
+        let path = Self::state_path(file).ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::NotFound, "no cache directory")
+        })?;
+        std::fs::create_dir_all(path.parent().expect("state file should have a parent"))?;
+
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec(self)?)?;
+        std::fs::rename(tmp, path)
+    }
+
+    /// Remove the state of the upload of `file`.
+    pub(crate) fn remove(file: &Path) {
+        if let Some(path) = Self::state_path(file) {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+/// Name of the state file of the firmware at the canonical path `file`: the
+/// SHA-256 of the path, the same with every build of the cli.
+fn state_file_name(file: &Path) -> String {
+    let digest = Sha256::digest(file.to_string_lossy().as_bytes());
+    format!("{}.json", hex(&digest))
+}
+
+/// Read the chunk of `file` starting at `offset`, at most `len` bytes long.
+pub(crate) async fn read_chunk(file: &Path, offset: u64, len: u64) -> std::io::Result<Vec<u8>> {
+    let mut file = tokio::fs::File::open(file).await?;
+    file.seek(SeekFrom::Start(offset)).await?;
+
+    let mut chunk = Vec::with_capacity(len as usize);
+    file.take(len).read_to_end(&mut chunk).await?;
+    Ok(chunk)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn state_file_is_named_after_the_sha256_of_the_path() {
+        assert_eq!(
+            state_file_name(Path::new("/firmware/router.bin")),
+            "e82dfa7065c261db0909049705d5d3a25bdbc1fbb77b4d1b5b35a451b2a19a69.json"
+        );
+    }
+}
@@ -0,0 +1,150 @@
+use std::{collections::HashMap, path::Path};
+
+const OS_RELEASE_PATHS: [&str; 2] = ["/etc/os-release", "/usr/lib/os-release"];
+
+/// Platform of the cli for the user agent, e.g. `linux; x86_64; ubuntu-22.04`.
+///
+/// On Linux the distribution is read from `os-release`, followed by `docker`
+/// when running in a container.
+pub(crate) fn platform() -> String {
+    let mut platform = vec![
+        std::env::consts::OS.to_string(),
+        std::env::consts::ARCH.to_string(),
+    ];
+
+    if cfg!(target_os = "linux") {
+        platform.extend(os_release_distro(&OS_RELEASE_PATHS));
+
+        if in_container() {
+            platform.push("docker".to_string());
+        }
+    }
+
+    platform.join("; ")
+}
+
+/// Distribution of the first `os-release` file found in `paths`.
+fn os_release_distro<P: AsRef<Path>>(paths: &[P]) -> Option<String> {
+    paths
+        .iter()
+        .find_map(|path| std::fs::read_to_string(path).ok())
+        .and_then(|content| distro(&parse_os_release(&content)))
+}
+
+/// Distribution described by the `os-release` entries, e.g. `ubuntu-22.04`.
+fn distro(os_release: &HashMap<String, String>) -> Option<String> {
+    let id = os_release.get("ID").filter(|id| !id.is_empty())?;
+    match os_release.get("VERSION_ID").filter(|v| !v.is_empty()) {
+        Some(version) => Some(format!("{}-{}", id, version)),
+        None => Some(id.clone()),
+    }
+}
+
+/// Parse the `KEY=value` entries of an `os-release` file, values can be
+/// quoted and use backslash escapes as in shell.
+fn parse_os_release(content: &str) -> HashMap<String, String> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| (key.trim().to_string(), unquote(value.trim())))
+        .collect()
+}
+
+fn unquote(value: &str) -> String {
+    let quote = match value.chars().next() {
+        Some(quote @ ('"' | '\'')) if value.len() > 1 && value.ends_with(quote) => quote,
+        _ => return value.to_string(),
+    };
+    let value = &value[1..value.len() - 1];
+
+    // Single quotes are literal
+    if quote == '\'' {
+        return value.to_string();
+    }
+
+    let mut unquoted = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unquoted.extend(chars.next()),
+            c => unquoted.push(c),
+        }
+    }
+    unquoted
+}
+
+/// Whether the cli runs in a container, from `/.dockerenv` or the cgroups
+/// of the init process.
+fn in_container() -> bool {
+    if Path::new("/.dockerenv").exists() {
+        return true;
+    }
+
+    std::fs::read_to_string("/proc/1/cgroup")
+        .map(|cgroup| {
+            ["docker", "kubepods", "containerd", "libpod"]
+                .iter()
+                .any(|runtime| cgroup.contains(runtime))
+        })
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures/os-release")
+            .join(name)
+    }
+
+    #[test]
+    fn debian_is_named_with_its_version() {
+        assert_eq!(
+            os_release_distro(&[fixture("debian")]).as_deref(),
+            Some("debian-12")
+        );
+    }
+
+    #[test]
+    fn alpine_is_named_with_its_unquoted_version() {
+        assert_eq!(
+            os_release_distro(&[fixture("alpine")]).as_deref(),
+            Some("alpine-3.19.1")
+        );
+    }
+
+    #[test]
+    fn distributions_without_version_or_pretty_name_are_named_by_id() {
+        assert_eq!(
+            os_release_distro(&[fixture("minimal")]).as_deref(),
+            Some("arch")
+        );
+    }
+
+    #[test]
+    fn missing_files_are_skipped() {
+        assert_eq!(
+            os_release_distro(&[fixture("missing"), fixture("debian")]).as_deref(),
+            Some("debian-12")
+        );
+        assert_eq!(os_release_distro(&[fixture("missing")]), None);
+    }
+
+    #[test]
+    fn values_are_unquoted_as_in_shell() {
+        let os_release = parse_os_release(
+            "# comment\nNAME='Arch Linux'\nPRETTY_NAME=\"Debian \\\"sid\\\"\"\n\nID = debian \n",
+        );
+
+        assert_eq!(os_release["NAME"], "Arch Linux");
+        assert_eq!(os_release["PRETTY_NAME"], "Debian \"sid\"");
+        assert_eq!(os_release["ID"], "debian");
+        assert_eq!(os_release.len(), 3);
+    }
+}
@@ -0,0 +1,598 @@
+//! Tar archives of the firmware directories given to `create`, e.g. an
+//! extracted root filesystem.
+//!
+//! The entries are sorted so that the same tree always gives the same
+//! archive, and with `--reproducible` the owners and times are left out too.
+
+use std::{
+    fmt,
+    fs::{self, File},
+    io::{self, BufWriter, IsTerminal, Read, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::UNIX_EPOCH,
+};
+
+use anyhow::{bail, Context, Result};
+use indicatif::{HumanBytes, ProgressBar, ProgressStyle};
+use sha2::{Digest, Sha256};
+
+use crate::api;
+
+/// Size of the headers and blocks of tar archives
+const BLOCK: usize = 512;
+/// Longest name and link target of a ustar header
+const NAME_LEN: usize = 100;
+/// Longest directory prefix of a ustar header
+const PREFIX_LEN: usize = 155;
+
+/// Glob matching the paths left out of an archive, e.g. `*.log`, `.git` or
+/// `var/cache/**`.
+///
+/// A glob without `/` matches a file or directory of that name anywhere,
+/// one with `/` the path from the root of the directory. `*` matches within
+/// a name, `**` across directories, `?` one character and `[abc]` one of a
+/// set. A trailing `/` only matches directories.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Glob {
+    glob: String,
+    pattern: Vec<char>,
+    anchored: bool,
+    dir_only: bool,
+}
+
+impl FromStr for Glob {
+    type Err = String;
+
+    fn from_str(glob: &str) -> Result<Self, Self::Err> {
+        let dir_only = glob.ends_with('/');
+        let pattern = glob.trim_start_matches("./").trim_end_matches('/');
+        let anchored = pattern.contains('/');
+        let pattern = pattern.trim_start_matches('/');
+        if pattern.is_empty() {
+            return Err(format!(
+                "invalid glob `{glob}`, expected e.g. *.log or var/cache/**"
+            ));
+        }
+        if pattern.matches('[').count() != pattern.matches(']').count() {
+            return Err(format!("invalid glob `{glob}`, unbalanced brackets"));
+        }
+        Ok(Self {
+            glob: glob.to_string(),
+            pattern: pattern.chars().collect(),
+            anchored,
+            dir_only,
+        })
+    }
+}
+
+impl fmt::Display for Glob {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.glob)
+    }
+}
+
+impl Glob {
+    /// Whether the entry at `path`, relative to the root of the archive and
+    /// separated by `/`, is matched.
+    pub fn matches(&self, path: &str, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+        let subject = match self.anchored {
+            true => path,
+            false => path.rsplit('/').next().unwrap_or(path),
+        };
+        glob_match(&self.pattern, &subject.chars().collect::<Vec<_>>())
+    }
+}
+
+fn glob_match(pattern: &[char], path: &[char]) -> bool {
+    match pattern {
+        [] => path.is_empty(),
+        ['*', '*', rest @ ..] => {
+            // `**/` also matches no directory at all
+            let rest = rest.strip_prefix(&['/']).unwrap_or(rest);
+            (0..=path.len()).any(|skip| {
+                (skip == 0 || path[skip - 1] == '/' || rest.is_empty())
+                    && glob_match(rest, &path[skip..])
+            })
+        }
+        ['*', rest @ ..] => (0..=path.len())
+            .take_while(|&skip| skip == 0 || path[skip - 1] != '/')
+            .any(|skip| glob_match(rest, &path[skip..])),
+        ['?', rest @ ..] => {
+            matches!(path.first(), Some(c) if *c != '/') && glob_match(rest, &path[1..])
+        }
+        ['[', rest @ ..] => {
+            let Some(end) = rest.iter().position(|c| *c == ']') else {
+                return false;
+            };
+            let (set, rest) = (&rest[..end], &rest[end + 1..]);
+            let (negated, set) = match set {
+                ['!' | '^', set @ ..] => (true, set),
+                _ => (false, set),
+            };
+            let Some(c) = path.first() else {
+                return false;
+            };
+            let in_set = set
+                .iter()
+                .enumerate()
+                .any(|(i, s)| match set.get(i + 1..i + 3) {
+                    Some(['-', to]) => (s..=to).contains(&c),
+                    _ => s == c,
+                });
+            in_set != negated && *c != '/' && glob_match(rest, &path[1..])
+        }
+        [p, rest @ ..] => path.first() == Some(p) && glob_match(rest, &path[1..]),
+    }
+}
+
+/// Whether `path` is a glob of firmwares, e.g. `artifacts/*.img`, rather
+/// than the path of one: it has `*`, `?` or `[` and isn't an existing file.
+pub fn is_glob(path: &str) -> bool {
+    path.contains(['*', '?', '[']) && !Path::new(path).exists()
+}
+
+/// Files matched by the glob `pattern`, sorted. As in the shell, the
+/// directories before the first wildcard are taken as is, and hidden names
+/// are only matched by a pattern starting them with `.`.
+pub fn expand(pattern: &str) -> Result<Vec<PathBuf>> {
+    let components: Vec<&str> = pattern.split('/').collect();
+    let literal = components
+        .iter()
+        .take_while(|component| !component.contains(['*', '?', '[']))
+        .count();
+    let base = match components[..literal].join("/") {
+        base if base.is_empty() && pattern.starts_with('/') => "/".to_string(),
+        base => base,
+    };
+    let rest = components[literal..].join("/");
+    if rest.is_empty() {
+        bail!("invalid glob `{pattern}`, expected e.g. artifacts/*.img");
+    }
+
+    let walk = GlobWalk {
+        pattern: rest.chars().collect(),
+        depth: match rest.contains("**") {
+            true => usize::MAX,
+            false => rest.split('/').count(),
+        },
+        hidden: rest.starts_with('.') || rest.contains("/."),
+    };
+    let root = match base.as_str() {
+        "" => Path::new("."),
+        base => Path::new(base),
+    };
+    let mut matches = vec![];
+    walk.dir(root, "", 1, &mut matches)?;
+    if matches.is_empty() {
+        bail!("no files match {pattern}");
+    }
+    matches.sort();
+    Ok(matches
+        .into_iter()
+        .map(|path| match base.as_str() {
+            "" => PathBuf::from(path),
+            base => Path::new(base).join(path),
+        })
+        .collect())
+}
+
+/// Walk of the directories below the literal part of a glob.
+struct GlobWalk {
+    pattern: Vec<char>,
+    /// Levels of directories matched, unbounded with `**`
+    depth: usize,
+    hidden: bool,
+}
+
+impl GlobWalk {
+    /// Add the files of `dir`, at `prefix` from the start of the walk, that
+    /// match to `matches`. Links to directories aren't followed.
+    fn dir(&self, dir: &Path, prefix: &str, level: usize, matches: &mut Vec<String>) -> Result<()> {
+        for entry in
+            fs::read_dir(dir).with_context(|| format!("error reading {}", dir.display()))?
+        {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if name.starts_with('.') && !self.hidden {
+                continue;
+            }
+            let path = format!("{prefix}{name}");
+            if entry.file_type()?.is_dir() {
+                if level < self.depth {
+                    self.dir(&entry.path(), &format!("{path}/"), level + 1, matches)?;
+                }
+            } else if fs::metadata(entry.path()).is_ok_and(|metadata| metadata.is_file())
+                && glob_match(&self.pattern, &path.chars().collect::<Vec<_>>())
+            {
+                matches.push(path);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// How a directory is archived.
+#[derive(Debug, Clone, Default)]
+pub struct ArchiveOptions {
+    /// Paths left out, with everything below them
+    pub exclude: Vec<Glob>,
+    /// Leave out the owners and times, all the entries being dated from
+    /// `SOURCE_DATE_EPOCH`, else from 1970
+    pub reproducible: bool,
+}
+
+/// Tar archive of a directory, in a temporary directory removed when
+/// dropped.
+#[derive(Debug)]
+pub struct Archive {
+    _dir: tempfile::TempDir,
+    pub path: PathBuf,
+    /// Entries archived: files, directories and symlinks
+    pub entries: u64,
+    pub size: u64,
+    pub sha256: String,
+}
+
+/// Entry of the tree to archive.
+struct Entry {
+    path: PathBuf,
+    /// Name in the archive, relative to the root and separated by `/`
+    name: String,
+    metadata: fs::Metadata,
+}
+
+/// Archive the directory `dir` into a tar named after it, showing the files
+/// archived on stderr.
+pub async fn archive(dir: &Path, options: &ArchiveOptions) -> Result<Archive> {
+    let dir = dir.to_path_buf();
+    let options = options.clone();
+    tokio::task::spawn_blocking(move || archive_blocking(&dir, &options))
+        .await
+        .context("archiving interrupted")?
+}
+
+fn archive_blocking(dir: &Path, options: &ArchiveOptions) -> Result<Archive> {
+    let mut entries = vec![];
+    walk(dir, "", options, &mut entries)
+        .with_context(|| format!("error reading directory {}", dir.display()))?;
+
+    let name = dir
+        .canonicalize()
+        .ok()
+        .and_then(|dir| {
+            dir.file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+        })
+        .unwrap_or_else(|| "firmware".to_string());
+    let temp = tempfile::Builder::new()
+        .prefix("cosmo-archive")
+        .tempdir()
+        .context("error creating a temporary directory")?;
+    let path = temp.path().join(format!("{name}.tar"));
+    let file = File::create(&path)
+        .with_context(|| format!("error creating archive {}", path.display()))?;
+
+    let mtime = match options.reproducible {
+        true => Some(
+            std::env::var("SOURCE_DATE_EPOCH")
+                .ok()
+                .and_then(|epoch| epoch.parse().ok())
+                .unwrap_or(0),
+        ),
+        false => None,
+    };
+
+    let progress = archive_progress(entries.len() as u64);
+    let mut writer = HashWriter {
+        inner: BufWriter::new(file),
+        hasher: Sha256::new(),
+        written: 0,
+    };
+    for entry in &entries {
+        progress.set_message(entry.name.clone());
+        append(&mut writer, entry, mtime)
+            .with_context(|| format!("error archiving {}", entry.path.display()))?;
+        progress.inc(1);
+    }
+    writer.write_all(&[0; BLOCK * 2])?;
+    writer.flush()?;
+    progress.finish_and_clear();
+
+    let archive = Archive {
+        _dir: temp,
+        path,
+        entries: entries.len() as u64,
+        size: writer.written,
+        sha256: api::hex(&writer.hasher.finalize()),
+    };
+    tracing::info!(
+        "Archived {} entries of {} into {} ({}), SHA-256 {}",
+        archive.entries,
+        dir.display(),
+        name + ".tar",
+        HumanBytes(archive.size),
+        archive.sha256
+    );
+    Ok(archive)
+}
+
+/// Bar of the entries archived, hidden when not a terminal or when quiet.
+fn archive_progress(entries: u64) -> ProgressBar {
+    if !std::io::stderr().is_terminal() || !tracing::enabled!(tracing::Level::INFO) {
+        return ProgressBar::hidden();
+    }
+    ProgressBar::new(entries).with_style(
+        ProgressStyle::with_template(
+            "[{elapsed_precise}] [{wide_bar}] {pos}/{len} files archived {wide_msg}",
+        )
+        .expect("valid progress template")
+        .progress_chars("=> "),
+    )
+}
+
+/// Collect the entries below `dir`, sorted by name and without the
+/// excluded ones. Symlinks are not followed.
+fn walk(
+    dir: &Path,
+    prefix: &str,
+    options: &ArchiveOptions,
+    entries: &mut Vec<Entry>,
+) -> io::Result<()> {
+    let mut children = fs::read_dir(dir)?.collect::<io::Result<Vec<_>>>()?;
+    children.sort_by_key(|child| child.file_name());
+
+    for child in children {
+        let path = child.path();
+        let metadata = fs::symlink_metadata(&path)?;
+        let Some(file_name) = child.file_name().to_str().map(str::to_string) else {
+            tracing::warn!("Skipping {}, its name is not valid UTF-8", path.display());
+            continue;
+        };
+        let name = format!("{prefix}{file_name}");
+        let is_dir = metadata.is_dir();
+        if let Some(glob) = options
+            .exclude
+            .iter()
+            .find(|glob| glob.matches(&name, is_dir))
+        {
+            tracing::debug!("Excluding {} matching {}", name, glob);
+            continue;
+        }
+
+        if is_dir {
+            let children = format!("{name}/");
+            entries.push(Entry {
+                path: path.clone(),
+                name: children.clone(),
+                metadata,
+            });
+            walk(&path, &children, options, entries)?;
+        } else if metadata.is_file() || metadata.file_type().is_symlink() {
+            entries.push(Entry {
+                path,
+                name,
+                metadata,
+            });
+        } else {
+            tracing::warn!(
+                "Skipping {}, not a file, directory nor symlink",
+                path.display()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Writer hashing and counting what it writes.
+struct HashWriter<W> {
+    inner: W,
+    hasher: Sha256,
+    written: u64,
+}
+
+impl<W: Write> Write for HashWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        self.written += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Append the header and the data of `entry`, dated `mtime` when given.
+fn append<W: Write>(writer: &mut W, entry: &Entry, mtime: Option<u64>) -> io::Result<()> {
+    let metadata = &entry.metadata;
+    let (type_flag, size, link) = if metadata.file_type().is_symlink() {
+        let target = fs::read_link(&entry.path)?;
+        (b'2', 0, Some(target.to_string_lossy().replace('\\', "/")))
+    } else if metadata.is_dir() {
+        (b'5', 0, None)
+    } else {
+        (b'0', metadata.len(), None)
+    };
+
+    let (mode, uid, gid) = permissions(metadata, mtime.is_some());
+    let mtime = mtime.unwrap_or_else(|| {
+        metadata
+            .modified()
+            .ok()
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |elapsed| elapsed.as_secs())
+    });
+
+    // Names and targets too long for the header go in a pax header before it
+    let split = split_name(&entry.name);
+    let mut records = vec![];
+    if split.is_none() {
+        records.push(("path", entry.name.as_str()));
+    }
+    if let Some(link) = link.as_deref().filter(|link| link.len() > NAME_LEN) {
+        records.push(("linkpath", link));
+    }
+    if !records.is_empty() {
+        let pax: Vec<u8> = records
+            .iter()
+            .flat_map(|(key, value)| pax_record(key, value).into_bytes())
+            .collect();
+        let header = Header {
+            name: ("", "pax_header"),
+            mode: 0o644,
+            uid: 0,
+            gid: 0,
+            size: pax.len() as u64,
+            mtime,
+            type_flag: b'x',
+            link: "",
+        };
+        writer.write_all(&header.bytes())?;
+        write_padded(writer, &pax)?;
+    }
+
+    let header = Header {
+        name: split.unwrap_or(("", truncate(&entry.name, NAME_LEN))),
+        mode,
+        uid,
+        gid,
+        size,
+        mtime,
+        type_flag,
+        link: link.as_deref().map_or("", |link| truncate(link, NAME_LEN)),
+    };
+    writer.write_all(&header.bytes())?;
+
+    if type_flag == b'0' {
+        let copied = io::copy(&mut File::open(&entry.path)?.take(size), writer)?;
+        if copied != size {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "the file changed while archiving it",
+            ));
+        }
+        let padding = (BLOCK - size as usize % BLOCK) % BLOCK;
+        writer.write_all(&vec![0; padding])?;
+    }
+    Ok(())
+}
+
+/// Mode, owner and group of an entry, the owners left out when
+/// `reproducible`.
+#[cfg(unix)]
+fn permissions(metadata: &fs::Metadata, reproducible: bool) -> (u32, u64, u64) {
+    use std::os::unix::fs::MetadataExt;
+
+    let mode = metadata.mode() & 0o7777;
+    match reproducible {
+        true => (mode, 0, 0),
+        false => (mode, u64::from(metadata.uid()), u64::from(metadata.gid())),
+    }
+}
+
+/// Mode of an entry, the only permission on Windows being read-only.
+#[cfg(not(unix))]
+fn permissions(metadata: &fs::Metadata, _reproducible: bool) -> (u32, u64, u64) {
+    let mode = match (metadata.is_dir(), metadata.permissions().readonly()) {
+        (true, _) => 0o755,
+        (false, true) => 0o444,
+        (false, false) => 0o644,
+    };
+    (mode, 0, 0)
+}
+
+/// Prefix and name of the ustar header of `name`, none when too long.
+fn split_name(name: &str) -> Option<(&str, &str)> {
+    if name.len() <= NAME_LEN {
+        return Some(("", name));
+    }
+    // The prefix ends before a `/`, directories keeping their trailing one
+    let search = name.strip_suffix('/').unwrap_or(name);
+    search
+        .match_indices('/')
+        .map(|(i, _)| (&name[..i], &name[i + 1..]))
+        .find(|(prefix, rest)| prefix.len() <= PREFIX_LEN && rest.len() <= NAME_LEN)
+}
+
+/// The longest prefix of `s` of at most `len` bytes, on a char boundary.
+fn truncate(s: &str, len: usize) -> &str {
+    let mut end = s.len().min(len);
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    &s[..end]
+}
+
+/// Pax record `<length> <key>=<value>\n`, the length counting itself.
+fn pax_record(key: &str, value: &str) -> String {
+    let rest = format!(" {key}={value}\n");
+    let mut len = rest.len() + 1;
+    while len.to_string().len() + rest.len() != len {
+        len += 1;
+    }
+    format!("{len}{rest}")
+}
+
+fn write_padded<W: Write>(writer: &mut W, data: &[u8]) -> io::Result<()> {
+    writer.write_all(data)?;
+    writer.write_all(&vec![0; (BLOCK - data.len() % BLOCK) % BLOCK])
+}
+
+/// Ustar header of an entry.
+struct Header<'a> {
+    /// Prefix and name
+    name: (&'a str, &'a str),
+    mode: u32,
+    uid: u64,
+    gid: u64,
+    size: u64,
+    mtime: u64,
+    type_flag: u8,
+    link: &'a str,
+}
+
+impl Header<'_> {
+    fn bytes(&self) -> [u8; BLOCK] {
+        let mut header = [0; BLOCK];
+        let (prefix, name) = self.name;
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        octal(&mut header[100..108], u64::from(self.mode));
+        octal(&mut header[108..116], self.uid);
+        octal(&mut header[116..124], self.gid);
+        octal(&mut header[124..136], self.size);
+        octal(&mut header[136..148], self.mtime);
+        header[156] = self.type_flag;
+        header[157..157 + self.link.len()].copy_from_slice(self.link.as_bytes());
+        header[257..263].copy_from_slice(b"ustar\0");
+        header[263..265].copy_from_slice(b"00");
+        header[345..345 + prefix.len()].copy_from_slice(prefix.as_bytes());
+
+        // Checksum of the header with its own field as spaces
+        header[148..156].copy_from_slice(b"        ");
+        let checksum: u32 = header.iter().map(|byte| u32::from(*byte)).sum();
+        header[148..155].copy_from_slice(format!("{:06o}\0", checksum).as_bytes());
+        header
+    }
+}
+
+/// Write `value` in octal in `field`, NUL terminated, or in base-256 when
+/// too large.
+fn octal(field: &mut [u8], value: u64) {
+    let digits = field.len() - 1;
+    let octal = format!("{:0width$o}", value, width = digits);
+    if octal.len() <= digits {
+        field[..digits].copy_from_slice(octal.as_bytes());
+        field[digits] = 0;
+    } else {
+        field.fill(0);
+        let bytes = value.to_be_bytes();
+        let start = field.len() - bytes.len();
+        field[start..].copy_from_slice(&bytes);
+        field[0] |= 0x80;
+    }
+}
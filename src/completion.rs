@@ -0,0 +1,199 @@
+use std::{io::Write, time::Duration};
+
+use clap::{Arg, ArgAction};
+use clap_complete::Shell;
+
+use crate::{
+    api::ApiServer,
+    cli::{self, ProjectFilter},
+    services::project_service,
+};
+
+/// Name of the executable, called by the scripts
+const BIN_NAME: &str = "cosmo";
+/// Maximum time spent listing the projects, the shell waiting for it
+const TIMEOUT: Duration = Duration::from_secs(1);
+/// Value names of the arguments given a project
+const PROJECT_VALUE_NAMES: [&str; 3] = ["PROJECT", "BASE", "TARGET"];
+
+/// Write the completion script of `shell`, completing the project arguments
+/// with `__complete project-ids` in bash, zsh, fish and PowerShell.
+pub fn generate(shell: Shell, buf: &mut dyn Write) -> std::io::Result<()> {
+    // Bash functions join the subcommand names with `__`, the hidden
+    // completion helper is left out of the script anyway
+    let mut app = cli::app().mut_subcommand("__complete", |cmd| cmd.name("complete"));
+    let bin = BIN_NAME;
+
+    let mut script = vec![];
+    clap_complete::generate(shell, &mut app, bin, &mut script);
+    let script = String::from_utf8(script).expect("completion script should be UTF-8");
+
+    let script = match shell {
+        Shell::Bash => format!(
+            r#"{script}
+_{bin}_projects() {{
+    local projects
+    projects=$({bin} __complete project-ids -- "${{COMP_WORDS[@]:0:COMP_CWORD+1}}" 2>/dev/null)
+    if [[ -n "${{projects}}" ]]; then
+        local IFS=$'\n'
+        COMPREPLY=( $(compgen -W "${{projects}}" -- "${{COMP_WORDS[COMP_CWORD]}}") )
+        return 0
+    fi
+    _{bin} "$@"
+}}
+
+complete -F _{bin}_projects -o bashdefault -o default {bin}
+"#
+        ),
+        Shell::Zsh => script.replacen(
+            &format!("_{bin}() {{\n"),
+            &format!(
+                r#"_{bin}() {{
+    local -a projects
+    projects=("${{(@f)$({bin} __complete project-ids -- "${{(@)words[1,CURRENT]}}" 2>/dev/null)}}")
+    if [[ -n "${{projects[1]}}" ]]; then
+        compadd -a projects
+        return
+    fi
+
+"#
+            ),
+            1,
+        ),
+        Shell::Fish => format!(
+            "{script}complete -c {bin} -a '({bin} __complete project-ids -- (commandline -opc) (commandline -ct) 2>/dev/null)'\n"
+        ),
+        Shell::PowerShell => script.replacen(
+            "    $completions = @(",
+            &format!(
+                r#"    $words = @($commandElements | ForEach-Object {{ $_.ToString() }})
+    if ($wordToComplete -eq '') {{
+        $words += ''
+    }}
+    $projects = @(& '{bin}' __complete project-ids -- @words 2>$null)
+    if ($projects.Count -gt 0) {{
+        return $projects | ForEach-Object {{
+            [CompletionResult]::new($_, $_, [CompletionResultType]::ParameterValue, $_)
+        }}
+    }}
+
+    $completions = @("#
+            ),
+            1,
+        ),
+        _ => script,
+    };
+
+    buf.write_all(script.as_bytes())
+}
+
+/// IDs and names of the cached projects starting with the last of `words`,
+/// when it is a project argument of the command line `words`.
+///
+/// Errors and a slow cache give no candidates, the shell then completing as
+/// usual.
+pub async fn project_candidates<U: ApiServer>(api_server: &mut U, words: &[String]) -> Vec<String> {
+    let Some(prefix) = project_prefix(words) else {
+        return vec![];
+    };
+
+    let filter = ProjectFilter::default();
+    let projects = project_service::list_projects(api_server, &filter, None);
+    let projects = match tokio::time::timeout(TIMEOUT, projects).await {
+        Ok(Ok(projects)) => projects,
+        Ok(Err(e)) => {
+            tracing::debug!("No project to complete: {e:#}");
+            return vec![];
+        }
+        Err(_) => {
+            tracing::debug!("No project to complete: listing the projects timed out");
+            return vec![];
+        }
+    };
+
+    let mut candidates = vec![];
+    for project in projects {
+        for candidate in [project.id.to_string(), project.name] {
+            if candidate.starts_with(prefix) && !candidates.contains(&candidate) {
+                candidates.push(candidate);
+            }
+        }
+    }
+    candidates
+}
+
+/// Last of `words`, the command line up to the word being completed, when
+/// it is the value of a project argument.
+fn project_prefix(words: &[String]) -> Option<&str> {
+    let (current, previous) = words.split_last()?;
+
+    let mut cmd = cli::app();
+    // Option waiting for its value, and index of the next positional
+    let mut pending: Option<Arg> = None;
+    let mut position = 0;
+    let mut only_positionals = false;
+
+    // The first word is the name of the program
+    for word in previous.iter().skip(1) {
+        if pending.is_some() {
+            // Bash splits `--id=value` at the `=`
+            if word != "=" {
+                pending = None;
+            }
+        } else if only_positionals || !word.starts_with('-') || word == "-" {
+            match cmd.find_subcommand(word) {
+                Some(subcommand) if !only_positionals && position == 0 => {
+                    cmd = subcommand.clone();
+                }
+                _ => position += 1,
+            }
+        } else if word == "--" {
+            only_positionals = true;
+        } else if let Some(long) = word.strip_prefix("--") {
+            if !long.contains('=') {
+                pending = cmd
+                    .get_arguments()
+                    .find(|arg| arg.get_long() == Some(long))
+                    .filter(|arg| takes_value(arg))
+                    .cloned();
+            }
+        } else if word.len() == 2 {
+            let short = word.chars().nth(1);
+            pending = cmd
+                .get_arguments()
+                .find(|arg| arg.get_short() == short)
+                .filter(|arg| takes_value(arg))
+                .cloned();
+        }
+    }
+
+    let arg = match pending {
+        Some(arg) => arg,
+        None if current.starts_with('-') && !only_positionals => return None,
+        None => {
+            let positionals: Vec<_> = cmd.get_positionals().collect();
+            match positionals.get(position) {
+                Some(arg) => (*arg).clone(),
+                // Values after the first of a repeated positional
+                None => {
+                    let last = positionals.last()?;
+                    if !matches!(last.get_action(), ArgAction::Append) {
+                        return None;
+                    }
+                    (*last).clone()
+                }
+            }
+        }
+    };
+
+    let is_project = arg.get_value_names().is_some_and(|names| {
+        names
+            .iter()
+            .any(|name| PROJECT_VALUE_NAMES.contains(&name.as_str()))
+    });
+    is_project.then_some(current.as_str())
+}
+
+fn takes_value(arg: &Arg) -> bool {
+    matches!(arg.get_action(), ArgAction::Set | ArgAction::Append)
+}
@@ -0,0 +1,631 @@
+use std::{
+    fmt, fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use clap::ValueEnum;
+use comfy_table::{Cell, Row, Table};
+use ini::Ini;
+use lazy_static::lazy_static;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use serde::{Serialize, Serializer};
+use url::Url;
+
+use crate::{
+    cli::{self, ExitCode, GatePolicy, TokenStore},
+    output::{self, CommandOutput, OutputMode},
+    COSMO_API_SERVER,
+};
+
+/// Configuration file of the current directory, e.g. of a repository,
+/// overriding the one of the user entry by entry.
+pub const LOCAL_CONFIG_FILE: &str = "cosmo.ini";
+
+/// Sections of the settings, the default one and those of the profiles
+pub(crate) const DEFAULT_SECTION: &str = "default";
+pub(crate) const PROFILE_SECTION_PREFIX: &str = "profile.";
+/// Entries of the settings sections that are not settings
+const OTHER_ENTRIES: [&str; 2] = ["api_key", "profile"];
+/// Sections allowed in the local file, besides the settings sections
+const LOCAL_SECTIONS: [&str; 1] = [POLICY_SECTION];
+const SECTIONS: [&str; 2] = [HEADERS_SECTION, POLICY_SECTION];
+const HEADERS_SECTION: &str = "headers";
+const POLICY_SECTION: &str = "policy";
+
+/// Setting of the configuration files, also given with a flag and possibly
+/// an environment variable, which win over the files.
+#[derive(Debug)]
+pub struct Setting {
+    /// Entry of the settings sections
+    pub key: &'static str,
+    /// Argument of the command line
+    pub arg: &'static str,
+    /// Subcommand of the argument, none for global ones
+    pub command: Option<&'static str>,
+    pub env: Option<&'static str>,
+    /// Whether the local file can set it. Those deciding where the api key
+    /// is sent can't be set by a file that came with a repository
+    pub local: bool,
+    pub default: fn() -> Option<String>,
+    validate: fn(&str) -> Result<(), String>,
+}
+
+pub const SETTINGS: &[Setting] = &[
+    Setting {
+        key: "api_server",
+        arg: "api_server",
+        command: None,
+        env: None,
+        local: false,
+        default: || Some(COSMO_API_SERVER.to_string()),
+        validate: |value| {
+            Url::parse(value)
+                .map(|_| ())
+                .map_err(|err| format!("expected a url: {}", err))
+        },
+    },
+    Setting {
+        key: "ca_cert",
+        arg: "ca_cert",
+        command: None,
+        env: None,
+        local: false,
+        default: || None,
+        validate: |_| Ok(()),
+    },
+    Setting {
+        key: "token_store",
+        arg: "token_store",
+        command: None,
+        env: None,
+        local: false,
+        default: || Some(TokenStore::default().to_string()),
+        validate: |value| TokenStore::from_str(value, true).map(|_| ()),
+    },
+    Setting {
+        key: "organization",
+        arg: "organization",
+        command: None,
+        env: Some("COSMO_ORG"),
+        local: true,
+        default: || None,
+        validate: |_| Ok(()),
+    },
+    Setting {
+        key: "timeout",
+        arg: "timeout",
+        command: None,
+        env: Some("COSMO_TIMEOUT"),
+        local: true,
+        default: || Some("60".to_string()),
+        validate: seconds,
+    },
+    Setting {
+        key: "upload_timeout",
+        arg: "upload_timeout",
+        command: None,
+        env: Some("COSMO_UPLOAD_TIMEOUT"),
+        local: true,
+        default: || Some("3600".to_string()),
+        validate: seconds,
+    },
+    Setting {
+        key: "retries",
+        arg: "retries",
+        command: None,
+        env: Some("COSMO_RETRIES"),
+        local: true,
+        default: || Some("3".to_string()),
+        validate: |value| {
+            value
+                .parse::<u32>()
+                .map(|_| ())
+                .map_err(|_| "expected a number".to_string())
+        },
+    },
+    Setting {
+        key: "update_check",
+        arg: "update_check",
+        command: None,
+        env: None,
+        local: true,
+        default: || Some("true".to_string()),
+        validate: |value| {
+            value
+                .parse::<bool>()
+                .map(|_| ())
+                .map_err(|_| "expected true or false".to_string())
+        },
+    },
+    Setting {
+        key: "output",
+        arg: "output",
+        command: None,
+        env: Some("COSMO_OUTPUT"),
+        local: true,
+        default: || Some("table".to_string()),
+        validate: |value| output_mode(value).map(|_| ()),
+    },
+    Setting {
+        key: "type",
+        arg: "fw_type",
+        command: Some("create-project"),
+        env: Some("COSMO_FW_TYPE"),
+        local: true,
+        default: || None,
+        validate: |_| Ok(()),
+    },
+];
+
+fn seconds(value: &str) -> Result<(), String> {
+    value
+        .parse::<u64>()
+        .map(|_| ())
+        .map_err(|_| "expected a number of seconds".to_string())
+}
+
+/// Output mode of the `output` setting, those fitting any command.
+pub fn output_mode(value: &str) -> Result<OutputMode, String> {
+    match OutputMode::from_str(value, true) {
+        Ok(mode @ (OutputMode::Table | OutputMode::Json | OutputMode::Yaml)) => Ok(mode),
+        _ => Err("expected table, json or yaml".to_string()),
+    }
+}
+
+/// Where the value of a setting comes from, by precedence.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    Flag,
+    Env(&'static str),
+    LocalFile(PathBuf),
+    UserFile(PathBuf),
+    Default,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Flag => write!(f, "command line"),
+            Self::Env(env) => write!(f, "{}", env),
+            Self::LocalFile(path) | Self::UserFile(path) => write!(f, "{}", path.display()),
+            Self::Default => write!(f, "default"),
+        }
+    }
+}
+
+impl Serialize for Source {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Value of a setting and where it comes from.
+#[derive(Debug, Clone, Serialize)]
+pub struct SettingValue {
+    pub key: &'static str,
+    pub value: Option<String>,
+    pub source: Source,
+}
+
+/// Effective settings of a profile, as shown by `config show`.
+#[derive(Debug, Serialize)]
+pub struct EffectiveConfig {
+    pub profile: String,
+    pub settings: Vec<SettingValue>,
+}
+
+impl EffectiveConfig {
+    pub fn get_table(&self) -> String {
+        let mut table = Table::new();
+        table.set_header(Row::from(vec![
+            Cell::new("KEY"),
+            Cell::new("VALUE"),
+            Cell::new("SOURCE"),
+        ]));
+        for setting in &self.settings {
+            table.add_row(Row::from(vec![
+                Cell::new(setting.key),
+                Cell::new(setting.value.as_deref().unwrap_or("-")),
+                Cell::new(&setting.source),
+            ]));
+        }
+
+        output::render(table)
+    }
+}
+
+/// The configuration files, the local one overriding the one of the user.
+#[derive(Debug, Default)]
+pub struct Config {
+    /// By precedence
+    layers: Vec<Layer>,
+}
+
+#[derive(Debug)]
+struct Layer {
+    path: PathBuf,
+    local: bool,
+    ini: Ini,
+}
+
+impl Config {
+    /// The configuration file of the current directory and the one of the
+    /// user at `user`, those that exist, failing on the invalid entries.
+    pub fn load(user: &Path) -> Result<Self> {
+        let mut layers = vec![];
+        for (path, local) in [
+            (PathBuf::from(LOCAL_CONFIG_FILE), true),
+            (user.to_path_buf(), false),
+        ] {
+            if !path.exists() {
+                continue;
+            }
+            let ini = Ini::load_from_file(&path)
+                .map_err(|err| anyhow!("invalid configuration file {}: {}", path.display(), err))?;
+            let layer = Layer { path, local, ini };
+            layer.check()?;
+            layers.push(layer);
+        }
+        Ok(Self { layers })
+    }
+
+    /// Value of `key` in `section`, from the file of highest precedence
+    /// having it.
+    pub fn get(&self, section: &str, key: &str) -> Option<(&str, Source)> {
+        self.layers.iter().find_map(|layer| {
+            let value = layer.ini.section(Some(section))?.get(key)?;
+            Some((value, layer.source()))
+        })
+    }
+
+    /// Entries of `section` of all the files, those of the local file
+    /// replacing those of the user one.
+    pub fn entries(&self, section: &str) -> Vec<(String, String)> {
+        let mut entries: Vec<(String, String)> = vec![];
+        for layer in self.layers.iter().rev() {
+            for (key, value) in layer
+                .ini
+                .section(Some(section))
+                .into_iter()
+                .flat_map(|s| s.iter())
+            {
+                entries.retain(|(k, _)| k != key);
+                entries.push((key.to_string(), value.to_string()));
+            }
+        }
+        entries
+    }
+
+    pub fn has_section(&self, section: &str) -> bool {
+        self.layers
+            .iter()
+            .any(|layer| layer.ini.section(Some(section)).is_some())
+    }
+}
+
+impl Layer {
+    fn source(&self) -> Source {
+        if self.local {
+            Source::LocalFile(self.path.clone())
+        } else {
+            Source::UserFile(self.path.clone())
+        }
+    }
+
+    fn check(&self) -> Result<()> {
+        let path = self.path.display();
+        for (section, properties) in self.ini.iter() {
+            let Some(section) = section else {
+                continue;
+            };
+            if section != DEFAULT_SECTION && !section.starts_with(PROFILE_SECTION_PREFIX) {
+                if self.local && !LOCAL_SECTIONS.contains(&section) {
+                    bail!(
+                        "[{}] of {} can only be set in the user configuration file",
+                        section,
+                        path
+                    );
+                }
+                if !SECTIONS.contains(&section) {
+                    tracing::warn!("Unknown section [{}] in {}", section, path);
+                }
+                continue;
+            }
+
+            for (key, value) in properties.iter() {
+                let setting = SETTINGS.iter().find(|setting| setting.key == key);
+                let local = setting.is_some_and(|setting| setting.local);
+                if self.local && !local && (setting.is_some() || OTHER_ENTRIES.contains(&key)) {
+                    bail!(
+                        "`{}` of [{}] in {} can only be set in the user configuration file",
+                        key,
+                        section,
+                        path
+                    );
+                }
+                match setting {
+                    Some(setting) => (setting.validate)(value).map_err(|err| {
+                        anyhow!("invalid `{}` of [{}] in {}: {}", key, section, path, err)
+                    })?,
+                    None if OTHER_ENTRIES.contains(&key) => {}
+                    None => {
+                        tracing::warn!("Unknown setting `{}` of [{}] in {}", key, section, path)
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `text` of a configuration file with `key` of `section` set to `value`,
+/// keeping the comments and the other lines as they are.
+pub fn set_entry(text: &str, section: &str, key: &str, value: &str) -> String {
+    let entry = format!("{}={}", key, value);
+    let mut lines: Vec<String> = text.lines().map(str::to_string).collect();
+    let is_section = |line: &str| line.trim_start().starts_with('[');
+
+    match lines
+        .iter()
+        .position(|line| line.trim() == format!("[{}]", section))
+    {
+        Some(start) => {
+            let end = lines[start + 1..]
+                .iter()
+                .position(|line| is_section(line))
+                .map_or(lines.len(), |i| start + 1 + i);
+            let existing = lines[start + 1..end].iter().position(|line| {
+                line.split_once('=')
+                    .is_some_and(|(name, _)| name.trim() == key)
+            });
+            match existing {
+                Some(i) => lines[start + 1 + i] = entry,
+                None => {
+                    // After the last entry, before the blank lines
+                    let last = lines[start..end]
+                        .iter()
+                        .rposition(|line| !line.trim().is_empty())
+                        .map_or(start, |i| start + i);
+                    lines.insert(last + 1, entry);
+                }
+            }
+        }
+        None => {
+            if lines.last().is_some_and(|line| !line.trim().is_empty()) {
+                lines.push(String::new());
+            }
+            lines.push(format!("[{}]", section));
+            lines.push(entry);
+        }
+    }
+
+    let mut text = lines.join("\n");
+    text.push('\n');
+    text
+}
+
+/// Check `value` of the setting `key`, as `config set` writes it.
+pub fn validate(key: &str, value: &str, local: bool) -> Result<()> {
+    let Some(setting) = SETTINGS.iter().find(|setting| setting.key == key) else {
+        bail!(
+            "unknown setting `{}`, the settings are: {}",
+            key,
+            SETTINGS
+                .iter()
+                .map(|setting| setting.key)
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    };
+    if local && !setting.local {
+        bail!("`{}` can only be set in the user configuration file", key);
+    }
+    (setting.validate)(value).map_err(|err| anyhow!("invalid `{}`: {}", key, err))
+}
+
+/// Path of the configuration file of the user, `COSMO_CONFIG_FILE` when set,
+/// e.g. to a mounted secret in containers, else in the OS config directory.
+pub fn file_path() -> &'static Path {
+    const CONFIG_DIR: &str = "cosmo-cli";
+    const CONFIG_FILE: &str = "config";
+
+    lazy_static! {
+        static ref CONFIG_PATH: PathBuf = std::env::var_os("COSMO_CONFIG_FILE")
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| {
+                dirs::config_dir().map(|config_dir| config_dir.join(CONFIG_DIR).join(CONFIG_FILE))
+            })
+            .expect("Error constructing the path of the configuration file");
+    }
+
+    &CONFIG_PATH
+}
+
+/// The configuration files, read once. Exits on invalid entries.
+pub fn files() -> &'static Config {
+    lazy_static! {
+        static ref CONFIG: Config = Config::load(file_path()).unwrap_or_else(|e| {
+            cli::report_error(&e);
+            ExitCode::Usage.exit()
+        });
+    }
+
+    &CONFIG
+}
+
+/// Read an optional entry of a `section` of the configuration files, if any.
+pub fn entry(section: &str, key: &str) -> Option<String> {
+    files()
+        .get(section, key)
+        .map(|(value, _)| value.to_string())
+}
+
+/// The configuration file of the user, empty when there is none yet.
+pub fn load_user_file() -> Result<Ini> {
+    match file_path().exists() {
+        true => Ini::load_from_file(file_path()).context("invalid configuration file"),
+        false => Ok(Ini::new()),
+    }
+}
+
+pub fn write_user_file(conf: &Ini) -> Result<()> {
+    let mut data = vec![];
+    conf.write_to(&mut data)?;
+    write_user_file_data(&data)
+}
+
+/// Atomically write the configuration file of the user, readable by the user
+/// only from its creation, so that a crash never leaves a truncated file
+/// behind.
+pub fn write_user_file_data(data: &[u8]) -> Result<()> {
+    let path = file_path();
+    fs::create_dir_all(path.parent().expect("config file should have a parent"))?;
+
+    let tmp = path.with_extension(format!("{}.tmp", std::process::id()));
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let written = options.open(&tmp).and_then(|mut file| {
+        file.write_all(data)?;
+        file.sync_all()?;
+        fs::rename(&tmp, path)
+    });
+    if written.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    written.with_context(|| format!("error writing {}", path.display()))
+}
+
+/// Fail when other users can write the configuration file of the user, as
+/// they could send the api key to a server of theirs.
+pub fn check_permissions() -> Result<()> {
+    #[cfg(unix)]
+    if let Ok(metadata) = fs::metadata(file_path()) {
+        use std::os::unix::fs::PermissionsExt;
+        if metadata.permissions().mode() & 0o022 != 0 {
+            bail!(
+                "{} can be written by other users, restrict it with `chmod 600` or pass --allow-writable-config",
+                file_path().display()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Extra headers of the `headers` section of the configuration files, those
+/// passed with `--header` replacing them.
+pub fn extra_headers(cli_headers: Vec<(HeaderName, HeaderValue)>) -> Result<HeaderMap> {
+    let mut headers = HeaderMap::new();
+
+    for (name, value) in files().entries(HEADERS_SECTION) {
+        let (name, value) = cli::header_entry(&name, &value)
+            .map_err(anyhow::Error::msg)
+            .context("invalid header in config file")?;
+        headers.insert(name, value);
+    }
+
+    let mut overrides = HeaderMap::new();
+    for (name, value) in cli_headers {
+        overrides.append(name, value);
+    }
+    headers.extend(overrides);
+
+    Ok(headers)
+}
+
+/// Thresholds of the `[policy]` sections of the configuration files, if any.
+pub fn policy() -> Result<GatePolicy> {
+    let entries = files().entries(POLICY_SECTION);
+
+    GatePolicy::from_entries(
+        entries
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str())),
+    )
+    .map_err(anyhow::Error::msg)
+    .context("invalid policy in config file")
+}
+
+/// Run a `config` subcommand for the profile `profile` of `section`,
+/// `settings` being those given with a flag or an environment variable.
+pub fn command(
+    action: &cli::Config,
+    section: &str,
+    profile: &str,
+    settings: &[SettingValue],
+) -> Result<Box<dyn CommandOutput>> {
+    match action {
+        cli::Config::Show => {
+            let settings = SETTINGS
+                .iter()
+                .map(|setting| {
+                    if let Some(given) = settings.iter().find(|given| given.key == setting.key) {
+                        return given.clone();
+                    }
+                    // Those of other commands aren't parsed by this one
+                    if let Some((env, value)) = setting.env.and_then(|env| {
+                        std::env::var(env)
+                            .ok()
+                            .filter(|value| !value.is_empty())
+                            .map(|value| (env, value))
+                    }) {
+                        return SettingValue {
+                            key: setting.key,
+                            value: Some(value),
+                            source: Source::Env(env),
+                        };
+                    }
+                    match files().get(section, setting.key) {
+                        Some((value, source)) => SettingValue {
+                            key: setting.key,
+                            value: Some(value.to_string()),
+                            source,
+                        },
+                        None => SettingValue {
+                            key: setting.key,
+                            value: (setting.default)(),
+                            source: Source::Default,
+                        },
+                    }
+                })
+                .collect();
+            Ok(Box::new(EffectiveConfig {
+                profile: profile.to_string(),
+                settings,
+            }))
+        }
+        cli::Config::Set { key, value, local } => {
+            validate(key, value, *local)?;
+
+            let path = match local {
+                true => Path::new(LOCAL_CONFIG_FILE),
+                false => file_path(),
+            };
+            let text = match fs::read_to_string(path) {
+                Ok(text) => text,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+                Err(e) => {
+                    return Err(e).with_context(|| format!("error reading {}", path.display()))
+                }
+            };
+            let text = set_entry(&text, section, key, value);
+            match local {
+                true => fs::write(path, text)
+                    .with_context(|| format!("error writing {}", path.display()))?,
+                false => write_user_file_data(text.as_bytes())?,
+            }
+
+            Ok(Box::new(format!(
+                "{} of profile {} set to {} in {}",
+                key,
+                profile,
+                value,
+                path.display()
+            )))
+        }
+    }
+}
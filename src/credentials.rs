@@ -0,0 +1,335 @@
+use std::{
+    fs,
+    io::{self, BufRead, Write},
+    path::Path,
+};
+
+use anyhow::{bail, Context, Result};
+use ini::Ini;
+use url::Url;
+
+#[cfg(feature = "keyring")]
+use crate::keyring;
+use crate::{
+    cli::{ApiKeySource, Prompt, TokenStore},
+    config, encryption, logging,
+    profile::{Profile, API_KEY_ENTRY, API_SERVER_ENTRY, TOKEN_STORE_ENTRY},
+};
+
+/// Passphrase of the api keys saved with `setup --encrypt-session`
+const PASSPHRASE_ENV: &str = "COSMO_SESSION_KEY";
+
+/// Save the api key of `profile`, with the server and the token store given
+/// on the command line, keeping the rest of the configuration.
+#[cfg_attr(not(feature = "keyring"), allow(unused_variables))]
+pub fn setup(
+    profile: &Profile,
+    api_key: Option<String>,
+    api_server: Option<Url>,
+    store: Option<TokenStore>,
+    encrypt: bool,
+    prompt: Prompt,
+) -> Result<()> {
+    // Given with --api-key or COSMO_API_KEY, else asked on the terminal
+    let api_key = match api_key {
+        Some(api_key) => api_key,
+        None => read_api_key(prompt)?,
+    };
+
+    let mut conf = config::load_user_file()?;
+    let token_store = match store {
+        Some(store) => store,
+        None => profile.token_store()?,
+    };
+    let section = conf
+        .entry(Some(profile.section()))
+        .or_insert_with(Default::default);
+    if let Some(api_server) = api_server {
+        section.insert(API_SERVER_ENTRY, api_server.to_string());
+    }
+    if let Some(store) = store {
+        section.insert(TOKEN_STORE_ENTRY, store.to_string());
+    }
+
+    #[cfg(feature = "keyring")]
+    if token_store == TokenStore::Keyring {
+        match keyring::set(&profile.keyring_account(), &api_key) {
+            Ok(()) => {
+                // Not leaving behind a key saved before
+                section.remove(API_KEY_ENTRY);
+                return config::write_user_file(&conf);
+            }
+            Err(e) => tracing::warn!(
+                "Keyring unavailable, saving the api key in the configuration file instead: {:#}",
+                e
+            ),
+        }
+    }
+
+    let api_key = if encrypt {
+        encryption::encrypt(&api_key, &read_passphrase(prompt, true)?)?
+    } else {
+        api_key
+    };
+    section.insert(API_KEY_ENTRY, api_key);
+    config::write_user_file(&conf)
+}
+
+/// Save `api_key` of `profile` in place of the previous one, read from
+/// `source`, keeping it encrypted when it was. The keys given on the command
+/// line or the environment are left to the user.
+pub fn save_rotated(
+    profile: &Profile,
+    source: Option<ApiKeySource>,
+    api_key: &str,
+    prompt: Prompt,
+) -> Result<Option<ApiKeySource>> {
+    match source {
+        #[cfg(feature = "keyring")]
+        Some(ApiKeySource::Keyring) => {
+            keyring::set(&profile.keyring_account(), api_key)?;
+            Ok(source)
+        }
+        Some(ApiKeySource::ConfigFile) => {
+            let mut conf = config::load_user_file()?;
+            let section = conf
+                .entry(Some(profile.section()))
+                .or_insert_with(Default::default);
+            let api_key = match section
+                .get(API_KEY_ENTRY)
+                .is_some_and(encryption::is_encrypted)
+            {
+                true => encryption::encrypt(api_key, &read_passphrase(prompt, false)?)?,
+                false => api_key.to_string(),
+            };
+            section.insert(API_KEY_ENTRY, api_key);
+            config::write_user_file(&conf)?;
+            Ok(source)
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Api key read from stdin or from `file`, if asked, without the trailing
+/// newline.
+pub fn input(stdin: bool, file: Option<&Path>) -> Result<Option<String>> {
+    let api_key = if stdin {
+        let mut line = String::new();
+        io::stdin()
+            .lock()
+            .read_line(&mut line)
+            .context("error reading the api key from stdin")?;
+        line
+    } else if let Some(file) = file {
+        #[cfg(unix)]
+        if let Ok(metadata) = fs::metadata(file) {
+            use std::os::unix::fs::PermissionsExt;
+            if metadata.permissions().mode() & 0o044 != 0 {
+                tracing::warn!(
+                    "{} can be read by other users, restrict it with `chmod 600`",
+                    file.display()
+                );
+            }
+        }
+        fs::read_to_string(file)
+            .with_context(|| format!("error reading the api key from {}", file.display()))?
+    } else {
+        return Ok(None);
+    };
+
+    let api_key = api_key.trim();
+    if api_key.is_empty() {
+        bail!("no api key given");
+    }
+    Ok(Some(api_key.to_string()))
+}
+
+/// Ask the api key on the terminal, failing at once when not interactive
+/// rather than waiting for a stdin that may never be closed, e.g. in CI.
+fn read_api_key(prompt: Prompt) -> Result<String> {
+    prompt.check(
+        "Insert your Api Key",
+        "pass it with --api-key, --api-key-stdin or the COSMO_API_KEY environment variable",
+    )?;
+
+    print!("Insert your Api Key: ");
+    io::stdout().flush()?;
+    let api_key = io::stdin()
+        .lock()
+        .lines()
+        .next()
+        .context("no api key entered")??;
+
+    let api_key = api_key.trim();
+    if api_key.is_empty() {
+        bail!("no api key entered");
+    }
+    Ok(api_key.to_string())
+}
+
+/// Passphrase of the encrypted api key, from `COSMO_SESSION_KEY` or
+/// asked on the terminal, twice when `confirm` is set.
+fn read_passphrase(prompt: Prompt, confirm: bool) -> Result<String> {
+    if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV) {
+        logging::redact(&passphrase);
+        if passphrase.is_empty() {
+            bail!("empty {}", PASSPHRASE_ENV);
+        }
+        return Ok(passphrase);
+    }
+
+    let ask = |question: &str| -> Result<String> {
+        prompt.check(
+            question,
+            &format!("pass it with the {} environment variable", PASSPHRASE_ENV),
+        )?;
+        eprint!("{}: ", question);
+        io::stderr().flush()?;
+        let mut line = String::new();
+        io::stdin()
+            .lock()
+            .read_line(&mut line)
+            .context("no passphrase entered")?;
+        Ok(line.trim_end_matches(['\r', '\n']).to_string())
+    };
+
+    let passphrase = ask("Passphrase of the api key")?;
+    if passphrase.is_empty() {
+        bail!("no passphrase entered");
+    }
+    if confirm && ask("Repeat the passphrase")? != passphrase {
+        bail!("the passphrases don't match");
+    }
+    Ok(passphrase)
+}
+
+/// Api key of `profile` saved by `setup` in `store`, and where it was read
+/// from.
+///
+/// With the keyring, a key still in the configuration file is moved to the
+/// keyring, and the configuration file is read when the keyring can't be
+/// reached.
+pub fn stored(
+    profile: &Profile,
+    store: TokenStore,
+    prompt: Prompt,
+) -> Result<(String, ApiKeySource)> {
+    #[cfg(feature = "keyring")]
+    if store == TokenStore::Keyring {
+        let account = profile.keyring_account();
+        match keyring::get(&account) {
+            Ok(Some(api_key)) => return Ok((api_key, ApiKeySource::Keyring)),
+            Ok(None) => {
+                let api_key = try_api_key_from_config_file(profile, prompt)?;
+                let moved = keyring::set(&account, &api_key)
+                    .and_then(|()| remove_config_api_key(profile).map(|_| ()));
+                return match moved {
+                    Ok(()) => {
+                        tracing::info!(
+                            "Moved the api key from {} to the keyring",
+                            config::file_path().display()
+                        );
+                        Ok((api_key, ApiKeySource::Keyring))
+                    }
+                    Err(e) => {
+                        tracing::warn!(
+                            "Could not move the api key to the keyring, keeping it in the configuration file: {:#}",
+                            e
+                        );
+                        Ok((api_key, ApiKeySource::ConfigFile))
+                    }
+                };
+            }
+            Err(e) => tracing::warn!(
+                "Keyring unavailable, reading the api key from the configuration file: {:#}",
+                e
+            ),
+        }
+    }
+
+    #[cfg(not(feature = "keyring"))]
+    if store == TokenStore::Keyring {
+        tracing::warn!(
+            "Built without the keyring feature, reading the api key from the configuration file"
+        );
+    }
+
+    try_api_key_from_config_file(profile, prompt).map(|api_key| (api_key, ApiKeySource::ConfigFile))
+}
+
+/// Remove the api key of `profile` from the configuration file, keeping the
+/// other entries, returning whether there was one.
+fn remove_config_api_key(profile: &Profile) -> Result<bool> {
+    let Ok(mut i) = Ini::load_from_file(config::file_path()) else {
+        return Ok(false);
+    };
+    if let Some(section) = i.section_mut(Some(profile.section())) {
+        if section.remove(API_KEY_ENTRY).is_some() {
+            config::write_user_file(&i)?;
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Remove the api key of `profile` saved by `setup` in `store`, returning
+/// whether there was one.
+#[cfg_attr(not(feature = "keyring"), allow(unused_variables))]
+pub fn forget(profile: &Profile, store: TokenStore) -> Result<bool> {
+    #[allow(unused_mut)]
+    let mut forgotten = remove_config_api_key(profile)?;
+
+    #[cfg(feature = "keyring")]
+    if store == TokenStore::Keyring {
+        let account = profile.keyring_account();
+        match keyring::get(&account) {
+            Ok(Some(_)) => {
+                keyring::delete(&account)?;
+                forgotten = true;
+            }
+            Ok(None) => {}
+            Err(e) => tracing::warn!(
+                "Keyring unavailable, the api key may still be saved there: {:#}",
+                e
+            ),
+        }
+    }
+
+    Ok(forgotten)
+}
+
+pub fn logout_message(profile: &Profile, revoked: bool, forgotten: bool) -> String {
+    match (revoked, forgotten) {
+        (true, true) => format!(
+            "Api key revoked and removed from profile {}",
+            profile.name()
+        ),
+        (true, false) => "Api key revoked".to_string(),
+        (false, true) => format!("Api key removed from profile {}", profile.name()),
+        (false, false) => format!("No api key saved in profile {}", profile.name()),
+    }
+}
+
+/// Api key of `profile` in the configuration file, decrypted when saved with
+/// `setup --encrypt-session`.
+fn try_api_key_from_config_file(profile: &Profile, prompt: Prompt) -> Result<String> {
+    let i = Ini::load_from_file(config::file_path())?;
+
+    let section = i
+        .section(Some(profile.section()))
+        .with_context(|| format!("no {} section found", profile.section()))?;
+
+    let api_key = section
+        .get(API_KEY_ENTRY)
+        .context("no api key entry found")?;
+
+    if encryption::is_encrypted(api_key) {
+        return encryption::decrypt(api_key, &read_passphrase(prompt, false)?).with_context(|| {
+            format!(
+                "cannot decrypt session, re-login with `cosmo setup` or check {}",
+                PASSPHRASE_ENV
+            )
+        });
+    }
+    Ok(api_key.to_string())
+}
@@ -0,0 +1,124 @@
+use anyhow::{anyhow, bail, Context, Result};
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use openssl::{
+    rand::rand_bytes,
+    symm::{decrypt_aead, encrypt_aead, Cipher},
+};
+
+/// Prefix of the encrypted values of the configuration file.
+const PREFIX: &str = "encrypted:";
+
+/// Format of the encrypted values: the key derived from the passphrase with
+/// Argon2id, whose parameters are stored with the value, and the value
+/// encrypted with AES-256-GCM.
+const FORMAT_VERSION: u8 = 1;
+/// Memory in KiB, iterations and parallelism of Argon2id for the new values,
+/// the minimum recommended by OWASP
+const ARGON2_M_COST: u32 = 19 * 1024;
+const ARGON2_T_COST: u32 = 2;
+const ARGON2_P_COST: u32 = 1;
+/// Upper bound of the memory of the stored parameters, 1 GiB
+const ARGON2_MAX_M_COST: u32 = 1024 * 1024;
+/// Format version and the three parameters of Argon2id
+const HEADER_LEN: usize = 1 + 3 * 4;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+
+/// Whether `value` of the configuration file is encrypted.
+pub fn is_encrypted(value: &str) -> bool {
+    value.starts_with(PREFIX)
+}
+
+/// `secret` encrypted with `passphrase`: the prefix then, in base64, the
+/// format version, the memory, iterations and parallelism of Argon2id as
+/// little endian u32, the salt, the nonce, the ciphertext and its tag.
+pub fn encrypt(secret: &str, passphrase: &str) -> Result<String> {
+    let mut salt = [0; SALT_LEN];
+    let mut nonce = [0; NONCE_LEN];
+    rand_bytes(&mut salt)?;
+    rand_bytes(&mut nonce)?;
+
+    let mut header = vec![FORMAT_VERSION];
+    for param in [ARGON2_M_COST, ARGON2_T_COST, ARGON2_P_COST] {
+        header.extend_from_slice(&param.to_le_bytes());
+    }
+
+    let params = Params::new(ARGON2_M_COST, ARGON2_T_COST, ARGON2_P_COST, None)
+        .map_err(|err| anyhow!("invalid Argon2id parameters: {}", err))?;
+    let key = key(passphrase, &salt, params)?;
+    let mut tag = [0; TAG_LEN];
+    // The header is authenticated, its parameters can't be tampered with
+    let ciphertext = encrypt_aead(
+        Cipher::aes_256_gcm(),
+        &key,
+        Some(&nonce),
+        &header,
+        secret.as_bytes(),
+        &mut tag,
+    )?;
+
+    let mut data = header;
+    data.extend_from_slice(&salt);
+    data.extend_from_slice(&nonce);
+    data.extend_from_slice(&ciphertext);
+    data.extend_from_slice(&tag);
+    Ok(format!("{}{}", PREFIX, STANDARD.encode(data)))
+}
+
+/// Secret of the encrypted `value`, failing on a wrong passphrase.
+pub fn decrypt(value: &str, passphrase: &str) -> Result<String> {
+    let data = value
+        .strip_prefix(PREFIX)
+        .and_then(|data| STANDARD.decode(data).ok())
+        .context("invalid encrypted value")?;
+
+    match data.first() {
+        Some(&FORMAT_VERSION) => {}
+        Some(version) => bail!(
+            "value encrypted with format version {}, unsupported by this cli version",
+            version
+        ),
+        None => bail!("invalid encrypted value"),
+    }
+    if data.len() < HEADER_LEN + SALT_LEN + NONCE_LEN + TAG_LEN {
+        bail!("invalid encrypted value");
+    }
+    let (header, rest) = data.split_at(HEADER_LEN);
+    let (salt, rest) = rest.split_at(SALT_LEN);
+    let (nonce, rest) = rest.split_at(NONCE_LEN);
+    let (ciphertext, tag) = rest.split_at(rest.len() - TAG_LEN);
+
+    let param = |index: usize| {
+        let start = 1 + index * 4;
+        u32::from_le_bytes(header[start..start + 4].try_into().expect("4 bytes"))
+    };
+    let (m_cost, t_cost, p_cost) = (param(0), param(1), param(2));
+    if m_cost > ARGON2_MAX_M_COST {
+        bail!("invalid encrypted value, Argon2id memory of {} KiB", m_cost);
+    }
+    let params = Params::new(m_cost, t_cost, p_cost, None)
+        .map_err(|err| anyhow!("invalid encrypted value, {}", err))?;
+
+    let key = key(passphrase, salt, params)?;
+    // A wrong passphrase only shows as a tag mismatch
+    let secret = decrypt_aead(
+        Cipher::aes_256_gcm(),
+        &key,
+        Some(nonce),
+        header,
+        ciphertext,
+        tag,
+    )
+    .map_err(|_| anyhow!("wrong passphrase or corrupted value"))?;
+    String::from_utf8(secret).map_err(|_| anyhow!("decrypted value is not valid UTF-8"))
+}
+
+fn key(passphrase: &str, salt: &[u8], params: Params) -> Result<[u8; 32]> {
+    let mut key = [0; 32];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|err| anyhow!("error deriving the key from the passphrase: {}", err))?;
+    Ok(key)
+}
@@ -0,0 +1,515 @@
+//! Detection of the type of a firmware from its contents, proposed by
+//! `create` when no type is given.
+//!
+//! Only the first megabytes and the last one are read, plus the headers of
+//! the entries of tar archives, never the whole file. Firmwares without a
+//! known signature are reported as unknown rather than guessed.
+
+use std::{
+    fmt,
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+    path::Path,
+};
+
+use crate::cli::{FwSubtype, FwType};
+
+/// Bytes read from the start of the firmware
+const HEAD_SIZE: u64 = 4 * 1024 * 1024;
+/// Bytes read from the end, where the flash images of UEFI have their boot
+/// volume
+const TAIL_SIZE: u64 = 1024 * 1024;
+/// Entries of a tar archive read looking for a container image
+const MAX_TAR_ENTRIES: usize = 10_000;
+/// Size of the headers and blocks of tar archives
+const TAR_BLOCK: u64 = 512;
+
+/// GUIDs of the UEFI capsules, as stored in their header
+const CAPSULE_GUIDS: [(&str, [u8; 16]); 3] = [
+    (
+        "EFI capsule",
+        guid(
+            0x3B6686BD,
+            0x0D76,
+            0x4030,
+            [0xB7, 0x0E, 0xB5, 0x51, 0x9E, 0x2F, 0xC5, 0xA0],
+        ),
+    ),
+    (
+        "FMP capsule",
+        guid(
+            0x6DCBD5ED,
+            0xE82D,
+            0x4C44,
+            [0xBD, 0xA1, 0x71, 0x94, 0x19, 0x9A, 0xD9, 0x2A],
+        ),
+    ),
+    (
+        "UEFI capsule",
+        guid(
+            0x4A3CA68B,
+            0x7723,
+            0x48FB,
+            [0x80, 0x3D, 0x57, 0x8C, 0xC1, 0xFE, 0xC4, 0x4D],
+        ),
+    ),
+];
+
+/// Signature of the Intel flash descriptor, at offset 16 of SPI images
+const FLASH_DESCRIPTOR: [u8; 4] = [0x5A, 0xA5, 0xF0, 0x0F];
+/// Magic of the U-Boot images, with the OS at offset 28 and the name at 32
+const UIMAGE_MAGIC: [u8; 4] = [0x27, 0x05, 0x19, 0x56];
+const UIMAGE_OS_LINUX: u8 = 5;
+const UIMAGE_OS_VXWORKS: u8 = 14;
+
+/// Type of a firmware found in its contents, or why none was.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Detection {
+    Detected {
+        fw_type: FwType,
+        fw_subtype: FwSubtype,
+        /// What was found and where, e.g. `squashfs superblock at 0x40`
+        evidence: String,
+    },
+    Unknown {
+        reason: String,
+    },
+}
+
+impl fmt::Display for Detection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Detected {
+                fw_type,
+                fw_subtype,
+                evidence,
+            } => write!(f, "{} {} ({})", fw_type, fw_subtype, evidence),
+            Self::Unknown { reason } => write!(f, "unknown ({})", reason),
+        }
+    }
+}
+
+/// Detect the type of the firmware at `path`.
+pub fn detect(path: &Path) -> io::Result<Detection> {
+    detect_reader(&mut File::open(path)?)
+}
+
+/// Detect the type of the firmware read by `reader`.
+pub fn detect_reader<R: Read + Seek>(reader: &mut R) -> io::Result<Detection> {
+    let len = reader.seek(SeekFrom::End(0))?;
+    if len == 0 {
+        return Ok(unknown("empty file"));
+    }
+    let head = read_at(reader, 0, HEAD_SIZE.min(len))?;
+    let tail = match len > HEAD_SIZE {
+        true => read_at(reader, len - TAIL_SIZE.min(len - HEAD_SIZE), TAIL_SIZE)?,
+        false => vec![],
+    };
+
+    if head.get(257..262) == Some(b"ustar") {
+        return tar(reader, len);
+    }
+    if let Some(format) = compression(&head) {
+        return Ok(unknown(&format!(
+            "{} compressed, its contents can't be seen without decompressing it",
+            format
+        )));
+    }
+
+    let detection = uefi(&head, &tail, len)
+        .or_else(|| vxworks(&head, &tail))
+        .or_else(|| linux(&head))
+        .unwrap_or_else(|| match head.starts_with(b"\x7fELF") {
+            true => unknown("ELF binary without the markers of VxWorks"),
+            false => unknown(&format!(
+                "no known signature in the first {} MB",
+                HEAD_SIZE.min(len).div_ceil(1024 * 1024)
+            )),
+        });
+    Ok(detection)
+}
+
+fn detected(fw_type: FwType, fw_subtype: FwSubtype, evidence: String) -> Detection {
+    Detection::Detected {
+        fw_type,
+        fw_subtype,
+        evidence,
+    }
+}
+
+fn unknown(reason: &str) -> Detection {
+    Detection::Unknown {
+        reason: reason.to_string(),
+    }
+}
+
+/// Up to `size` bytes at `offset`.
+fn read_at<R: Read + Seek>(reader: &mut R, offset: u64, size: u64) -> io::Result<Vec<u8>> {
+    reader.seek(SeekFrom::Start(offset))?;
+    let mut buf = vec![];
+    reader.take(size).read_to_end(&mut buf)?;
+    Ok(buf)
+}
+
+/// A GUID as laid out in memory, the first three fields little endian.
+const fn guid(data1: u32, data2: u16, data3: u16, data4: [u8; 8]) -> [u8; 16] {
+    let d1 = data1.to_le_bytes();
+    let d2 = data2.to_le_bytes();
+    let d3 = data3.to_le_bytes();
+    [
+        d1[0], d1[1], d1[2], d1[3], d2[0], d2[1], d3[0], d3[1], data4[0], data4[1], data4[2],
+        data4[3], data4[4], data4[5], data4[6], data4[7],
+    ]
+}
+
+fn u16_le(buf: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        buf.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn u16_be(buf: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(
+        buf.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn u32_le(buf: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        buf.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+fn u64_le(buf: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(
+        buf.get(offset..offset + 8)?.try_into().ok()?,
+    ))
+}
+
+/// Offsets of `needle` in `haystack`.
+fn find_all<'a>(haystack: &'a [u8], needle: &'a [u8]) -> impl Iterator<Item = usize> + 'a {
+    haystack
+        .windows(needle.len())
+        .enumerate()
+        .filter(move |(_, window)| *window == needle)
+        .map(|(offset, _)| offset)
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    find_all(haystack, needle).next().is_some()
+}
+
+/// Compression of the whole file, hiding its contents.
+fn compression(head: &[u8]) -> Option<&'static str> {
+    const FORMATS: [(&[u8], &str); 6] = [
+        (&[0x1F, 0x8B], "gzip"),
+        (&[0xFD, b'7', b'z', b'X', b'Z', 0x00], "xz"),
+        (b"BZh", "bzip2"),
+        (&[0x28, 0xB5, 0x2F, 0xFD], "zstd"),
+        (b"PK\x03\x04", "zip"),
+        (&[b'7', b'z', 0xBC, 0xAF, 0x27, 0x1C], "7z"),
+    ];
+    FORMATS
+        .iter()
+        .find(|(magic, _)| head.starts_with(magic))
+        .map(|(_, format)| *format)
+}
+
+/// Docker and LXC images, from the names of the entries of the archive.
+fn tar<R: Read + Seek>(reader: &mut R, len: u64) -> io::Result<Detection> {
+    let names = tar_entries(reader, len)?;
+    let has = |name: &str| names.iter().any(|entry| entry == name);
+
+    if has("manifest.json") {
+        return Ok(detected(
+            FwType::Container,
+            FwSubtype::Docker,
+            "tar archive with a docker manifest.json".to_string(),
+        ));
+    }
+    if has("oci-layout") && has("index.json") {
+        return Ok(detected(
+            FwType::Container,
+            FwSubtype::Docker,
+            "tar archive of an OCI image layout".to_string(),
+        ));
+    }
+    if names.iter().any(|entry| entry.starts_with("rootfs/"))
+        && (has("config") || has("metadata.yaml"))
+    {
+        return Ok(detected(
+            FwType::Container,
+            FwSubtype::Lxc,
+            "tar archive of an LXC rootfs and its configuration".to_string(),
+        ));
+    }
+    // Root filesystems, e.g. the archive of an extracted image
+    let under = |dir: &str| names.iter().any(|entry| entry.starts_with(dir));
+    if under("etc/") && (under("bin/") || under("sbin/") || under("usr/bin/")) {
+        return Ok(
+            match has("etc/openwrt_release") || has("etc/openwrt_version") {
+                true => detected(
+                    FwType::Linux,
+                    FwSubtype::Openwrt,
+                    "tar archive of an OpenWrt root filesystem".to_string(),
+                ),
+                false => detected(
+                    FwType::Linux,
+                    FwSubtype::Generic,
+                    "tar archive of a Linux root filesystem".to_string(),
+                ),
+            },
+        );
+    }
+    Ok(unknown(
+        "tar archive without a docker manifest, an LXC rootfs nor a Linux root filesystem",
+    ))
+}
+
+/// Names of the entries of a tar archive, without the leading `./`, reading
+/// only their headers.
+fn tar_entries<R: Read + Seek>(reader: &mut R, len: u64) -> io::Result<Vec<String>> {
+    let mut names = vec![];
+    let mut long_name = None;
+    let mut offset = 0;
+
+    while offset + TAR_BLOCK <= len && names.len() < MAX_TAR_ENTRIES {
+        let header = read_at(reader, offset, TAR_BLOCK)?;
+        if header.iter().all(|byte| *byte == 0) {
+            break;
+        }
+        let Some(size) = tar_size(&header[124..136]) else {
+            break;
+        };
+        let data = offset + TAR_BLOCK;
+
+        match header[156] {
+            // GNU long name of the next entry
+            b'L' => {
+                let name = read_at(reader, data, size.min(4096))?;
+                long_name = Some(c_string(&name));
+            }
+            // Pax extended headers
+            b'x' | b'g' => {}
+            _ => {
+                let name = long_name.take().unwrap_or_else(|| {
+                    let name = c_string(&header[0..100]);
+                    match &header[257..262] == b"ustar" && header[345] != 0 {
+                        true => format!("{}/{}", c_string(&header[345..500]), name),
+                        false => name,
+                    }
+                });
+                names.push(name.trim_start_matches("./").to_string());
+            }
+        }
+        offset = data + size.div_ceil(TAR_BLOCK) * TAR_BLOCK;
+    }
+    Ok(names)
+}
+
+/// Size field of a tar header, octal or base-256.
+fn tar_size(field: &[u8]) -> Option<u64> {
+    if field[0] & 0x80 != 0 {
+        return Some(
+            field[1..]
+                .iter()
+                .fold(0u64, |size, byte| (size << 8) | u64::from(*byte)),
+        );
+    }
+    let octal = std::str::from_utf8(field).ok()?;
+    let octal = octal.trim_matches(|c: char| c == '\0' || c == ' ');
+    match octal {
+        "" => Some(0),
+        _ => u64::from_str_radix(octal, 8).ok(),
+    }
+}
+
+fn c_string(bytes: &[u8]) -> String {
+    let end = bytes
+        .iter()
+        .position(|byte| *byte == 0)
+        .unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).into_owned()
+}
+
+/// UEFI capsules, and flash images or volumes with a firmware volume.
+fn uefi(head: &[u8], tail: &[u8], len: u64) -> Option<Detection> {
+    if let Some((name, _)) = CAPSULE_GUIDS
+        .iter()
+        .find(|(_, guid)| head.starts_with(guid))
+    {
+        return Some(detected(
+            FwType::Uefi,
+            FwSubtype::Generic,
+            format!("{} header", name),
+        ));
+    }
+
+    let descriptor = head.get(16..20) == Some(&FLASH_DESCRIPTOR[..]);
+    let volume = firmware_volume(head, len)
+        .map(|offset| offset as u64)
+        .or_else(|| {
+            firmware_volume(tail, len).map(|offset| len - tail.len() as u64 + offset as u64)
+        });
+    match (descriptor, volume) {
+        (_, Some(offset)) => Some(detected(
+            FwType::Uefi,
+            FwSubtype::Generic,
+            format!("UEFI firmware volume at {:#x}", offset),
+        )),
+        (true, None) => Some(detected(
+            FwType::Uefi,
+            FwSubtype::Generic,
+            "Intel flash descriptor".to_string(),
+        )),
+        (false, None) => None,
+    }
+}
+
+/// Offset in `buf` of the first valid firmware volume header, whose `_FVH`
+/// signature is at offset 40.
+fn firmware_volume(buf: &[u8], len: u64) -> Option<usize> {
+    find_all(buf, b"_FVH")
+        .filter_map(|signature| signature.checked_sub(40))
+        .find(|&start| {
+            let volume_len = u64_le(buf, start + 32).unwrap_or_default();
+            let header_len = u16_le(buf, start + 48).unwrap_or_default();
+            let revision = buf.get(start + 55).copied().unwrap_or_default();
+            (1..=2).contains(&revision)
+                && header_len >= 56
+                && u64::from(header_len) < volume_len
+                && volume_len <= len
+        })
+}
+
+/// VxWorks images, by their U-Boot header or their banner next to the name
+/// of Wind River.
+fn vxworks(head: &[u8], tail: &[u8]) -> Option<Detection> {
+    if head.starts_with(&UIMAGE_MAGIC) && head.get(28) == Some(&UIMAGE_OS_VXWORKS) {
+        return Some(detected(
+            FwType::Vxworks,
+            FwSubtype::Generic,
+            "U-Boot image of VxWorks".to_string(),
+        ));
+    }
+
+    let banner = |buf: &[u8]| contains(buf, b"VxWorks") && contains(buf, b"Wind River");
+    (banner(head) || banner(tail)).then(|| {
+        let format = match head.starts_with(b"\x7fELF") {
+            true => "ELF binary",
+            false => "image",
+        };
+        detected(
+            FwType::Vxworks,
+            FwSubtype::Generic,
+            format!("{} with the VxWorks and Wind River banners", format),
+        )
+    })
+}
+
+/// Linux images, by the superblock of their root filesystem or their
+/// kernel header, the distribution told by the names in the headers.
+fn linux(head: &[u8]) -> Option<Detection> {
+    let evidence = filesystem(head).or_else(|| {
+        if head.starts_with(&UIMAGE_MAGIC) && head.get(28) == Some(&UIMAGE_OS_LINUX) {
+            Some("U-Boot image of Linux".to_string())
+        } else if head.starts_with(b"HDR0") {
+            Some("TRX header".to_string())
+        } else {
+            None
+        }
+    })?;
+
+    let distribution = [
+        (&b"OpenWrt"[..], FwSubtype::Openwrt),
+        (b"LEDE", FwSubtype::Openwrt),
+        (b"Poky", FwSubtype::Yocto),
+        (b"Yocto", FwSubtype::Yocto),
+        (b"Buildroot", FwSubtype::Buildroot),
+    ]
+    .into_iter()
+    .find(|(name, _)| contains(head, name));
+    Some(match distribution {
+        Some((name, subtype)) => detected(
+            FwType::Linux,
+            subtype,
+            format!("{}, named {}", evidence, String::from_utf8_lossy(name)),
+        ),
+        None => detected(FwType::Linux, FwSubtype::Generic, evidence),
+    })
+}
+
+/// Superblock of a filesystem of Linux images, with its offset.
+fn filesystem(head: &[u8]) -> Option<String> {
+    let found = |name: &str, offset: usize| Some(format!("{} superblock at {:#x}", name, offset));
+
+    if u16_le(head, 1024 + 56) == Some(0xEF53) {
+        return found("ext", 0);
+    }
+    for (offset, window) in head.windows(4).enumerate() {
+        let valid = match window {
+            b"hsqs" => squashfs(head, offset, u16_le, u32_le),
+            b"sqsh" => squashfs(head, offset, u16_be, |buf, offset| {
+                Some(u32::from_be_bytes(
+                    buf.get(offset..offset + 4)?.try_into().ok()?,
+                ))
+            }),
+            [0x45, 0x3D, 0xCD, 0x28] => {
+                head.get(offset + 16..offset + 32) == Some(b"Compressed ROMFS")
+            }
+            b"UBI#" => offset % 512 == 0 && head.get(offset + 4) == Some(&1),
+            [0x85, 0x19, _, _] => offset % 4 == 0 && jffs2(head, offset),
+            _ => false,
+        };
+        if valid {
+            let name = match window {
+                b"hsqs" | b"sqsh" => "squashfs",
+                b"UBI#" => "UBI",
+                [0x85, 0x19, _, _] => "jffs2",
+                _ => "cramfs",
+            };
+            return found(name, offset);
+        }
+    }
+    None
+}
+
+/// Whether a squashfs superblock at `offset` has a known version and a
+/// valid block size.
+fn squashfs(
+    head: &[u8],
+    offset: usize,
+    u16_at: fn(&[u8], usize) -> Option<u16>,
+    u32_at: fn(&[u8], usize) -> Option<u32>,
+) -> bool {
+    let major = u16_at(head, offset + 28);
+    let block_size = u32_at(head, offset + 12).unwrap_or_default();
+    matches!(major, Some(3 | 4))
+        && block_size.is_power_of_two()
+        && (4096..=1024 * 1024).contains(&block_size)
+}
+
+/// Whether three jffs2 nodes follow each other from `offset`, the magic of
+/// one node alone being too short.
+fn jffs2(head: &[u8], offset: usize) -> bool {
+    const NODE_TYPES: [u16; 5] = [0xE001, 0xE002, 0x2003, 0x2004, 0x2006];
+
+    let mut node = offset;
+    for _ in 0..3 {
+        let magic = u16_le(head, node);
+        let node_type = u16_le(head, node + 2).unwrap_or_default();
+        let node_len = u32_le(head, node + 4).unwrap_or_default() as usize;
+        if magic != Some(0x1985)
+            || !NODE_TYPES.contains(&node_type)
+            || !(12..1 << 20).contains(&node_len)
+        {
+            return false;
+        }
+        node += node_len.div_ceil(4) * 4;
+        // Padding up to the next node
+        while u16_le(head, node) == Some(0xFFFF) || u16_le(head, node) == Some(0) {
+            node += 4;
+        }
+    }
+    true
+}
@@ -0,0 +1,34 @@
+use ::keyring::{Entry, Error};
+use anyhow::{Context, Result};
+
+/// Service of the api keys in the keyring of the OS: the Secret Service on
+/// Linux, the Keychain on macOS and the Credential Manager on Windows.
+const SERVICE: &str = "cosmo-cli";
+
+fn entry(account: &str) -> Result<Entry> {
+    Entry::new(SERVICE, account).context("error opening the keyring")
+}
+
+/// Api key of `account` stored in the keyring, if any.
+pub fn get(account: &str) -> Result<Option<String>> {
+    match entry(account)?.get_password() {
+        Ok(api_key) => Ok(Some(api_key)),
+        Err(Error::NoEntry) => Ok(None),
+        Err(err) => Err(err).context("error reading the keyring"),
+    }
+}
+
+/// Store `api_key` of `account` in the keyring, replacing the previous one.
+pub fn set(account: &str, api_key: &str) -> Result<()> {
+    entry(account)?
+        .set_password(api_key)
+        .context("error writing to the keyring")
+}
+
+/// Remove the api key of `account` from the keyring, if any.
+pub fn delete(account: &str) -> Result<()> {
+    match entry(account)?.delete_credential() {
+        Ok(()) | Err(Error::NoEntry) => Ok(()),
+        Err(err) => Err(err).context("error deleting from the keyring"),
+    }
+}
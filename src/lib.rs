@@ -1,32 +1,91 @@
-use std::{
-    fs::File,
-    io::{BufReader, Read},
-};
+use std::{path::Path, time::Duration};
 
 use anyhow::{anyhow, bail, Context};
-use api::ApiServer;
+use api::{
+    ApiServer, ImageReference, PingReport, PlainClient, Platform, RegistryCredentials,
+    RemoteFirmware, UploadOptions,
+};
 use cli::Command;
+use futures::StreamExt;
 use lazy_static::lazy_static;
+use reqwest::header::HeaderMap;
 
 use crate::{
-    cli::{Analysis, ApiKeyAction, CommandOutput, Organization},
+    archive::ArchiveOptions,
+    cli::{
+        Analysis, ApiKeyAction, ApiKeySource, CsvOptions, CveFilter, CveSort, ExitCode, FwSubtype,
+        HardeningOptions, Organization, PasswordHashOptions, ProjectAction, ProjectColumn,
+        ProjectRef, Prompt, ReportFormat, Webhook,
+    },
+    config::EffectiveConfig,
+    fw_detect::Detection,
+    output::CommandOutput,
     services::{
-        apikey_service::{self, ApiKeyData},
+        apikey_service::{self, ApiKeyData, Identity, ListedApiKey, RotatedApiKey},
+        baseline_service::{self, Baseline, BaselineEntry, Suppression},
+        batch_service::{self, BatchDefaults, BatchReport, BatchSource},
+        component_service::{self, Component, LicenseSummary},
+        crypto_service::{self, CryptoMaterial},
+        diff_service::{self, ProjectDiff},
+        gate_service::{self, GateReport},
+        hardening_service::HardeningReport,
+        junit_service::{self, JunitReport},
+        kernel_service::{self, KernelReport},
         organization_service::{self, OrganizationData},
+        password_service::Credential,
         project_service::{self, *},
+        report_service,
+        sarif_service::{self, SarifLog},
+        sbom_service::{self, Sbom},
+        uefi_service::{SecureBootReport, SurfaceReport, VulnerableModule},
+        usage_service::{self, Usage},
+        version_service,
+        webhook_service::{self, WebhookData},
     },
 };
 
 pub mod api;
+pub mod archive;
 pub mod cli;
+pub mod completion;
+pub mod config;
+pub mod credentials;
+pub mod encryption;
+pub mod fw_detect;
+#[cfg(feature = "keyring")]
+pub mod keyring;
+pub mod logging;
+pub mod output;
+pub mod profile;
+pub mod style;
+pub mod update;
+
+pub use services::project_service::WaitError;
 
 mod services {
     pub mod apikey_service;
+    pub mod baseline_service;
+    pub mod batch_service;
+    pub mod component_service;
+    pub mod crypto_service;
+    pub mod diff_service;
+    pub mod gate_service;
+    pub mod hardening_service;
+    pub mod junit_service;
+    pub mod kernel_service;
     pub mod organization_service;
+    pub mod password_service;
     pub mod project_service;
+    pub mod report_service;
+    pub mod sarif_service;
+    pub mod sbom_service;
+    pub mod uefi_service;
+    pub mod usage_service;
+    pub mod version_service;
+    pub mod webhook_service;
 }
 
-const COSMO_API_SERVER: &str = "https://cosmo-api.exein.io:443";
+pub const COSMO_API_SERVER: &str = "https://cosmo-api.exein.io:443";
 
 pub fn version() -> &'static str {
     #[cfg(debug_assertions)]
@@ -41,568 +100,2160 @@ pub fn version() -> &'static str {
     &VERSION
 }
 
-fn read_bytes_from_file(filename: &str) -> Result<Vec<u8>, std::io::Error> {
-    let f = File::open(filename)?;
-    let reader = BufReader::new(f);
-    reader.bytes().collect()
-}
-
-#[allow(dead_code)]
-async fn check_version<U: ApiServer>(api_server: &U) -> Result<(), anyhow::Error> {
-    let current_version = semver::Version::parse(version())?;
-    let latest_version = api_server.updates_check().await?;
-
-    // TODO: fix repo path
-    if current_version < latest_version.version {
-        println!(
-            r#"
-A new version of Exein Cosmo is available! Download it at path/to/repo/releases/latest
-and install it by running ./exein-analyzer-cli-installer.run in your terminal.
-"#
-        );
-        println!("{}", latest_version.changelog);
-    }
-
-    Ok(())
-}
-
-/// This function panics if cmd is [Command::Setup]
-pub async fn run_cmd<U: ApiServer>(
+/// This function panics if cmd is [Command::Setup], [Command::Cache],
+/// [Command::Profile], [Command::Config], [Command::Logout],
+/// [Command::Completions], [Command::Complete] or a
+/// [Command::CreateProject] only printing the firmware hash
+pub async fn run_cmd<U: ApiServer + Sync>(
     cmd: Command,
     api_server: &mut U,
+    plain_client: Option<&PlainClient>,
+    prompt: Prompt,
 ) -> Result<Box<dyn CommandOutput>, anyhow::Error> {
-    // check_version(api_server).await?; //TODO
+    let mut resolver = ProjectResolver::default();
 
     let cmd_output: Box<dyn CommandOutput> = match cmd {
-        Command::Setup => {
+        Command::Setup
+        | Command::Cache(_)
+        | Command::Profile(_)
+        | Command::Config(_)
+        | Command::Logout { .. }
+        | Command::Completions { .. }
+        | Command::Complete { .. } => {
             unreachable!("handled before")
         }
         Command::CreateProject {
             fw_filepath,
+            from_url,
+            source_headers,
+            stream,
+            manifest,
+            parallel,
+            name_template,
+            docker_image,
+            platform,
+            registry_user,
+            registry_password,
             fw_type,
             fw_subtype,
             name,
             description,
             organization,
+            tags,
+            resume,
+            compress,
+            filename,
+            max_size,
+            exclude,
+            reproducible,
+            print_hash_only,
+            allow_unknown_type,
+            auto_type,
+            dry_run,
+            wait,
+            poll_interval,
+            wait_timeout,
+            ..
         } => {
-            log::info!("Creating Project...");
-            let project_created = project_service::create(
-                &fw_filepath,
-                &fw_type,
-                &fw_subtype,
-                &name,
-                description.as_deref(),
-                organization.as_deref(),
-                api_server,
-            )
-            .await?;
+            if print_hash_only {
+                unreachable!("handled before")
+            }
 
-            let project_id = project_created.id;
-            Box::new(format!("Project created successfull with ID: {project_id}\nThe security scan is currently in progress, please allow up to a few minutes for completion. We will notify you via email as soon as the scan is over."))
-        }
-        Command::List => {
-            impl CommandOutput for Vec<Project> {
-                fn text(&self) -> String {
-                    Project::get_table_from_list(self)
+            // A project for each firmware of the manifest or of the glob
+            let batch = match (manifest, &fw_filepath) {
+                (Some(manifest), _) => {
+                    let entries = batch_service::load(&manifest)?;
+                    Some((BatchSource::Manifest(manifest), entries))
                 }
-
-                fn json(&self) -> String {
-                    serde_json::to_string(self).unwrap()
+                (None, Some(pattern)) if archive::is_glob(pattern) => {
+                    let entries = batch_service::from_glob(
+                        pattern,
+                        &name_template.unwrap_or_default(),
+                        description.as_deref(),
+                    )?;
+                    Some((BatchSource::Glob(pattern.clone()), entries))
                 }
+                _ => None,
+            };
+            if let Some((source, entries)) = batch {
+                let defaults = BatchDefaults {
+                    fw_type,
+                    fw_subtype,
+                    tags,
+                    allow_unknown_type,
+                };
+                let entries = batch_service::validate(&source, entries, &defaults)?;
+                let organization = match organization {
+                    Some(organization) => Some(
+                        organization_service::resolve(api_server, &organization)
+                            .await?
+                            .id
+                            .to_string(),
+                    ),
+                    None => None,
+                };
+                let options = UploadOptions {
+                    compress,
+                    ..Default::default()
+                };
+                let archive_options = ArchiveOptions {
+                    exclude,
+                    reproducible,
+                };
+                let mut results = batch_service::create_all(
+                    &*api_server,
+                    entries,
+                    organization.as_deref(),
+                    &options,
+                    &archive_options,
+                    parallel.into(),
+                )
+                .await;
+                if wait {
+                    tracing::info!("Waiting for the security scans to complete...");
+                    batch_service::wait_all(
+                        api_server,
+                        &mut results,
+                        Duration::from_secs(poll_interval),
+                        Duration::from_secs(wait_timeout),
+                    )
+                    .await;
+                }
+                return Ok(Box::new(BatchReport::new(source, results)));
             }
 
-            let projects: Vec<Project> = project_service::list_projects(api_server).await?;
-
-            Box::new(projects)
-        }
-        Command::Overview { project_id } => {
-            let overview = project_service::overview(api_server, project_id).await?;
-            log::debug!("res:: {:#?}", overview);
-
-            let fw_type = overview["project"]["project_type"]
-                .as_str()
-                .context("error extracting string")?;
-            log::debug!("project type {}", fw_type);
-            match fw_type {
-                "LINUX" => {
-                    impl CommandOutput for LinuxProjectOverview {
-                        fn text(&self) -> String {
-                            LinuxProjectOverview::get_text_output(self)
-                        }
-
-                        fn json(&self) -> String {
-                            serde_json::to_string(self).unwrap()
-                        }
+            // Checked when parsed unless only printing the hash or in a batch
+            let name = name.expect("project name");
+
+            // Images are containers, sent as the archive of `docker save`
+            let (fw_type, fw_subtype) = match &docker_image {
+                Some(_) => {
+                    if fw_type
+                        .as_deref()
+                        .is_some_and(|fw_type| fw_type != "container")
+                    {
+                        bail!("--docker-image creates a container project, pass --type container or no --type");
                     }
-
-                    let lpo: LinuxProjectOverview = serde_json::from_value(overview)?;
-
-                    Box::new(lpo)
+                    let fw_subtype = match fw_subtype.as_str() {
+                        "generic" => "docker".to_string(),
+                        "lxc" => bail!("--docker-image pulls docker images, not lxc containers"),
+                        _ => fw_subtype,
+                    };
+                    (Some("container".to_string()), fw_subtype)
                 }
-                "CONTAINER" => {
-                    impl CommandOutput for ContainerProjectOverview {
-                        fn text(&self) -> String {
-                            ContainerProjectOverview::get_text_output(self)
-                        }
-
-                        fn json(&self) -> String {
-                            serde_json::to_string(self).unwrap()
+                None => (fw_type, fw_subtype),
+            };
+
+            // Directories are sent as a tar archive, removed once done
+            let options = ArchiveOptions {
+                exclude,
+                reproducible,
+            };
+            let archive = match (&fw_filepath, &docker_image) {
+                (Some(fw_filepath), _) => archive_dir(fw_filepath, &options).await?,
+                (None, Some(image)) => {
+                    let credentials = match registry_user {
+                        Some(username) => Some(RegistryCredentials {
+                            username,
+                            password: registry_password.context(
+                                "no password for --registry-user, pass --registry-password or set COSMO_REGISTRY_PASSWORD",
+                            )?,
+                        }),
+                        None => api::docker_credentials(&image.registry),
+                    };
+                    let platform = platform.unwrap_or_else(Platform::current);
+                    let plain_client = downloads(plain_client)?;
+                    Some(pull_image(plain_client, image, &platform, credentials.as_ref()).await?)
+                }
+                (None, None) => None,
+            };
+            let fw_filepath = match &archive {
+                Some(archive) => Some(archive.path.to_string_lossy().into_owned()),
+                None => fw_filepath,
+            };
+            let (fw_type, fw_subtype) = match (fw_type, &fw_filepath) {
+                (Some(fw_type), _) => (Some(fw_type), fw_subtype),
+                // Stdin is only read while uploading it
+                (None, Some(fw_filepath)) if fw_filepath == "-" => bail!(
+                    "no firmware type, pass --type or set `type` in the configuration file for a firmware read from stdin"
+                ),
+                (None, Some(fw_filepath)) => {
+                    match detect_type(fw_filepath, &fw_subtype, auto_type, prompt) {
+                        Ok((fw_type, fw_subtype)) => (Some(fw_type), fw_subtype),
+                        // Reported with the other checks
+                        Err(e) if dry_run => {
+                            tracing::warn!("{:#}", e);
+                            (None, fw_subtype)
                         }
+                        Err(e) => return Err(e),
                     }
-
-                    let lpo: ContainerProjectOverview = serde_json::from_value(overview)?;
-
-                    Box::new(lpo)
                 }
-                "UEFI" => {
-                    impl CommandOutput for UefiProjectOverview {
-                        fn text(&self) -> String {
-                            UefiProjectOverview::get_text_output(self)
-                        }
-
-                        fn json(&self) -> String {
-                            serde_json::to_string(self).unwrap()
-                        }
+                // The firmware of --from-url is only read while uploading it
+                (None, None) => (None, fw_subtype),
+            };
+            if dry_run {
+                // Required by clap without --from-url, which excludes --dry-run
+                let fw_filepath = fw_filepath.expect("firmware path");
+                return Ok(Box::new(
+                    project_service::create_dry_run(
+                        &fw_filepath,
+                        fw_type.as_deref(),
+                        &fw_subtype,
+                        &name,
+                        organization.as_deref(),
+                        allow_unknown_type,
+                        api_server,
+                    )
+                    .await,
+                ));
+            }
+            let fw_type = fw_type
+                .context("no firmware type, pass --type or set `type` in the configuration file")?;
+
+            let organization = match organization {
+                Some(organization) => Some(
+                    organization_service::resolve(api_server, &organization)
+                        .await?
+                        .id
+                        .to_string(),
+                ),
+                None => None,
+            };
+
+            tracing::info!("Creating Project...");
+            let options = UploadOptions {
+                resume,
+                compress,
+                filename,
+                max_size: Some(max_size),
+                hide_progress: false,
+            };
+            let project_created = match (from_url, fw_filepath) {
+                (Some(url), _) => {
+                    let mut headers = HeaderMap::new();
+                    for (name, mut value) in source_headers {
+                        value.set_sensitive(true);
+                        headers.append(name, value);
                     }
+                    let firmware = RemoteFirmware {
+                        url,
+                        headers,
+                        stream,
+                    };
+                    let created = api_server
+                        .create_from_url(
+                            &firmware,
+                            &fw_type,
+                            &fw_subtype,
+                            &name,
+                            description.as_deref(),
+                            organization.as_deref(),
+                            &tags,
+                            &options,
+                        )
+                        .await;
+                    match created {
+                        Ok(created) => created,
+                        Err(e) => return Err(usage_service::explain_quota(&*api_server, e).await),
+                    }
+                }
+                (None, Some(fw_filepath)) => {
+                    project_service::create(
+                        &fw_filepath,
+                        &fw_type,
+                        &fw_subtype,
+                        &name,
+                        description.as_deref(),
+                        organization.as_deref(),
+                        &tags,
+                        &options,
+                        api_server,
+                    )
+                    .await?
+                }
+                (None, None) => {
+                    unreachable!("--file required by clap without --from-url or --docker-image")
+                }
+            };
 
-                    let upo: UefiProjectOverview = serde_json::from_value(overview)?;
+            let project_id = project_created.id;
+            let sha256 = project_created.sha256;
+
+            if wait {
+                tracing::info!("Project created with ID: {project_id}, firmware SHA-256: {sha256}");
+                tracing::info!("Waiting for the security scan to complete...");
+                let overview = project_service::wait_analysis(
+                    api_server,
+                    project_id,
+                    Duration::from_secs(poll_interval),
+                    Duration::from_secs(wait_timeout),
+                )
+                .await?;
+                return Ok(Box::new(CreatedProject {
+                    id: project_id,
+                    sha256,
+                    overview: Some(ProjectOverview::from_value(overview)?),
+                }));
+            }
 
-                    Box::new(upo)
-                }
-                "VXWORKS" => {
-                    impl CommandOutput for VxworksProjectOverview {
-                        fn text(&self) -> String {
-                            VxworksProjectOverview::get_text_output(self)
-                        }
+            Box::new(CreatedProject {
+                id: project_id,
+                sha256,
+                overview: None,
+            })
+        }
+        Command::Ping => {
+            let report = api_server.ping().await?;
 
-                        fn json(&self) -> String {
-                            serde_json::to_string(self).unwrap()
-                        }
-                    }
-                    let vpo: VxworksProjectOverview = serde_json::from_value(overview)?;
+            Box::new(report)
+        }
+        Command::Version => Box::new(version_service::version(api_server).await),
+        Command::SelfUpdate { check } => {
+            let plain_client = downloads(plain_client)?;
+            Box::new(update::self_update(api_server, plain_client, check).await?)
+        }
+        Command::Whoami { source } => {
+            let identity = apikey_service::whoami(api_server, source).await?;
+            Box::new(identity)
+        }
+        Command::Usage => {
+            let usage = api_server.usage().await?;
+
+            Box::new(usage)
+        }
+        Command::List {
+            limit,
+            mut filter,
+            sort,
+            reverse,
+            columns,
+            wide,
+            csv,
+            ndjson,
+            template,
+        } => {
+            if let Some(organization) = &filter.organization {
+                let organization = organization_service::resolve(api_server, organization).await?;
+                filter.organization = Some(organization.id.to_string());
+                filter.organization_name = Some(organization.name).filter(|name| !name.is_empty());
+            }
 
-                    Box::new(vpo)
+            // Sorting needs all the projects first
+            if ndjson && sort.is_none() {
+                project_service::for_each_project(api_server, &filter, limit, |project| {
+                    output::print_ndjson(&serde_json::to_value(project).unwrap_or_default())
+                })
+                .await?;
+                return Ok(Box::new(Streamed));
+            }
+
+            let projects: Vec<Project> = match sort {
+                // Sorted over all the projects, the limit keeps the first ones
+                Some(sort) => {
+                    let mut projects =
+                        project_service::list_projects(api_server, &filter, None).await?;
+                    project_service::sort_projects(&mut projects, sort, reverse);
+                    projects.truncate(limit.unwrap_or(usize::MAX));
+                    projects
                 }
-                np => bail!("Type not supported: {}", np), //TODO: remove branch with prehemptive parse
+                None => project_service::list_projects(api_server, &filter, limit).await?,
+            };
+
+            if let Some(template) = template {
+                let lines: Vec<String> = projects
+                    .iter()
+                    .map(|project| {
+                        template.render(&serde_json::to_value(project).unwrap_or_default())
+                    })
+                    .collect();
+                return Ok(Box::new(lines.join("\n")));
+            }
+
+            let columns = match columns.as_slice() {
+                _ if wide => ProjectColumn::ALL.to_vec(),
+                [] if csv.enabled => ProjectColumn::CSV.to_vec(),
+                [] => ProjectColumn::TABLE.to_vec(),
+                _ => columns,
+            };
+            if csv.enabled {
+                let header = columns
+                    .iter()
+                    .map(|column| column.name().to_string())
+                    .collect();
+                let rows = projects.iter().map(|project| {
+                    columns
+                        .iter()
+                        .map(|column| project.column(*column, &csv.list_delimiter))
+                        .collect()
+                });
+                return Ok(Box::new(CsvOutput(output::csv_rows(header, rows, &csv))));
             }
+            Box::new(ProjectList { projects, columns })
+        }
+        Command::Overview { project } => {
+            let project_id = resolver
+                .resolve_or_pick(api_server, project.as_ref(), prompt)
+                .await?;
+            let overview = project_service::overview(api_server, project_id).await?;
+            tracing::debug!("res:: {:#?}", overview);
+
+            overview_output(overview)?
         }
         Command::Analysis {
-            project_id,
+            project,
             analysis,
+            analyses,
+            all,
             page,
             per_page,
+            cve_filter,
+            sort,
+            hardening,
+            passwords,
+            only_failing,
+            expiring_within,
+            baseline,
+            write_baseline,
+            csv,
+            ndjson,
+            sarif,
+            junit,
         } => {
-            let res = project_service::analysis(api_server, project_id, &analysis, page, per_page)
-                .await?;
+            let cve_check = matches!(analysis, Some(Analysis::CveCheck))
+                || analyses.iter().any(|a| matches!(a, Analysis::CveCheck))
+                || all;
+            if !cve_check && (!cve_filter.is_empty() || sort.is_some()) {
+                bail!("--severity, --min-cvss, --package and --sort only apply to the cve-check analysis");
+            }
+            let hardening_selected = matches!(analysis, Some(Analysis::Hardening))
+                || analyses.iter().any(|a| matches!(a, Analysis::Hardening))
+                || all;
+            if !hardening_selected && !hardening.is_empty() {
+                bail!("--filter, --path-prefix and --limit only apply to the hardening analysis");
+            }
+            let password_hash = matches!(analysis, Some(Analysis::PasswordHash))
+                || analyses.iter().any(|a| matches!(a, Analysis::PasswordHash))
+                || all;
+            if !password_hash && (passwords.only_weak || passwords.show_secrets) {
+                bail!("--only-weak and --show-secrets only apply to the password-hash analysis");
+            }
+            let kernel = matches!(analysis, Some(Analysis::Kernel))
+                || analyses.iter().any(|a| matches!(a, Analysis::Kernel))
+                || all;
+            if !kernel && only_failing {
+                bail!("--only-failing only applies to the kernel analysis");
+            }
+            let crypto = matches!(analysis, Some(Analysis::Crypto))
+                || analyses.iter().any(|a| matches!(a, Analysis::Crypto))
+                || all;
+            if !crypto && expiring_within.is_some() {
+                bail!("--expiring-within only applies to the crypto analysis");
+            }
+            if csv.enabled && analysis.is_none() {
+                bail!("CSV output needs a single analysis, pass it with --analysis");
+            }
+            if baseline.is_some() || write_baseline.is_some() {
+                if !matches!(analysis, Some(Analysis::CveCheck)) {
+                    bail!("--baseline and --write-baseline need --analysis cve-check");
+                }
+                if csv.enabled || ndjson || sarif || junit {
+                    bail!("--baseline is only available with the table, json and yaml output");
+                }
+            }
+            let baseline = baseline.as_deref().map(Baseline::load).transpose()?;
 
-            if let Some(err) = res.error {
-                Box::new(format!("Analysis {} error: {}", analysis, err))
-            } else {
-                let result = res.result.unwrap(); // Safe to unwrap
+            let project_id = resolver
+                .resolve_or_pick(api_server, project.as_ref(), prompt)
+                .await?;
+            if sarif || junit {
+                // Every finding, regardless of the page
+                let analyses = match analysis {
+                    Some(analysis) => vec![analysis],
+                    None if all => project_analyses(api_server, project_id).await?,
+                    None => analyses,
+                };
+                if junit {
+                    let report =
+                        junit_service::findings(api_server, project_id, &analyses, &cve_filter)
+                            .await?;
+                    return Ok(Box::new(report));
+                }
+                let log =
+                    sarif_service::findings(api_server, project_id, &analyses, &cve_filter).await?;
+                return Ok(Box::new(log));
+            }
 
-                log::info!("FW type:{} | Analysis: {}", res.fw_type, res.name);
+            if let Some(path) = write_baseline {
+                // Every CVE, regardless of the page
+                let results =
+                    project_service::analysis_results(api_server, project_id, &Analysis::CveCheck)
+                        .await?;
+                let mut found: Vec<LinuxCveCheckAnalysis> =
+                    serde_json::from_value(serde_json::Value::Array(results))?;
+                found.retain(|cve| cve_filter.matches(cve));
+
+                let previous = match path.exists() {
+                    true => Some(Baseline::load(&path)?),
+                    false => None,
+                };
+                let baseline = Baseline::new(project_id, &found, previous.as_ref());
+                baseline.save(&path)?;
+                return Ok(Box::new(format!(
+                    "Baseline of {} CVEs saved to {}",
+                    baseline.findings.len(),
+                    path.display()
+                )));
+            }
 
-                match analysis {
-                    // Linux/Container Analysis
-                    Analysis::Hardening => {
-                        impl CommandOutput for Vec<LinuxHardeningAnalysis> {
-                            fn text(&self) -> String {
-                                LinuxHardeningAnalysis::get_table_from_list(self)
+            let options = AnalysisOptions {
+                filter: cve_filter,
+                sort,
+                hardening,
+                passwords,
+                only_failing,
+                expiring_within,
+            };
+            if let Some(Analysis::Hardening) = analysis {
+                // The summary is about every binary, regardless of the page
+                let results =
+                    project_service::analysis_results(api_server, project_id, &Analysis::Hardening)
+                        .await?;
+                let binaries: Vec<LinuxHardeningAnalysis> =
+                    serde_json::from_value(serde_json::Value::Array(results))?;
+                let report = HardeningReport::new(binaries, &options.hardening);
+                if csv.enabled {
+                    return Ok(Box::new(CsvOutput(output::csv(
+                        &report.binaries,
+                        Some(project_id),
+                        &csv,
+                    ))));
+                }
+                return Ok(Box::new(report));
+            }
+            if let Some(Analysis::Kernel) = analysis {
+                // The score is about every option, regardless of the page
+                let report = kernel_service::report(api_server, project_id, only_failing).await?;
+                if csv.enabled {
+                    return Ok(Box::new(CsvOutput(output::csv(
+                        &report.options,
+                        Some(project_id),
+                        &csv,
+                    ))));
+                }
+                return Ok(Box::new(report));
+            }
+            if let (Some(analysis @ (Analysis::Surface | Analysis::UefiSecurityScan)), false) =
+                (&analysis, csv.enabled)
+            {
+                // The summary and the modules are about every entry, regardless of the page
+                let results =
+                    project_service::analysis_results(api_server, project_id, analysis).await?;
+                let result = serde_json::Value::Array(results);
+                return Ok(match analysis {
+                    Analysis::Surface => {
+                        Box::new(SurfaceReport::new(serde_json::from_value(result)?))
+                    }
+                    _ => Box::new(VulnerableModule::from_findings(&serde_json::from_value::<
+                        Vec<UefiSecurityScan>,
+                    >(
+                        result
+                    )?)),
+                });
+            }
+            if let Some(Analysis::Crypto) = analysis {
+                // Private keys are paired with the certificates of any page
+                let results =
+                    project_service::analysis_results(api_server, project_id, &Analysis::Crypto)
+                        .await?;
+                let found: Vec<LinuxCryptoAnalysis> =
+                    serde_json::from_value(serde_json::Value::Array(results))?;
+                let material = crypto_service::material(&found, expiring_within);
+                if csv.enabled {
+                    return Ok(Box::new(CsvOutput(output::csv(
+                        &material,
+                        Some(project_id),
+                        &csv,
+                    ))));
+                }
+                return Ok(Box::new(material));
+            }
+            if let Some(analysis) = analysis {
+                let res =
+                    project_service::analysis(api_server, project_id, &analysis, page, per_page)
+                        .await?;
+
+                if let Some(err) = res.error {
+                    Box::new(format!("Analysis {} error: {}", analysis, err))
+                } else if csv.enabled {
+                    Box::new(CsvOutput(analysis_csv(
+                        &analysis, res, project_id, &options, &csv,
+                    )?))
+                } else if let Some(baseline) = &baseline {
+                    let result = res.result.context("analysis without result")?;
+                    let mut found: Vec<LinuxCveCheckAnalysis> = serde_json::from_value(result)?;
+                    found.retain(|cve| options.filter.matches(cve));
+                    if let Some(sort) = options.sort {
+                        project_service::sort_cves(&mut found, sort);
+                    }
+                    Box::new(BaselineCves::new(found, baseline))
+                } else {
+                    analysis_output(&analysis, res, &options)?
+                }
+            } else {
+                let analyses = if all {
+                    project_analyses(api_server, project_id).await?
+                } else {
+                    analyses
+                };
+
+                if ndjson {
+                    let mut results = project_service::analyses_stream(
+                        api_server, project_id, &analyses, page, per_page,
+                    );
+                    while let Some((analysis, res)) = results.next().await {
+                        let output = res.and_then(|res| match res.error {
+                            Some(err) => Err(anyhow!(err)),
+                            None => analysis_output(&analysis, res, &options),
+                        });
+                        let line = match output {
+                            Ok(output) => serde_json::json!({
+                                "analysis": analysis.to_string(),
+                                "result": serde_json::from_str::<serde_json::Value>(&output.json())
+                                    .unwrap_or_default(),
+                            }),
+                            Err(err) => {
+                                tracing::warn!("Analysis {} failed: {:#}", analysis, err);
+                                serde_json::json!({
+                                    "analysis": analysis.to_string(),
+                                    "error": format!("{:#}", err),
+                                })
                             }
+                        };
+                        output::print_ndjson(&line);
+                    }
+                    return Ok(Box::new(Streamed));
+                }
 
-                            fn json(&self) -> String {
-                                serde_json::to_string(self).unwrap()
+                let results =
+                    project_service::analyses(api_server, project_id, &analyses, page, per_page)
+                        .await
+                        .into_iter()
+                        .map(|(analysis, res)| {
+                            let output = res.and_then(|res| match res.error {
+                                Some(err) => Err(anyhow!(err)),
+                                None => analysis_output(&analysis, res, &options),
+                            });
+                            if let Err(err) = &output {
+                                tracing::warn!("Analysis {} failed: {:#}", analysis, err);
                             }
-                        }
+                            (analysis, output)
+                        })
+                        .collect();
 
-                        let an: Vec<LinuxHardeningAnalysis> = serde_json::from_value(result)?;
+                Box::new(AnalysesOutput(results))
+            }
+        }
+        Command::Delete {
+            project,
+            yes,
+            confirm_name,
+        } => {
+            let project_id = resolver
+                .resolve_or_pick(api_server, project.as_ref(), prompt)
+                .await?;
+            if !yes {
+                let name = resolver.name(api_server, project_id).await?;
+                confirm_delete(&[(project_id, name)], confirm_name, prompt)?;
+            }
+            project_service::delete(api_server, project_id).await?;
+            Box::new(format!("Project {} deleted", project_id))
+        }
+        Command::Report {
+            project,
+            savepath,
+            format,
+            analyses,
+            template,
+            force,
+            wait,
+            poll_interval,
+            wait_timeout,
+        } => {
+            let project_id = resolver.resolve(api_server, &project).await?;
+            match format {
+                ReportFormat::Pdf => {
+                    let wait = wait.then(|| {
+                        (
+                            Duration::from_secs(poll_interval),
+                            Duration::from_secs(wait_timeout),
+                        )
+                    });
+                    let size =
+                        project_service::report(api_server, project_id, &savepath, force, wait)
+                            .await?;
+                    Box::new(format!("Report saved to {} ({} bytes)", savepath, size))
+                }
+                ReportFormat::Markdown | ReportFormat::Html => {
+                    let analyses = match analyses.is_empty() {
+                        true => report_service::REPORT_ANALYSES.to_vec(),
+                        false => analyses,
+                    };
+                    let report = report_service::save(
+                        api_server,
+                        project_id,
+                        savepath,
+                        format,
+                        &analyses,
+                        template.as_deref(),
+                        force,
+                    )
+                    .await?;
+                    Box::new(format!("Report saved to {}", report))
+                }
+            }
+        }
+        Command::Gate {
+            project,
+            policy,
+            baseline,
+            sarif,
+            junit,
+        } => {
+            if policy.is_empty() {
+                bail!("No policy rule, pass thresholds like --max-critical 0 or set them in the [policy] section of the config file");
+            }
 
-                        Box::new(an)
+            let project_id = resolver.resolve(api_server, &project).await?;
+            let baseline = baseline.as_deref().map(Baseline::load).transpose()?;
+            let mut report =
+                gate_service::evaluate(api_server, project_id, &policy, baseline.as_ref()).await?;
+            if sarif {
+                report.sarif = Some(
+                    sarif_service::findings(
+                        api_server,
+                        project_id,
+                        &sarif_service::SARIF_ANALYSES,
+                        &CveFilter::default(),
+                    )
+                    .await?,
+                );
+            }
+            if junit {
+                report.junit = Some(
+                    junit_service::gate(api_server, &report, &policy, baseline.as_ref()).await?,
+                );
+            }
+            Box::new(report)
+        }
+        Command::Diff {
+            base,
+            target,
+            analyses,
+            fail_on_regression,
+        } => {
+            let base = resolver.resolve(api_server, &base).await?;
+            let target = resolver.resolve(api_server, &target).await?;
+            let diff = diff_service::diff(api_server, base, target, &analyses).await?;
+            Box::new(DiffOutput {
+                diff,
+                fail_on_regression,
+            })
+        }
+        Command::Project(action) => match action {
+            ProjectAction::Status { project } => {
+                let project_id = resolver
+                    .resolve_or_pick(api_server, project.as_ref(), prompt)
+                    .await?;
+                let status = project_service::status(api_server, project_id).await?;
+                Box::new(status)
+            }
+            ProjectAction::Watch {
+                project,
+                poll_interval,
+                timeout,
+                ndjson,
+            } => {
+                let project_id = resolver.resolve(api_server, &project).await?;
+                let change = project_service::watch(
+                    api_server,
+                    project_id,
+                    Duration::from_secs(poll_interval),
+                    Duration::from_secs(timeout),
+                    |change| match ndjson {
+                        true => {
+                            output::print_ndjson(&serde_json::to_value(change).unwrap_or_default())
+                        }
+                        false => println!("{}", change),
+                    },
+                )
+                .await?;
+                Box::new(change)
+            }
+            ProjectAction::Edit {
+                project,
+                name,
+                description,
+            } => {
+                let project_id = resolver.resolve(api_server, &project).await?;
+                project_service::update(
+                    api_server,
+                    project_id,
+                    name.as_deref(),
+                    description.as_deref(),
+                )
+                .await?;
+                Box::new(format!("Project {} updated", project_id))
+            }
+            ProjectAction::Tag {
+                project,
+                add,
+                remove,
+            } => {
+                let project_id = resolver.resolve(api_server, &project).await?;
+                let tags = project_service::tag(api_server, project_id, &add, &remove).await?;
+                if tags.is_empty() {
+                    Box::new(format!("Project {} has no tags", project_id))
+                } else {
+                    Box::new(format!("Project {} tags: {}", project_id, tags.join(", ")))
+                }
+            }
+            ProjectAction::Rescan {
+                project,
+                force,
+                wait,
+                poll_interval,
+                wait_timeout,
+            } => {
+                let project_id = resolver.resolve(api_server, &project).await?;
+                let job = project_service::rescan(api_server, project_id, force).await?;
+
+                let queued = match &job.job_id {
+                    Some(job_id) => {
+                        format!("Rescan of project {} queued as job {}", project_id, job_id)
+                    }
+                    None => format!("Rescan of project {} queued", project_id),
+                };
+                if wait {
+                    tracing::info!("{}", queued);
+                    tracing::info!("Waiting for the security scan to complete...");
+                    let overview = project_service::wait_analysis(
+                        api_server,
+                        project_id,
+                        Duration::from_secs(poll_interval),
+                        Duration::from_secs(wait_timeout),
+                    )
+                    .await?;
+                    return overview_output(overview);
+                }
+                Box::new(queued)
+            }
+            ProjectAction::Delete {
+                projects,
+                name_prefix,
+                older_than,
+                yes,
+                confirm_name,
+            } => {
+                let project_ids = if projects.is_empty() {
+                    let selected = project_service::select_projects(
+                        api_server,
+                        name_prefix.as_deref(),
+                        older_than,
+                    )
+                    .await?;
+                    if selected.is_empty() {
+                        return Ok(Box::new("No project matches the filters".to_string()));
                     }
-                    Analysis::CveCheck => {
-                        impl CommandOutput for Vec<LinuxCveCheckAnalysis> {
-                            fn text(&self) -> String {
-                                LinuxCveCheckAnalysis::get_table_from_list(self)
-                            }
 
-                            fn json(&self) -> String {
-                                serde_json::to_string(self).unwrap()
-                            }
+                    if !yes {
+                        eprintln!(
+                            "{}",
+                            Project::get_table_from_list(&selected, ProjectColumn::TABLE)
+                        );
+                        let selected: Vec<_> = selected
+                            .iter()
+                            .map(|project| (project.id, project.name.clone()))
+                            .collect();
+                        confirm_delete(&selected, confirm_name, prompt)?;
+                    }
+                    selected.iter().map(|project| project.id).collect()
+                } else {
+                    let mut project_ids = vec![];
+                    for project in project_refs(projects)? {
+                        project_ids.push(resolver.resolve(api_server, &project).await?);
+                    }
+                    if !yes {
+                        let mut named = vec![];
+                        for project_id in &project_ids {
+                            named
+                                .push((*project_id, resolver.name(api_server, *project_id).await?));
                         }
+                        confirm_delete(&named, confirm_name, prompt)?;
+                    }
+                    project_ids
+                };
 
-                        let an: Vec<LinuxCveCheckAnalysis> = serde_json::from_value(result)?;
+                let results = project_service::delete_many(api_server, &project_ids).await;
+                let total = results.len();
+                let (deleted, failed): (Vec<_>, Vec<_>) =
+                    results.into_iter().partition(|(_, res)| res.is_ok());
 
-                        Box::new(an)
+                if !failed.is_empty() {
+                    for (project_id, _) in &deleted {
+                        tracing::info!("Project {} deleted", project_id);
                     }
-                    Analysis::SecurityScan => {
-                        impl CommandOutput for Vec<LinuxSecurityScanAnalysis> {
-                            fn text(&self) -> String {
-                                LinuxSecurityScanAnalysis::get_table_from_list(self)
-                            }
-
-                            fn json(&self) -> String {
-                                serde_json::to_string(self).unwrap()
-                            }
+                    for (project_id, res) in &failed {
+                        if let Err(err) = res {
+                            tracing::error!("Error deleting project {}: {:#}", project_id, err);
                         }
+                    }
+                    bail!("{} of {} projects not deleted", failed.len(), total);
+                }
 
-                        let an: Vec<LinuxSecurityScanAnalysis> = serde_json::from_value(result)?;
-
-                        Box::new(an)
+                Box::new(DeletedProjects(
+                    deleted
+                        .into_iter()
+                        .map(|(project_id, _)| project_id)
+                        .collect(),
+                ))
+            }
+            ProjectAction::Sbom {
+                project,
+                format,
+                savepath,
+            } => {
+                let project_id = resolver.resolve(api_server, &project).await?;
+                let sbom = sbom_service::export(api_server, project_id, format).await?;
+                match savepath {
+                    Some(savepath) => {
+                        std::fs::write(&savepath, format!("{}\n", sbom.document))
+                            .with_context(|| format!("error writing {}", savepath.display()))?;
+                        Box::new(format!(
+                            "SBOM saved to {} ({} packages)",
+                            savepath.display(),
+                            sbom.packages
+                        ))
                     }
-                    Analysis::PasswordHash => {
-                        impl CommandOutput for Vec<LinuxPasswordHashAnalysis> {
-                            fn text(&self) -> String {
-                                LinuxPasswordHashAnalysis::get_table_from_list(self)
-                            }
+                    None => Box::new(sbom),
+                }
+            }
+            ProjectAction::Components {
+                project,
+                license_only,
+                copyleft,
+                search,
+                csv,
+            } => {
+                let project_id = resolver.resolve(api_server, &project).await?;
+                let components =
+                    component_service::list(api_server, project_id, copyleft, search.as_deref())
+                        .await?;
+                match (license_only, csv.enabled) {
+                    (true, true) => Box::new(CsvOutput(output::csv(
+                        &LicenseSummary::new(&components).licenses,
+                        Some(project_id),
+                        &csv,
+                    ))),
+                    (true, false) => Box::new(LicenseSummary::new(&components)),
+                    (false, true) => {
+                        Box::new(CsvOutput(output::csv(&components, Some(project_id), &csv)))
+                    }
+                    (false, false) => Box::new(components),
+                }
+            }
+            ProjectAction::Download {
+                project,
+                savepath,
+                force,
+            } => {
+                let project_id = resolver.resolve(api_server, &project).await?;
+                let size =
+                    project_service::download_firmware(api_server, project_id, &savepath, force)
+                        .await?;
+                Box::new(format!("Firmware saved to {} ({} bytes)", savepath, size))
+            }
+        },
 
-                            fn json(&self) -> String {
-                                serde_json::to_string(self).unwrap()
-                            }
-                        }
+        Command::Organization(action) => match action {
+            Organization::Create { name, description } => {
+                organization_service::create(api_server, &name, &description).await?;
+                Box::new(format!("Organization created: {}", name))
+            }
+            Organization::List => {
+                let org = organization_service::list(api_server).await?;
+                Box::new(org)
+            }
+            Organization::Delete { id } => {
+                organization_service::delete(api_server, id).await?;
+                Box::new(format!("Organization deleted. ID: {}", id))
+            }
+        },
+        Command::Webhook(action) => match action {
+            Webhook::List { project } => {
+                let project = match project {
+                    Some(project) => Some(resolver.resolve(api_server, &project).await?),
+                    None => None,
+                };
+                let webhooks = webhook_service::list(api_server, project).await?;
+                Box::new(webhooks)
+            }
+            Webhook::Add {
+                url,
+                events,
+                project,
+                secret,
+            } => {
+                let project = match project {
+                    Some(project) => Some(resolver.resolve(api_server, &project).await?),
+                    None => None,
+                };
+                let webhook =
+                    webhook_service::create(api_server, &url, &events, project, secret.as_deref())
+                        .await?;
+                Box::new(webhook)
+            }
+            Webhook::Remove { id } => {
+                webhook_service::delete(api_server, id).await?;
+                Box::new(format!("Webhook {} removed", id))
+            }
+        },
+        Command::Apikey {
+            action,
+            out,
+            show_secret,
+            yes,
+        } => match action {
+            ApiKeyAction::Create => {
+                let apikey_data = apikey_service::create(api_server).await?;
+                Box::new(apikey_data)
+            }
+            ApiKeyAction::List => {
+                let apikeys = apikey_service::list(api_server, show_secret).await?;
+                Box::new(apikeys)
+            }
+            ApiKeyAction::Delete => {
+                if !yes
+                    && !prompt.confirm(
+                        "Delete the api key in use? The cli will need a new one",
+                        "pass --yes to delete it without asking",
+                    )?
+                {
+                    bail!("Deletion not confirmed, pass --yes to delete without asking");
+                }
+                apikey_service::delete(api_server).await?;
+                Box::new("api key deleted")
+            }
+            // Not saved, the configuration being out of reach of the library
+            ApiKeyAction::Rotate => {
+                rotate_api_key(api_server, out.as_deref(), |_| Ok(None)).await?
+            }
+        },
+    };
 
-                        let an: Vec<LinuxPasswordHashAnalysis> = serde_json::from_value(result)?;
+    Ok(cmd_output)
+}
 
-                        Box::new(an)
-                    }
-                    Analysis::Crypto => {
-                        impl CommandOutput for Vec<LinuxCryptoAnalysis> {
-                            fn text(&self) -> String {
-                                LinuxCryptoAnalysis::get_table_from_list(self)
-                            }
+/// Replace the api key, saving the new one with `save` and writing it to
+/// `out` if given, for the callers knowing where the previous one was read
+/// from.
+pub async fn rotate_api_key<U: ApiServer>(
+    api_server: &mut U,
+    out: Option<&Path>,
+    save: impl FnOnce(&str) -> Result<Option<ApiKeySource>, anyhow::Error>,
+) -> Result<Box<dyn CommandOutput>, anyhow::Error> {
+    let rotated = apikey_service::rotate(api_server, out, save).await?;
+    Ok(Box::new(rotated))
+}
 
-                            fn json(&self) -> String {
-                                serde_json::to_string(self).unwrap()
-                            }
-                        }
+/// Archive `fw_filepath` when it is a directory, `-` being stdin.
+pub async fn archive_dir(
+    fw_filepath: &str,
+    options: &ArchiveOptions,
+) -> Result<Option<archive::Archive>, anyhow::Error> {
+    if fw_filepath == "-" || !Path::new(fw_filepath).is_dir() {
+        if !options.exclude.is_empty() || options.reproducible {
+            tracing::warn!("--exclude and --reproducible only apply to directories");
+        }
+        return Ok(None);
+    }
+    let archive = archive::archive(Path::new(fw_filepath), options)
+        .await
+        .with_context(|| format!("error archiving directory {}", fw_filepath))?;
+    Ok(Some(archive))
+}
 
-                        let an: Vec<LinuxCryptoAnalysis> = serde_json::from_value(result)?;
+/// Client of the downloads outside of the api, none when offline.
+fn downloads(plain_client: Option<&PlainClient>) -> Result<&PlainClient, anyhow::Error> {
+    plain_client.context("Downloading is not available offline")
+}
 
-                        Box::new(an)
-                    }
-                    Analysis::Nvram => {
-                        impl CommandOutput for Vec<LinuxNvramAnalysis> {
-                            fn text(&self) -> String {
-                                LinuxNvramAnalysis::get_table_from_list(self)
-                            }
+/// Pull `image` from its registry and archive it as `docker save` does,
+/// the archive being removed once done.
+async fn pull_image(
+    plain_client: &PlainClient,
+    image: &ImageReference,
+    platform: &Platform,
+    credentials: Option<&RegistryCredentials>,
+) -> Result<archive::Archive, anyhow::Error> {
+    tracing::info!("Pulling {} for {}...", image, platform);
+    let dir = tempfile::Builder::new()
+        .prefix("cosmo-image")
+        .tempdir()
+        .context("error creating a temporary directory")?;
+    let layout = dir.path().join(image.file_name());
+    let pulled = api::pull_image(plain_client, image, platform, credentials, &layout)
+        .await
+        .with_context(|| format!("error pulling {}", image))?;
+    tracing::info!("Pulled {}: {}", image, pulled);
+
+    let options = ArchiveOptions {
+        exclude: vec![],
+        reproducible: true,
+    };
+    archive::archive(&layout, &options)
+        .await
+        .with_context(|| format!("error archiving {}", image))
+}
 
-                            fn json(&self) -> String {
-                                serde_json::to_string(self).unwrap()
-                            }
-                        }
+/// Type and subtype of the firmware at `fw_filepath` detected from its
+/// contents, used as is with `auto_type` and else once confirmed. A subtype
+/// other than the default one is kept when valid for the type detected.
+fn detect_type(
+    fw_filepath: &str,
+    fw_subtype: &str,
+    auto_type: bool,
+    prompt: Prompt,
+) -> Result<(String, String), anyhow::Error> {
+    const NO_TYPE: &str = "pass --type or set `type` in the configuration file";
+
+    let detection = fw_detect::detect(std::path::Path::new(fw_filepath))
+        .with_context(|| format!("error reading file {}", fw_filepath))?;
+    let (fw_type, detected_subtype, evidence) = match detection {
+        Detection::Detected {
+            fw_type,
+            fw_subtype,
+            evidence,
+        } => (fw_type, fw_subtype, evidence),
+        Detection::Unknown { reason } => {
+            bail!("no firmware type and none detected ({reason}), {NO_TYPE}")
+        }
+    };
+    let fw_subtype = match cli::parse_firmware(&fw_type.to_string(), fw_subtype) {
+        Ok((_, fw_subtype)) if fw_subtype != FwSubtype::Generic => fw_subtype,
+        _ => detected_subtype,
+    };
 
-                        let an: Vec<LinuxNvramAnalysis> = serde_json::from_value(result)?;
+    if !auto_type {
+        let question = format!(
+            "{} looks like a {} {} firmware ({}), create it as such?",
+            fw_filepath, fw_type, fw_subtype, evidence
+        );
+        if !prompt.confirm(
+            &question,
+            "pass --auto-type to use the detected type, or --type",
+        )? {
+            bail!("Detected type not confirmed, {NO_TYPE}");
+        }
+    }
+    tracing::info!(
+        "Firmware type {} {}, detected from its {}",
+        fw_type,
+        fw_subtype,
+        evidence
+    );
+    Ok((fw_type.to_string(), fw_subtype.to_string()))
+}
 
-                        Box::new(an)
-                    }
-                    Analysis::Kernel => {
-                        impl CommandOutput for Vec<LinuxKernelAnalysis> {
-                            fn text(&self) -> String {
-                                LinuxKernelAnalysis::get_table_from_list(self)
-                            }
+/// Ask to confirm the deletion of `projects`, by typing the name of each
+/// one with `confirm_name`.
+fn confirm_delete(
+    projects: &[(uuid::Uuid, String)],
+    confirm_name: bool,
+    prompt: Prompt,
+) -> Result<(), anyhow::Error> {
+    const REMEDY: &str = "pass --yes to delete without asking";
+
+    let confirmed = if confirm_name {
+        let mut confirmed = true;
+        for (id, name) in projects {
+            let question = format!("Type the name of project {} to delete it", id);
+            if !prompt.confirm_typed(&question, name, REMEDY)? {
+                confirmed = false;
+                break;
+            }
+        }
+        confirmed
+    } else {
+        let question = match projects {
+            [(id, name)] => format!("Delete project {} ({})?", name, id),
+            _ => format!("Delete these {} projects?", projects.len()),
+        };
+        prompt.confirm(&question, REMEDY)?
+    };
 
-                            fn json(&self) -> String {
-                                serde_json::to_string(self).unwrap()
-                            }
-                        }
+    if !confirmed {
+        bail!("Deletion not confirmed, pass --yes to delete without asking");
+    }
+    Ok(())
+}
 
-                        let an: Vec<LinuxKernelAnalysis> = serde_json::from_value(result)?;
+/// Projects given on the command line, `-` reading them from stdin one per
+/// line.
+fn project_refs(projects: Vec<String>) -> Result<Vec<ProjectRef>, anyhow::Error> {
+    let mut refs = vec![];
+    for project in projects {
+        if project == "-" {
+            for line in std::io::stdin().lines() {
+                let line = line.context("error reading projects from stdin")?;
+                let line = line.trim();
+                if !line.is_empty() {
+                    refs.push(line.parse().map_err(anyhow::Error::msg)?);
+                }
+            }
+        } else {
+            refs.push(project.parse().map_err(anyhow::Error::msg)?);
+        }
+    }
+    Ok(refs)
+}
 
-                        Box::new(an)
-                    }
-                    Analysis::SoftwareBOM => {
-                        impl CommandOutput for Vec<LinuxSoftwareBOMAnalysis> {
-                            fn text(&self) -> String {
-                                LinuxSoftwareBOMAnalysis::get_table_from_list(self)
-                            }
+/// Analyses available for the type of the project.
+async fn project_analyses<U: ApiServer>(
+    api_server: &mut U,
+    project_id: uuid::Uuid,
+) -> Result<Vec<Analysis>, anyhow::Error> {
+    let overview = project_service::overview(api_server, project_id).await?;
+    let fw_type = overview["project"]["project_type"]
+        .as_str()
+        .context("error extracting string")?;
+    Ok(Analysis::for_project_type(fw_type)
+        .with_context(|| format!("Type not supported: {}", fw_type))?
+        .to_vec())
+}
 
-                            fn json(&self) -> String {
-                                serde_json::to_string(self).unwrap()
-                            }
-                        }
+/// Output of the `overview` of a project.
+fn overview_output(overview: serde_json::Value) -> Result<Box<dyn CommandOutput>, anyhow::Error> {
+    Ok(Box::new(ProjectOverview::from_value(overview)?))
+}
 
-                        let an: Vec<LinuxSoftwareBOMAnalysis> = serde_json::from_value(result)?;
+/// Filters and order of the results of the analyses.
+struct AnalysisOptions {
+    /// Filters of the CVEs of the cve-check analysis
+    filter: CveFilter,
+    sort: Option<CveSort>,
+    hardening: HardeningOptions,
+    passwords: PasswordHashOptions,
+    /// Only the kernel options not as recommended
+    only_failing: bool,
+    /// Window of the expiring flag of the certificates
+    expiring_within: Option<Duration>,
+}
 
-                        Box::new(an)
-                    }
-                    Analysis::StaticCode => {
-                        let analysis_result: Vec<LinuxStaticCodeAnalysis> =
-                            serde_json::from_value(result)?;
-                        let analysis_parsed: Result<Vec<LinuxStaticCode>, anyhow::Error> =
-                            analysis_result
-                                .into_iter()
-                                .map(|executable_flaw| {
-                                    let flaw_str = executable_flaw
-                                        .flaws
-                                        .as_str()
-                                        .ok_or_else(|| anyhow!("failed to access flaw string"));
-
-                                    let flaw_parsed = flaw_str.and_then(|flaw| {
-                                        serde_json::from_str::<LinuxStaticCodeAnalysisFlaws>(flaw)
-                                            .map_err(|_| anyhow!("failed to parse flaw"))
-                                    });
-
-                                    flaw_parsed.map(|flaw| LinuxStaticCode {
-                                        line: flaw.line.trim().to_string(),
-                                        descr: flaw.descr.trim().to_string(),
-                                        flaw_type: flaw.flaw_type.trim().to_string(),
-                                        filename: executable_flaw.filename,
-                                    })
-                                })
-                                .collect();
-                        let analysis_parsed = analysis_parsed?;
+/// Output of the result of `analysis`.
+fn analysis_output(
+    analysis: &Analysis,
+    res: ProjectAnalysis,
+    options: &AnalysisOptions,
+) -> Result<Box<dyn CommandOutput>, anyhow::Error> {
+    let result = res.result.context("analysis without result")?;
 
-                        log::trace!("Analysis parsed: {:#?}", analysis_parsed);
+    tracing::info!("FW type:{} | Analysis: {}", res.fw_type, res.name);
 
-                        impl CommandOutput for Vec<LinuxStaticCode> {
-                            fn text(&self) -> String {
-                                LinuxStaticCode::get_table_from_list(self)
-                            }
+    let output: Box<dyn CommandOutput> = match analysis {
+        // Linux/Container Analysis
+        Analysis::Hardening => {
+            let an: Vec<LinuxHardeningAnalysis> = serde_json::from_value(result)?;
 
-                            fn json(&self) -> String {
-                                serde_json::to_string(self).unwrap()
-                            }
-                        }
+            Box::new(HardeningReport::new(an, &options.hardening))
+        }
+        Analysis::CveCheck => {
+            let mut an: Vec<LinuxCveCheckAnalysis> = serde_json::from_value(result)?;
+            an.retain(|cve| options.filter.matches(cve));
+            if let Some(sort) = options.sort {
+                project_service::sort_cves(&mut an, sort);
+            }
 
-                        let an = LinuxStaticCode::get_table_from_list(&analysis_parsed);
+            Box::new(an)
+        }
+        Analysis::SecurityScan => {
+            let an: Vec<LinuxSecurityScanAnalysis> = serde_json::from_value(result)?;
 
-                        Box::new(an)
-                    }
-                    // UEFI Analysis
-                    Analysis::Access => {
-                        impl CommandOutput for Vec<UefiAccess> {
-                            fn text(&self) -> String {
-                                UefiAccess::get_table_from_list(self)
-                            }
+            Box::new(an)
+        }
+        Analysis::PasswordHash => {
+            let an: Vec<LinuxPasswordHashAnalysis> = serde_json::from_value(result)?;
 
-                            fn json(&self) -> String {
-                                serde_json::to_string(self).unwrap()
-                            }
-                        }
+            Box::new(credentials(&an, &options.passwords))
+        }
+        Analysis::Crypto => {
+            let an: Vec<LinuxCryptoAnalysis> = serde_json::from_value(result)?;
 
-                        let an: Vec<UefiAccess> = serde_json::from_value(result)?;
+            Box::new(crypto_service::material(&an, options.expiring_within))
+        }
+        Analysis::Nvram => {
+            let an: Vec<LinuxNvramAnalysis> = serde_json::from_value(result)?;
 
-                        Box::new(an)
-                    }
-                    Analysis::IntelBootGuard => {
-                        impl CommandOutput for UefiIntelBootGuard {
-                            fn text(&self) -> String {
-                                let table = UefiIntelBootGuardRsa::get_table_from_list(&self.rsa);
-                                format!("{}\nACM: {}", table, self.acm)
-                            }
+            Box::new(an)
+        }
+        Analysis::Kernel => {
+            let an: Vec<LinuxKernelAnalysis> = serde_json::from_value(result)?;
 
-                            fn json(&self) -> String {
-                                serde_json::to_string(self).unwrap()
-                            }
-                        }
+            Box::new(KernelReport::new(&an, options.only_failing))
+        }
+        Analysis::SoftwareBOM => {
+            let an: Vec<LinuxSoftwareBOMAnalysis> = serde_json::from_value(result)?;
 
-                        let an: UefiIntelBootGuard = serde_json::from_value(result)?;
+            Box::new(an)
+        }
+        Analysis::StaticCode => {
+            let analysis_parsed = static_code_flaws(result)?;
 
-                        Box::new(an)
-                    }
-                    Analysis::Surface => {
-                        impl CommandOutput for Vec<UefiSurface> {
-                            fn text(&self) -> String {
-                                UefiSurface::get_table_from_list(self)
-                            }
+            tracing::trace!("Analysis parsed: {:#?}", analysis_parsed);
 
-                            fn json(&self) -> String {
-                                serde_json::to_string(self).unwrap()
-                            }
-                        }
+            let an = LinuxStaticCode::get_table_from_list(&analysis_parsed);
 
-                        let an: Vec<UefiSurface> = serde_json::from_value(result)?;
+            Box::new(an)
+        }
+        // UEFI Analysis
+        Analysis::Access => {
+            let an: Vec<UefiAccess> = serde_json::from_value(result)?;
 
-                        Box::new(an)
-                    }
-                    Analysis::SecureBoot => {
-                        impl CommandOutput for UefiSecureBoot {
-                            fn text(&self) -> String {
-                                [
-                                    UefiSecureBootCerts::get_table_from_list(
-                                        &self.certs.kek,
-                                        "kek",
-                                    ),
-                                    UefiSecureBootCerts::get_table_from_list(
-                                        &[self.certs.pk.clone()],
-                                        "pk",
-                                    ),
-                                    UefiSecureBootCerts::get_table_from_list(
-                                        &self.databases.certs.db,
-                                        "db",
-                                    ),
-                                    UefiSecureBootCerts::get_table_from_list(
-                                        &self.databases.certs.dbx,
-                                        "dbx",
-                                    ),
-                                ]
-                                .join("\n")
-                            }
+            Box::new(an)
+        }
+        Analysis::IntelBootGuard => {
+            let an: UefiIntelBootGuard = serde_json::from_value(result)?;
 
-                            fn json(&self) -> String {
-                                serde_json::to_string(self).unwrap()
-                            }
-                        }
+            Box::new(an)
+        }
+        Analysis::Surface => {
+            let an: Vec<UefiSurface> = serde_json::from_value(result)?;
 
-                        let an: UefiSecureBoot = serde_json::from_value(result)?;
+            Box::new(SurfaceReport::new(an))
+        }
+        Analysis::SecureBoot => {
+            let an: UefiSecureBoot = serde_json::from_value(result)?;
 
-                        Box::new(an)
-                    }
+            Box::new(SecureBootReport::new(&an))
+        }
 
-                    Analysis::UefiSecurityScan => {
-                        impl CommandOutput for Vec<UefiSecurityScan> {
-                            fn text(&self) -> String {
-                                UefiSecurityScan::get_table_from_list(self)
-                            }
+        Analysis::UefiSecurityScan => {
+            let an: Vec<UefiSecurityScan> = serde_json::from_value(result)?;
 
-                            fn json(&self) -> String {
-                                serde_json::to_string(self).unwrap()
-                            }
-                        }
+            Box::new(VulnerableModule::from_findings(&an))
+        }
+        Analysis::PeimDxe => {
+            let an: Vec<UefiPeimDxe> = serde_json::from_value(result)?;
 
-                        let an: Vec<UefiSecurityScan> = serde_json::from_value(result)?;
+            Box::new(an)
+        }
+        // Vxworks Analysis
+        Analysis::Functions => {
+            let an: Vec<VxworksData> = serde_json::from_value(result)?;
 
-                        Box::new(an)
-                    }
-                    Analysis::PeimDxe => {
-                        impl CommandOutput for Vec<UefiPeimDxe> {
-                            fn text(&self) -> String {
-                                UefiPeimDxe::get_table_from_list(self)
-                            }
+            Box::new(an)
+        }
+        Analysis::Symbols => {
+            let an: Vec<VxworksData> = serde_json::from_value(result)?;
 
-                            fn json(&self) -> String {
-                                serde_json::to_string(self).unwrap()
-                            }
-                        }
+            Box::new(an)
+        }
+        Analysis::Tasks => {
+            let an: Vec<VxworksTask> = serde_json::from_value(result)?;
 
-                        let an: Vec<UefiPeimDxe> = serde_json::from_value(result)?;
+            Box::new(an)
+        }
+        Analysis::Capabilities => {
+            let an: Vec<VxworksCapability> = serde_json::from_value(result)?;
 
-                        Box::new(an)
-                    }
-                    // Vxworks Analysis
-                    Analysis::Functions => {
-                        let an: Vec<VxworksData> = serde_json::from_value(result)?;
+            Box::new(an)
+        }
+    };
 
-                        Box::new(an)
-                    }
-                    Analysis::Symbols => {
-                        impl CommandOutput for Vec<VxworksData> {
-                            fn text(&self) -> String {
-                                VxworksData::get_table_from_list(self)
-                            }
+    Ok(output)
+}
 
-                            fn json(&self) -> String {
-                                serde_json::to_string(self).unwrap()
-                            }
-                        }
+/// Accounts of the password-hash analysis, the hashes masked unless
+/// --show-secrets is given.
+fn credentials(
+    found: &[LinuxPasswordHashAnalysis],
+    options: &PasswordHashOptions,
+) -> Vec<Credential> {
+    found
+        .iter()
+        .map(|found| Credential::new(found, options.show_secrets))
+        .filter(|credential| !options.only_weak || credential.is_weak())
+        .collect()
+}
 
-                        let an: Vec<VxworksData> = serde_json::from_value(result)?;
+/// CSV of the result of `analysis`, for the analyses with typed records.
+fn analysis_csv(
+    analysis: &Analysis,
+    res: ProjectAnalysis,
+    project_id: uuid::Uuid,
+    cves: &AnalysisOptions,
+    options: &CsvOptions,
+) -> Result<String, anyhow::Error> {
+    let result = res.result.context("analysis without result")?;
+    let project_id = Some(project_id);
+
+    let csv = match analysis {
+        Analysis::Hardening => {
+            let an: Vec<LinuxHardeningAnalysis> = serde_json::from_value(result)?;
+            let report = HardeningReport::new(an, &cves.hardening);
+            output::csv(&report.binaries, project_id, options)
+        }
+        Analysis::CveCheck => {
+            let mut an: Vec<LinuxCveCheckAnalysis> = serde_json::from_value(result)?;
+            an.retain(|cve| cves.filter.matches(cve));
+            if let Some(sort) = cves.sort {
+                project_service::sort_cves(&mut an, sort);
+            }
+            output::csv(&an, project_id, options)
+        }
+        Analysis::SecurityScan => {
+            let an: Vec<LinuxSecurityScanAnalysis> = serde_json::from_value(result)?;
+            output::csv(&an, project_id, options)
+        }
+        Analysis::PasswordHash => {
+            let an: Vec<LinuxPasswordHashAnalysis> = serde_json::from_value(result)?;
+            output::csv(&credentials(&an, &cves.passwords), project_id, options)
+        }
+        Analysis::Crypto => {
+            let an: Vec<LinuxCryptoAnalysis> = serde_json::from_value(result)?;
+            let material = crypto_service::material(&an, cves.expiring_within);
+            output::csv(&material, project_id, options)
+        }
+        Analysis::Nvram => {
+            let an: Vec<LinuxNvramAnalysis> = serde_json::from_value(result)?;
+            output::csv(&an, project_id, options)
+        }
+        Analysis::Kernel => {
+            let an: Vec<LinuxKernelAnalysis> = serde_json::from_value(result)?;
+            let report = KernelReport::new(&an, cves.only_failing);
+            output::csv(&report.options, project_id, options)
+        }
+        Analysis::SoftwareBOM => {
+            let an: Vec<LinuxSoftwareBOMAnalysis> = serde_json::from_value(result)?;
+            output::csv(&an, project_id, options)
+        }
+        Analysis::StaticCode => output::csv(&static_code_flaws(result)?, project_id, options),
+        _ => bail!("CSV output is not available for the {} analysis", analysis),
+    };
 
-                        Box::new(an)
-                    }
-                    Analysis::Tasks => {
-                        impl CommandOutput for Vec<VxworksTask> {
-                            fn text(&self) -> String {
-                                VxworksTask::get_table_from_list(self)
-                            }
+    Ok(csv)
+}
 
-                            fn json(&self) -> String {
-                                serde_json::to_string(self).unwrap()
-                            }
-                        }
+type AnalysisResult = Result<Box<dyn CommandOutput>, anyhow::Error>;
+
+/// Output of several analyses of a project, each with its result or error.
+pub struct AnalysesOutput(Vec<(Analysis, AnalysisResult)>);
+
+impl CommandOutput for AnalysesOutput {
+    fn text(&self) -> String {
+        self.0
+            .iter()
+            .map(|(analysis, output)| match output {
+                Ok(output) => format!("{}\n{}", analysis, output.text()),
+                Err(err) => format!("{}\nError: {:#}", analysis, err),
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
 
-                        let an: Vec<VxworksTask> = serde_json::from_value(result)?;
+    fn json(&self) -> String {
+        let analyses: serde_json::Map<String, serde_json::Value> = self
+            .0
+            .iter()
+            .map(|(analysis, output)| {
+                let value = match output {
+                    Ok(output) => serde_json::from_str(&output.json()).unwrap_or_default(),
+                    Err(err) => serde_json::json!({ "error": format!("{:#}", err) }),
+                };
+                (analysis.to_string(), value)
+            })
+            .collect();
+        serde_json::Value::Object(analyses).to_string()
+    }
+}
 
-                        Box::new(an)
-                    }
-                    Analysis::Capabilities => {
-                        impl CommandOutput for Vec<VxworksCapability> {
-                            fn text(&self) -> String {
-                                VxworksCapability::get_table_from_list(self)
-                            }
+/// Projects deleted by `project delete`.
+pub struct DeletedProjects(Vec<uuid::Uuid>);
 
-                            fn json(&self) -> String {
-                                serde_json::to_string(self).unwrap()
-                            }
-                        }
+impl CommandOutput for DeletedProjects {
+    fn text(&self) -> String {
+        self.0
+            .iter()
+            .map(|project_id| format!("Project {} deleted", project_id))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
 
-                        let an: Vec<VxworksCapability> = serde_json::from_value(result)?;
+    fn json(&self) -> String {
+        serde_json::to_string(&self.0).unwrap_or_default()
+    }
+}
 
-                        Box::new(an)
-                    }
-                }
-            }
-        }
-        Command::Delete { project_id } => {
-            project_service::delete(api_server, project_id).await?;
-            Box::new(format!("Project {} deleted", project_id))
+/// Project created by `create`, with its overview when waited for.
+pub struct CreatedProject {
+    id: uuid::Uuid,
+    sha256: String,
+    overview: Option<ProjectOverview>,
+}
+
+impl CreatedProject {
+    fn message(&self) -> String {
+        format!("Project created successfull with ID: {}\nFirmware SHA-256: {}\nThe security scan is currently in progress, please allow up to a few minutes for completion. We will notify you via email as soon as the scan is over.", self.id, self.sha256)
+    }
+}
+
+impl CommandOutput for CreatedProject {
+    fn text(&self) -> String {
+        match &self.overview {
+            Some(overview) => overview.text(),
+            None => self.message(),
         }
-        Command::Report {
-            project_id,
-            savepath,
-        } => {
-            let report = project_service::report(api_server, project_id, savepath).await?;
-            Box::new(format!("Report saved to {}", report))
+    }
+
+    fn json(&self) -> String {
+        match &self.overview {
+            Some(overview) => overview.json(),
+            None => self.message().json(),
         }
+    }
 
-        Command::Organization(action) => {
-            impl CommandOutput for Vec<OrganizationData> {
-                fn text(&self) -> String {
-                    OrganizationData::get_table_from_list(self)
-                }
+    fn value(&self) -> Option<String> {
+        Some(self.id.to_string())
+    }
+}
 
-                fn json(&self) -> String {
-                    serde_json::to_string(self).unwrap()
-                }
-            }
+/// Output printed line by line while running the command.
+pub struct Streamed;
 
-            match action {
-                Organization::Create { name, description } => {
-                    organization_service::create(api_server, &name, &description).await?;
-                    Box::new(format!("Organization created: {}", name))
-                }
-                Organization::List => {
-                    let org = organization_service::list(api_server).await?;
-                    Box::new(org)
-                }
-                Organization::Delete { id } => {
-                    organization_service::delete(api_server, id).await?;
-                    Box::new(format!("Organization deleted. ID: {}", id))
+impl CommandOutput for Streamed {
+    fn text(&self) -> String {
+        String::new()
+    }
+
+    fn json(&self) -> String {
+        String::new()
+    }
+
+    fn ndjson(&self) -> String {
+        String::new()
+    }
+}
+
+/// CSV output of `list` and `analysis`.
+pub struct CsvOutput(String);
+
+/// The CSV itself, whatever the output mode.
+impl CommandOutput for CsvOutput {
+    fn text(&self) -> String {
+        self.0.clone()
+    }
+
+    fn json(&self) -> String {
+        self.0.clone()
+    }
+
+    fn csv(&self) -> Option<String> {
+        Some(self.0.clone())
+    }
+}
+
+/// The document itself in its format, whatever the output mode.
+impl CommandOutput for Sbom {
+    fn text(&self) -> String {
+        self.document.clone()
+    }
+
+    fn json(&self) -> String {
+        self.document.clone()
+    }
+}
+
+impl CommandOutput for Vec<Component> {
+    fn text(&self) -> String {
+        Component::get_table_from_list(self)
+    }
+
+    fn json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+
+impl CommandOutput for LicenseSummary {
+    fn text(&self) -> String {
+        self.get_text_output()
+    }
+
+    fn json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+
+impl CommandOutput for CreateDryRun {
+    fn text(&self) -> String {
+        self.get_text_output()
+    }
+
+    fn json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    fn exit_code(&self) -> Option<ExitCode> {
+        self.exit_code
+    }
+}
+
+impl CommandOutput for GateReport {
+    fn text(&self) -> String {
+        self.get_text_output()
+    }
+
+    fn json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    fn sarif(&self) -> Option<String> {
+        self.sarif.as_ref().map(|log| log.text())
+    }
+
+    fn junit(&self) -> Option<String> {
+        self.junit.as_ref().map(JunitReport::xml)
+    }
+
+    fn exit_code(&self) -> Option<ExitCode> {
+        (!self.passed).then_some(ExitCode::Gate)
+    }
+}
+
+impl CommandOutput for SarifLog {
+    fn text(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    fn json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    fn sarif(&self) -> Option<String> {
+        Some(self.text())
+    }
+}
+
+/// CVEs of a results page with their suppression by a baseline.
+struct BaselineCves {
+    cves: Vec<LinuxCveCheckAnalysis>,
+    suppressions: Vec<Suppression>,
+    /// Entries of the baseline past their expiry date, of CVEs of the page
+    expired: Vec<BaselineEntry>,
+}
+
+impl BaselineCves {
+    fn new(cves: Vec<LinuxCveCheckAnalysis>, baseline: &Baseline) -> Self {
+        let today = chrono::Utc::now().date_naive();
+        let suppressions: Vec<Suppression> = cves
+            .iter()
+            .map(|cve| baseline.suppression(cve, today))
+            .collect();
+
+        let mut expired: Vec<BaselineEntry> = vec![];
+        for (cve, suppression) in cves.iter().zip(&suppressions) {
+            if let (Suppression::Expired, Some(entry)) = (suppression, baseline.entry(cve)) {
+                if !expired.contains(entry) {
+                    expired.push(entry.clone());
                 }
             }
         }
-        Command::Apikey { action } => {
-            impl CommandOutput for ApiKeyData {
-                fn text(&self) -> String {
-                    format!(
-                        "api key: {} created on {}",
-                        self.api_key, self.creation_date
-                    )
-                }
 
-                fn json(&self) -> String {
-                    serde_json::to_string(self).unwrap()
-                }
-            }
+        Self {
+            cves,
+            suppressions,
+            expired,
+        }
+    }
 
-            match action {
-                ApiKeyAction::Create => {
-                    let apikey_data = apikey_service::create(api_server).await?;
-                    Box::new(apikey_data)
-                }
-                ApiKeyAction::List => {
-                    if let Some(apikey_data) = apikey_service::list(api_server).await? {
-                        Box::new(apikey_data)
-                    } else {
-                        Box::new("No API key found!")
-                    }
-                }
-                ApiKeyAction::Delete => {
-                    apikey_service::delete(api_server).await?;
-                    Box::new("api key deleted")
-                }
+    fn suppressed(&self) -> usize {
+        self.suppressions
+            .iter()
+            .filter(|suppression| **suppression == Suppression::Suppressed)
+            .count()
+    }
+}
+
+impl CommandOutput for BaselineCves {
+    fn text(&self) -> String {
+        let suppressed: Vec<bool> = self
+            .suppressions
+            .iter()
+            .map(|suppression| *suppression == Suppression::Suppressed)
+            .collect();
+
+        let mut lines = vec![
+            LinuxCveCheckAnalysis::get_table_with_suppressed(&self.cves, &suppressed),
+            format!(
+                "{} CVEs, {} suppressed by the baseline",
+                self.cves.len() - self.suppressed(),
+                self.suppressed()
+            ),
+        ];
+        lines.extend(self.expired.iter().map(baseline_service::expired_message));
+        lines.join("\n")
+    }
+
+    fn json(&self) -> String {
+        let cves: Vec<serde_json::Value> = self
+            .cves
+            .iter()
+            .zip(&self.suppressions)
+            .map(|(cve, suppression)| {
+                let mut cve = serde_json::to_value(cve).unwrap_or_default();
+                cve["suppressed"] = (*suppression == Suppression::Suppressed).into();
+                cve
+            })
+            .collect();
+
+        serde_json::json!({
+            "cves": cves,
+            "active": self.cves.len() - self.suppressed(),
+            "suppressed": self.suppressed(),
+            "expired_suppressions": self.expired,
+        })
+        .to_string()
+    }
+}
+
+/// Differences of two projects, failing on regressions when asked to.
+struct DiffOutput {
+    diff: ProjectDiff,
+    fail_on_regression: bool,
+}
+
+impl CommandOutput for DiffOutput {
+    fn text(&self) -> String {
+        self.diff.get_text_output()
+    }
+
+    fn json(&self) -> String {
+        serde_json::to_string(&self.diff).unwrap()
+    }
+
+    fn exit_code(&self) -> Option<ExitCode> {
+        (self.fail_on_regression && self.diff.regression).then_some(ExitCode::Gate)
+    }
+}
+
+impl CommandOutput for JunitReport {
+    fn text(&self) -> String {
+        self.xml()
+    }
+
+    fn json(&self) -> String {
+        self.xml()
+    }
+
+    fn junit(&self) -> Option<String> {
+        Some(self.xml())
+    }
+}
+
+impl CommandOutput for Identity {
+    fn text(&self) -> String {
+        self.get_text_output()
+    }
+
+    fn json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+
+impl CommandOutput for Usage {
+    fn text(&self) -> String {
+        self.get_text_output()
+    }
+
+    fn json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+
+impl CommandOutput for PingReport {
+    fn text(&self) -> String {
+        let ms = |ms: Option<u128>| {
+            ms.map(|ms| format!("{} ms", ms))
+                .unwrap_or_else(|| "skipped".to_string())
+        };
+        let resolved = self
+            .resolved
+            .iter()
+            .map(|addr| addr.ip().to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        format!(
+            "Server: {}\nResolved: {}\nDNS: {}\nTCP connect: {}\nReachability: {} ms\nAuthentication: {} ms",
+            self.address,
+            if resolved.is_empty() { "-" } else { &resolved },
+            ms(self.dns_ms),
+            ms(self.tcp_ms),
+            self.reachability_ms,
+            self.authentication_ms
+        )
+    }
+
+    fn json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+
+/// Projects of `list`, the table showing `columns`.
+pub struct ProjectList {
+    pub projects: Vec<Project>,
+    pub columns: Vec<ProjectColumn>,
+}
+
+impl CommandOutput for ProjectList {
+    fn text(&self) -> String {
+        Project::get_table_from_list(&self.projects, &self.columns)
+    }
+
+    fn json(&self) -> String {
+        serde_json::to_string(&self.projects).unwrap()
+    }
+}
+
+impl CommandOutput for StatusChange {
+    fn text(&self) -> String {
+        self.to_string()
+    }
+
+    fn json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    /// The same line as the changes printed before it
+    fn ndjson(&self) -> String {
+        serde_json::to_value(self).unwrap_or_default().to_string()
+    }
+
+    fn exit_code(&self) -> Option<ExitCode> {
+        matches!(self.status, ProjectStatus::Failed { .. }).then_some(ExitCode::Failure)
+    }
+}
+
+impl CommandOutput for ProjectStatus {
+    fn text(&self) -> String {
+        self.to_string()
+    }
+
+    fn json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    fn value(&self) -> Option<String> {
+        Some(ProjectStatusDTO::from(self.clone()).status)
+    }
+}
+
+/// The summary for humans, the overview as received otherwise.
+impl CommandOutput for ProjectOverview {
+    fn text(&self) -> String {
+        self.get_text_output()
+    }
+
+    fn json(&self) -> String {
+        self.raw.to_string()
+    }
+}
+
+impl CommandOutput for HardeningReport {
+    fn text(&self) -> String {
+        self.get_text_output()
+    }
+
+    fn json(&self) -> String {
+        serde_json::to_string(&self.binaries).unwrap()
+    }
+}
+
+impl CommandOutput for Vec<LinuxHardeningAnalysis> {
+    fn text(&self) -> String {
+        LinuxHardeningAnalysis::get_table_from_list(self)
+    }
+
+    fn json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+
+impl CommandOutput for Vec<LinuxCveCheckAnalysis> {
+    fn text(&self) -> String {
+        LinuxCveCheckAnalysis::get_table_from_list(self)
+    }
+
+    fn json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+
+impl CommandOutput for Vec<LinuxSecurityScanAnalysis> {
+    fn text(&self) -> String {
+        LinuxSecurityScanAnalysis::get_table_from_list(self)
+    }
+
+    fn json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+
+impl CommandOutput for Vec<Credential> {
+    fn text(&self) -> String {
+        Credential::get_table_from_list(self)
+    }
+
+    fn json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+
+impl CommandOutput for Vec<CryptoMaterial> {
+    fn text(&self) -> String {
+        CryptoMaterial::get_table_from_list(self)
+    }
+
+    fn json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+
+impl CommandOutput for Vec<LinuxNvramAnalysis> {
+    fn text(&self) -> String {
+        LinuxNvramAnalysis::get_table_from_list(self)
+    }
+
+    fn json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+
+impl CommandOutput for KernelReport {
+    fn text(&self) -> String {
+        self.get_text_output()
+    }
+
+    fn json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+
+impl CommandOutput for Vec<LinuxSoftwareBOMAnalysis> {
+    fn text(&self) -> String {
+        LinuxSoftwareBOMAnalysis::get_table_from_list(self)
+    }
+
+    fn json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+
+impl CommandOutput for Vec<LinuxStaticCode> {
+    fn text(&self) -> String {
+        LinuxStaticCode::get_table_from_list(self)
+    }
+
+    fn json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+
+impl CommandOutput for Vec<UefiAccess> {
+    fn text(&self) -> String {
+        UefiAccess::get_table_from_list(self)
+    }
+
+    fn json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+
+impl CommandOutput for UefiIntelBootGuard {
+    fn text(&self) -> String {
+        self.get_text_output()
+    }
+
+    fn json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+
+impl CommandOutput for SurfaceReport {
+    fn text(&self) -> String {
+        self.get_text_output()
+    }
+
+    fn json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+
+impl CommandOutput for SecureBootReport {
+    fn text(&self) -> String {
+        self.get_text_output()
+    }
+
+    fn json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+
+impl CommandOutput for Vec<VulnerableModule> {
+    fn text(&self) -> String {
+        VulnerableModule::get_table_from_list(self)
+    }
+
+    fn json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+
+impl CommandOutput for Vec<UefiPeimDxe> {
+    fn text(&self) -> String {
+        UefiPeimDxe::get_table_from_list(self)
+    }
+
+    fn json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+
+impl CommandOutput for Vec<VxworksData> {
+    fn text(&self) -> String {
+        VxworksData::get_table_from_list(self)
+    }
+
+    fn json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+
+impl CommandOutput for Vec<VxworksTask> {
+    fn text(&self) -> String {
+        VxworksTask::get_table_from_list(self)
+    }
+
+    fn json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+
+impl CommandOutput for Vec<VxworksCapability> {
+    fn text(&self) -> String {
+        VxworksCapability::get_table_from_list(self)
+    }
+
+    fn json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+
+impl CommandOutput for Vec<OrganizationData> {
+    fn text(&self) -> String {
+        OrganizationData::get_table_from_list(self)
+    }
+
+    fn json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+
+impl CommandOutput for Vec<WebhookData> {
+    fn text(&self) -> String {
+        WebhookData::get_table_from_list(self)
+    }
+
+    fn json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+
+/// The webhook just added.
+impl CommandOutput for WebhookData {
+    fn text(&self) -> String {
+        format!("Webhook {} added for {}", self.id, self.url)
+    }
+
+    fn json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    fn value(&self) -> Option<String> {
+        Some(self.id.to_string())
+    }
+}
+
+impl CommandOutput for Vec<ListedApiKey> {
+    fn text(&self) -> String {
+        if self.is_empty() {
+            "No API key found!".to_string()
+        } else {
+            ListedApiKey::get_table_from_list(self)
+        }
+    }
+
+    fn json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+
+impl CommandOutput for EffectiveConfig {
+    fn text(&self) -> String {
+        format!("Profile: {}\n{}", self.profile, self.get_table())
+    }
+
+    fn json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+
+impl CommandOutput for RotatedApiKey {
+    fn text(&self) -> String {
+        let text = match (&self.api_key, &self.file) {
+            (Some(api_key), _) => {
+                format!("new api key: {} created on {}", api_key, self.creation_date)
             }
+            (None, Some(file)) => format!(
+                "new api key created on {}, written to {}",
+                self.creation_date,
+                file.display()
+            ),
+            (None, None) => unreachable!("api key neither shown nor written"),
+        };
+        match self.saved_in {
+            Some(ApiKeySource::Keyring) => format!("{}, saved in the keyring", text),
+            Some(_) => format!("{}, saved in the configuration file", text),
+            None => text,
         }
-    };
+    }
 
-    Ok(cmd_output)
+    fn json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+
+impl CommandOutput for ApiKeyData {
+    fn text(&self) -> String {
+        format!(
+            "api key: {} created on {}",
+            self.api_key, self.creation_date
+        )
+    }
+
+    fn json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    fn value(&self) -> Option<String> {
+        Some(self.api_key.to_string())
+    }
 }
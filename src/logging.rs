@@ -0,0 +1,594 @@
+//! Diagnostics on stderr: the `tracing` events of the cli and the `log`
+//! records of its dependencies, filtered by level and target, written as
+//! text for humans or as one JSON object per line, the secrets redacted.
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    env, fmt,
+    io::{self, Write},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, RwLock,
+    },
+};
+
+use chrono::{SecondsFormat, Utc};
+use console::{style, Color};
+use lazy_static::lazy_static;
+use serde_json::{Map, Number, Value};
+use tracing::{
+    field::{Field, Visit},
+    level_filters::LevelFilter,
+    span, Event, Level, Metadata, Subscriber,
+};
+
+use tracing_core::span::Current;
+
+use crate::cli::LogFormat;
+
+/// Environment variable of the filter, e.g. `debug` or `cosmo_cli::api=trace,info`
+pub const FILTER_ENV: &str = "COSMO_LOG";
+/// Written in place of the secrets
+pub const REDACTED: &str = "<redacted>";
+
+/// Parts of the names of the fields holding secrets, compared without case,
+/// dashes and underscores
+const SECRET_FIELDS: [&str; 7] = [
+    "token",
+    "password",
+    "passphrase",
+    "secret",
+    "apikey",
+    "authorization",
+    "cookie",
+];
+/// Targets of the cli, logged at the level given, the dependencies at most
+/// at info
+const TARGETS: [&str; 2] = ["cosmo", "cosmo_cli"];
+
+lazy_static! {
+    /// Values never written, wherever they appear
+    static ref SECRETS: RwLock<Vec<String>> = RwLock::new(vec![]);
+}
+
+thread_local! {
+    /// Spans entered by the current thread, the innermost last
+    static ENTERED: RefCell<Vec<u64>> = const { RefCell::new(vec![]) };
+}
+
+/// Write the diagnostics to stderr from now on, filtered by `level` and by
+/// the directives of [`FILTER_ENV`], or of `RUST_LOG` when not set.
+pub fn init(level: LevelFilter, format: LogFormat) {
+    let mut filter = LogFilter::new(level);
+    let directives = env::var(FILTER_ENV).or_else(|_| env::var("RUST_LOG"));
+    let invalid = directives.ok().and_then(|spec| filter.parse(&spec).err());
+
+    let logger = Logger::new(
+        filter,
+        format,
+        console::colors_enabled_stderr(),
+        io::stderr(),
+    );
+    log::set_max_level(log_level_filter(logger.inner.filter.max_level()));
+    // Only the first logger is kept
+    let _ = log::set_logger(Box::leak(Box::new(logger.clone())));
+    let _ = tracing::subscriber::set_global_default(logger);
+
+    if let Some(err) = invalid {
+        tracing::warn!("Ignoring {}: {}", FILTER_ENV, err);
+    }
+}
+
+/// Never write `secret`, e.g. the api key in use, even as part of a message.
+pub fn redact(secret: &str) {
+    // Too short to be told apart from the rest of the text
+    if secret.len() < 4 {
+        return;
+    }
+    let mut secrets = SECRETS.write().unwrap_or_else(|e| e.into_inner());
+    if !secrets.iter().any(|known| known == secret) {
+        secrets.push(secret.to_string());
+    }
+}
+
+/// Whether the field `name` holds a secret, e.g. `api_key` or `X-API-KEY`.
+pub fn is_secret(name: &str) -> bool {
+    let name: String = name
+        .chars()
+        .filter(|c| *c != '-' && *c != '_')
+        .collect::<String>()
+        .to_lowercase();
+    SECRET_FIELDS.iter().any(|secret| name.contains(secret))
+}
+
+/// `text` with the values of the JSON fields holding secrets redacted, as
+/// is when not JSON.
+pub fn redact_json(text: &str) -> String {
+    fn redact_value(value: &mut Value) {
+        match value {
+            Value::Object(map) => {
+                for (name, value) in map {
+                    if is_secret(name) && !value.is_null() {
+                        *value = Value::String(REDACTED.to_string());
+                    } else {
+                        redact_value(value);
+                    }
+                }
+            }
+            Value::Array(values) => values.iter_mut().for_each(redact_value),
+            _ => {}
+        }
+    }
+
+    match serde_json::from_str::<Value>(text) {
+        Ok(mut value) => {
+            redact_value(&mut value);
+            value.to_string()
+        }
+        Err(_) => text.to_string(),
+    }
+}
+
+/// Levels of the targets, given as directives like `RUST_LOG`.
+#[derive(Debug, Clone)]
+pub struct LogFilter {
+    /// Level of the targets without a directive
+    default: LevelFilter,
+    /// Targets and their levels
+    directives: Vec<(String, LevelFilter)>,
+}
+
+impl LogFilter {
+    /// The cli at `level`, its dependencies at most at info.
+    pub fn new(level: LevelFilter) -> Self {
+        Self {
+            default: level.min(LevelFilter::INFO),
+            directives: TARGETS
+                .iter()
+                .map(|target| (target.to_string(), level))
+                .collect(),
+        }
+    }
+
+    /// Apply the comma separated directives `spec`: a level for all the
+    /// targets, e.g. `debug`, or for a target and its modules, e.g.
+    /// `reqwest=trace`. Nothing is applied when one is invalid.
+    pub fn parse(&mut self, spec: &str) -> Result<(), String> {
+        let mut filter = self.clone();
+        for directive in spec.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            let (target, level) = match directive.split_once('=') {
+                Some((target, level)) => (Some(target.trim()), level.trim()),
+                None => (None, directive),
+            };
+            let level: LevelFilter = level
+                .parse()
+                .map_err(|_| format!("invalid level in `{}`", directive))?;
+            match target {
+                Some("") => return Err(format!("missing target in `{}`", directive)),
+                Some(target) => {
+                    filter.directives.retain(|(known, _)| known != target);
+                    filter.directives.push((target.to_string(), level));
+                }
+                None => {
+                    filter.default = level;
+                    filter.directives.clear();
+                }
+            }
+        }
+        *self = filter;
+        Ok(())
+    }
+
+    /// Whether the events of `target` at `level` are written.
+    pub fn enabled(&self, target: &str, level: &Level) -> bool {
+        *level <= self.level(target)
+    }
+
+    /// Level of `target`, given by the directive of its closest module.
+    fn level(&self, target: &str) -> LevelFilter {
+        self.directives
+            .iter()
+            .filter(|(module, _)| {
+                target == module
+                    || target
+                        .strip_prefix(module.as_str())
+                        .is_some_and(|rest| rest.starts_with("::"))
+            })
+            .max_by_key(|(module, _)| module.len())
+            .map(|(_, level)| *level)
+            .unwrap_or(self.default)
+    }
+
+    /// Most verbose level of a target.
+    pub fn max_level(&self) -> LevelFilter {
+        self.directives
+            .iter()
+            .map(|(_, level)| *level)
+            .fold(self.default, LevelFilter::max)
+    }
+}
+
+/// Subscriber of the `tracing` events and logger of the `log` records,
+/// writing them with the fields of the spans entered.
+#[derive(Clone)]
+pub struct Logger {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    filter: LogFilter,
+    format: LogFormat,
+    /// Whether the levels of the text format are colored
+    color: bool,
+    out: Mutex<Box<dyn Write + Send>>,
+    spans: Mutex<HashMap<u64, SpanData>>,
+    next_id: AtomicU64,
+}
+
+struct SpanData {
+    metadata: &'static Metadata<'static>,
+    fields: Fields,
+    /// Handles of the span, removed when none is left
+    refs: usize,
+}
+
+impl Logger {
+    /// Logger writing to `out` the events passing `filter`.
+    pub fn new(
+        filter: LogFilter,
+        format: LogFormat,
+        color: bool,
+        out: impl Write + Send + 'static,
+    ) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                filter,
+                format,
+                color,
+                out: Mutex::new(Box::new(out)),
+                spans: Mutex::new(HashMap::new()),
+                next_id: AtomicU64::new(1),
+            }),
+        }
+    }
+
+    /// Write an event of `target` at `level`, in the spans entered.
+    fn write(&self, level: &Level, target: &str, fields: Fields) {
+        let spans: Vec<(&'static str, Fields)> = {
+            let spans = self.inner.spans.lock().unwrap_or_else(|e| e.into_inner());
+            ENTERED.with(|entered| {
+                entered
+                    .borrow()
+                    .iter()
+                    .filter_map(|id| spans.get(id))
+                    .map(|span| (span.metadata.name(), span.fields.clone()))
+                    .collect()
+            })
+        };
+
+        let mut line = match self.inner.format {
+            LogFormat::Text => self.text(level, &fields),
+            LogFormat::Json => json(level, target, &fields, &spans),
+        };
+        for secret in SECRETS.read().unwrap_or_else(|e| e.into_inner()).iter() {
+            line = line.replace(secret.as_str(), REDACTED);
+        }
+
+        let mut out = self.inner.out.lock().unwrap_or_else(|e| e.into_inner());
+        let _ = writeln!(out, "{}", line);
+    }
+
+    /// `[LEVEL] message name=value ...`
+    fn text(&self, level: &Level, fields: &Fields) -> String {
+        let color = match *level {
+            Level::ERROR => Color::Red,
+            Level::WARN => Color::Yellow,
+            Level::INFO => Color::Green,
+            Level::DEBUG => Color::Blue,
+            Level::TRACE => Color::Cyan,
+        };
+        let enabled = self.inner.color;
+        let bracket = |b: &'static str| style(b).color256(8).force_styling(enabled);
+        let mut line = format!(
+            "{}{}{}",
+            bracket("["),
+            style(format!("{:<5}", level))
+                .fg(color)
+                .force_styling(enabled),
+            bracket("]")
+        );
+
+        if let Some(message) = fields.get("message") {
+            line.push(' ');
+            line.push_str(&value_text(message));
+        }
+        for (name, value) in fields.0.iter().filter(|(name, _)| *name != "message") {
+            line.push_str(&format!(" {}={}", name, value_text(value)));
+        }
+        line
+    }
+}
+
+/// One JSON object of the event, with the fields of its spans, the
+/// outermost first.
+fn json(level: &Level, target: &str, fields: &Fields, spans: &[(&'static str, Fields)]) -> String {
+    let spans: Vec<Value> = spans
+        .iter()
+        .map(|(name, fields)| {
+            let mut span = Map::new();
+            span.insert("name".to_string(), Value::String(name.to_string()));
+            span.extend(fields.to_map());
+            Value::Object(span)
+        })
+        .collect();
+
+    let mut event = Map::new();
+    event.insert(
+        "timestamp".to_string(),
+        Value::String(Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true)),
+    );
+    event.insert("level".to_string(), Value::String(level.to_string()));
+    event.insert("target".to_string(), Value::String(target.to_string()));
+    event.insert("fields".to_string(), Value::Object(fields.to_map()));
+    if !spans.is_empty() {
+        event.insert("spans".to_string(), Value::Array(spans));
+    }
+    Value::Object(event).to_string()
+}
+
+fn value_text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        value => value.to_string(),
+    }
+}
+
+impl Subscriber for Logger {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        self.inner
+            .filter
+            .enabled(metadata.target(), metadata.level())
+    }
+
+    fn max_level_hint(&self) -> Option<LevelFilter> {
+        Some(self.inner.filter.max_level())
+    }
+
+    fn new_span(&self, span: &span::Attributes<'_>) -> span::Id {
+        let mut fields = Fields::default();
+        span.record(&mut Redact(&mut fields));
+
+        let id = self.inner.next_id.fetch_add(1, Ordering::Relaxed);
+        let data = SpanData {
+            metadata: span.metadata(),
+            fields,
+            refs: 1,
+        };
+        self.inner
+            .spans
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(id, data);
+        span::Id::from_u64(id)
+    }
+
+    fn record(&self, span: &span::Id, values: &span::Record<'_>) {
+        let mut spans = self.inner.spans.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(data) = spans.get_mut(&span.into_u64()) {
+            values.record(&mut Redact(&mut data.fields));
+        }
+    }
+
+    fn record_follows_from(&self, _span: &span::Id, _follows: &span::Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut fields = Fields::default();
+        event.record(&mut Redact(&mut fields));
+        let metadata = event.metadata();
+        self.write(metadata.level(), metadata.target(), fields);
+    }
+
+    fn current_span(&self) -> Current {
+        let spans = self.inner.spans.lock().unwrap_or_else(|e| e.into_inner());
+        ENTERED.with(|entered| {
+            entered
+                .borrow()
+                .last()
+                .and_then(|id| Some((*id, spans.get(id)?.metadata)))
+                .map_or_else(Current::none, |(id, metadata)| {
+                    Current::new(span::Id::from_u64(id), metadata)
+                })
+        })
+    }
+
+    fn enter(&self, span: &span::Id) {
+        ENTERED.with(|entered| entered.borrow_mut().push(span.into_u64()));
+    }
+
+    fn exit(&self, span: &span::Id) {
+        ENTERED.with(|entered| {
+            let mut entered = entered.borrow_mut();
+            if let Some(i) = entered.iter().rposition(|id| *id == span.into_u64()) {
+                entered.remove(i);
+            }
+        });
+    }
+
+    fn clone_span(&self, span: &span::Id) -> span::Id {
+        let mut spans = self.inner.spans.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(data) = spans.get_mut(&span.into_u64()) {
+            data.refs += 1;
+        }
+        span.clone()
+    }
+
+    fn try_close(&self, span: span::Id) -> bool {
+        let mut spans = self.inner.spans.lock().unwrap_or_else(|e| e.into_inner());
+        let id = span.into_u64();
+        match spans.get_mut(&id) {
+            Some(data) if data.refs > 1 => {
+                data.refs -= 1;
+                false
+            }
+            Some(_) => {
+                spans.remove(&id);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl log::Log for Logger {
+    fn enabled(&self, metadata: &log::Metadata<'_>) -> bool {
+        self.inner
+            .filter
+            .enabled(metadata.target(), &tracing_level(metadata.level()))
+    }
+
+    fn log(&self, record: &log::Record<'_>) {
+        if !log::Log::enabled(self, record.metadata()) {
+            return;
+        }
+        let mut fields = Fields::default();
+        fields.set("message", Value::String(record.args().to_string()));
+        self.write(&tracing_level(record.level()), record.target(), fields);
+    }
+
+    fn flush(&self) {
+        let _ = self
+            .inner
+            .out
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .flush();
+    }
+}
+
+fn tracing_level(level: log::Level) -> Level {
+    match level {
+        log::Level::Error => Level::ERROR,
+        log::Level::Warn => Level::WARN,
+        log::Level::Info => Level::INFO,
+        log::Level::Debug => Level::DEBUG,
+        log::Level::Trace => Level::TRACE,
+    }
+}
+
+fn log_level_filter(level: LevelFilter) -> log::LevelFilter {
+    match level.into_level() {
+        None => log::LevelFilter::Off,
+        Some(Level::ERROR) => log::LevelFilter::Error,
+        Some(Level::WARN) => log::LevelFilter::Warn,
+        Some(Level::INFO) => log::LevelFilter::Info,
+        Some(Level::DEBUG) => log::LevelFilter::Debug,
+        Some(Level::TRACE) => log::LevelFilter::Trace,
+    }
+}
+
+/// Fields of an event or of a span, in the order recorded.
+#[derive(Debug, Clone, Default)]
+struct Fields(Vec<(&'static str, Value)>);
+
+impl Fields {
+    fn get(&self, name: &str) -> Option<&Value> {
+        self.0
+            .iter()
+            .find(|(known, _)| *known == name)
+            .map(|(_, value)| value)
+    }
+
+    fn set(&mut self, name: &'static str, value: Value) {
+        match self.0.iter_mut().find(|(known, _)| *known == name) {
+            Some((_, known)) => *known = value,
+            None => self.0.push((name, value)),
+        }
+    }
+
+    fn to_map(&self) -> Map<String, Value> {
+        self.0
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.clone()))
+            .collect()
+    }
+}
+
+impl Visit for Fields {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.set(field.name(), Value::String(format!("{:?}", value)));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.set(field.name(), Value::String(value.to_string()));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.set(field.name(), Value::from(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.set(field.name(), Value::from(value));
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        let value = Number::from_f64(value).map_or(Value::Null, Value::Number);
+        self.set(field.name(), value);
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.set(field.name(), Value::Bool(value));
+    }
+}
+
+/// Filter of the fields, recording [`REDACTED`] in place of the values of
+/// those holding secrets before they reach the visitor.
+struct Redact<'a, V>(&'a mut V);
+
+impl<V: Visit> Redact<'_, V> {
+    fn redacted(&mut self, field: &Field) -> bool {
+        let secret = is_secret(field.name());
+        if secret {
+            self.0.record_str(field, REDACTED);
+        }
+        secret
+    }
+}
+
+impl<V: Visit> Visit for Redact<'_, V> {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if !self.redacted(field) {
+            self.0.record_debug(field, value);
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if !self.redacted(field) {
+            self.0.record_str(field, value);
+        }
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        if !self.redacted(field) {
+            self.0.record_i64(field, value);
+        }
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        if !self.redacted(field) {
+            self.0.record_u64(field, value);
+        }
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        if !self.redacted(field) {
+            self.0.record_f64(field, value);
+        }
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        if !self.redacted(field) {
+            self.0.record_bool(field, value);
+        }
+    }
+}
@@ -1,141 +1,577 @@
 use std::{
-    fs,
-    io::{self, BufRead, Write},
+    io::{self, IsTerminal},
     path::{Path, PathBuf},
 };
 
 use anyhow::Context;
-use ini::Ini;
-use lazy_static::lazy_static;
-use log::LevelFilter;
+use url::Url;
 
 use cosmo_cli::{
-    api::HttpApiServer,
-    cli::{self, Command},
+    api::{self, ApiServer, HttpApiServer, HttpApiServerOptions, OfflineApiServer, PlainClient},
+    archive::ArchiveOptions,
+    cli::{
+        self, ApiKeyAction, ApiKeySource, Cache, Command, CompletionKind, CosmoCliOpts, ExitCode,
+        Prompt, TokenStore,
+    },
+    completion, config, credentials, logging,
+    output::{self, CommandOutput, OutputMode},
+    profile::{self, Profile, CA_CERT_ENTRY},
+    style, update,
 };
 
-const INI_CONFIG_SECTION: &str = "default";
-const API_KEY_ENTRY: &str = "api_key";
-
 #[tokio::main]
 async fn main() {
-    let cli_opts = cli::parse_from(&mut std::env::args_os()).unwrap_or_else(|e| e.exit());
+    let mut cli_opts = cli::parse_from(&mut std::env::args_os()).unwrap_or_else(|e| e.exit());
+
+    style::init(cli_opts.color, cli_opts.ascii);
+    logging::init(cli_opts.log_level_filter, cli_opts.log_format);
+    // Never written in the diagnostics, even as part of an error
+    let proxy_password = cli_opts
+        .proxy
+        .as_deref()
+        .and_then(|proxy| Url::parse(proxy).ok())
+        .and_then(|proxy| proxy.password().map(str::to_string));
+    for secret in [
+        &cli_opts.api_key,
+        &cli_opts.client_cert_password,
+        &proxy_password,
+    ]
+    .into_iter()
+    .flatten()
+    {
+        logging::redact(secret);
+    }
 
-    setup_logger(cli_opts.log_level_filter);
+    if let Command::Completions { shell } = cli_opts.command {
+        if let Err(e) = completion::generate(shell, &mut io::stdout()) {
+            cli::report_error(
+                &anyhow::Error::new(e).context("error writing the completion script"),
+            );
+            ExitCode::Failure.exit()
+        }
+        std::process::exit(0)
+    }
 
     // TODO: check if needed
     openssl_probe::init_ssl_cert_env_vars();
 
-    human_panic::setup_panic!(Metadata {
-        name: env!("CARGO_PKG_NAME").into(),
-        version: cosmo_cli::version().into(),
-        authors: "Exein <support@exein.io>".into(),
-        homepage: "https://cosmo.exein.io".into(),
-    });
+    // Left by the last self-update on Windows
+    update::remove_replaced();
+
+    human_panic::setup_panic!(human_panic::Metadata::new(
+        env!("CARGO_PKG_NAME"),
+        cosmo_cli::version()
+    )
+    .authors("Exein <support@exein.io>")
+    .homepage("https://cosmo.exein.io"));
+
+    let profile = configure(&mut cli_opts);
+
+    // Candidates of the shell completion, never from the network
+    if let Command::Complete {
+        kind: CompletionKind::ProjectIds,
+        words,
+    } = &cli_opts.command
+    {
+        if let Some(cache) = profile.response_cache() {
+            let mut api_server = OfflineApiServer::new(cache);
+            for candidate in completion::project_candidates(&mut api_server, words).await {
+                println!("{candidate}");
+            }
+        }
+        std::process::exit(0)
+    }
+
+    // The profile may not exist yet
+    if let Some(outcome) = run_config_cmd(&cli_opts, &profile).await {
+        finish(outcome, cli_opts.output_mode)
+    }
 
-    // Handle setup command before the others
-    if let Command::Setup = cli_opts.command {
-        if let Err(e) = setup_config() {
-            let e = e.context("error initializing the configuration");
+    if !profile.exists() {
+        cli::report_error(&anyhow::anyhow!(
+            "profile {} not found in {}, create it with `cosmo --profile {} setup`",
+            profile.name(),
+            config::file_path().display(),
+            profile.name()
+        ));
+        ExitCode::Usage.exit()
+    }
+    let token_store = match cli_opts.token_store {
+        Some(token_store) => token_store,
+        None => profile.token_store().unwrap_or_else(|e| {
             cli::report_error(&e);
-            std::process::exit(1)
+            ExitCode::Usage.exit()
+        }),
+    };
+
+    // Neither the api key nor the server are needed
+    if let Some(outcome) = run_local_cmd(&cli_opts.command, &profile, token_store) {
+        finish(outcome, cli_opts.output_mode)
+    }
+
+    // Thresholds not given on the command line come from the config file
+    if let Command::Gate { policy, .. } = &mut cli_opts.command {
+        match config::policy() {
+            Ok(config) => *policy = std::mem::take(policy).or(config),
+            Err(e) => {
+                cli::report_error(&e);
+                ExitCode::Usage.exit()
+            }
         }
+    }
 
-        cli::print_cmd_output(&"Configuration complete", cli_opts.output_mode);
-        std::process::exit(0)
+    if let Some(organization) = cli_opts.organization.clone() {
+        cli_opts.command.default_organization(organization);
+    }
+
+    if cli_opts.offline {
+        run_offline(cli_opts, &profile).await
+    }
+
+    let (mut api_server, plain_client, stored_in) =
+        http_clients(&mut cli_opts, &profile, token_store).await;
+
+    match &cli_opts.command {
+        // Revoked before being forgotten, and forgotten even when the
+        // revocation fails
+        Command::Logout { revoke: true } => {
+            let outcome = logout(&mut api_server, &profile, token_store).await;
+            finish(outcome, cli_opts.output_mode)
+        }
+        // The new api key takes the place of the previous one where it was
+        // read from
+        Command::Apikey {
+            action: ApiKeyAction::Rotate,
+            out,
+            ..
+        } => {
+            let outcome = cosmo_cli::rotate_api_key(&mut api_server, out.as_deref(), |api_key| {
+                logging::redact(api_key);
+                credentials::save_rotated(&profile, stored_in, api_key, cli_opts.prompt)
+            })
+            .await;
+            finish(outcome, cli_opts.output_mode)
+        }
+        _ => run(cli_opts, api_server, plain_client).await,
+    }
+}
+
+/// Run the command with the api server, dropping it on Ctrl-C so that
+/// in-flight requests are aborted, and exit with its code.
+async fn run(
+    cli_opts: CosmoCliOpts,
+    mut api_server: HttpApiServer,
+    plain_client: PlainClient,
+) -> ! {
+    // Once done, the notice doesn't get in the way of the output nor of
+    // the scripts
+    let notify_update = cli_opts.update_check
+        && !cli_opts.quiet
+        && io::stderr().is_terminal()
+        && !matches!(cli_opts.command, Command::SelfUpdate { .. });
+
+    let cleanup_on_abort = matches!(
+        cli_opts.command,
+        Command::CreateProject {
+            cleanup_on_abort: true,
+            ..
+        }
+    );
+
+    let outcome = tokio::select! {
+        outcome = cosmo_cli::run_cmd(cli_opts.command, &mut api_server, Some(&plain_client), cli_opts.prompt) => Some(outcome),
+        _ = tokio::signal::ctrl_c() => None,
+    };
+
+    match outcome {
+        Some(Ok(cmd_output)) => {
+            tracing::debug!("Printing in {:?} mode", cli_opts.output_mode);
+            output::print_cmd_output(&*cmd_output, cli_opts.output_mode);
+            if notify_update {
+                update::notify(&api_server).await;
+            }
+            if let Some(code) = cmd_output.exit_code() {
+                code.exit()
+            }
+        }
+        Some(Err(e)) => {
+            cli::report_error(&e);
+            output::print_error(&e, cli_opts.output_mode);
+            tracing::error!("Invocation ID: {}", api_server.invocation_id());
+            exit_on_ctrl_c();
+            // The project was created fine, only its analysis didn't succeed
+            if e.downcast_ref::<cosmo_cli::WaitError>().is_none() {
+                cleanup_created_project(&mut api_server, cleanup_on_abort, cli_opts.prompt).await;
+            }
+            ExitCode::from_error(&e).exit()
+        }
+        None => {
+            tracing::warn!("Interrupted");
+            exit_on_ctrl_c();
+            cleanup_created_project(&mut api_server, cleanup_on_abort, cli_opts.prompt).await;
+            ExitCode::Interrupted.exit()
+        }
+    }
+    std::process::exit(0)
+}
+
+/// Check and read the configuration files, completing `cli_opts` with their
+/// settings, and select the profile. Exits on invalid options or settings.
+fn configure(cli_opts: &mut CosmoCliOpts) -> Profile {
+    if !cli_opts.allow_writable_config {
+        if let Err(e) = config::check_permissions() {
+            cli::report_error(&e);
+            ExitCode::Failure.exit()
+        }
+    }
+    // Fail early on an invalid configuration file
+    config::files();
+
+    // Given with --api-key-stdin or --api-key-file, in place of COSMO_API_KEY
+    match credentials::input(cli_opts.api_key_stdin, cli_opts.api_key_file.as_deref()) {
+        Ok(Some(api_key)) => cli_opts.api_key = Some(api_key),
+        Ok(None) => {}
+        Err(e) => {
+            cli::report_error(&e);
+            ExitCode::Usage.exit()
+        }
+    }
+
+    // Settings of the profile given with --profile or COSMO_PROFILE, else of
+    // the default one of the configuration file
+    let profile = match Profile::select(cli_opts.profile.take()) {
+        Ok(profile) => profile,
+        Err(e) => {
+            cli::report_error(&e);
+            ExitCode::Usage.exit()
+        }
+    };
+
+    // Settings given neither with a flag nor an environment variable come
+    // from the configuration files
+    for setting in config::SETTINGS {
+        if cli_opts
+            .settings
+            .iter()
+            .any(|given| given.key == setting.key)
+        {
+            continue;
+        }
+        if let Some((value, _)) = config::files().get(&profile.section(), setting.key) {
+            cli_opts.apply_setting(setting.key, value);
+        }
+    }
+
+    // Typos of the type are caught before uploading the whole firmware, the
+    // dry run reporting them with the other checks
+    if let Command::CreateProject {
+        fw_type: Some(fw_type),
+        fw_subtype,
+        allow_unknown_type,
+        print_hash_only: false,
+        dry_run: false,
+        ..
+    } = &mut cli_opts.command
+    {
+        match cli::parse_firmware(fw_type, fw_subtype) {
+            Ok((parsed_type, parsed_subtype)) => {
+                *fw_type = parsed_type.to_string();
+                *fw_subtype = parsed_subtype.to_string();
+            }
+            Err(e) if *allow_unknown_type => {
+                tracing::warn!("{}. Sending it as is", e)
+            }
+            Err(e) => {
+                cli::report_error(&anyhow::anyhow!(
+                    "{}. Pass --allow-unknown-type to send it anyway",
+                    e
+                ));
+                ExitCode::Usage.exit()
+            }
+        }
+    }
+
+    // Scripts get the single value of the commands having one
+    if cli_opts.quiet && matches!(cli_opts.output_mode, OutputMode::Table) {
+        cli_opts.output_mode = OutputMode::Value;
+    }
+
+    profile
+}
+
+/// Run the commands of the configuration and those needing no profile, none
+/// for the others.
+async fn run_config_cmd(
+    cli_opts: &CosmoCliOpts,
+    profile: &Profile,
+) -> Option<Result<Box<dyn CommandOutput>, anyhow::Error>> {
+    let outcome = match &cli_opts.command {
+        Command::Profile(action) => profile::command(action, profile),
+        Command::Config(action) => config::command(
+            action,
+            &profile.section(),
+            profile.name(),
+            &cli_opts.settings,
+        ),
+        Command::Setup => credentials::setup(
+            profile,
+            cli_opts.api_key.clone(),
+            cli_opts.api_server.clone(),
+            cli_opts.token_store,
+            cli_opts.encrypt_session,
+            cli_opts.prompt,
+        )
+        .map(|()| Box::new("Configuration complete") as Box<dyn CommandOutput>)
+        .context("error initializing the configuration"),
+        // Hashing a firmware doesn't need the api key
+        Command::CreateProject {
+            fw_filepath: Some(fw_filepath),
+            exclude,
+            reproducible,
+            print_hash_only: true,
+            ..
+        } => {
+            let options = ArchiveOptions {
+                exclude: exclude.clone(),
+                reproducible: *reproducible,
+            };
+            let sha256 = match cosmo_cli::archive_dir(fw_filepath, &options).await {
+                Ok(Some(archive)) => Ok(archive.sha256),
+                Ok(None) => api::file_sha256(Path::new(fw_filepath))
+                    .await
+                    .with_context(|| format!("error reading file {}", fw_filepath)),
+                Err(e) => Err(e),
+            };
+            sha256.map(|sha256| Box::new(format!("{sha256}  {fw_filepath}")) as _)
+        }
+        _ => return None,
+    };
+    Some(outcome)
+}
+
+/// Run the commands of `profile` needing neither the api key nor the server,
+/// none for the others.
+fn run_local_cmd(
+    command: &Command,
+    profile: &Profile,
+    token_store: TokenStore,
+) -> Option<Result<Box<dyn CommandOutput>, anyhow::Error>> {
+    let outcome = match command {
+        // Forgetting the api key doesn't need it, revoking it does
+        Command::Logout { revoke: false } => credentials::forget(profile, token_store)
+            .map(|forgotten| Box::new(credentials::logout_message(profile, false, forgotten)) as _),
+        Command::Cache(Cache::Clear) => profile
+            .required_response_cache()
+            .and_then(|cache| {
+                cache
+                    .clear()
+                    .with_context(|| format!("error removing {}", cache.dir().display()))
+            })
+            .map(|()| Box::new("Cache cleared") as _),
+        _ => return None,
+    };
+    Some(outcome)
+}
+
+/// Serve the cached responses, without api key nor http client.
+async fn run_offline(cli_opts: CosmoCliOpts, profile: &Profile) -> ! {
+    let mut api_server = match profile.required_response_cache() {
+        Ok(cache) => OfflineApiServer::new(cache),
+        Err(e) => {
+            cli::report_error(&e);
+            ExitCode::Failure.exit()
+        }
+    };
+
+    let outcome =
+        cosmo_cli::run_cmd(cli_opts.command, &mut api_server, None, cli_opts.prompt).await;
+    if let (Ok(_), Some(cached_at)) = (&outcome, api_server.cached_at()) {
+        tracing::warn!(
+            "Offline: showing the response cached on {}",
+            cached_at
+                .with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M:%S %Z")
+        );
     }
+    finish(outcome, cli_opts.output_mode)
+}
 
+/// Clients of the api server and of the downloads, with the api key of
+/// `cli_opts` or of `profile` and where it was read from. Exits when they
+/// can't be set up.
+async fn http_clients(
+    cli_opts: &mut CosmoCliOpts,
+    profile: &Profile,
+    token_store: TokenStore,
+) -> (HttpApiServer, PlainClient, Option<ApiKeySource>) {
     // Choose api key in the following order
     //
-    // 1. check if it's passed via command line argument
-    // 2. try read from configuration file
-    let api_key = match cli_opts.api_key {
+    // 1. check if it's passed via command line argument or COSMO_API_KEY
+    // 2. try read from the keyring or the configuration file
+    let mut stored_in = None;
+    let api_key = match cli_opts.api_key.take() {
         Some(ak) => ak,
-        None => match try_api_key_from_config_file() {
-            Ok(ak) => ak,
+        None => match credentials::stored(profile, token_store, cli_opts.prompt) {
+            Ok((ak, source)) => {
+                if let Command::Whoami { source: whoami } = &mut cli_opts.command {
+                    *whoami = Some(source);
+                }
+                stored_in = Some(source);
+                ak
+            }
+            // Versions and releases are asked without the api key
+            Err(e)
+                if matches!(
+                    cli_opts.command,
+                    Command::Version | Command::SelfUpdate { .. }
+                ) =>
+            {
+                tracing::debug!("No api key: {:#}", e);
+                String::new()
+            }
             Err(e) => {
                 let e = e.context("error reading api key from config file");
                 cli::report_error(&e);
-                println!("\nRun the 'setup' command to initialize the configuration");
-                std::process::exit(1)
+                println!("\nRun the 'setup' command to initialize the configuration, or pass the api key with --api-key or the COSMO_API_KEY environment variable");
+                ExitCode::Auth.exit()
             }
         },
     };
+    logging::redact(&api_key);
 
-    let mut api_server = HttpApiServer::new(cli_opts.api_server, api_key).await;
+    let api_server = match cli_opts.api_server.take() {
+        Some(api_server) => api_server,
+        None => match profile.api_server() {
+            Ok(api_server) => api_server,
+            Err(e) => {
+                cli::report_error(&e);
+                ExitCode::Usage.exit()
+            }
+        },
+    };
+    tracing::debug!("Using profile {} with {}", profile.name(), api_server);
 
-    // Run Command
-    match cosmo_cli::run_cmd(cli_opts.command, &mut api_server).await {
-        Ok(cmd_output) => {
-            log::debug!("Printing in {:?} mode", cli_opts.output_mode);
-            cli::print_cmd_output(&*cmd_output, cli_opts.output_mode)
-        }
+    let headers = match config::extra_headers(std::mem::take(&mut cli_opts.headers)) {
+        Ok(headers) => headers,
         Err(e) => {
             cli::report_error(&e);
-            std::process::exit(1)
+            ExitCode::Usage.exit()
         }
-    }
-}
+    };
 
-/// Setup the logger given the `LevelFilter`.
-fn setup_logger(filter: LevelFilter) {
-    env_logger::builder()
-        .format_timestamp(None)
-        .format_target(false)
-        .format_module_path(false)
-        .filter_level(filter)
-        .init()
-}
+    let options = HttpApiServerOptions {
+        timeout: cli_opts.timeout,
+        upload_timeout: cli_opts.upload_timeout,
+        upload_limit: cli_opts.upload_limit,
+        max_response_size: cli_opts.max_response_size,
+        retries: cli_opts.retries,
+        rate_limit_wait: cli_opts.rate_limit_wait,
+        proxy: cli_opts.proxy.take(),
+        ca_cert: cli_opts
+            .ca_cert
+            .take()
+            .or_else(|| profile.entry(CA_CERT_ENTRY).map(PathBuf::from)),
+        client_cert: cli_opts.client_cert.take(),
+        client_key: cli_opts.client_key.take(),
+        client_cert_password: cli_opts.client_cert_password.take(),
+        insecure: cli_opts.insecure,
+        progress: !cli_opts.quiet,
+        platform_user_agent: cli_opts.platform_user_agent,
+        headers,
+        override_headers: cli_opts.override_headers,
+        cache: if cli_opts.no_cache {
+            None
+        } else {
+            profile.response_cache()
+        },
+        ..Default::default()
+    };
 
-fn setup_config() -> Result<(), anyhow::Error> {
-    // Read api key from stdin
-    // TODO: hadle unwraps
-    let stdin = io::stdin();
-    let mut iterator = stdin.lock().lines();
-    print!("Insert your Api Key: ");
-    io::stdout().flush()?;
-    let api_key = iterator.next().unwrap().unwrap();
-
-    // Write config
-    let config_file_path = config_file_path();
-    fs::create_dir_all(
-        config_file_path
-            .parent()
-            .expect("config file should have a parent"),
-    )?;
-    let mut conf = Ini::new();
-    conf.with_section(Some(INI_CONFIG_SECTION))
-        .set(API_KEY_ENTRY, api_key);
-    conf.write_to_file(config_file_path)?;
-
-    Ok(())
-}
+    let plain_client = PlainClient::new(&options).unwrap_or_else(|e| {
+        let e = anyhow::Error::new(e).context("error initializing the http client");
+        cli::report_error(&e);
+        ExitCode::from_error(&e).exit()
+    });
+    let api_server = HttpApiServer::new(api_server, api_key, options)
+        .await
+        .unwrap_or_else(|e| {
+            let e = anyhow::Error::new(e).context("error initializing the http client");
+            cli::report_error(&e);
+            ExitCode::from_error(&e).exit()
+        });
 
-/// Get the token path depending on the operating system.
-/// Uses crate `dirs` to find OS cache directory.
-fn config_file_path() -> &'static Path {
-    const TOKEN_CACHE_DIR: &str = "cosmo-cli";
-    const TOKEN_CACHE_FILE: &str = "config";
+    (api_server, plain_client, stored_in)
+}
 
-    lazy_static! {
-        static ref TOKEN_CACHE_PATH: PathBuf = dirs::config_dir()
-            .map(|home_dir| home_dir.join(TOKEN_CACHE_DIR).join(TOKEN_CACHE_FILE))
-            .expect("Error constructing the path for the cache of the token");
+/// Revoke the api key in use, then remove it from `profile` even when the
+/// revocation failed.
+async fn logout(
+    api_server: &mut HttpApiServer,
+    profile: &Profile,
+    token_store: TokenStore,
+) -> Result<Box<dyn CommandOutput>, anyhow::Error> {
+    let revoked = api_server.apikey_delete().await;
+    if let Err(e) = &revoked {
+        tracing::warn!("Could not revoke the api key, removing it anyway: {}", e);
     }
+    let forgotten = credentials::forget(profile, token_store)?;
+    revoked.context("error revoking the api key")?;
 
-    &TOKEN_CACHE_PATH
+    Ok(Box::new(credentials::logout_message(
+        profile, true, forgotten,
+    )))
 }
 
-fn try_api_key_from_config_file() -> Result<String, anyhow::Error> {
-    let i = Ini::load_from_file(config_file_path())?;
+/// Print the output of a command and exit with its code, or report its
+/// error.
+fn finish(outcome: Result<Box<dyn CommandOutput>, anyhow::Error>, mode: OutputMode) -> ! {
+    match outcome {
+        Ok(cmd_output) => {
+            output::print_cmd_output(&*cmd_output, mode);
+            if let Some(code) = cmd_output.exit_code() {
+                code.exit()
+            }
+            std::process::exit(0)
+        }
+        Err(e) => {
+            cli::report_error(&e);
+            output::print_error(&e, mode);
+            ExitCode::from_error(&e).exit()
+        }
+    }
+}
 
-    let default_section = i
-        .section(Some(INI_CONFIG_SECTION))
-        .context("no default section found")?;
+/// Exit as soon as Ctrl-C is pressed, skipping any cleanup.
+fn exit_on_ctrl_c() {
+    tokio::spawn(async {
+        let _ = tokio::signal::ctrl_c().await;
+        ExitCode::Interrupted.exit()
+    });
+}
+
+/// Delete the project created by a `create` that was interrupted or failed
+/// afterwards, without asking when `cleanup` is set.
+async fn cleanup_created_project(api_server: &mut HttpApiServer, cleanup: bool, prompt: Prompt) {
+    let Some(project_id) = api_server.created_project() else {
+        return;
+    };
 
-    let api_key = default_section
-        .get(API_KEY_ENTRY)
-        .context("no api key entry found")?;
+    // Left in place when it can't be asked, the hint being enough
+    let question = format!("Delete the project {} just created?", project_id);
+    if !cleanup
+        && !prompt
+            .confirm(&question, "pass --cleanup-on-abort to delete it")
+            .unwrap_or(false)
+    {
+        eprintln!("Delete it with `cosmo delete --id {}`", project_id);
+        return;
+    }
 
-    Ok(api_key.to_string())
+    match api_server.delete(&project_id).await {
+        Ok(()) => tracing::info!("Project {} deleted", project_id),
+        Err(e) => {
+            tracing::error!("Error deleting project {}: {}", project_id, e);
+            eprintln!("Delete it with `cosmo delete --id {}`", project_id);
+        }
+    }
 }
@@ -0,0 +1,450 @@
+use std::{
+    ffi::OsStr,
+    io::{IsTerminal, Write},
+};
+
+use clap::{
+    builder::{EnumValueParser, PossibleValue, TypedValueParser},
+    ValueEnum,
+};
+use comfy_table::{ColumnConstraint, ContentArrangement, Table, Width};
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::{
+    cli::{CsvOptions, ExitCode},
+    style,
+};
+
+#[derive(Debug, Clone, ValueEnum)]
+pub enum OutputMode {
+    /// Tables and messages for humans
+    #[value(alias = "text")]
+    Table,
+    /// Field names stable between releases
+    Json,
+    Yaml,
+    /// One JSON object per line, the lists printed as they are received
+    Ndjson,
+    /// SARIF 2.1.0 log of the findings, for the analysis and gate commands
+    Sarif,
+    /// JUnit XML of the findings as test cases, for the analysis and gate commands
+    Junit,
+    /// CSV of the projects or of the results of an analysis, for the list and analysis commands
+    Csv,
+    /// Line of each project of the list command, given as `template=<TEMPLATE>`
+    #[value(skip)]
+    Template(String),
+    /// Single value of the create, apikey create and status commands, the
+    /// table otherwise, set by `--quiet` with the table mode
+    #[value(skip)]
+    Value,
+}
+
+/// Parser of `--output`, the modes or `template=<TEMPLATE>`.
+#[derive(Debug, Clone)]
+pub struct OutputModeParser;
+
+impl TypedValueParser for OutputModeParser {
+    type Value = OutputMode;
+
+    fn parse_ref(
+        &self,
+        cmd: &clap::Command,
+        arg: Option<&clap::Arg>,
+        value: &OsStr,
+    ) -> Result<Self::Value, clap::Error> {
+        match value
+            .to_str()
+            .and_then(|value| value.strip_prefix("template="))
+        {
+            Some(template) => Ok(OutputMode::Template(template.to_string())),
+            None => EnumValueParser::<OutputMode>::new().parse_ref(cmd, arg, value),
+        }
+    }
+
+    fn possible_values(&self) -> Option<Box<dyn Iterator<Item = PossibleValue> + '_>> {
+        let template = PossibleValue::new("template=<TEMPLATE>")
+            .help("Line of each project of the list command, e.g. template='{{.id}} {{.name}}'");
+        Some(Box::new(
+            OutputMode::value_variants()
+                .iter()
+                .filter_map(ValueEnum::to_possible_value)
+                .chain([template]),
+        ))
+    }
+}
+
+/// Template of a line of output, with the fields of a serialized struct as
+/// `{{.field}}`.
+#[derive(Debug, Clone)]
+pub struct Template(Vec<TemplatePart>);
+
+#[derive(Debug, Clone)]
+enum TemplatePart {
+    Text(String),
+    Field(String),
+}
+
+impl Template {
+    /// Parse `template`, whose fields have to be among `fields`.
+    pub fn parse(template: &str, fields: &[&str]) -> Result<Self, String> {
+        let mut parts = vec![];
+        let mut rest = template;
+        while let Some(start) = rest.find("{{") {
+            if start > 0 {
+                parts.push(TemplatePart::Text(rest[..start].to_string()));
+            }
+            let end = rest[start..]
+                .find("}}")
+                .ok_or_else(|| format!("unclosed {{{{ in template: {}", template))?;
+            let action = rest[start + 2..start + end].trim();
+            let field = action.strip_prefix('.').ok_or_else(|| {
+                format!(
+                    "invalid {{{{{}}}}} in template, fields are written {{{{.field}}}}",
+                    action
+                )
+            })?;
+            if !fields.contains(&field) {
+                return Err(format!(
+                    "unknown field {} in template, valid fields: {}",
+                    field,
+                    fields.join(", ")
+                ));
+            }
+            parts.push(TemplatePart::Field(field.to_string()));
+            rest = &rest[start + end + 2..];
+        }
+        if !rest.is_empty() {
+            parts.push(TemplatePart::Text(rest.to_string()));
+        }
+        Ok(Self(parts))
+    }
+
+    /// Render the template with the fields of `value`, missing and null ones
+    /// empty and lists joined with commas.
+    pub fn render(&self, value: &Value) -> String {
+        fn field(value: &Value) -> String {
+            match value {
+                Value::Null => String::new(),
+                Value::String(s) => s.clone(),
+                Value::Array(items) => items.iter().map(field).collect::<Vec<_>>().join(","),
+                value => value.to_string(),
+            }
+        }
+
+        self.0
+            .iter()
+            .map(|part| match part {
+                TemplatePart::Text(text) => text.clone(),
+                TemplatePart::Field(name) => value.get(name).map(field).unwrap_or_default(),
+            })
+            .collect()
+    }
+}
+
+pub fn print_cmd_output<T: CommandOutput + ?Sized>(cmd_output: &T, mode: OutputMode) {
+    let output = match mode {
+        OutputMode::Table => cmd_output.text(),
+        OutputMode::Json => cmd_output.json(),
+        OutputMode::Yaml => cmd_output.yaml(),
+        OutputMode::Sarif => cmd_output.sarif().unwrap_or_else(|| cmd_output.json()),
+        OutputMode::Junit => cmd_output.junit().unwrap_or_else(|| cmd_output.json()),
+        OutputMode::Csv => cmd_output.csv().unwrap_or_else(|| cmd_output.json()),
+        OutputMode::Ndjson => cmd_output.ndjson(),
+        OutputMode::Template(_) => cmd_output.text(),
+        OutputMode::Value => cmd_output.value().unwrap_or_else(|| cmd_output.text()),
+    };
+    // Streamed outputs were printed already
+    if output.is_empty() && matches!(mode, OutputMode::Ndjson) {
+        return;
+    }
+    println!("{output}")
+}
+
+/// Print the error ending an NDJSON output as its last line.
+pub fn print_error(e: &anyhow::Error, mode: OutputMode) {
+    if let OutputMode::Ndjson = mode {
+        print_ndjson(&serde_json::json!({ "error": format!("{:#}", e) }));
+    }
+}
+
+/// Print `value` as a line of NDJSON, flushed for the readers to see it at
+/// once.
+pub fn print_ndjson(value: &Value) {
+    let mut stdout = std::io::stdout().lock();
+    let _ = writeln!(stdout, "{}", value);
+    let _ = stdout.flush();
+}
+
+/// Output of a command in the output modes.
+pub trait CommandOutput {
+    fn text(&self) -> String;
+    fn json(&self) -> String;
+
+    /// The JSON output as YAML, as is when not JSON.
+    fn yaml(&self) -> String {
+        match serde_json::from_str(&self.json()) {
+            Ok(value) => yaml(&value),
+            Err(_) => self.json(),
+        }
+    }
+
+    /// The JSON output with one line per item of a list.
+    fn ndjson(&self) -> String {
+        match serde_json::from_str(&self.json()) {
+            Ok(Value::Array(items)) => items
+                .iter()
+                .map(Value::to_string)
+                .collect::<Vec<_>>()
+                .join("\n"),
+            _ => self.json(),
+        }
+    }
+
+    /// SARIF log of the findings in the output, if any.
+    fn sarif(&self) -> Option<String> {
+        None
+    }
+
+    /// JUnit XML of the findings in the output, if any.
+    fn junit(&self) -> Option<String> {
+        None
+    }
+
+    /// CSV of the records in the output, if any.
+    fn csv(&self) -> Option<String> {
+        None
+    }
+
+    /// Single value of the output printed with `--quiet`, for scripts, if
+    /// any.
+    fn value(&self) -> Option<String> {
+        None
+    }
+
+    /// Exit code of a command whose output reports a failure, e.g. a
+    /// violated policy.
+    fn exit_code(&self) -> Option<ExitCode> {
+        None
+    }
+}
+
+impl CommandOutput for &str {
+    fn text(&self) -> String {
+        self.to_string()
+    }
+
+    fn json(&self) -> String {
+        serde_json::json!({
+            "msg": self,
+        })
+        .to_string()
+    }
+}
+
+impl CommandOutput for String {
+    fn text(&self) -> String {
+        self.to_owned()
+    }
+
+    fn json(&self) -> String {
+        serde_json::json!({
+            "msg": self,
+        })
+        .to_string()
+    }
+}
+
+impl CommandOutput for () {
+    fn text(&self) -> String {
+        String::new()
+    }
+
+    fn json(&self) -> String {
+        String::new()
+    }
+}
+
+/// Record of the CSV output, one row of documented columns.
+pub trait CsvRecord {
+    /// Names of the columns
+    const HEADER: &'static [&'static str];
+
+    /// Fields of the row, list values joined with `list_delimiter`.
+    fn record(&self, list_delimiter: &str) -> Vec<String>;
+}
+
+/// CSV of `records`, quoted as of RFC 4180, with a `project_id` column last when
+/// `project_id` is given.
+pub fn csv<T: CsvRecord>(records: &[T], project_id: Option<Uuid>, options: &CsvOptions) -> String {
+    let project_id = project_id.map(|project_id| project_id.to_string());
+
+    let mut header: Vec<String> = T::HEADER.iter().map(|name| name.to_string()).collect();
+    header.extend(project_id.as_ref().map(|_| "project_id".to_string()));
+    let rows = records.iter().map(|record| {
+        let mut row = record.record(&options.list_delimiter);
+        row.extend(project_id.clone());
+        row
+    });
+
+    csv_rows(header, rows, options)
+}
+
+/// CSV of `rows` under `header`, quoted as of RFC 4180.
+pub fn csv_rows(
+    header: Vec<String>,
+    rows: impl IntoIterator<Item = Vec<String>>,
+    options: &CsvOptions,
+) -> String {
+    let header = Some(header).filter(|_| !options.no_header);
+    header
+        .into_iter()
+        .chain(rows)
+        .map(|row| {
+            row.iter()
+                .map(|field| csv_field(field))
+                .collect::<Vec<_>>()
+                .join(",")
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Quote `field` if it contains a comma, a quote or a line break, doubling
+/// its quotes.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Render `table` to fit the width of the terminal when printed to one,
+/// never wrapped otherwise, styled as set by `--color` and `--ascii`.
+///
+/// Cells are wrapped between words only, a column being at least as wide
+/// as its longest word.
+pub fn render(mut table: Table) -> String {
+    style::table(&mut table);
+    if !std::io::stdout().is_terminal() {
+        return table.to_string();
+    }
+
+    let mut widths: Vec<u16> = vec![];
+    for row in table.row_iter() {
+        for (i, cell) in row.cell_iter().enumerate() {
+            let longest = cell
+                .content()
+                .split_whitespace()
+                .map(|word| word.chars().count())
+                .max()
+                .unwrap_or(0);
+            if i >= widths.len() {
+                widths.resize(i + 1, 0);
+            }
+            widths[i] = widths[i].max(u16::try_from(longest).unwrap_or(u16::MAX));
+        }
+    }
+
+    table.set_content_arrangement(ContentArrangement::Dynamic);
+    for (column, width) in table.column_iter_mut().zip(widths) {
+        // The width includes the padding of the cells
+        let width = width.saturating_add(column.padding_width());
+        column.set_constraint(ColumnConstraint::LowerBoundary(Width::Fixed(width)));
+    }
+    table.to_string()
+}
+
+/// YAML of `value`, in block style.
+pub fn yaml(value: &Value) -> String {
+    let mut out = String::new();
+    match value {
+        Value::Object(map) if !map.is_empty() => write_yaml(&mut out, value, 0),
+        Value::Array(items) if !items.is_empty() => write_yaml(&mut out, value, 0),
+        scalar => out.push_str(&yaml_scalar(scalar)),
+    }
+    out.trim_end().to_string()
+}
+
+/// Write the entries of the non empty collection `value`, one per line
+/// indented by `indent`.
+fn write_yaml(out: &mut String, value: &Value, indent: usize) {
+    let pad = " ".repeat(indent);
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                out.push_str(&format!("{}{}:", pad, yaml_string(key)));
+                write_yaml_child(out, value, indent + 2);
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                match item {
+                    // The first entry on the line of the dash, e.g. `- name: x`
+                    Value::Object(map) if !map.is_empty() => {
+                        let mut nested = String::new();
+                        write_yaml(&mut nested, item, indent + 2);
+                        out.push_str(&format!("{}- {}", pad, &nested[indent + 2..]));
+                    }
+                    Value::Array(items) if !items.is_empty() => {
+                        let mut nested = String::new();
+                        write_yaml(&mut nested, item, indent + 2);
+                        out.push_str(&format!("{}- {}", pad, &nested[indent + 2..]));
+                    }
+                    scalar => out.push_str(&format!("{}- {}\n", pad, yaml_scalar(scalar))),
+                }
+            }
+        }
+        scalar => out.push_str(&format!("{}{}\n", pad, yaml_scalar(scalar))),
+    }
+}
+
+/// Write the value of a mapping entry, on the line of its key if a scalar.
+fn write_yaml_child(out: &mut String, value: &Value, indent: usize) {
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            out.push('\n');
+            write_yaml(out, value, indent);
+        }
+        Value::Array(items) if !items.is_empty() => {
+            out.push('\n');
+            write_yaml(out, value, indent);
+        }
+        scalar => out.push_str(&format!(" {}\n", yaml_scalar(scalar))),
+    }
+}
+
+fn yaml_scalar(value: &Value) -> String {
+    match value {
+        Value::Null => "null".to_string(),
+        Value::Bool(b) => b.to_string(),
+        Value::Number(n) => n.to_string(),
+        Value::String(s) => yaml_string(s),
+        Value::Array(_) => "[]".to_string(),
+        Value::Object(_) => "{}".to_string(),
+    }
+}
+
+/// `s` plain when it can't be taken for anything else, double quoted
+/// otherwise.
+fn yaml_string(s: &str) -> String {
+    const RESERVED: [&str; 11] = [
+        "true", "false", "null", "yes", "no", "on", "off", "y", "n", "~", "",
+    ];
+
+    let plain = s.starts_with(|c: char| c.is_ascii_alphabetic() || matches!(c, '/' | '_'))
+        && s.chars().all(|c| {
+            c.is_ascii_alphanumeric() || matches!(c, ' ' | '_' | '-' | '.' | '/' | ':' | '@' | '+')
+        })
+        && !s.ends_with([' ', ':'])
+        && !s.contains(": ")
+        && !RESERVED.contains(&s.to_ascii_lowercase().as_str());
+
+    if plain {
+        s.to_string()
+    } else {
+        serde_json::to_string(s).expect("string serialization")
+    }
+}
@@ -0,0 +1,269 @@
+use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
+use serde::Serialize;
+use url::Url;
+
+#[cfg(feature = "keyring")]
+use crate::keyring;
+use crate::{
+    api::ResponseCache,
+    cli::{self, ApiKeySource, TokenStore},
+    config::{self, DEFAULT_SECTION, PROFILE_SECTION_PREFIX},
+    output::CommandOutput,
+    COSMO_API_SERVER,
+};
+
+/// Entries of the sections of the profiles
+pub(crate) const API_KEY_ENTRY: &str = "api_key";
+pub(crate) const API_SERVER_ENTRY: &str = "api_server";
+pub const CA_CERT_ENTRY: &str = "ca_cert";
+pub(crate) const TOKEN_STORE_ENTRY: &str = "token_store";
+const DEFAULT_PROFILE_ENTRY: &str = "profile";
+const ORGANIZATION_ENTRY: &str = "organization";
+
+/// Profile of the configuration file: the `default` section, or a
+/// `profile.<name>` section with its own server, api key and cache.
+#[derive(Debug, Clone)]
+pub struct Profile {
+    /// None for the `default` section
+    name: Option<String>,
+}
+
+impl Profile {
+    /// Profile given with --profile or COSMO_PROFILE, else the one set as
+    /// default in the configuration file.
+    pub fn select(name: Option<String>) -> Result<Self> {
+        let name = match name {
+            Some(name) => name,
+            None => match config::entry(DEFAULT_SECTION, DEFAULT_PROFILE_ENTRY) {
+                Some(name) => cli::parse_profile_name(&name)
+                    .map_err(anyhow::Error::msg)
+                    .context("invalid default profile in config file")?,
+                None => return Ok(Self { name: None }),
+            },
+        };
+        Ok(Self::named(name))
+    }
+
+    pub fn named(name: String) -> Self {
+        Self {
+            name: (name != DEFAULT_SECTION).then_some(name),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        self.name.as_deref().unwrap_or(DEFAULT_SECTION)
+    }
+
+    pub fn section(&self) -> String {
+        match &self.name {
+            Some(name) => format!("{}{}", PROFILE_SECTION_PREFIX, name),
+            None => DEFAULT_SECTION.to_string(),
+        }
+    }
+
+    /// Whether the configuration file has the profile, the default one
+    /// always existing.
+    pub fn exists(&self) -> bool {
+        self.name.is_none() || config::files().has_section(&self.section())
+    }
+
+    pub fn entry(&self, key: &str) -> Option<String> {
+        config::entry(&self.section(), key)
+    }
+
+    /// Server of the profile, the production one when not set.
+    pub fn api_server(&self) -> Result<Url> {
+        let api_server = self
+            .entry(API_SERVER_ENTRY)
+            .unwrap_or_else(|| COSMO_API_SERVER.to_string());
+        Url::parse(&api_server)
+            .with_context(|| format!("invalid api_server of profile {}", self.name()))
+    }
+
+    pub fn token_store(&self) -> Result<TokenStore> {
+        match self.entry(TOKEN_STORE_ENTRY) {
+            Some(store) => TokenStore::from_str(&store, true)
+                .map_err(anyhow::Error::msg)
+                .with_context(|| format!("invalid token_store of profile {}", self.name())),
+            None => Ok(TokenStore::default()),
+        }
+    }
+
+    /// Account of the api key in the keyring.
+    #[cfg(feature = "keyring")]
+    pub fn keyring_account(&self) -> String {
+        match &self.name {
+            Some(name) => format!("{}.{}", API_KEY_ENTRY, name),
+            None => API_KEY_ENTRY.to_string(),
+        }
+    }
+
+    pub fn response_cache(&self) -> Option<ResponseCache> {
+        match &self.name {
+            Some(name) => ResponseCache::for_profile(name),
+            None => ResponseCache::new(),
+        }
+    }
+
+    /// Cache of the api responses of the profile, failing without a cache
+    /// directory.
+    pub fn required_response_cache(&self) -> Result<ResponseCache> {
+        self.response_cache().context("no cache directory found")
+    }
+
+    fn settings(&self, default: &str) -> Result<ProfileSettings> {
+        let token_store = self.token_store()?;
+        #[cfg(feature = "keyring")]
+        let in_keyring = token_store == TokenStore::Keyring
+            && keyring::get(&self.keyring_account()).is_ok_and(|api_key| api_key.is_some());
+        #[cfg(not(feature = "keyring"))]
+        let in_keyring = false;
+
+        Ok(ProfileSettings {
+            name: self.name().to_string(),
+            default: self.name() == default,
+            api_server: self.api_server()?.to_string(),
+            ca_cert: self.entry(CA_CERT_ENTRY),
+            organization: self.entry(ORGANIZATION_ENTRY),
+            token_store,
+            api_key_saved_in: if in_keyring {
+                Some(ApiKeySource::Keyring)
+            } else if self.entry(API_KEY_ENTRY).is_some() {
+                Some(ApiKeySource::ConfigFile)
+            } else {
+                None
+            },
+        })
+    }
+}
+
+/// Settings of a profile, as shown by `profile list` and `profile show`.
+#[derive(Debug, Serialize)]
+struct ProfileSettings {
+    name: String,
+    /// Whether the profile is used without --profile nor COSMO_PROFILE
+    default: bool,
+    api_server: String,
+    ca_cert: Option<String>,
+    organization: Option<String>,
+    token_store: TokenStore,
+    api_key_saved_in: Option<ApiKeySource>,
+}
+
+impl CommandOutput for ProfileSettings {
+    fn text(&self) -> String {
+        let mut lines = vec![
+            format!(
+                "Profile: {}{}",
+                self.name,
+                if self.default { " (default)" } else { "" }
+            ),
+            format!("Server: {}", self.api_server),
+        ];
+        if let Some(ca_cert) = &self.ca_cert {
+            lines.push(format!("CA certificate: {}", ca_cert));
+        }
+        if let Some(organization) = &self.organization {
+            lines.push(format!("Organization: {}", organization));
+        }
+        lines.push(format!("Token store: {}", self.token_store));
+        lines.push(format!(
+            "Api key: {}",
+            match self.api_key_saved_in {
+                Some(ApiKeySource::Keyring) => "saved in the keyring",
+                Some(_) => "saved in the configuration file",
+                None => "not saved",
+            }
+        ));
+        lines.join("\n")
+    }
+
+    fn json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+
+struct ProfileList(Vec<ProfileSettings>);
+
+impl CommandOutput for ProfileList {
+    fn text(&self) -> String {
+        let width = self
+            .0
+            .iter()
+            .map(|profile| profile.name.len())
+            .max()
+            .unwrap_or_default();
+        self.0
+            .iter()
+            .map(|profile| {
+                format!(
+                    "{} {:width$}  {}",
+                    if profile.default { "*" } else { " " },
+                    profile.name,
+                    profile.api_server
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn json(&self) -> String {
+        serde_json::to_string(&self.0).unwrap()
+    }
+}
+
+/// Run a `profile` subcommand, `profile` being the one in use.
+pub fn command(action: &cli::Profile, profile: &Profile) -> Result<Box<dyn CommandOutput>> {
+    let default =
+        config::entry(DEFAULT_SECTION, DEFAULT_PROFILE_ENTRY).unwrap_or(DEFAULT_SECTION.into());
+
+    match action {
+        cli::Profile::List => {
+            let conf = config::load_user_file()?;
+            let mut profiles = vec![Profile { name: None }.settings(&default)?];
+            for section in conf.sections().flatten() {
+                if let Some(name) = section.strip_prefix(PROFILE_SECTION_PREFIX) {
+                    profiles.push(Profile::named(name.to_string()).settings(&default)?);
+                }
+            }
+            Ok(Box::new(ProfileList(profiles)))
+        }
+        cli::Profile::Show { name } => {
+            let profile = match name {
+                Some(name) => Profile::named(name.clone()),
+                None => profile.clone(),
+            };
+            if !profile.exists() {
+                bail!("profile {} not found", profile.name());
+            }
+            Ok(Box::new(profile.settings(&default)?))
+        }
+        cli::Profile::SetDefault { name } => {
+            let profile = Profile::named(name.clone());
+            if !profile.exists() {
+                bail!(
+                    "profile {} not found, create it with `cosmo --profile {} setup`",
+                    profile.name(),
+                    profile.name()
+                );
+            }
+
+            let mut conf = config::load_user_file()?;
+            let section = conf
+                .entry(Some(DEFAULT_SECTION.to_string()))
+                .or_insert_with(Default::default);
+            match profile.name {
+                Some(_) => section.insert(DEFAULT_PROFILE_ENTRY, profile.name()),
+                None => {
+                    section.remove(DEFAULT_PROFILE_ENTRY);
+                }
+            }
+            config::write_user_file(&conf)?;
+            Ok(Box::new(format!(
+                "Default profile set to {}",
+                profile.name()
+            )))
+        }
+    }
+}
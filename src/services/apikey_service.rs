@@ -1,21 +1,100 @@
-use anyhow::Result;
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
 use chrono::{DateTime, Utc};
+use comfy_table::{Cell, Row, Table};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use crate::api::ApiServer;
+use crate::{api::ApiServer, cli::ApiKeySource, output};
 
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ApiKeyData {
+    /// Id of the key, when the server tells it apart from the key itself
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<Uuid>,
     pub api_key: Uuid,
     pub creation_date: DateTime<Utc>,
+    /// Last request authenticated with the key, when the server tracks it
+    #[serde(
+        default,
+        alias = "lastUsedDate",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub last_used: Option<DateTime<Utc>>,
+    #[serde(default, alias = "expiryDate", skip_serializing_if = "Option::is_none")]
+    pub expiration_date: Option<DateTime<Utc>>,
 }
 
-//List API key
-pub async fn list<U: ApiServer>(api_server: &mut U) -> Result<Option<ApiKeyData>> {
-    let ak = api_server.apikey_list().await?;
-    Ok(ak)
+impl ApiKeyData {
+    /// Id of the key, the key itself unless the server gives another one.
+    pub fn id(&self) -> Uuid {
+        self.id.unwrap_or(self.api_key)
+    }
+}
+
+/// Api key of `apikey --action list`, the key itself masked unless asked
+/// otherwise.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListedApiKey {
+    pub api_key: String,
+    pub creation_date: DateTime<Utc>,
+    pub last_used: Option<DateTime<Utc>>,
+    pub expiration_date: Option<DateTime<Utc>>,
+}
+
+impl ListedApiKey {
+    pub fn get_table_from_list(list: &[ListedApiKey]) -> String {
+        let date = |date: Option<DateTime<Utc>>| {
+            date.map(|date| date.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+                .unwrap_or_else(|| "-".to_string())
+        };
+
+        let mut table = Table::new();
+        table.set_header(Row::from(vec![
+            Cell::new("API KEY"),
+            Cell::new("CREATED"),
+            Cell::new("LAST USED"),
+            Cell::new("EXPIRES"),
+        ]));
+        for api_key in list {
+            table.add_row(Row::from(vec![
+                Cell::new(&api_key.api_key),
+                Cell::new(date(Some(api_key.creation_date))),
+                Cell::new(date(api_key.last_used)),
+                Cell::new(date(api_key.expiration_date)),
+            ]));
+        }
+
+        output::render(table)
+    }
+}
+
+//List API keys, showing the keys themselves only with `show_secret`
+pub async fn list<U: ApiServer>(
+    api_server: &mut U,
+    show_secret: bool,
+) -> Result<Vec<ListedApiKey>> {
+    let aks = api_server.apikey_list().await?;
+    Ok(aks
+        .into_iter()
+        .map(|ak| ListedApiKey {
+            api_key: if show_secret {
+                ak.api_key.to_string()
+            } else {
+                mask(&ak.api_key.to_string())
+            },
+            creation_date: ak.creation_date,
+            last_used: ak.last_used,
+            expiration_date: ak.expiration_date,
+        })
+        .collect())
 }
 
 // Delete an API key
@@ -30,3 +109,164 @@ pub async fn create<U: ApiServer>(api_server: &mut U) -> Result<ApiKeyData> {
 
     Ok(ak)
 }
+
+/// Api key replacing the previous one.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RotatedApiKey {
+    /// None when written to `file`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<Uuid>,
+    pub creation_date: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<PathBuf>,
+    /// Where the previous key was read from and the new one saved, the
+    /// configuration file or the keyring
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub saved_in: Option<ApiKeySource>,
+}
+
+// Replace the API key, saving the new one with `save` in place of the
+// previous one and writing it to `out` if given. `save` returns where the key
+// was saved, none when it was given on the command line or the environment
+pub async fn rotate<U: ApiServer>(
+    api_server: &mut U,
+    out: Option<&Path>,
+    save: impl FnOnce(&str) -> Result<Option<ApiKeySource>>,
+) -> Result<RotatedApiKey> {
+    let ak = api_server
+        .apikey_rotate()
+        .await
+        .context("error rotating the api key")?;
+
+    let mut rotated = RotatedApiKey {
+        api_key: Some(ak.api_key),
+        creation_date: ak.creation_date,
+        file: None,
+        saved_in: None,
+    };
+    // Shown rather than lost when it can't be saved or written
+    match save(&ak.api_key.to_string()) {
+        Ok(saved_in) => rotated.saved_in = saved_in,
+        Err(err) => tracing::error!(
+            "Error saving the new api key: {:#}, save it with `cosmo setup`",
+            err
+        ),
+    }
+    if let Some(out) = out {
+        match write_secret(out, &ak.api_key.to_string()) {
+            Ok(()) => {
+                rotated.api_key = None;
+                rotated.file = Some(out.to_path_buf());
+            }
+            Err(err) => tracing::error!(
+                "Error writing the new api key to {}: {}, showing it instead",
+                out.display(),
+                err
+            ),
+        }
+    }
+    Ok(rotated)
+}
+
+/// Write `secret` to `path`, readable by the user only.
+fn write_secret(path: &Path, secret: &str) -> std::io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(path)?;
+    // The mode only applies to new files
+    #[cfg(unix)]
+    file.set_permissions(std::os::unix::fs::PermissionsExt::from_mode(0o600))?;
+    file.write_all(format!("{}\n", secret).as_bytes())
+}
+
+/// Identity the server knows the api key in use by.
+#[derive(Debug, Serialize)]
+pub struct Identity {
+    pub server: String,
+    pub api_key_source: Option<ApiKeySource>,
+    /// The key with all but its last characters masked
+    pub api_key: Option<String>,
+    pub api_key_created: Option<DateTime<Utc>>,
+    pub organizations: Vec<IdentityOrganization>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct IdentityOrganization {
+    pub id: Uuid,
+    pub name: String,
+}
+
+impl Identity {
+    pub fn get_text_output(&self) -> String {
+        let source = match self.api_key_source {
+            Some(ApiKeySource::Flag) => " (--api-key)",
+            Some(ApiKeySource::Env) => " (COSMO_API_KEY)",
+            Some(ApiKeySource::Stdin) => " (stdin)",
+            Some(ApiKeySource::File) => " (--api-key-file)",
+            Some(ApiKeySource::ConfigFile) => " (config file)",
+            Some(ApiKeySource::Keyring) => " (keyring)",
+            None => "",
+        };
+        let organizations = self
+            .organizations
+            .iter()
+            .map(|organization| organization.name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        format!(
+            "Server: {}\nApi key: {}{}\nCreated: {}\nOrganizations: {}",
+            self.server,
+            self.api_key.as_deref().unwrap_or("-"),
+            source,
+            self.api_key_created
+                .map(|created| created.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+                .unwrap_or_else(|| "-".to_string()),
+            if organizations.is_empty() {
+                "-"
+            } else {
+                &organizations
+            }
+        )
+    }
+}
+
+// Identity of the api key in use, read from `source`
+pub async fn whoami<U: ApiServer>(
+    api_server: &mut U,
+    source: Option<ApiKeySource>,
+) -> Result<Identity> {
+    // The first key when the server lists several
+    let api_key = api_server.apikey_list().await?.into_iter().next();
+    let organizations = api_server
+        .organization_list()
+        .await?
+        .into_iter()
+        .map(|organization| IdentityOrganization {
+            id: organization.id,
+            name: organization.name,
+        })
+        .collect();
+
+    Ok(Identity {
+        server: api_server.address().to_string(),
+        api_key_source: source,
+        api_key: api_key
+            .as_ref()
+            .map(|api_key| mask(&api_key.api_key.to_string())),
+        api_key_created: api_key.map(|api_key| api_key.creation_date),
+        organizations,
+    })
+}
+
+/// `secret` with all but its last 4 characters masked.
+fn mask(secret: &str) -> String {
+    let visible = secret.len().saturating_sub(4);
+    secret
+        .char_indices()
+        .map(|(i, c)| if i < visible && c != '-' { '*' } else { c })
+        .collect()
+}
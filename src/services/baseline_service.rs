@@ -0,0 +1,141 @@
+use std::path::Path;
+
+use anyhow::{anyhow, bail, Result};
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::services::project_service::LinuxCveCheckAnalysis;
+
+/// Version of the baseline file format
+const BASELINE_VERSION: u32 = 1;
+
+/// CVEs accepted by a security review, suppressed from the analysis output
+/// and the gate thresholds until their expiry date.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Baseline {
+    pub version: u32,
+    /// Project the baseline was written from
+    #[serde(default)]
+    pub project_id: Option<Uuid>,
+    #[serde(default)]
+    pub created: Option<DateTime<Utc>>,
+    pub findings: Vec<BaselineEntry>,
+}
+
+/// Accepted CVE of a package.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BaselineEntry {
+    pub cve: String,
+    pub package: String,
+    /// Why the CVE was accepted, filled in by the reviewers
+    #[serde(default)]
+    pub justification: String,
+    /// Last day of the suppression, forever when none
+    #[serde(default)]
+    pub expires: Option<NaiveDate>,
+}
+
+/// Suppression of a CVE by a baseline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Suppression {
+    /// Not in the baseline
+    None,
+    Suppressed,
+    /// In the baseline but past its expiry date, counted again
+    Expired,
+}
+
+impl BaselineEntry {
+    fn matches(&self, cve: &LinuxCveCheckAnalysis) -> bool {
+        self.cve.eq_ignore_ascii_case(&cve.cveid) && self.package.eq_ignore_ascii_case(&cve.product)
+    }
+
+    fn expired(&self, today: NaiveDate) -> bool {
+        self.expires.is_some_and(|expires| expires < today)
+    }
+}
+
+impl Baseline {
+    pub fn load(path: &Path) -> Result<Self> {
+        let data = std::fs::read(path)
+            .map_err(|err| anyhow!("error reading baseline {}: {}", path.display(), err))?;
+        let baseline: Self = serde_json::from_slice(&data)
+            .map_err(|err| anyhow!("invalid baseline {}: {}", path.display(), err))?;
+        if baseline.version != BASELINE_VERSION {
+            bail!(
+                "unsupported version {} of baseline {}, expected {}",
+                baseline.version,
+                path.display(),
+                BASELINE_VERSION
+            );
+        }
+        Ok(baseline)
+    }
+
+    /// Baseline of the `cves` of a project, keeping the justification and
+    /// expiry date of the CVEs already in `previous`.
+    pub fn new(project_id: Uuid, cves: &[LinuxCveCheckAnalysis], previous: Option<&Self>) -> Self {
+        let mut findings: Vec<BaselineEntry> = vec![];
+        for cve in cves {
+            if findings.iter().any(|entry| entry.matches(cve)) {
+                continue;
+            }
+            let entry = previous
+                .and_then(|previous| previous.findings.iter().find(|entry| entry.matches(cve)))
+                .cloned()
+                .unwrap_or_else(|| BaselineEntry {
+                    cve: cve.cveid.clone(),
+                    package: cve.product.clone(),
+                    justification: String::new(),
+                    expires: None,
+                });
+            findings.push(entry);
+        }
+
+        Self {
+            version: BASELINE_VERSION,
+            project_id: Some(project_id),
+            created: Some(Utc::now()),
+            findings,
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let data = serde_json::to_string_pretty(self)? + "\n";
+        std::fs::write(path, data).map_err(|err| {
+            anyhow!(
+                "Error writing data to file: {}. Reason: {}",
+                path.display(),
+                err
+            )
+        })
+    }
+
+    /// Suppression of `cve` on `today`.
+    pub fn suppression(&self, cve: &LinuxCveCheckAnalysis, today: NaiveDate) -> Suppression {
+        match self.entry(cve) {
+            Some(entry) if entry.expired(today) => Suppression::Expired,
+            Some(_) => Suppression::Suppressed,
+            None => Suppression::None,
+        }
+    }
+
+    /// Entry of `cve`, if any.
+    pub fn entry(&self, cve: &LinuxCveCheckAnalysis) -> Option<&BaselineEntry> {
+        self.findings.iter().find(|entry| entry.matches(cve))
+    }
+}
+
+/// Line calling out the expired suppression `entry`.
+pub fn expired_message(entry: &BaselineEntry) -> String {
+    format!(
+        "Suppression of {} in {} expired on {}, counted again",
+        entry.cve,
+        entry.package,
+        entry
+            .expires
+            .map(|expires| expires.to_string())
+            .unwrap_or_default()
+    )
+}
@@ -0,0 +1,829 @@
+//! Projects created in a batch from a manifest listing the firmwares,
+//! `create --manifest`, or from a glob matching them, `create 'out/*.img'`.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Context, Result};
+use comfy_table::{Cell, Row, Table};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use uuid::Uuid;
+
+use crate::{
+    api::{ApiServer, UploadOptions},
+    archive::{self, ArchiveOptions},
+    cli::{self, ExitCode},
+    output::{self, CommandOutput},
+    services::project_service::{self, ProjectStatus, FILE_SIZE_LIMIT},
+};
+
+/// Firmware of a manifest, as written in it.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ManifestEntry {
+    /// Path of the firmware, relative to the manifest
+    pub path: PathBuf,
+    pub name: String,
+    /// Type of the firmware, by default the one of --type
+    #[serde(rename = "type")]
+    pub fw_type: Option<String>,
+    /// Subtype of the firmware, by default the one of --subtype
+    pub subtype: Option<String>,
+    pub description: Option<String>,
+    /// Tags, added to the ones of --tag
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+/// Where the firmwares of a batch come from.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchSource {
+    Manifest(PathBuf),
+    Glob(String),
+}
+
+/// Name of the projects created from a glob, with the placeholders
+/// `{stem}`, `{filename}`, `{ext}`, `{date}` and `{index}`, e.g.
+/// `{stem}-{date}`.
+#[derive(Debug, Clone)]
+pub struct NameTemplate(String);
+
+/// Placeholders of [NameTemplate]
+const NAME_PLACEHOLDERS: [&str; 5] = ["stem", "filename", "ext", "date", "index"];
+
+/// Values of the command line used by the entries not giving theirs.
+#[derive(Debug, Clone)]
+pub struct BatchDefaults {
+    pub fw_type: Option<String>,
+    pub fw_subtype: String,
+    pub tags: Vec<String>,
+    pub allow_unknown_type: bool,
+}
+
+/// Firmware of a manifest, checked and ready to be uploaded.
+#[derive(Debug, Clone)]
+pub struct BatchEntry {
+    pub path: PathBuf,
+    pub name: String,
+    pub fw_type: String,
+    pub fw_subtype: String,
+    pub description: Option<String>,
+    pub tags: Vec<String>,
+}
+
+/// Outcome of an entry of the manifest.
+#[derive(Debug, Serialize)]
+pub struct BatchResult {
+    /// Position of the entry in the manifest, from 1
+    pub entry: usize,
+    pub name: String,
+    pub path: PathBuf,
+    pub project_id: Option<Uuid>,
+    pub sha256: Option<String>,
+    /// Status of the analysis once waited for
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<ProjectStatus>,
+    pub error: Option<String>,
+}
+
+/// Outcome of all the entries of the manifest, in its order.
+#[derive(Debug, Serialize)]
+pub struct BatchReport {
+    #[serde(flatten)]
+    pub source: BatchSource,
+    pub results: Vec<BatchResult>,
+    /// Entries whose upload or analysis failed
+    pub failed: usize,
+}
+
+/// Entries of the manifest at `path`, in YAML or, when named `*.json`, in
+/// JSON: a list of firmwares, or a mapping listing them under `firmwares`.
+pub fn load(path: &Path) -> Result<Vec<ManifestEntry>> {
+    let source = std::fs::read_to_string(path)
+        .with_context(|| format!("error reading manifest {}", path.display()))?;
+    let json = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+    let document = match json {
+        true => serde_json::from_str(&source).map_err(anyhow::Error::from),
+        false => parse_yaml(&source),
+    }
+    .with_context(|| format!("invalid manifest {}", path.display()))?;
+
+    let entries = match document {
+        Value::Array(entries) => entries,
+        Value::Object(mut document) if document.contains_key("firmwares") => {
+            match document.remove("firmwares") {
+                Some(Value::Array(entries)) => entries,
+                _ => bail!(
+                    "invalid manifest {}, `firmwares` isn't a list",
+                    path.display()
+                ),
+            }
+        }
+        _ => bail!(
+            "invalid manifest {}, expected a list of firmwares or a `firmwares` list",
+            path.display()
+        ),
+    };
+    if entries.is_empty() {
+        bail!("no firmwares in manifest {}", path.display());
+    }
+    entries
+        .into_iter()
+        .enumerate()
+        .map(|(i, entry)| {
+            serde_json::from_value(entry)
+                .with_context(|| format!("invalid manifest {}, entry {}", path.display(), i + 1))
+        })
+        .collect()
+}
+
+/// Entries of the files matched by the glob `pattern`, in the order of
+/// their paths, named after them with `template`.
+pub fn from_glob(
+    pattern: &str,
+    template: &NameTemplate,
+    description: Option<&str>,
+) -> Result<Vec<ManifestEntry>> {
+    let date = chrono::Utc::now().format("%Y-%m-%d").to_string();
+    Ok(archive::expand(pattern)?
+        .into_iter()
+        .enumerate()
+        .map(|(i, path)| ManifestEntry {
+            name: template.render(&path, &date, i + 1),
+            path,
+            fw_type: None,
+            subtype: None,
+            description: description.map(str::to_string),
+            tags: vec![],
+        })
+        .collect())
+}
+
+/// Check all the entries of the batch from `source` before uploading any,
+/// reporting all the invalid ones at once.
+pub fn validate(
+    source: &BatchSource,
+    entries: Vec<ManifestEntry>,
+    defaults: &BatchDefaults,
+) -> Result<Vec<BatchEntry>> {
+    // The paths of a manifest are relative to it, the ones of a glob as given
+    let dir = match source {
+        BatchSource::Manifest(manifest) => manifest.parent().unwrap_or(Path::new("")),
+        BatchSource::Glob(_) => Path::new(""),
+    };
+    let mut checked = vec![];
+    let mut errors = vec![];
+    let mut names: HashMap<String, usize> = HashMap::new();
+
+    for (i, entry) in entries.into_iter().enumerate() {
+        let mut problems = vec![];
+
+        let path = dir.join(&entry.path);
+        match std::fs::metadata(&path) {
+            Ok(metadata) if metadata.is_dir() => {}
+            Ok(metadata) if metadata.len() == 0 => {
+                problems.push(format!("empty file {}", path.display()))
+            }
+            Ok(metadata) if metadata.len() as usize > FILE_SIZE_LIMIT => problems.push(format!(
+                "{} exceeds the maximum file size of {} bytes",
+                path.display(),
+                FILE_SIZE_LIMIT
+            )),
+            Ok(_) => {}
+            Err(err) => problems.push(format!("error reading {}: {}", path.display(), err)),
+        }
+
+        if let Err(e) = project_service::check_name(&entry.name) {
+            problems.push(format!("invalid name, {}", e));
+        }
+        if let Some(first) = names.insert(entry.name.clone(), i + 1) {
+            tracing::warn!(
+                "Entries {} and {} of the {} have the same name `{}`",
+                first,
+                i + 1,
+                source.kind(),
+                entry.name
+            );
+        }
+
+        let fw_subtype = entry
+            .subtype
+            .clone()
+            .unwrap_or_else(|| defaults.fw_subtype.clone());
+        let firmware = match entry.fw_type.as_ref().or(defaults.fw_type.as_ref()) {
+            Some(fw_type) => match cli::parse_firmware(fw_type, &fw_subtype) {
+                Ok((fw_type, fw_subtype)) => Some((fw_type.to_string(), fw_subtype.to_string())),
+                Err(e) if defaults.allow_unknown_type => {
+                    tracing::warn!("Entry {}: {}. Sending it as is", i + 1, e);
+                    Some((fw_type.clone(), fw_subtype))
+                }
+                Err(e) => {
+                    problems.push(e);
+                    None
+                }
+            },
+            None => {
+                problems.push("no type, set its `type` or pass --type".to_string());
+                None
+            }
+        };
+
+        let mut tags = defaults.tags.clone();
+        for tag in &entry.tags {
+            match cli::parse_tag(tag) {
+                Ok(tag) if !tags.contains(&tag) => tags.push(tag),
+                Ok(_) => {}
+                Err(e) => problems.push(e),
+            }
+        }
+
+        match firmware {
+            Some((fw_type, fw_subtype)) if problems.is_empty() => checked.push(BatchEntry {
+                path,
+                name: entry.name,
+                fw_type,
+                fw_subtype,
+                description: entry.description,
+                tags,
+            }),
+            _ => errors.extend(
+                problems
+                    .into_iter()
+                    .map(|problem| format!("entry {} ({}): {}", i + 1, entry.name, problem)),
+            ),
+        }
+    }
+
+    if !errors.is_empty() {
+        bail!(
+            "invalid {}, nothing uploaded:\n  {}",
+            source,
+            errors.join("\n  ")
+        );
+    }
+    Ok(checked)
+}
+
+/// Create a project for each of `entries`, `parallel` at a time. A failed
+/// entry doesn't stop the others.
+pub async fn create_all<U: ApiServer>(
+    api_server: &U,
+    entries: Vec<BatchEntry>,
+    organization: Option<&str>,
+    options: &UploadOptions,
+    archive_options: &ArchiveOptions,
+    parallel: usize,
+) -> Vec<BatchResult> {
+    let total = entries.len();
+    let options = UploadOptions {
+        hide_progress: options.hide_progress || parallel > 1,
+        ..options.clone()
+    };
+    let options = &options;
+
+    let mut results: Vec<BatchResult> = futures::stream::iter(entries.into_iter().enumerate())
+        .map(|(i, entry)| async move {
+            tracing::info!("Creating {} ({}/{})...", entry.name, i + 1, total);
+            let created =
+                create_entry(api_server, &entry, organization, options, archive_options).await;
+            let mut result = BatchResult {
+                entry: i + 1,
+                name: entry.name,
+                path: entry.path,
+                project_id: None,
+                sha256: None,
+                status: None,
+                error: None,
+            };
+            match created {
+                Ok(created) => {
+                    tracing::info!("{}: project {} created", result.name, created.id);
+                    result.project_id = Some(created.id);
+                    result.sha256 = Some(created.sha256);
+                }
+                Err(e) => {
+                    tracing::error!("{}: {:#}", result.name, e);
+                    result.error = Some(format!("{:#}", e));
+                }
+            }
+            result
+        })
+        .buffer_unordered(parallel.max(1))
+        .collect()
+        .await;
+
+    results.sort_by_key(|result| result.entry);
+    results
+}
+
+async fn create_entry<U: ApiServer>(
+    api_server: &U,
+    entry: &BatchEntry,
+    organization: Option<&str>,
+    options: &UploadOptions,
+    archive_options: &ArchiveOptions,
+) -> Result<project_service::ProjectCreated> {
+    let fw_filepath = entry.path.to_string_lossy();
+    let archive = crate::archive_dir(&fw_filepath, archive_options).await?;
+    let fw_filepath = match &archive {
+        Some(archive) => archive.path.to_string_lossy(),
+        None => fw_filepath,
+    };
+    project_service::create(
+        &fw_filepath,
+        &entry.fw_type,
+        &entry.fw_subtype,
+        &entry.name,
+        entry.description.as_deref(),
+        organization,
+        &entry.tags,
+        options,
+        api_server,
+    )
+    .await
+}
+
+/// Wait for the analyses of the projects created, up to `timeout` for all
+/// of them, setting their final status.
+pub async fn wait_all<U: ApiServer>(
+    api_server: &mut U,
+    results: &mut [BatchResult],
+    poll_interval: Duration,
+    timeout: Duration,
+) {
+    let spinner = project_service::wait_spinner();
+    let deadline = Instant::now() + timeout;
+    let total = results
+        .iter()
+        .filter(|result| result.project_id.is_some())
+        .count();
+
+    loop {
+        let mut pending = 0;
+        for result in results.iter_mut() {
+            let Some(project_id) = result.project_id else {
+                continue;
+            };
+            if matches!(
+                result.status,
+                Some(ProjectStatus::Completed | ProjectStatus::Failed { .. })
+            ) {
+                continue;
+            }
+            match api_server.status(&project_id).await {
+                Ok(status) => {
+                    if result.status.as_ref() != Some(&status) {
+                        tracing::debug!("Project {} status: {}", project_id, status);
+                    }
+                    if !matches!(
+                        status,
+                        ProjectStatus::Completed | ProjectStatus::Failed { .. }
+                    ) {
+                        pending += 1;
+                    }
+                    result.status = Some(status);
+                }
+                Err(e) => {
+                    tracing::warn!("Status of project {} not available: {}", project_id, e);
+                    pending += 1;
+                }
+            }
+        }
+        spinner.set_message(format!("{} of {} analyses done", total - pending, total));
+        if pending == 0 {
+            break;
+        }
+
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            for result in results.iter_mut().filter(|result| {
+                result.project_id.is_some()
+                    && !matches!(
+                        result.status,
+                        Some(ProjectStatus::Completed | ProjectStatus::Failed { .. })
+                    )
+            }) {
+                result.error = Some(format!(
+                    "analysis not completed after {}s",
+                    timeout.as_secs()
+                ));
+            }
+            break;
+        }
+        tokio::time::sleep(poll_interval.min(remaining)).await;
+    }
+
+    spinner.finish_and_clear();
+}
+
+impl BatchSource {
+    fn kind(&self) -> &'static str {
+        match self {
+            BatchSource::Manifest(_) => "manifest",
+            BatchSource::Glob(_) => "glob",
+        }
+    }
+}
+
+impl fmt::Display for BatchSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BatchSource::Manifest(manifest) => write!(f, "manifest {}", manifest.display()),
+            BatchSource::Glob(glob) => write!(f, "glob {}", glob),
+        }
+    }
+}
+
+impl FromStr for NameTemplate {
+    type Err = String;
+
+    fn from_str(template: &str) -> Result<Self, Self::Err> {
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            let end = rest[start..]
+                .find('}')
+                .ok_or_else(|| format!("unclosed {{ in name template `{}`", template))?;
+            let placeholder = &rest[start + 1..start + end];
+            if !NAME_PLACEHOLDERS.contains(&placeholder) {
+                return Err(format!(
+                    "unknown {{{}}} in name template `{}`, valid placeholders: {}",
+                    placeholder,
+                    template,
+                    NAME_PLACEHOLDERS
+                        .map(|placeholder| format!("{{{placeholder}}}"))
+                        .join(", ")
+                ));
+            }
+            rest = &rest[start + end + 1..];
+        }
+        Ok(Self(template.to_string()))
+    }
+}
+
+impl Default for NameTemplate {
+    fn default() -> Self {
+        Self("{stem}".to_string())
+    }
+}
+
+impl NameTemplate {
+    /// Name of the project of the firmware at `path`, the `index`th matched.
+    fn render(&self, path: &Path, date: &str, index: usize) -> String {
+        let part = |part: Option<&std::ffi::OsStr>| {
+            part.map(|part| part.to_string_lossy().into_owned())
+                .unwrap_or_default()
+        };
+        let mut name = String::new();
+        let mut rest = self.0.as_str();
+        while let Some(start) = rest.find('{') {
+            let end = start + rest[start..].find('}').expect("checked when parsed");
+            name.push_str(&rest[..start]);
+            name.push_str(&match &rest[start + 1..end] {
+                "stem" => part(path.file_stem()),
+                "filename" => part(path.file_name()),
+                "ext" => part(path.extension()),
+                "date" => date.to_string(),
+                _ => index.to_string(),
+            });
+            rest = &rest[end + 1..];
+        }
+        name.push_str(rest);
+        name
+    }
+}
+
+impl BatchReport {
+    pub fn new(source: BatchSource, results: Vec<BatchResult>) -> Self {
+        let failed = results.iter().filter(|result| result.failed()).count();
+        Self {
+            source,
+            results,
+            failed,
+        }
+    }
+
+    pub fn get_text_output(&self) -> String {
+        let waited = self.results.iter().any(|result| result.status.is_some());
+        let mut table = Table::new();
+        let mut header = vec![
+            Cell::new("ENTRY"),
+            Cell::new("NAME"),
+            Cell::new("FILE"),
+            Cell::new("PROJECT ID"),
+        ];
+        if waited {
+            header.push(Cell::new("STATUS"));
+        }
+        header.push(Cell::new("ERROR"));
+        table.set_header(Row::from(header));
+
+        for result in &self.results {
+            let mut row = vec![
+                Cell::new(result.entry),
+                Cell::new(&result.name),
+                Cell::new(result.path.display()),
+                Cell::new(
+                    result
+                        .project_id
+                        .map_or("-".to_string(), |id| id.to_string()),
+                ),
+            ];
+            if waited {
+                row.push(Cell::new(
+                    result
+                        .status
+                        .as_ref()
+                        .map_or("-".to_string(), |status| status.to_string()),
+                ));
+            }
+            row.push(Cell::new(result.error.as_deref().unwrap_or("-")));
+            table.add_row(Row::from(row));
+        }
+
+        let summary = match self.failed {
+            0 => format!("{} projects created", self.results.len()),
+            failed => format!("{} of {} entries failed", failed, self.results.len()),
+        };
+        format!("{}\n{}", output::render(table), summary)
+    }
+}
+
+impl BatchResult {
+    fn failed(&self) -> bool {
+        self.error.is_some() || matches!(self.status, Some(ProjectStatus::Failed { .. }))
+    }
+}
+
+impl CommandOutput for BatchReport {
+    fn text(&self) -> String {
+        self.get_text_output()
+    }
+
+    fn json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    fn exit_code(&self) -> Option<ExitCode> {
+        (self.failed > 0).then_some(ExitCode::Failure)
+    }
+}
+
+/// Line of a YAML document, without its indentation.
+#[derive(Debug, Clone, Copy)]
+struct Line<'a> {
+    number: usize,
+    indent: usize,
+    text: &'a str,
+}
+
+/// Parse the subset of YAML manifests are written in: block mappings and
+/// lists, plain and quoted strings, flow lists like `[a, b]`, `|` and `>`
+/// block strings, and comments. Scalars are all read as strings.
+fn parse_yaml(source: &str) -> Result<Value> {
+    let mut lines = vec![];
+    for (i, raw) in source.lines().enumerate() {
+        let text = raw.trim_end();
+        let content = text.trim_start_matches(' ');
+        if content.is_empty() || content.starts_with('#') || text == "---" {
+            continue;
+        }
+        if content.starts_with('\t') {
+            bail!("line {}: tabs can't indent YAML, use spaces", i + 1);
+        }
+        lines.push(Line {
+            number: i + 1,
+            indent: text.len() - content.len(),
+            text: content,
+        });
+    }
+
+    let mut parser = YamlParser { lines, pos: 0 };
+    if parser.lines.is_empty() {
+        return Ok(Value::Null);
+    }
+    let document = parser.node()?;
+    match parser.lines.get(parser.pos) {
+        Some(line) => bail!("line {}: unexpected indentation", line.number),
+        None => Ok(document),
+    }
+}
+
+struct YamlParser<'a> {
+    lines: Vec<Line<'a>>,
+    pos: usize,
+}
+
+impl YamlParser<'_> {
+    /// The list or mapping starting at the current line.
+    fn node(&mut self) -> Result<Value> {
+        let line = self.lines[self.pos];
+        match is_list_item(line.text) {
+            true => self.list(line.indent),
+            false => self.mapping(line.indent),
+        }
+    }
+
+    fn list(&mut self, indent: usize) -> Result<Value> {
+        let mut items = vec![];
+        while let Some(line) = self.lines.get(self.pos).copied() {
+            if line.indent != indent || !is_list_item(line.text) {
+                break;
+            }
+            let rest = line.text[1..].trim_start_matches(' ');
+            let item = Line {
+                number: line.number,
+                indent: indent + line.text.len() - rest.len(),
+                text: rest,
+            };
+
+            let value = strip_comment(rest);
+            if value.is_empty() {
+                self.pos += 1;
+                items.push(self.nested(indent, false)?);
+            } else if is_list_item(value) || (!is_quoted(value) && split_key(value).is_some()) {
+                // The item starts on the line of its `-`
+                self.lines[self.pos] = item;
+                items.push(self.node()?);
+            } else {
+                self.pos += 1;
+                items.push(scalar(value, line.number)?);
+            }
+        }
+        Ok(Value::Array(items))
+    }
+
+    fn mapping(&mut self, indent: usize) -> Result<Value> {
+        let mut map = Map::new();
+        while let Some(line) = self.lines.get(self.pos).copied() {
+            if line.indent != indent {
+                break;
+            }
+            let text = strip_comment(line.text);
+            let Some((key, value)) = split_key(text).filter(|_| !is_list_item(text)) else {
+                bail!("line {}: expected `key: value`", line.number);
+            };
+            let key = match scalar(key, line.number)? {
+                Value::String(key) => key,
+                _ => bail!("line {}: invalid key", line.number),
+            };
+            if map.contains_key(&key) {
+                bail!("line {}: duplicate key `{}`", line.number, key);
+            }
+            self.pos += 1;
+
+            let value = match value {
+                "" => self.nested(indent, true)?,
+                "|" | "|-" | "|+" => self.block_string(indent, "\n"),
+                ">" | ">-" | ">+" => self.block_string(indent, " "),
+                value => scalar(value, line.number)?,
+            };
+            map.insert(key, value);
+        }
+        Ok(Value::Object(map))
+    }
+
+    /// Value on the lines after a key or a `-` at `indent`, a list being
+    /// allowed at the same indentation as the key.
+    fn nested(&mut self, indent: usize, key: bool) -> Result<Value> {
+        match self.lines.get(self.pos) {
+            Some(line) if line.indent > indent => self.node(),
+            Some(line) if key && line.indent == indent && is_list_item(line.text) => {
+                self.list(indent)
+            }
+            _ => Ok(Value::Null),
+        }
+    }
+
+    /// Lines more indented than `indent`, joined by `separator`.
+    fn block_string(&mut self, indent: usize, separator: &str) -> Value {
+        let mut lines = vec![];
+        let first = self.lines.get(self.pos).map(|line| line.indent);
+        while let Some(line) = self.lines.get(self.pos) {
+            if line.indent <= indent {
+                break;
+            }
+            let extra = line.indent - first.unwrap_or(line.indent);
+            lines.push(format!("{}{}", " ".repeat(extra), line.text));
+            self.pos += 1;
+        }
+        Value::String(lines.join(separator))
+    }
+}
+
+fn is_list_item(text: &str) -> bool {
+    text == "-" || text.starts_with("- ")
+}
+
+fn is_quoted(text: &str) -> bool {
+    text.starts_with(['"', '\'', '[', '{'])
+}
+
+/// `text` without its comment, a `#` after a space outside quotes.
+fn strip_comment(text: &str) -> &str {
+    let mut quote = None;
+    let mut previous = ' ';
+    for (i, c) in text.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') if previous == ' ' || previous == '[' || previous == ',' => {
+                quote = Some(c)
+            }
+            (Some(open), c) if c == open => quote = None,
+            (None, '#') if previous == ' ' => return text[..i].trim_end(),
+            _ => {}
+        }
+        previous = c;
+    }
+    text.trim_end()
+}
+
+/// Key and value of a `key: value` line.
+fn split_key(text: &str) -> Option<(&str, &str)> {
+    let end = match text.chars().next()? {
+        quote @ ('"' | '\'') => text[1..].find(quote)? + 2,
+        _ => 0,
+    };
+    let colon = text[end..]
+        .match_indices(':')
+        .map(|(i, _)| end + i)
+        .find(|&i| text[i + 1..].is_empty() || text[i + 1..].starts_with(' '))?;
+    Some((text[..colon].trim(), text[colon + 1..].trim()))
+}
+
+/// Value of a scalar or of a flow list.
+fn scalar(text: &str, number: usize) -> Result<Value> {
+    if let Some(inner) = text.strip_prefix('[') {
+        let Some(inner) = inner.strip_suffix(']') else {
+            bail!("line {}: unterminated list", number);
+        };
+        return split_flow(inner)
+            .into_iter()
+            .filter(|item| !item.is_empty())
+            .map(|item| scalar(item, number))
+            .collect::<Result<Vec<_>>>()
+            .map(Value::Array);
+    }
+    if text.starts_with('{') || text.starts_with(['&', '*', '!']) {
+        bail!(
+            "line {}: flow mappings, anchors and tags aren't supported",
+            number
+        );
+    }
+    if let Some(inner) = text.strip_prefix('"') {
+        let mut value = String::new();
+        let mut chars = inner.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' if chars.as_str().trim().is_empty() => return Ok(Value::String(value)),
+                '"' => break,
+                '\\' => match chars.next() {
+                    Some('n') => value.push('\n'),
+                    Some('t') => value.push('\t'),
+                    Some(c @ ('"' | '\\' | '/')) => value.push(c),
+                    _ => bail!("line {}: unsupported escape", number),
+                },
+                c => value.push(c),
+            }
+        }
+        bail!("line {}: invalid double-quoted string", number);
+    }
+    if let Some(inner) = text.strip_prefix('\'') {
+        return match inner.strip_suffix('\'') {
+            Some(inner) => Ok(Value::String(inner.replace("''", "'"))),
+            None => bail!("line {}: invalid single-quoted string", number),
+        };
+    }
+    Ok(match text {
+        "~" | "null" | "Null" | "NULL" => Value::Null,
+        text => Value::String(text.to_string()),
+    })
+}
+
+/// Items of a flow list, split on the commas outside quotes.
+fn split_flow(inner: &str) -> Vec<&str> {
+    let mut items = vec![];
+    let mut quote = None;
+    let mut start = 0;
+    for (i, c) in inner.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(open), c) if c == open => quote = None,
+            (None, ',') => {
+                items.push(inner[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(inner[start..].trim());
+    items
+}
@@ -0,0 +1,240 @@
+//! Software components of the software BOM analysis, a quick look at the
+//! packages and their licenses before exporting the whole BOM.
+
+use anyhow::Result;
+use comfy_table::{Cell, CellAlignment, Row, Table};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{
+    api::ApiServer,
+    cli::Analysis,
+    output::{self, CsvRecord},
+    services::{
+        project_service::{self, LinuxSoftwareBOMAnalysis},
+        sbom_service,
+    },
+    style,
+};
+
+/// License of the components without one
+const UNKNOWN_LICENSE: &str = "UNKNOWN";
+
+/// Origin of the components not found by a package manager
+const BINARY_MATCH: &str = "binary-match";
+
+/// Package of the software BOM analysis.
+#[derive(Debug, Serialize)]
+pub struct Component {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// Package manager the component was found by, e.g. `dpkg`, or
+    /// `binary-match`
+    pub origin: String,
+    /// `UNKNOWN` when not found
+    pub license: String,
+    pub path: String,
+}
+
+impl Component {
+    fn new(package: &LinuxSoftwareBOMAnalysis) -> Self {
+        let license = package
+            .license
+            .as_deref()
+            .map(str::trim)
+            .filter(|license| !license.is_empty())
+            .unwrap_or(UNKNOWN_LICENSE);
+        let origin = package
+            .origin
+            .clone()
+            .or_else(|| package.purl.as_deref().and_then(purl_origin))
+            .unwrap_or_else(|| BINARY_MATCH.to_string());
+
+        Component {
+            name: package
+                .name
+                .clone()
+                .unwrap_or_else(|| package.filename.clone()),
+            version: package
+                .version
+                .clone()
+                .or_else(|| sbom_service::soname_version(&package.resolve))
+                .or_else(|| sbom_service::soname_version(&package.filename)),
+            origin,
+            license: license.to_string(),
+            path: package.resolve.clone(),
+        }
+    }
+
+    pub fn get_table_from_list(list: &[Component]) -> String {
+        let mut table = Table::new();
+        table.set_header(Row::from(vec![
+            Cell::new("PACKAGE"),
+            Cell::new("VERSION"),
+            Cell::new("ORIGIN"),
+            Cell::new("LICENSE"),
+        ]));
+
+        for component in list {
+            table.add_row(Row::from(vec![
+                Cell::new(&component.name),
+                Cell::new(component.version.as_deref().unwrap_or("-")),
+                Cell::new(&component.origin),
+                license_cell(&component.license),
+            ]));
+        }
+
+        output::render(table)
+    }
+}
+
+impl CsvRecord for Component {
+    const HEADER: &'static [&'static str] = &["name", "version", "origin", "license", "path"];
+
+    fn record(&self, _list_delimiter: &str) -> Vec<String> {
+        vec![
+            self.name.clone(),
+            self.version.clone().unwrap_or_default(),
+            self.origin.clone(),
+            self.license.clone(),
+            self.path.clone(),
+        ]
+    }
+}
+
+/// Components under a license.
+#[derive(Debug, Serialize)]
+pub struct LicenseCount {
+    pub license: String,
+    pub components: usize,
+    pub copyleft: bool,
+}
+
+impl CsvRecord for LicenseCount {
+    const HEADER: &'static [&'static str] = &["license", "components", "copyleft"];
+
+    fn record(&self, _list_delimiter: &str) -> Vec<String> {
+        vec![
+            self.license.clone(),
+            self.components.to_string(),
+            self.copyleft.to_string(),
+        ]
+    }
+}
+
+/// Licenses of the components, the most frequent first.
+#[derive(Debug, Serialize)]
+#[serde(transparent)]
+pub struct LicenseSummary {
+    pub licenses: Vec<LicenseCount>,
+}
+
+impl LicenseSummary {
+    pub fn new(components: &[Component]) -> Self {
+        let mut licenses: Vec<LicenseCount> = vec![];
+        for component in components {
+            match licenses
+                .iter_mut()
+                .find(|count| count.license == component.license)
+            {
+                Some(count) => count.components += 1,
+                None => licenses.push(LicenseCount {
+                    license: component.license.clone(),
+                    components: 1,
+                    copyleft: is_copyleft(&component.license),
+                }),
+            }
+        }
+        licenses.sort_by(|a, b| {
+            b.components
+                .cmp(&a.components)
+                .then_with(|| a.license.cmp(&b.license))
+        });
+
+        LicenseSummary { licenses }
+    }
+
+    pub fn get_text_output(&self) -> String {
+        let mut table = Table::new();
+        table.set_header(Row::from(vec![
+            Cell::new("LICENSE"),
+            Cell::new("COMPONENTS"),
+            Cell::new("COPYLEFT"),
+        ]));
+        for count in &self.licenses {
+            table.add_row(Row::from(vec![
+                license_cell(&count.license),
+                Cell::new(count.components).set_alignment(CellAlignment::Right),
+                Cell::new(if count.copyleft { "yes" } else { "no" }),
+            ]));
+        }
+
+        let components: usize = self.licenses.iter().map(|count| count.components).sum();
+        format!(
+            "{}\n{} components under {} licenses",
+            output::render(table),
+            components,
+            self.licenses.len()
+        )
+    }
+}
+
+/// Whether `license` is of the GPL, LGPL or AGPL families.
+fn is_copyleft(license: &str) -> bool {
+    let license = license.to_lowercase();
+    license.contains("gpl") || license.contains("general public")
+}
+
+/// Origin of a package url by its type, e.g. `dpkg` for `pkg:deb/...`.
+fn purl_origin(purl: &str) -> Option<String> {
+    let (kind, _) = purl.strip_prefix("pkg:")?.split_once('/')?;
+    let origin = match kind.to_lowercase().as_str() {
+        "deb" => "dpkg",
+        "rpm" => "rpm",
+        "opkg" | "ipk" | "openwrt" => "opkg",
+        "apk" | "alpine" => "apk",
+        _ => return None,
+    };
+    Some(origin.to_string())
+}
+
+fn license_cell(license: &str) -> Cell {
+    match license {
+        UNKNOWN_LICENSE => style::dim(Cell::new(license)),
+        license => Cell::new(license),
+    }
+}
+
+// Components of a project, only the ones under a copyleft license with
+// `copyleft`, and the ones whose name contains `search`
+pub async fn list<U: ApiServer>(
+    api_server: &U,
+    project_id: Uuid,
+    copyleft: bool,
+    search: Option<&str>,
+) -> Result<Vec<Component>> {
+    let results =
+        project_service::analysis_results(api_server, project_id, &Analysis::SoftwareBOM).await?;
+    let packages: Vec<LinuxSoftwareBOMAnalysis> =
+        serde_json::from_value(serde_json::Value::Array(results))?;
+
+    let search = search.map(str::to_lowercase);
+    let mut components: Vec<Component> = packages
+        .iter()
+        .map(Component::new)
+        .filter(|component| !copyleft || is_copyleft(&component.license))
+        .filter(|component| {
+            search
+                .as_ref()
+                .is_none_or(|search| component.name.to_lowercase().contains(search))
+        })
+        .collect();
+    components.sort_by(|a, b| {
+        a.name
+            .to_lowercase()
+            .cmp(&b.name.to_lowercase())
+            .then_with(|| a.version.cmp(&b.version))
+    });
+    Ok(components)
+}
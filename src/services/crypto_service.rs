@@ -0,0 +1,301 @@
+//! Crypto analysis: the certificates and keys found in the firmware, flagged
+//! when expired or about to, weak, known defaults, or private keys shipped
+//! along with a certificate.
+
+use std::{path::Path, time::Duration};
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, SecondsFormat, Utc};
+use comfy_table::{Cell, CellAlignment, Row, Table};
+use serde::Serialize;
+
+use crate::{
+    cli::CryptoFlag,
+    output::{self, CsvRecord},
+    services::project_service::LinuxCryptoAnalysis,
+    style::{self, Severity},
+};
+
+/// Window of the expiring flag when --expiring-within is not given
+pub const EXPIRING_WITHIN: Duration = Duration::from_secs(90 * 24 * 60 * 60);
+
+/// Shortest RSA key not flagged as weak, in bits
+const RSA_MIN_SIZE: u16 = 2048;
+
+impl CryptoFlag {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::PrivateKey => "private-key",
+            Self::DefaultKey => "default-key",
+            Self::Expired => "expired",
+            Self::WeakKey => "weak-key",
+            Self::Expiring => "expiring",
+        }
+    }
+
+    fn severity(self) -> Severity {
+        match self {
+            Self::PrivateKey | Self::DefaultKey => Severity::Critical,
+            Self::Expired | Self::WeakKey => Severity::High,
+            Self::Expiring => Severity::Medium,
+        }
+    }
+}
+
+/// Kind of material, told by the type reported by the server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Certificate,
+    PrivateKey,
+    Other,
+}
+
+impl Kind {
+    fn of(found: &LinuxCryptoAnalysis) -> Self {
+        let kind = found.r#type.to_lowercase();
+        if kind.contains("cert") {
+            Self::Certificate
+        } else if kind.contains("private") {
+            Self::PrivateKey
+        } else {
+            Self::Other
+        }
+    }
+}
+
+/// Certificate or key of the crypto analysis, with its flags.
+#[derive(Debug, Serialize)]
+pub struct CryptoMaterial {
+    pub file: String,
+    pub r#type: String,
+    pub algorithm: String,
+    /// Size of the key in bits
+    pub size: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subject: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub issuer: Option<String>,
+    /// Expiry date of a certificate, as reported by the server
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub not_after: Option<String>,
+    /// The most severe first
+    pub flags: Vec<CryptoFlag>,
+    /// Severity of the most severe flag
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub severity: Option<&'static str>,
+}
+
+impl CryptoMaterial {
+    fn new(found: &LinuxCryptoAnalysis, flags: Vec<CryptoFlag>) -> Self {
+        CryptoMaterial {
+            file: found.filename.clone(),
+            r#type: found.r#type.clone(),
+            algorithm: found.subtype.clone(),
+            size: found.pubsz,
+            subject: found.subject.clone(),
+            issuer: found.issuer.clone(),
+            not_after: found.not_after.clone(),
+            severity: flags.first().map(|flag| flag.severity().as_str()),
+            flags,
+        }
+    }
+
+    pub fn is_flagged(&self) -> bool {
+        !self.flags.is_empty()
+    }
+
+    /// `file (flag, flag)`, for the gate.
+    pub fn summary(&self) -> String {
+        let flags: Vec<&str> = self.flags.iter().map(|flag| flag.as_str()).collect();
+        format!("{} ({})", self.file, flags.join(", "))
+    }
+
+    pub fn get_table_from_list(list: &[CryptoMaterial]) -> String {
+        let subject = list.iter().any(|material| material.subject.is_some());
+        let issuer = list.iter().any(|material| material.issuer.is_some());
+        let not_after = list.iter().any(|material| material.not_after.is_some());
+
+        let mut table = Table::new();
+        let mut header = vec![
+            Cell::new("FILE"),
+            Cell::new("TYPE"),
+            Cell::new("ALGORITHM"),
+            Cell::new("SIZE"),
+        ];
+        if subject {
+            header.push(Cell::new("SUBJECT"));
+        }
+        if issuer {
+            header.push(Cell::new("ISSUER"));
+        }
+        if not_after {
+            header.push(Cell::new("NOT AFTER"));
+        }
+        header.push(Cell::new("FLAGS"));
+        table.set_header(Row::from(header));
+
+        let optional = |value: &Option<String>| Cell::new(value.as_deref().unwrap_or("-"));
+        for material in list {
+            let severity = material.flags.first().map(|flag| flag.severity());
+            let mut row = vec![
+                match severity {
+                    Some(Severity::Critical) => Severity::Critical.cell(&material.file),
+                    _ => Cell::new(&material.file),
+                },
+                Cell::new(&material.r#type),
+                Cell::new(&material.algorithm),
+                Cell::new(material.size).set_alignment(CellAlignment::Right),
+            ];
+            if subject {
+                row.push(optional(&material.subject));
+            }
+            if issuer {
+                row.push(optional(&material.issuer));
+            }
+            if not_after {
+                row.push(Cell::new(material.not_after.as_deref().map_or(
+                    "-".to_string(),
+                    |date| match parse_date(date) {
+                        Some(date) => date.format("%Y-%m-%d").to_string(),
+                        None => date.to_string(),
+                    },
+                )));
+            }
+            let flags: Vec<&str> = material.flags.iter().map(|flag| flag.as_str()).collect();
+            row.push(match severity {
+                Some(severity) => severity.cell(&flags.join(", ")),
+                None => style::dim(Cell::new("-")),
+            });
+            table.add_row(Row::from(row));
+        }
+
+        let flagged = list.iter().filter(|material| material.is_flagged()).count();
+        format!(
+            "{}\n{} of {} certificates and keys flagged",
+            output::render(table),
+            flagged,
+            list.len()
+        )
+    }
+}
+
+impl CsvRecord for CryptoMaterial {
+    const HEADER: &'static [&'static str] = &[
+        "file",
+        "type",
+        "algorithm",
+        "size",
+        "subject",
+        "issuer",
+        "not_after",
+        "flags",
+        "severity",
+    ];
+
+    fn record(&self, list_delimiter: &str) -> Vec<String> {
+        let flags: Vec<&str> = self.flags.iter().map(|flag| flag.as_str()).collect();
+        vec![
+            self.file.clone(),
+            self.r#type.clone(),
+            self.algorithm.clone(),
+            self.size.to_string(),
+            self.subject.clone().unwrap_or_default(),
+            self.issuer.clone().unwrap_or_default(),
+            // Normalized for the spreadsheets, as reported when not a date
+            self.not_after
+                .as_deref()
+                .map(|date| match parse_date(date) {
+                    Some(date) => date.to_rfc3339_opts(SecondsFormat::Secs, true),
+                    None => date.to_string(),
+                })
+                .unwrap_or_default(),
+            flags.join(list_delimiter),
+            self.severity.unwrap_or_default().to_string(),
+        ]
+    }
+}
+
+/// Certificates and keys `found` with their flags, the certificates
+/// expiring within `expiring_within`, or 90 days, flagged as expiring. The
+/// most severe first.
+pub fn material(
+    found: &[LinuxCryptoAnalysis],
+    expiring_within: Option<Duration>,
+) -> Vec<CryptoMaterial> {
+    let now = Utc::now();
+    let expiring = chrono::Duration::from_std(expiring_within.unwrap_or(EXPIRING_WITHIN))
+        .ok()
+        .and_then(|window| now.checked_add_signed(window))
+        .unwrap_or(DateTime::<Utc>::MAX_UTC);
+    // Directories and files with a certificate, a private key in them being
+    // the one of the certificate
+    let certificates: Vec<&Path> = found
+        .iter()
+        .filter(|found| Kind::of(found) == Kind::Certificate)
+        .flat_map(|found| {
+            let file = Path::new(&found.filename);
+            [Some(file), file.parent()]
+        })
+        .flatten()
+        .collect();
+
+    let mut material: Vec<CryptoMaterial> = found
+        .iter()
+        .map(|found| {
+            let kind = Kind::of(found);
+            let mut flags = vec![];
+            if kind == Kind::PrivateKey {
+                let file = Path::new(&found.filename);
+                if [Some(file), file.parent()]
+                    .into_iter()
+                    .flatten()
+                    .any(|path| certificates.contains(&path))
+                {
+                    flags.push(CryptoFlag::PrivateKey);
+                }
+            }
+            if found.known_default == Some(true) {
+                flags.push(CryptoFlag::DefaultKey);
+            }
+            if let Some(not_after) = found.not_after.as_deref() {
+                match parse_date(not_after) {
+                    Some(date) if date <= now => flags.push(CryptoFlag::Expired),
+                    Some(date) if date <= expiring => flags.push(CryptoFlag::Expiring),
+                    Some(_) => {}
+                    None => tracing::debug!("Unknown expiry date {}", not_after),
+                }
+            }
+            if found.subtype.eq_ignore_ascii_case("rsa") && found.pubsz < RSA_MIN_SIZE {
+                flags.push(CryptoFlag::WeakKey);
+            }
+            flags.sort();
+            CryptoMaterial::new(found, flags)
+        })
+        .collect();
+
+    // Flagged first, then by file
+    material.sort_by(|a, b| match (a.flags.first(), b.flags.first()) {
+        (Some(a_flag), Some(b_flag)) => a_flag.cmp(b_flag).then_with(|| a.file.cmp(&b.file)),
+        (Some(_), None) => std::cmp::Ordering::Less,
+        (None, Some(_)) => std::cmp::Ordering::Greater,
+        (None, None) => a.file.cmp(&b.file),
+    });
+    material
+}
+
+/// Date of `date`, in RFC 3339, `2025-01-31 12:00:00`, `2025-01-31` or the
+/// OpenSSL format, like `Jan 31 12:00:00 2025 GMT`, in UTC.
+fn parse_date(date: &str) -> Option<DateTime<Utc>> {
+    let date = date.trim();
+    if let Ok(date) = DateTime::parse_from_rfc3339(date) {
+        return Some(date.with_timezone(&Utc));
+    }
+    NaiveDateTime::parse_from_str(date, "%b %e %H:%M:%S %Y GMT")
+        .or_else(|_| NaiveDateTime::parse_from_str(date, "%Y-%m-%d %H:%M:%S"))
+        .ok()
+        .or_else(|| {
+            NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                .ok()?
+                .and_hms_opt(0, 0, 0)
+        })
+        .map(|date| date.and_utc())
+}
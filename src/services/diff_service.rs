@@ -0,0 +1,436 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use clap::ValueEnum;
+use console::{style, Color};
+use serde::Serialize;
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::{
+    api::ApiServer,
+    cli::Analysis,
+    services::{
+        kernel_service::{KernelOption, Verdict},
+        project_service::{self, LinuxKernelAnalysis},
+    },
+    style::{self, Severity},
+};
+
+/// Differences between the findings of a base project and of a target
+/// project, e.g. two releases of a firmware.
+#[derive(Debug, Serialize)]
+pub struct ProjectDiff {
+    pub base: Uuid,
+    pub target: Uuid,
+    pub score: ScoreDelta,
+    /// Findings of the target only
+    pub added: Vec<Finding>,
+    /// Findings of the base only
+    pub removed: Vec<Finding>,
+    /// Findings of both whose fields changed
+    pub changed: Vec<ChangedFinding>,
+    /// Security relevant kernel options no longer as recommended in the target
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub kernel_regressions: Vec<String>,
+    /// Whether the target has critical CVEs the base didn't have, or kernel
+    /// options no longer as recommended
+    pub regression: bool,
+    /// Whether the target has critical CVEs the base didn't have
+    #[serde(skip)]
+    cve_regression: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ScoreDelta {
+    pub base: Option<f64>,
+    pub target: Option<f64>,
+    pub delta: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Finding {
+    pub analysis: String,
+    /// Identifier of the finding in both projects, e.g. `CVE-2023-0001 openssl`
+    pub key: String,
+    pub description: String,
+    pub result: Value,
+}
+
+impl Finding {
+    /// Description of the finding, in the color of its severity if any.
+    fn text(&self) -> String {
+        match self
+            .result
+            .get("severity")
+            .and_then(Value::as_str)
+            .and_then(Severity::parse)
+        {
+            Some(severity) => severity.paint(&self.description).to_string(),
+            None => self.description.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChangedFinding {
+    pub analysis: String,
+    pub key: String,
+    pub description: String,
+    pub changes: Vec<FieldChange>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FieldChange {
+    pub field: String,
+    pub base: Value,
+    pub target: Value,
+}
+
+impl ProjectDiff {
+    /// Summary of the differences, colored when printed to a terminal: added
+    /// findings marked in red, removed ones in green, and the CVEs in the
+    /// color of their severity.
+    pub fn get_text_output(&self) -> String {
+        let arrow = style::arrow();
+        let mut lines = vec![format!(
+            "{} {} {}",
+            style(self.base).bold(),
+            style(arrow).dim(),
+            style(self.target).bold()
+        )];
+
+        if let (Some(base), Some(target), Some(delta)) =
+            (self.score.base, self.score.target, self.score.delta)
+        {
+            let delta = match delta {
+                delta if delta > 0.0 => style(format!("+{}", delta)).fg(Color::Green),
+                delta if delta < 0.0 => style(delta.to_string()).fg(Color::Red),
+                _ => style("unchanged".to_string()).dim(),
+            };
+            lines.push(format!("Score {} {} {} ({})", base, arrow, target, delta));
+        }
+
+        let mut analyses: Vec<&str> = vec![];
+        for analysis in self
+            .added
+            .iter()
+            .map(|finding| &finding.analysis)
+            .chain(self.removed.iter().map(|finding| &finding.analysis))
+            .chain(self.changed.iter().map(|finding| &finding.analysis))
+        {
+            if !analyses.contains(&analysis.as_str()) {
+                analyses.push(analysis);
+            }
+        }
+        if analyses.is_empty() {
+            lines.push("No differences".to_string());
+        }
+
+        for analysis in analyses {
+            let added: Vec<&Finding> = self
+                .added
+                .iter()
+                .filter(|finding| finding.analysis == analysis)
+                .collect();
+            let removed: Vec<&Finding> = self
+                .removed
+                .iter()
+                .filter(|finding| finding.analysis == analysis)
+                .collect();
+            let changed: Vec<&ChangedFinding> = self
+                .changed
+                .iter()
+                .filter(|finding| finding.analysis == analysis)
+                .collect();
+
+            lines.push(String::new());
+            lines.push(format!(
+                "{}: {} added, {} removed, {} changed",
+                style(analysis).bold(),
+                added.len(),
+                removed.len(),
+                changed.len()
+            ));
+            for finding in added {
+                lines.push(format!(
+                    "  {} {}",
+                    style("+").fg(Color::Red),
+                    finding.text()
+                ));
+            }
+            for finding in removed {
+                lines.push(format!(
+                    "  {} {}",
+                    style("-").fg(Color::Green),
+                    finding.text()
+                ));
+            }
+            for finding in changed {
+                let changes: Vec<String> = finding
+                    .changes
+                    .iter()
+                    .map(|change| {
+                        format!(
+                            "{} {} {} {}",
+                            change.field,
+                            field_text(&change.base),
+                            arrow,
+                            field_text(&change.target)
+                        )
+                    })
+                    .collect();
+                lines.push(
+                    style(format!(
+                        "  ~ {}: {}",
+                        finding.description,
+                        changes.join(", ")
+                    ))
+                    .fg(Color::Yellow)
+                    .to_string(),
+                );
+            }
+        }
+
+        if self.regression {
+            lines.push(String::new());
+        }
+        if self.cve_regression {
+            lines.push(
+                style("Regression: new critical CVEs")
+                    .fg(Color::Red)
+                    .bold()
+                    .to_string(),
+            );
+        }
+        if !self.kernel_regressions.is_empty() {
+            lines.push(
+                style(format!(
+                    "Regression: kernel options no longer as recommended: {}",
+                    self.kernel_regressions.join(", ")
+                ))
+                .fg(Color::Red)
+                .bold()
+                .to_string(),
+            );
+        }
+        lines.join("\n")
+    }
+}
+
+/// How the results of an analysis are matched between the projects.
+struct DiffSpec {
+    /// Identifier of a result, stable between releases
+    key: fn(&Value) -> String,
+    /// Fields whose changes are reported, the others are ignored
+    tracked: &'static [&'static str],
+    describe: fn(&Value) -> String,
+}
+
+fn spec(analysis: &Analysis) -> DiffSpec {
+    match analysis {
+        Analysis::CveCheck => DiffSpec {
+            key: |result| {
+                format!(
+                    "{} {}",
+                    text(result, "cveid"),
+                    text(result, "product").to_lowercase()
+                )
+            },
+            tracked: &["version", "severity", "cvss", "patch"],
+            describe: |result| {
+                format!(
+                    "{} in {} {} ({})",
+                    text(result, "cveid"),
+                    text(result, "product"),
+                    text(result, "version"),
+                    text(result, "severity").to_lowercase()
+                )
+            },
+        },
+        Analysis::SoftwareBOM => DiffSpec {
+            key: |result| match result.get("name").and_then(Value::as_str) {
+                Some(name) if !name.is_empty() => name.to_lowercase(),
+                _ => text(result, "filename"),
+            },
+            tracked: &["version", "license"],
+            describe: |result| {
+                let name = match result.get("name").and_then(Value::as_str) {
+                    Some(name) if !name.is_empty() => name.to_string(),
+                    _ => text(result, "filename"),
+                };
+                format!("{} {}", name, text(result, "version"))
+                    .trim_end()
+                    .to_string()
+            },
+        },
+        Analysis::Hardening => DiffSpec {
+            key: |result| text(result, "filename"),
+            tracked: &[
+                "score",
+                "canary",
+                "fortify",
+                "nx",
+                "pie",
+                "relro",
+                "execstack",
+            ],
+            describe: |result| text(result, "filename"),
+        },
+        Analysis::Kernel => DiffSpec {
+            key: |result| text(result, "name"),
+            tracked: &["enabled"],
+            describe: |result| text(result, "name"),
+        },
+        Analysis::PasswordHash => DiffSpec {
+            // The hashes themselves are left out
+            key: |result| text(result, "username"),
+            tracked: &[],
+            describe: |result| format!("Password hash of user {}", text(result, "username")),
+        },
+        // Without a known identifier, any change is a result removed and one added
+        _ => DiffSpec {
+            key: |result| result.to_string(),
+            tracked: &[],
+            describe: |result| match result {
+                Value::Object(fields) => fields
+                    .values()
+                    .map(field_text)
+                    .filter(|value| !value.is_empty())
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                value => field_text(value),
+            },
+        },
+    }
+}
+
+// Differences between the `analyses` of the project `base` and `target`
+pub async fn diff<U: ApiServer>(
+    api_server: &mut U,
+    base: Uuid,
+    target: Uuid,
+    analyses: &[Analysis],
+) -> Result<ProjectDiff> {
+    let base_score = api_server.overview(&base).await?["project"]["score"].as_f64();
+    let target_score = api_server.overview(&target).await?["project"]["score"].as_f64();
+
+    let mut added = vec![];
+    let mut removed = vec![];
+    let mut changed = vec![];
+    let mut kernel_regressions = vec![];
+    for analysis in analyses {
+        let (base_results, target_results) = futures::try_join!(
+            project_service::analysis_results(&*api_server, base, analysis),
+            project_service::analysis_results(&*api_server, target, analysis),
+        )?;
+
+        let name = analysis
+            .to_possible_value()
+            .map(|value| value.get_name().to_string())
+            .unwrap_or_else(|| analysis.to_string());
+        let spec = spec(analysis);
+        let finding = |result: Value| Finding {
+            analysis: name.clone(),
+            key: (spec.key)(&result),
+            description: (spec.describe)(&result),
+            result,
+        };
+
+        let mut base_by_key: HashMap<String, &Value> = base_results
+            .iter()
+            .map(|result| ((spec.key)(result), result))
+            .collect();
+        for result in target_results {
+            let key = (spec.key)(&result);
+            let base_result = base_by_key.remove(&key);
+            if matches!(analysis, Analysis::Kernel)
+                && kernel_verdict(&result) == Some(Verdict::Bad)
+                && base_result.and_then(kernel_verdict) != Some(Verdict::Bad)
+            {
+                kernel_regressions.push(key.clone());
+            }
+            let Some(base_result) = base_result else {
+                added.push(finding(result));
+                continue;
+            };
+
+            let changes: Vec<FieldChange> = spec
+                .tracked
+                .iter()
+                .filter(|field| base_result.get(field) != result.get(field))
+                .map(|field| FieldChange {
+                    field: field.to_string(),
+                    base: base_result.get(*field).cloned().unwrap_or_default(),
+                    target: result.get(*field).cloned().unwrap_or_default(),
+                })
+                .collect();
+            if !changes.is_empty() {
+                changed.push(ChangedFinding {
+                    analysis: name.clone(),
+                    key,
+                    description: (spec.describe)(&result),
+                    changes,
+                });
+            }
+        }
+
+        // In the order of the base results
+        for result in &base_results {
+            if base_by_key.remove(&(spec.key)(result)).is_some() {
+                removed.push(finding(result.clone()));
+            }
+        }
+    }
+
+    let cve_regression = added.iter().any(|finding| {
+        finding.analysis == "cve-check"
+            && text(&finding.result, "severity").eq_ignore_ascii_case("critical")
+    }) || changed.iter().any(|finding| {
+        finding.analysis == "cve-check"
+            && finding.changes.iter().any(|change| {
+                change.field == "severity"
+                    && change
+                        .target
+                        .as_str()
+                        .is_some_and(|severity| severity.eq_ignore_ascii_case("critical"))
+            })
+    });
+
+    Ok(ProjectDiff {
+        base,
+        target,
+        score: ScoreDelta {
+            base: base_score,
+            target: target_score,
+            delta: base_score
+                .zip(target_score)
+                .map(|(base, target)| ((target - base) * 100.0).round() / 100.0),
+        },
+        added,
+        removed,
+        changed,
+        regression: cve_regression || !kernel_regressions.is_empty(),
+        cve_regression,
+        kernel_regressions,
+    })
+}
+
+/// Verdict of a result of the kernel analysis, if security relevant.
+fn kernel_verdict(result: &Value) -> Option<Verdict> {
+    let found: LinuxKernelAnalysis = serde_json::from_value(result.clone()).ok()?;
+    KernelOption::new(&found).verdict
+}
+
+fn text(result: &Value, field: &str) -> String {
+    result.get(field).map(field_text).unwrap_or_default()
+}
+
+/// Value of a field as text, without the quotes of strings.
+fn field_text(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(text) => text.clone(),
+        value => value.to_string(),
+    }
+}
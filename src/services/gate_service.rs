@@ -0,0 +1,273 @@
+use std::collections::HashMap;
+
+use anyhow::{bail, Context, Result};
+use chrono::Utc;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{
+    api::ApiServer,
+    cli::{Analysis, GatePolicy},
+    services::{
+        baseline_service::{expired_message, Baseline, BaselineEntry, Suppression},
+        crypto_service,
+        junit_service::JunitReport,
+        password_service::Credential,
+        project_service::{
+            self, LinuxCryptoAnalysis, LinuxCveCheckAnalysis, LinuxPasswordHashAnalysis,
+        },
+        sarif_service::SarifLog,
+    },
+};
+
+/// Rule of a policy and whether the project satisfies it.
+#[derive(Debug, Serialize)]
+pub struct GateRule {
+    pub rule: String,
+    pub passed: bool,
+    /// What was found, e.g. `3 critical CVEs`
+    pub detail: String,
+}
+
+/// Outcome of the policy gate of a project.
+#[derive(Debug, Serialize)]
+pub struct GateReport {
+    pub project_id: Uuid,
+    pub passed: bool,
+    pub rules: Vec<GateRule>,
+    /// Findings of the project, for the SARIF output
+    #[serde(skip)]
+    pub sarif: Option<SarifLog>,
+    /// Rules and findings of the project, for the JUnit output
+    #[serde(skip)]
+    pub junit: Option<JunitReport>,
+    /// CVEs accepted in the baseline, left out of the thresholds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suppressed: Option<u32>,
+    /// Accepted CVEs past their expiry date, counted again
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub expired_suppressions: Vec<BaselineEntry>,
+}
+
+impl GateReport {
+    pub fn get_text_output(&self) -> String {
+        let mut lines: Vec<String> = self
+            .rules
+            .iter()
+            .map(|rule| {
+                let outcome = if rule.passed { "PASS" } else { "FAIL" };
+                format!("{}  {}: {}", outcome, rule.rule, rule.detail)
+            })
+            .collect();
+
+        if let Some(suppressed) = self.suppressed {
+            lines.push(format!("{} CVEs suppressed by the baseline", suppressed));
+        }
+        lines.extend(self.expired_suppressions.iter().map(expired_message));
+
+        let violated = self.rules.iter().filter(|rule| !rule.passed).count();
+        lines.push(if self.passed {
+            format!("Gate passed for project {}", self.project_id)
+        } else {
+            format!(
+                "Gate failed for project {}: {} of {} rules violated",
+                self.project_id,
+                violated,
+                self.rules.len()
+            )
+        });
+        lines.join("\n")
+    }
+}
+
+// Check the findings of a project against the thresholds of `policy`, the
+// CVEs accepted in `baseline` left out
+pub async fn evaluate<U: ApiServer>(
+    api_server: &mut U,
+    project_id: Uuid,
+    policy: &GatePolicy,
+    baseline: Option<&Baseline>,
+) -> Result<GateReport> {
+    let mut rules = vec![];
+    let mut suppressed = None;
+    let mut expired_suppressions = vec![];
+
+    let max_cves = [
+        ("critical", policy.max_critical),
+        ("high", policy.max_high),
+        ("medium", policy.max_medium),
+        ("low", policy.max_low),
+    ];
+    if max_cves.iter().any(|(_, max)| max.is_some()) {
+        let counts = cve_counts(api_server, project_id, baseline).await?;
+        for (severity, max) in max_cves {
+            if let Some(max) = max {
+                let count = counts.active.get(severity).copied().unwrap_or(0);
+                let detail = match counts.suppressed.get(severity) {
+                    Some(accepted) => {
+                        format!("{} {} CVEs, {} suppressed", count, severity, accepted)
+                    }
+                    None => format!("{} {} CVEs", count, severity),
+                };
+                rules.push(GateRule {
+                    rule: format!("max-{} {}", severity, max),
+                    passed: count <= max,
+                    detail,
+                });
+            }
+        }
+        if baseline.is_some() {
+            suppressed = Some(counts.suppressed.values().sum());
+        }
+        expired_suppressions = counts.expired;
+    }
+
+    if let Some(min_score) = policy.min_score {
+        let overview = api_server.overview(&project_id).await?;
+        let score = overview["project"]["score"]
+            .as_f64()
+            .context("security score not reported by the server")?;
+        rules.push(GateRule {
+            rule: format!("min-score {}", min_score),
+            passed: score >= f64::from(min_score),
+            detail: format!("score {}", score),
+        });
+    }
+
+    for analysis in &policy.fail_on {
+        // A single finding is enough to violate the rule
+        let res = api_server.analysis(&project_id, analysis, 0, 1).await?;
+        if let Some(err) = res.error {
+            bail!("Analysis {} error: {}", analysis, err);
+        }
+        let found = res
+            .result
+            .as_ref()
+            .and_then(|result| result.as_array())
+            .is_some_and(|result| !result.is_empty());
+        rules.push(GateRule {
+            rule: format!("fail-on {}", analysis),
+            passed: !found,
+            detail: if found {
+                "findings present"
+            } else {
+                "no findings"
+            }
+            .to_string(),
+        });
+    }
+
+    if policy.fail_on_weak_passwords {
+        let results =
+            project_service::analysis_results(api_server, project_id, &Analysis::PasswordHash)
+                .await?;
+        let found: Vec<LinuxPasswordHashAnalysis> =
+            serde_json::from_value(serde_json::Value::Array(results))?;
+        let weak: Vec<String> = found
+            .iter()
+            .map(|found| Credential::new(found, false))
+            .filter(Credential::is_weak)
+            .map(|credential| format!("{} ({})", credential.username, credential.algorithm))
+            .collect();
+        rules.push(GateRule {
+            rule: "fail-on-weak-passwords".to_string(),
+            passed: weak.is_empty(),
+            detail: match weak.is_empty() {
+                true => "no account with an empty or weak password".to_string(),
+                false => format!(
+                    "{} accounts with an empty or weak password: {}",
+                    weak.len(),
+                    weak.join(", ")
+                ),
+            },
+        });
+    }
+
+    if !policy.fail_on_crypto.is_empty() {
+        let results =
+            project_service::analysis_results(api_server, project_id, &Analysis::Crypto).await?;
+        let found: Vec<LinuxCryptoAnalysis> =
+            serde_json::from_value(serde_json::Value::Array(results))?;
+        let flagged: Vec<String> = crypto_service::material(&found, policy.expiring_within)
+            .into_iter()
+            .filter(|material| {
+                material
+                    .flags
+                    .iter()
+                    .any(|flag| policy.fail_on_crypto.contains(flag))
+            })
+            .map(|material| material.summary())
+            .collect();
+        let flags: Vec<&str> = policy
+            .fail_on_crypto
+            .iter()
+            .map(|flag| flag.as_str())
+            .collect();
+        rules.push(GateRule {
+            rule: format!("fail-on-crypto {}", flags.join(",")),
+            passed: flagged.is_empty(),
+            detail: match flagged.is_empty() {
+                true => "no certificate or key flagged".to_string(),
+                false => format!(
+                    "{} certificates and keys flagged: {}",
+                    flagged.len(),
+                    flagged.join(", ")
+                ),
+            },
+        });
+    }
+
+    Ok(GateReport {
+        project_id,
+        passed: rules.iter().all(|rule| rule.passed),
+        rules,
+        sarif: None,
+        junit: None,
+        suppressed,
+        expired_suppressions,
+    })
+}
+
+/// CVEs of a project by lowercase severity.
+#[derive(Debug, Default)]
+struct CveCounts {
+    active: HashMap<String, u32>,
+    /// Accepted in the baseline
+    suppressed: HashMap<String, u32>,
+    /// Entries of the baseline past their expiry date, of CVEs still found
+    expired: Vec<BaselineEntry>,
+}
+
+async fn cve_counts<U: ApiServer>(
+    api_server: &U,
+    project_id: Uuid,
+    baseline: Option<&Baseline>,
+) -> Result<CveCounts> {
+    let results =
+        project_service::analysis_results(api_server, project_id, &Analysis::CveCheck).await?;
+    let cves: Vec<LinuxCveCheckAnalysis> =
+        serde_json::from_value(serde_json::Value::Array(results))?;
+
+    let today = Utc::now().date_naive();
+    let mut counts = CveCounts::default();
+    for cve in cves {
+        let suppression = baseline.map_or(Suppression::None, |baseline| {
+            baseline.suppression(&cve, today)
+        });
+        let counted = match suppression {
+            Suppression::Suppressed => &mut counts.suppressed,
+            Suppression::Expired | Suppression::None => &mut counts.active,
+        };
+        *counted.entry(cve.severity.to_lowercase()).or_insert(0) += 1;
+
+        if let (Suppression::Expired, Some(entry)) = (
+            suppression,
+            baseline.and_then(|baseline| baseline.entry(&cve)),
+        ) {
+            if !counts.expired.contains(entry) {
+                counts.expired.push(entry.clone());
+            }
+        }
+    }
+    Ok(counts)
+}
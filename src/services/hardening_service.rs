@@ -0,0 +1,232 @@
+//! Binary hardening analysis: the share of the binaries built with each
+//! mitigation, and the binaries missing the most of them.
+
+use std::path::Path;
+
+use comfy_table::{Cell, CellAlignment, Row, Table};
+
+use crate::{
+    cli::{HardeningFlag, HardeningOptions},
+    output,
+    services::project_service::LinuxHardeningAnalysis,
+    style::{self, Severity},
+};
+
+/// Rows of the table of the binaries when --limit is not given
+const TABLE_ROWS: usize = 20;
+
+/// Mitigations against the exploitation of a binary, counted to rank the
+/// binaries.
+const MITIGATIONS: [Mitigation; 5] = [
+    Mitigation::Canary,
+    Mitigation::Nx,
+    Mitigation::Pie,
+    Mitigation::Relro,
+    Mitigation::Fortify,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mitigation {
+    Canary,
+    Nx,
+    Pie,
+    Relro,
+    Fortify,
+}
+
+impl Mitigation {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Canary => "CANARY",
+            Self::Nx => "NX",
+            Self::Pie => "PIE",
+            Self::Relro => "RELRO",
+            Self::Fortify => "FORTIFY",
+        }
+    }
+
+    /// Whether `binary` was built with the mitigation.
+    fn applied(self, binary: &LinuxHardeningAnalysis) -> bool {
+        match self {
+            Self::Canary => binary.canary,
+            Self::Nx => binary.nx,
+            Self::Pie => binary.has_pie(),
+            Self::Relro => binary.has_relro(),
+            Self::Fortify => binary.fortify,
+        }
+    }
+
+    /// Value of the mitigation in the table, the text reported for PIE
+    /// and RELRO.
+    fn value(self, binary: &LinuxHardeningAnalysis) -> String {
+        match self {
+            Self::Pie => binary.pie.clone(),
+            Self::Relro => binary.relro.clone(),
+            _ => yes_no(self.applied(binary)).to_string(),
+        }
+    }
+}
+
+/// Number of mitigations `binary` was built without.
+fn missing(binary: &LinuxHardeningAnalysis) -> usize {
+    MITIGATIONS
+        .iter()
+        .filter(|mitigation| !mitigation.applied(binary))
+        .count()
+}
+
+fn yes_no(value: bool) -> &'static str {
+    match value {
+        true => "yes",
+        false => "no",
+    }
+}
+
+impl HardeningOptions {
+    pub fn is_empty(&self) -> bool {
+        self.flags.is_empty() && self.path_prefix.is_none() && self.limit.is_none()
+    }
+
+    /// Whether `binary` is under --path-prefix, the directories compared
+    /// whole and the leading `/` being optional.
+    fn in_scope(&self, binary: &LinuxHardeningAnalysis) -> bool {
+        self.path_prefix.as_ref().is_none_or(|prefix| {
+            Path::new(binary.filename.trim_start_matches('/'))
+                .starts_with(prefix.trim_start_matches('/'))
+        })
+    }
+
+    /// Whether `binary` has all the flags of --filter.
+    fn matches(&self, binary: &LinuxHardeningAnalysis) -> bool {
+        self.flags.iter().all(|flag| match flag {
+            HardeningFlag::NoCanary => !binary.canary,
+            HardeningFlag::NoNx => !binary.nx,
+            HardeningFlag::NoPie => !binary.has_pie(),
+            HardeningFlag::NoRelro => !binary.has_relro(),
+            HardeningFlag::PartialRelro => binary.relro.trim().eq_ignore_ascii_case("partial"),
+            HardeningFlag::NoFortify => !binary.fortify,
+            HardeningFlag::NotStripped => !binary.stripped,
+            HardeningFlag::ExecStack => binary.execstack,
+            HardeningFlag::Suid => binary.suid,
+        })
+    }
+}
+
+/// Binaries of the hardening analysis, with the share of them built with
+/// each mitigation.
+#[derive(Debug)]
+pub struct HardeningReport {
+    /// Binaries under --path-prefix, the ones of the summary
+    total: usize,
+    /// Binaries built with each mitigation, then the stripped ones
+    summary: Vec<(&'static str, usize)>,
+    /// Binaries matching the filters, before --limit
+    matched: usize,
+    /// Binaries matching the filters, missing the most mitigations first
+    pub binaries: Vec<LinuxHardeningAnalysis>,
+    /// Rows of the table
+    rows: usize,
+}
+
+impl HardeningReport {
+    pub fn new(binaries: Vec<LinuxHardeningAnalysis>, options: &HardeningOptions) -> Self {
+        let mut binaries: Vec<LinuxHardeningAnalysis> = binaries
+            .into_iter()
+            .filter(|binary| options.in_scope(binary))
+            .collect();
+        let count = |applied: &dyn Fn(&LinuxHardeningAnalysis) -> bool| {
+            binaries.iter().filter(|binary| applied(binary)).count()
+        };
+        let mut summary: Vec<(&'static str, usize)> = MITIGATIONS
+            .iter()
+            .map(|mitigation| {
+                (
+                    mitigation.name(),
+                    count(&|binary| mitigation.applied(binary)),
+                )
+            })
+            .collect();
+        summary.push(("STRIPPED", count(&|binary| binary.stripped)));
+        let total = binaries.len();
+
+        binaries.retain(|binary| options.matches(binary));
+        // Missing the most mitigations first, then the lowest score
+        binaries.sort_by(|a, b| {
+            missing(b)
+                .cmp(&missing(a))
+                .then_with(|| a.score.cmp(&b.score))
+                .then_with(|| a.filename.cmp(&b.filename))
+        });
+        let matched = binaries.len();
+        if let Some(limit) = options.limit {
+            binaries.truncate(limit);
+        }
+
+        HardeningReport {
+            total,
+            summary,
+            matched,
+            binaries,
+            rows: options.limit.unwrap_or(TABLE_ROWS),
+        }
+    }
+
+    pub fn get_text_output(&self) -> String {
+        let mut summary = Table::new();
+        let mut header = vec![Cell::new("BINARIES")];
+        header.extend(self.summary.iter().map(|(name, _)| Cell::new(name)));
+        summary.set_header(Row::from(header));
+        let mut row = vec![Cell::new(self.total)];
+        row.extend(self.summary.iter().map(|(_, count)| {
+            let percent = match self.total {
+                0 => 0,
+                total => count * 100 / total,
+            };
+            Cell::new(format!("{}%", percent)).set_alignment(CellAlignment::Right)
+        }));
+        summary.add_row(Row::from(row));
+
+        let arch = self.binaries.iter().any(|binary| binary.arch.is_some());
+        let mut table = Table::new();
+        let mut header = vec![Cell::new("PATH")];
+        if arch {
+            header.push(Cell::new("ARCH"));
+        }
+        header.push(Cell::new("MISSING"));
+        header.extend(
+            MITIGATIONS
+                .iter()
+                .map(|mitigation| Cell::new(mitigation.name())),
+        );
+        header.extend([Cell::new("STRIPPED"), Cell::new("SCORE")]);
+        table.set_header(Row::from(header));
+
+        for binary in self.binaries.iter().take(self.rows) {
+            let mut row = vec![Cell::new(&binary.filename)];
+            if arch {
+                row.push(Cell::new(binary.arch.as_deref().unwrap_or("-")));
+            }
+            row.push(Cell::new(missing(binary)).set_alignment(CellAlignment::Right));
+            row.extend(MITIGATIONS.iter().map(|mitigation| {
+                let value = mitigation.value(binary);
+                match mitigation.applied(binary) {
+                    true => Cell::new(value),
+                    false => Severity::High.cell(&value),
+                }
+            }));
+            row.push(style::dim(Cell::new(yes_no(binary.stripped))));
+            row.push(Cell::new(binary.score).set_alignment(CellAlignment::Right));
+            table.add_row(Row::from(row));
+        }
+
+        let mut text = format!("{}\n{}", output::render(summary), output::render(table));
+        let shown = self.binaries.len().min(self.rows);
+        if shown < self.matched {
+            text.push_str(&format!(
+                "\n{} of {} binaries shown, see them all with --limit {}",
+                shown, self.matched, self.matched
+            ));
+        }
+        text
+    }
+}
@@ -0,0 +1,355 @@
+use std::collections::HashSet;
+
+use anyhow::{bail, Result};
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use uuid::Uuid;
+
+use crate::{
+    api::ApiServer,
+    cli::{Analysis, CveFilter, GatePolicy},
+    services::{
+        baseline_service::{Baseline, Suppression},
+        gate_service::GateReport,
+        project_service::{
+            self, LinuxCveCheckAnalysis, LinuxHardeningAnalysis, LinuxPasswordHashAnalysis,
+        },
+    },
+};
+
+/// Analyses whose findings are mapped to test cases
+pub const JUNIT_ANALYSES: [Analysis; 3] = [
+    Analysis::CveCheck,
+    Analysis::Hardening,
+    Analysis::PasswordHash,
+];
+
+/// JUnit XML report of a project, a test suite per analysis whose failed
+/// test cases are the findings.
+#[derive(Debug)]
+pub struct JunitReport {
+    project_id: Uuid,
+    timestamp: DateTime<Utc>,
+    suites: Vec<TestSuite>,
+}
+
+#[derive(Debug)]
+struct TestSuite {
+    name: String,
+    cases: Vec<TestCase>,
+}
+
+#[derive(Debug)]
+struct TestCase {
+    name: String,
+    failure: Option<Failure>,
+}
+
+#[derive(Debug)]
+struct Failure {
+    message: String,
+    kind: String,
+    text: String,
+}
+
+impl JunitReport {
+    fn new(project_id: Uuid, suites: Vec<TestSuite>) -> Self {
+        Self {
+            project_id,
+            timestamp: Utc::now(),
+            suites,
+        }
+    }
+
+    /// The report as XML, in the schema of the Ant JUnit task read by CI
+    /// servers.
+    pub fn xml(&self) -> String {
+        let failures = |suite: &TestSuite| {
+            suite
+                .cases
+                .iter()
+                .filter(|case| case.failure.is_some())
+                .count()
+        };
+        // UTC without offset, as the schema wants it
+        let timestamp = self.timestamp.format("%Y-%m-%dT%H:%M:%S");
+
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        xml += &format!(
+            "<testsuites name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"0\" time=\"0\">\n",
+            env!("CARGO_PKG_NAME"),
+            self.suites
+                .iter()
+                .map(|suite| suite.cases.len())
+                .sum::<usize>(),
+            self.suites.iter().map(failures).sum::<usize>(),
+        );
+        for (id, suite) in self.suites.iter().enumerate() {
+            xml += &format!(
+                "  <testsuite name=\"{}\" id=\"{}\" package=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"0\" skipped=\"0\" time=\"0\" timestamp=\"{}\">\n",
+                escape(&suite.name),
+                id,
+                env!("CARGO_PKG_NAME"),
+                suite.cases.len(),
+                failures(suite),
+                timestamp,
+            );
+            xml += "    <properties>\n";
+            xml += &format!(
+                "      <property name=\"project_id\" value=\"{}\"/>\n",
+                self.project_id
+            );
+            xml += &format!(
+                "      <property name=\"timestamp\" value=\"{}\"/>\n",
+                self.timestamp
+                    .to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+            );
+            xml += "    </properties>\n";
+            for case in &suite.cases {
+                let classname = format!("{}.{}", env!("CARGO_PKG_NAME"), suite.name);
+                let attributes = format!(
+                    "name=\"{}\" classname=\"{}\" time=\"0\"",
+                    escape(&case.name),
+                    escape(&classname)
+                );
+                match &case.failure {
+                    Some(failure) => {
+                        xml += &format!("    <testcase {}>\n", attributes);
+                        xml += &format!(
+                            "      <failure message=\"{}\" type=\"{}\">{}</failure>\n",
+                            escape(&failure.message),
+                            escape(&failure.kind),
+                            escape(&failure.text)
+                        );
+                        xml += "    </testcase>\n";
+                    }
+                    None => xml += &format!("    <testcase {}/>\n", attributes),
+                }
+            }
+            xml += "    <system-out/>\n    <system-err/>\n  </testsuite>\n";
+        }
+        xml += "</testsuites>";
+        xml
+    }
+}
+
+// Findings of the `analyses` of a project, the CVEs matching `cve_filter`
+// failing and the others passing
+pub async fn findings<U: ApiServer>(
+    api_server: &U,
+    project_id: Uuid,
+    analyses: &[Analysis],
+    cve_filter: &CveFilter,
+) -> Result<JunitReport> {
+    let analyses: Vec<&Analysis> = analyses
+        .iter()
+        .filter(|analysis| JUNIT_ANALYSES.contains(analysis))
+        .collect();
+    if analyses.is_empty() {
+        bail!("JUnit output only covers the cve-check, hardening and password-hash analyses");
+    }
+
+    let mut suites = vec![];
+    for analysis in analyses {
+        suites.push(
+            analysis_suite(api_server, project_id, analysis, |cve| {
+                cve_filter.matches(cve)
+            })
+            .await?,
+        );
+    }
+    Ok(JunitReport::new(project_id, suites))
+}
+
+// Rules of the gate `report`, and the findings they are about: the CVEs of
+// the severities over their maximum, those accepted in `baseline` passing,
+// and the findings of the `fail_on` analyses
+pub async fn gate<U: ApiServer>(
+    api_server: &U,
+    report: &GateReport,
+    policy: &GatePolicy,
+    baseline: Option<&Baseline>,
+) -> Result<JunitReport> {
+    let mut suites = vec![TestSuite {
+        name: "policy".to_string(),
+        cases: report
+            .rules
+            .iter()
+            .map(|rule| TestCase {
+                name: rule.rule.clone(),
+                failure: (!rule.passed).then(|| Failure {
+                    message: rule.detail.clone(),
+                    kind: "policy".to_string(),
+                    text: format!("{}: {}", rule.rule, rule.detail),
+                }),
+            })
+            .collect(),
+    }];
+
+    let max_cves = [
+        ("critical", policy.max_critical),
+        ("high", policy.max_high),
+        ("medium", policy.max_medium),
+        ("low", policy.max_low),
+    ];
+    if max_cves.iter().any(|(_, max)| max.is_some()) {
+        let cves: Vec<LinuxCveCheckAnalysis> = serde_json::from_value(serde_json::Value::Array(
+            project_service::analysis_results(api_server, report.project_id, &Analysis::CveCheck)
+                .await?,
+        ))?;
+        let today = Utc::now().date_naive();
+        let suppressed = |cve: &LinuxCveCheckAnalysis| {
+            baseline
+                .is_some_and(|baseline| baseline.suppression(cve, today) == Suppression::Suppressed)
+        };
+
+        let over: HashSet<&str> = max_cves
+            .iter()
+            .filter_map(|(severity, max)| {
+                let max = (*max)?;
+                let count = cves
+                    .iter()
+                    .filter(|cve| cve.severity.eq_ignore_ascii_case(severity) && !suppressed(cve))
+                    .count();
+                (count > max as usize).then_some(*severity)
+            })
+            .collect();
+        suites.push(cve_suite(&cves, |cve| {
+            over.contains(cve.severity.to_ascii_lowercase().as_str()) && !suppressed(cve)
+        }));
+    }
+
+    for analysis in &policy.fail_on {
+        if matches!(analysis, Analysis::Hardening | Analysis::PasswordHash) {
+            suites.push(analysis_suite(api_server, report.project_id, analysis, |_| true).await?);
+        }
+    }
+
+    Ok(JunitReport::new(report.project_id, suites))
+}
+
+async fn analysis_suite<U: ApiServer>(
+    api_server: &U,
+    project_id: Uuid,
+    analysis: &Analysis,
+    failing: impl Fn(&LinuxCveCheckAnalysis) -> bool,
+) -> Result<TestSuite> {
+    let found = project_service::analysis_results(api_server, project_id, analysis).await?;
+    let found = serde_json::Value::Array(found);
+    Ok(match analysis {
+        Analysis::CveCheck => cve_suite(
+            &serde_json::from_value::<Vec<LinuxCveCheckAnalysis>>(found)?,
+            failing,
+        ),
+        Analysis::Hardening => hardening_suite(&serde_json::from_value::<
+            Vec<LinuxHardeningAnalysis>,
+        >(found)?),
+        Analysis::PasswordHash => credentials_suite(&serde_json::from_value::<
+            Vec<LinuxPasswordHashAnalysis>,
+        >(found)?),
+        _ => unreachable!("analysis without test cases"),
+    })
+}
+
+fn cve_suite(
+    cves: &[LinuxCveCheckAnalysis],
+    failing: impl Fn(&LinuxCveCheckAnalysis) -> bool,
+) -> TestSuite {
+    TestSuite {
+        name: suite_name(&Analysis::CveCheck),
+        cases: cves
+            .iter()
+            .map(|cve| TestCase {
+                name: cve.cveid.clone(),
+                failure: failing(cve).then(|| {
+                    let score = cve
+                        .cvss_score()
+                        .map(|score| format!(", CVSS {}", score))
+                        .unwrap_or_default();
+                    Failure {
+                        message: format!("{} ({} {})", cve.summary, cve.product, cve.version),
+                        kind: cve.severity.to_ascii_lowercase(),
+                        text: format!(
+                            "{} in {} {}, severity {}{}: {}",
+                            cve.cveid, cve.product, cve.version, cve.severity, score, cve.summary
+                        ),
+                    }
+                }),
+            })
+            .collect(),
+    }
+}
+
+/// Binaries lacking any of the protections failing, the others passing.
+fn hardening_suite(binaries: &[LinuxHardeningAnalysis]) -> TestSuite {
+    TestSuite {
+        name: suite_name(&Analysis::Hardening),
+        cases: binaries
+            .iter()
+            .map(|binary| {
+                let lacking = binary.missing_protections();
+                TestCase {
+                    name: binary.filename.clone(),
+                    failure: (!lacking.is_empty()).then(|| Failure {
+                        message: format!("Built without {}", lacking.join(", ")),
+                        kind: "hardening".to_string(),
+                        text: format!(
+                            "{} built without {}, hardening score {}",
+                            binary.filename,
+                            lacking.join(", "),
+                            binary.score
+                        ),
+                    }),
+                }
+            })
+            .collect(),
+    }
+}
+
+fn credentials_suite(credentials: &[LinuxPasswordHashAnalysis]) -> TestSuite {
+    // The hash itself is left out of the report
+    TestSuite {
+        name: suite_name(&Analysis::PasswordHash),
+        cases: credentials
+            .iter()
+            .map(|credentials| TestCase {
+                name: credentials.username.clone(),
+                failure: Some(Failure {
+                    message: format!("Password hash of user {} found", credentials.username),
+                    kind: "hardcoded-credentials".to_string(),
+                    text: format!(
+                        "Password hash of user {} stored in the firmware",
+                        credentials.username
+                    ),
+                }),
+            })
+            .collect(),
+    }
+}
+
+/// Name of the suite of `analysis`, as given on the command line.
+fn suite_name(analysis: &Analysis) -> String {
+    analysis
+        .to_possible_value()
+        .map(|value| value.get_name().to_string())
+        .unwrap_or_else(|| analysis.to_string())
+}
+
+/// Escape text for XML attributes and content, dropping the characters XML
+/// can't represent.
+fn escape(text: &str) -> String {
+    text.chars()
+        .filter(|c| matches!(c, '\t' | '\n' | '\r') || !c.is_control())
+        .fold(String::with_capacity(text.len()), |mut escaped, c| {
+            match c {
+                '&' => escaped.push_str("&amp;"),
+                '<' => escaped.push_str("&lt;"),
+                '>' => escaped.push_str("&gt;"),
+                '"' => escaped.push_str("&quot;"),
+                '\'' => escaped.push_str("&apos;"),
+                '\n' => escaped.push_str("&#10;"),
+                c => escaped.push(c),
+            }
+            escaped
+        })
+}
@@ -0,0 +1,341 @@
+//! Kernel analysis: the security relevant CONFIG options of the kernel
+//! checked against the recommended ones, with the version and the CVEs of
+//! the kernel.
+
+use std::fmt;
+
+use anyhow::Result;
+use comfy_table::{Cell, Row, Table};
+use console::style;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{
+    api::ApiServer,
+    cli::{Analysis, CveSort},
+    output::{self, CsvRecord},
+    services::project_service::{self, LinuxCveCheckAnalysis, LinuxKernelAnalysis},
+    style::Severity,
+};
+
+/// Group of the options without a recommendation
+const OTHER_GROUP: &str = "Other";
+
+/// Security relevant options, whether they should be enabled, and their
+/// group, after the Kernel Self Protection Project recommendations.
+const RECOMMENDATIONS: &[(&str, bool, &str)] = &[
+    ("CONFIG_STRICT_KERNEL_RWX", true, "Memory protection"),
+    ("CONFIG_DEBUG_RODATA", true, "Memory protection"),
+    ("CONFIG_STRICT_MODULE_RWX", true, "Memory protection"),
+    ("CONFIG_RANDOMIZE_BASE", true, "Memory protection"),
+    ("CONFIG_RANDOMIZE_MEMORY", true, "Memory protection"),
+    ("CONFIG_HARDENED_USERCOPY", true, "Memory protection"),
+    ("CONFIG_SLAB_FREELIST_RANDOM", true, "Memory protection"),
+    ("CONFIG_SLAB_FREELIST_HARDENED", true, "Memory protection"),
+    ("CONFIG_INIT_ON_ALLOC_DEFAULT_ON", true, "Memory protection"),
+    ("CONFIG_STRICT_DEVMEM", true, "Memory protection"),
+    ("CONFIG_IO_STRICT_DEVMEM", true, "Memory protection"),
+    ("CONFIG_DEVMEM", false, "Memory protection"),
+    ("CONFIG_DEVKMEM", false, "Memory protection"),
+    ("CONFIG_COMPAT_BRK", false, "Memory protection"),
+    ("CONFIG_STACKPROTECTOR", true, "Stack protection"),
+    ("CONFIG_STACKPROTECTOR_STRONG", true, "Stack protection"),
+    ("CONFIG_CC_STACKPROTECTOR", true, "Stack protection"),
+    ("CONFIG_CC_STACKPROTECTOR_STRONG", true, "Stack protection"),
+    ("CONFIG_VMAP_STACK", true, "Stack protection"),
+    ("CONFIG_SCHED_STACK_END_CHECK", true, "Stack protection"),
+    ("CONFIG_FORTIFY_SOURCE", true, "Stack protection"),
+    ("CONFIG_MODULE_SIG", true, "Modules"),
+    ("CONFIG_MODULE_SIG_FORCE", true, "Modules"),
+    ("CONFIG_MODULE_SIG_ALL", true, "Modules"),
+    ("CONFIG_SECURITY", true, "Access control"),
+    ("CONFIG_SECURITY_YAMA", true, "Access control"),
+    ("CONFIG_SECCOMP", true, "Access control"),
+    ("CONFIG_SECCOMP_FILTER", true, "Access control"),
+    ("CONFIG_BUG", true, "Access control"),
+    ("CONFIG_KEXEC", false, "Attack surface"),
+    ("CONFIG_HIBERNATION", false, "Attack surface"),
+    ("CONFIG_LEGACY_PTYS", false, "Attack surface"),
+    ("CONFIG_PROC_KCORE", false, "Attack surface"),
+    ("CONFIG_BINFMT_MISC", false, "Attack surface"),
+    ("CONFIG_USELIB", false, "Attack surface"),
+    ("CONFIG_MODIFY_LDT_SYSCALL", false, "Attack surface"),
+    ("CONFIG_ACPI_CUSTOM_METHOD", false, "Attack surface"),
+    ("CONFIG_DEBUG_FS", false, "Attack surface"),
+];
+
+/// Whether an option is as recommended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Verdict {
+    Good,
+    Bad,
+}
+
+impl fmt::Display for Verdict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Good => write!(f, "good"),
+            Self::Bad => write!(f, "bad"),
+        }
+    }
+}
+
+/// CONFIG option of the kernel analysis, with its verdict when it is
+/// security relevant.
+#[derive(Debug, Serialize)]
+pub struct KernelOption {
+    pub name: String,
+    pub enabled: bool,
+    /// Whether the option should be enabled
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recommended: Option<bool>,
+    pub group: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verdict: Option<Verdict>,
+}
+
+impl KernelOption {
+    /// `found` rated with the recommendation of the server, else with the
+    /// built-in one.
+    pub fn new(found: &LinuxKernelAnalysis) -> Self {
+        let builtin = recommendation(&found.name);
+        let recommended = found.recommended.or(builtin.map(|(enabled, _)| enabled));
+        let group = found
+            .group
+            .clone()
+            .or(builtin.map(|(_, group)| group.to_string()))
+            .unwrap_or_else(|| OTHER_GROUP.to_string());
+
+        KernelOption {
+            name: found.name.clone(),
+            enabled: found.enabled,
+            recommended,
+            group,
+            verdict: recommended.map(|recommended| match recommended == found.enabled {
+                true => Verdict::Good,
+                false => Verdict::Bad,
+            }),
+        }
+    }
+}
+
+impl CsvRecord for KernelOption {
+    const HEADER: &'static [&'static str] = &["name", "enabled", "recommended", "verdict", "group"];
+
+    fn record(&self, _list_delimiter: &str) -> Vec<String> {
+        vec![
+            self.name.clone(),
+            self.enabled.to_string(),
+            self.recommended
+                .map(|recommended| recommended.to_string())
+                .unwrap_or_default(),
+            self.verdict
+                .map(|verdict| verdict.to_string())
+                .unwrap_or_default(),
+            self.group.clone(),
+        ]
+    }
+}
+
+/// Recommended state and group of the option `name`, with or without its
+/// `CONFIG_` prefix, if security relevant.
+pub fn recommendation(name: &str) -> Option<(bool, &'static str)> {
+    let name = name.trim().to_ascii_uppercase();
+    let name = match name.starts_with("CONFIG_") {
+        true => name,
+        false => format!("CONFIG_{}", name),
+    };
+    RECOMMENDATIONS
+        .iter()
+        .find(|(option, _, _)| *option == name)
+        .map(|(_, enabled, group)| (*enabled, *group))
+}
+
+/// Whether `cve` is a CVE of the Linux kernel.
+pub fn is_kernel_cve(cve: &LinuxCveCheckAnalysis) -> bool {
+    matches!(
+        cve.product.to_ascii_lowercase().as_str(),
+        "linux_kernel" | "linux" | "kernel"
+    )
+}
+
+/// Kernel of a project: its version, the CONFIG options with their
+/// verdict and the CVEs of the kernel.
+#[derive(Debug, Serialize)]
+pub struct KernelReport {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// Share of the security relevant options as recommended, out of 100
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub score: Option<u8>,
+    /// Security relevant options as recommended
+    pub good: usize,
+    /// Security relevant options, as recommended or not
+    pub rated: usize,
+    pub options: Vec<KernelOption>,
+    /// CVEs of the kernel, when the cve-check analysis was fetched
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cves: Option<Vec<LinuxCveCheckAnalysis>>,
+    #[serde(skip)]
+    only_failing: bool,
+}
+
+impl KernelReport {
+    /// Report of the options `found`, only the ones not as recommended
+    /// kept when `only_failing`, the score being about all of them.
+    pub fn new(found: &[LinuxKernelAnalysis], only_failing: bool) -> Self {
+        let mut options: Vec<KernelOption> = found.iter().map(KernelOption::new).collect();
+        let rated = options
+            .iter()
+            .filter(|option| option.verdict.is_some())
+            .count();
+        let good = options
+            .iter()
+            .filter(|option| option.verdict == Some(Verdict::Good))
+            .count();
+        if only_failing {
+            options.retain(|option| option.verdict == Some(Verdict::Bad));
+        }
+
+        KernelReport {
+            version: None,
+            score: (rated > 0).then(|| (good * 100 / rated) as u8),
+            good,
+            rated,
+            options,
+            cves: None,
+            only_failing,
+        }
+    }
+
+    pub fn get_text_output(&self) -> String {
+        let mut lines = vec![];
+        if let Some(version) = &self.version {
+            lines.push(format!("Kernel {}", style(version).bold()));
+        }
+        if let Some(score) = self.score {
+            lines.push(format!(
+                "Score {}, {} of {} security options as recommended",
+                style(score).bold(),
+                self.good,
+                self.rated
+            ));
+        }
+
+        // Groups in the order of the recommendations, the others last
+        let mut groups: Vec<&str> = vec![];
+        for group in RECOMMENDATIONS
+            .iter()
+            .map(|(_, _, group)| *group)
+            .chain(self.options.iter().map(|option| option.group.as_str()))
+        {
+            if !groups.contains(&group) && group != OTHER_GROUP {
+                groups.push(group);
+            }
+        }
+        groups.push(OTHER_GROUP);
+
+        for group in groups {
+            let options: Vec<&KernelOption> = self
+                .options
+                .iter()
+                .filter(|option| option.group == group)
+                .collect();
+            if options.is_empty() {
+                continue;
+            }
+
+            let mut table = Table::new();
+            table.set_header(Row::from(vec![
+                Cell::new("OPTION"),
+                Cell::new("ENABLED"),
+                Cell::new("RECOMMENDED"),
+                Cell::new("VERDICT"),
+            ]));
+            for option in options {
+                let yes_no = |enabled: bool| if enabled { "yes" } else { "no" };
+                table.add_row(Row::from(vec![
+                    Cell::new(&option.name),
+                    Cell::new(yes_no(option.enabled)),
+                    Cell::new(option.recommended.map_or("-", yes_no)),
+                    match option.verdict {
+                        Some(Verdict::Bad) => Severity::High.cell("bad"),
+                        Some(Verdict::Good) => Cell::new("good"),
+                        None => Cell::new("-"),
+                    },
+                ]));
+            }
+            lines.push(String::new());
+            lines.push(style(group).bold().to_string());
+            lines.push(output::render(table));
+        }
+        if self.only_failing && self.options.is_empty() {
+            lines.push(String::new());
+            lines.push("All the security options as recommended".to_string());
+        }
+
+        if let Some(cves) = &self.cves {
+            lines.push(String::new());
+            match cves.is_empty() {
+                true => lines.push("No kernel CVEs".to_string()),
+                false => {
+                    lines.push(
+                        style(format!("Kernel CVEs ({})", cves.len()))
+                            .bold()
+                            .to_string(),
+                    );
+                    lines.push(LinuxCveCheckAnalysis::get_table_from_list(cves));
+                }
+            }
+        }
+        lines.join("\n")
+    }
+}
+
+// Kernel of a project: all the options of the kernel analysis, the version
+// of the overview and the kernel CVEs of the cve-check analysis
+pub async fn report<U: ApiServer>(
+    api_server: &mut U,
+    project_id: Uuid,
+    only_failing: bool,
+) -> Result<KernelReport> {
+    let results =
+        project_service::analysis_results(&*api_server, project_id, &Analysis::Kernel).await?;
+    let found: Vec<LinuxKernelAnalysis> =
+        serde_json::from_value(serde_json::Value::Array(results))?;
+    let mut report = KernelReport::new(&found, only_failing);
+
+    // Both are extras, the options are shown without them
+    report.cves =
+        match project_service::analysis_results(&*api_server, project_id, &Analysis::CveCheck)
+            .await
+            .and_then(|results| {
+                Ok(serde_json::from_value::<Vec<LinuxCveCheckAnalysis>>(
+                    serde_json::Value::Array(results),
+                )?)
+            }) {
+            Ok(mut cves) => {
+                cves.retain(is_kernel_cve);
+                project_service::sort_cves(&mut cves, CveSort::Cvss);
+                Some(cves)
+            }
+            Err(e) => {
+                tracing::warn!("Kernel CVEs not available: {:#}", e);
+                None
+            }
+        };
+    report.version = match api_server.overview(&project_id).await {
+        Ok(overview) => overview["info"]["kernel"]
+            .as_str()
+            .filter(|version| !version.is_empty())
+            .map(str::to_string),
+        Err(e) => {
+            tracing::debug!("Overview not available: {}", e);
+            None
+        }
+    }
+    .or_else(|| report.cves.as_ref()?.first().map(|cve| cve.version.clone()));
+
+    Ok(report)
+}
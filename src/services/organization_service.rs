@@ -1,10 +1,10 @@
-use crate::api::ApiServer;
-use anyhow::Result;
+use crate::{api::ApiServer, output};
+use anyhow::{bail, Result};
 use comfy_table::{Cell, Row, Table};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct OrganizationData {
     pub id: Uuid,
     pub name: String,
@@ -40,7 +40,7 @@ impl OrganizationData {
             table.add_row(row);
         }
 
-        table.to_string()
+        output::render(table)
     }
 }
 
@@ -50,6 +50,59 @@ pub async fn list<U: ApiServer>(api_server: &mut U) -> Result<Vec<OrganizationDa
     Ok(orgs)
 }
 
+// Organization of the account with the ID or name `reference`, failing when
+// the account isn't a member of it. An ID is used as is when the
+// organizations of the account can't be listed.
+pub async fn resolve<U: ApiServer>(
+    api_server: &mut U,
+    reference: &str,
+) -> Result<OrganizationData> {
+    let id = Uuid::parse_str(reference).ok();
+    let orgs = match api_server.organization_list().await {
+        Ok(orgs) => orgs,
+        Err(err) => match id {
+            Some(id) => {
+                tracing::debug!("Organizations not listed, using {} as is: {}", id, err);
+                return Ok(OrganizationData {
+                    id,
+                    name: String::new(),
+                    description: String::new(),
+                    built_in: false,
+                });
+            }
+            None => return Err(err.into()),
+        },
+    };
+
+    if let Some(org) = orgs.iter().find(|org| Some(org.id) == id) {
+        return Ok(org.clone());
+    }
+    let named: Vec<&OrganizationData> = orgs
+        .iter()
+        .filter(|org| org.name.eq_ignore_ascii_case(reference))
+        .collect();
+    match named.as_slice() {
+        [org] => Ok((*org).clone()),
+        [] => bail!(
+            "The account is not a member of the organization `{}`, its organizations are: {}",
+            reference,
+            orgs.iter()
+                .map(|org| org.name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        _ => bail!(
+            "Several organizations are named `{}`, use the ID: {}",
+            reference,
+            named
+                .iter()
+                .map(|org| org.id.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
+
 // Delete an organization
 pub async fn delete<U: ApiServer>(api_server: &mut U, id: Uuid) -> Result<()> {
     api_server.organization_delete(&id).await?;
@@ -0,0 +1,251 @@
+//! Password hashes found in the firmware, with their algorithm, told by
+//! their `$id$` prefix, and whether it is weak. The hashes are masked unless
+//! asked for.
+
+use std::fmt;
+
+use comfy_table::{Cell, Row, Table};
+use serde::Serialize;
+
+use crate::{
+    output::{self, CsvRecord},
+    services::project_service::LinuxPasswordHashAnalysis,
+    style::{self, Severity},
+};
+
+/// Shown in place of the hashes
+const MASK: &str = "********";
+
+/// Algorithm of a password hash, told by its format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum HashAlgorithm {
+    /// No password at all
+    Empty,
+    /// Locked account, its hash starting with `!` or `*`
+    Locked,
+    Des,
+    BsdiDes,
+    Md5Crypt,
+    SunMd5,
+    AprMd5,
+    Sha1Crypt,
+    Bcrypt,
+    Sha256Crypt,
+    Sha512Crypt,
+    Scrypt,
+    Yescrypt,
+    GostYescrypt,
+    Unknown,
+}
+
+impl HashAlgorithm {
+    pub fn detect(hash: &str) -> Self {
+        let hash = hash.trim();
+        if hash.is_empty() {
+            return Self::Empty;
+        }
+        if hash.starts_with(['!', '*']) {
+            return Self::Locked;
+        }
+        if let Some(rest) = hash.strip_prefix('$') {
+            return match rest.split('$').next().unwrap_or_default() {
+                "1" => Self::Md5Crypt,
+                "2" | "2a" | "2b" | "2x" | "2y" => Self::Bcrypt,
+                "5" => Self::Sha256Crypt,
+                "6" => Self::Sha512Crypt,
+                "7" => Self::Scrypt,
+                "y" => Self::Yescrypt,
+                "gy" => Self::GostYescrypt,
+                "sha1" => Self::Sha1Crypt,
+                "apr1" => Self::AprMd5,
+                // `$md5$` or `$md5,rounds=N$`
+                id if id.starts_with("md5") => Self::SunMd5,
+                _ => Self::Unknown,
+            };
+        }
+
+        let crypt_chars = |text: &str| {
+            text.chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '/')
+        };
+        match hash.strip_prefix('_') {
+            Some(rest) if rest.len() == 19 && crypt_chars(rest) => Self::BsdiDes,
+            None if hash.len() == 13 && crypt_chars(hash) => Self::Des,
+            _ => Self::Unknown,
+        }
+    }
+
+    /// Whether the hashes of the algorithm are cracked in little time.
+    pub fn is_weak(self) -> bool {
+        matches!(
+            self,
+            Self::Des | Self::BsdiDes | Self::Md5Crypt | Self::SunMd5 | Self::AprMd5
+        )
+    }
+}
+
+impl fmt::Display for HashAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Empty => "empty",
+            Self::Locked => "locked",
+            Self::Des => "DES",
+            Self::BsdiDes => "BSDi DES",
+            Self::Md5Crypt => "MD5-crypt",
+            Self::SunMd5 => "Sun MD5",
+            Self::AprMd5 => "Apache MD5",
+            Self::Sha1Crypt => "SHA1-crypt",
+            Self::Bcrypt => "bcrypt",
+            Self::Sha256Crypt => "SHA-256-crypt",
+            Self::Sha512Crypt => "SHA-512-crypt",
+            Self::Scrypt => "scrypt",
+            Self::Yescrypt => "yescrypt",
+            Self::GostYescrypt => "gost-yescrypt",
+            Self::Unknown => "unknown",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// How much the password of an account is at risk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Verdict {
+    /// Anyone can log in
+    Empty,
+    /// Hashed with a weak algorithm
+    Weak,
+    /// No login with a password
+    Locked,
+    /// Format not recognized, to check by hand
+    Unknown,
+    Ok,
+}
+
+impl fmt::Display for Verdict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Empty => "empty",
+            Self::Weak => "weak",
+            Self::Locked => "locked",
+            Self::Unknown => "unknown",
+            Self::Ok => "ok",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Account of the password-hash analysis.
+#[derive(Debug, Serialize)]
+pub struct Credential {
+    pub username: String,
+    pub algorithm: HashAlgorithm,
+    pub verdict: Verdict,
+    /// File the hash was found in, when the server reports it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    /// Masked unless asked for, but for its `$id$` prefix
+    pub hash: String,
+}
+
+impl Credential {
+    pub fn new(found: &LinuxPasswordHashAnalysis, show_secrets: bool) -> Self {
+        let algorithm = HashAlgorithm::detect(found.password());
+        let verdict = match algorithm {
+            HashAlgorithm::Empty => Verdict::Empty,
+            HashAlgorithm::Locked => Verdict::Locked,
+            HashAlgorithm::Unknown => Verdict::Unknown,
+            algorithm if algorithm.is_weak() => Verdict::Weak,
+            _ => Verdict::Ok,
+        };
+        let hash = match show_secrets {
+            true => found.password().to_string(),
+            false => mask(found.password()),
+        };
+
+        Credential {
+            username: found.username.clone(),
+            algorithm,
+            verdict,
+            file: found.file.clone(),
+            hash,
+        }
+    }
+
+    /// Whether the account can be logged in without a password, or with
+    /// one hashed with a weak algorithm.
+    pub fn is_weak(&self) -> bool {
+        matches!(self.verdict, Verdict::Empty | Verdict::Weak)
+    }
+
+    pub fn get_table_from_list(list: &[Credential]) -> String {
+        let file = list.iter().any(|credential| credential.file.is_some());
+
+        let mut table = Table::new();
+        let mut header = vec![
+            Cell::new("USERNAME"),
+            Cell::new("ALGORITHM"),
+            Cell::new("VERDICT"),
+        ];
+        if file {
+            header.push(Cell::new("FILE"));
+        }
+        header.push(Cell::new("HASH"));
+        table.set_header(Row::from(header));
+
+        for credential in list {
+            let verdict = credential.verdict.to_string();
+            let mut row = vec![
+                Cell::new(&credential.username),
+                Cell::new(credential.algorithm),
+                match credential.verdict {
+                    Verdict::Empty => Severity::Critical.cell(&verdict),
+                    Verdict::Weak => Severity::High.cell(&verdict),
+                    Verdict::Unknown => Severity::Medium.cell(&verdict),
+                    Verdict::Locked | Verdict::Ok => Cell::new(verdict),
+                },
+            ];
+            if file {
+                row.push(Cell::new(credential.file.as_deref().unwrap_or("-")));
+            }
+            row.push(style::dim(Cell::new(&credential.hash)));
+            table.add_row(Row::from(row));
+        }
+
+        output::render(table)
+    }
+}
+
+impl CsvRecord for Credential {
+    const HEADER: &'static [&'static str] = &["username", "algorithm", "verdict", "file", "hash"];
+
+    fn record(&self, _list_delimiter: &str) -> Vec<String> {
+        vec![
+            self.username.clone(),
+            self.algorithm.to_string(),
+            self.verdict.to_string(),
+            self.file.clone().unwrap_or_default(),
+            self.hash.clone(),
+        ]
+    }
+}
+
+/// `hash` masked, but for its `$id$` prefix or the `!` locking the account.
+fn mask(hash: &str) -> String {
+    let hash = hash.trim();
+    if hash.is_empty() {
+        return String::new();
+    }
+    let prefix = match hash.strip_prefix('$') {
+        Some(rest) => match rest.split_once('$') {
+            Some((id, _)) => format!("${}$", id),
+            None => String::new(),
+        },
+        None => hash
+            .chars()
+            .take_while(|c| matches!(c, '!' | '*'))
+            .collect(),
+    };
+    format!("{}{}", prefix, MASK)
+}
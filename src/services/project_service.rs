@@ -1,18 +1,91 @@
-use std::{fs::File, path::Path};
-
-use anyhow::{anyhow, Result};
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, HashSet},
+    fmt,
+    fs::File,
+    io::IsTerminal,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, bail, Result};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
 use comfy_table::{Cell, CellAlignment, Row, Table};
+use console::{style, Color};
+use futures::stream::{self, Stream, StreamExt};
+use indicatif::{ProgressBar, ProgressStyle};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use crate::{api::ApiServer, cli::Analysis};
+use crate::{
+    api::{ApiServer, ApiServerError, UploadOptions},
+    cli::{
+        self, Analysis, CveFilter, CveSort, ExitCode, ProjectColumn, ProjectFilter, ProjectRef,
+        ProjectSort, Prompt,
+    },
+    output::{self, CsvRecord},
+    services::usage_service,
+    style::{self, Severity},
+};
 
 pub const FILE_SIZE_LIMIT: usize = 2147483648; // 2 Gb
+/// Maximum length of a project name, in characters
+pub const MAX_NAME_LEN: usize = 255;
 pub const CVE_DETAILS_BASE_URL: &str = "https://nvd.nist.gov/vuln/detail/";
 
+/// Number of projects requested per page when listing them
+const LIST_PAGE_SIZE: u32 = 100;
+
 #[derive(Deserialize, Debug)]
 pub struct ProjectIdDTO {
     pub id: Uuid,
+    /// SHA-256 of the firmware received, if computed by the server
+    pub sha256: Option<String>,
+}
+
+/// Analysis queued by a rescan of a project.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RescanJob {
+    /// Reference of the analysis job, if sent by the server
+    #[serde(default, alias = "id", skip_serializing_if = "Option::is_none")]
+    pub job_id: Option<String>,
+}
+
+/// Tags to add to and remove from a project.
+#[derive(Serialize, Debug)]
+pub struct ProjectTagsUpdateDTO<'a> {
+    pub add: &'a [String],
+    pub remove: &'a [String],
+}
+
+/// Firmware the server is asked to accept before uploading it, by
+/// `create --dry-run`.
+#[derive(Serialize, Debug)]
+pub struct UploadValidationDTO<'a> {
+    pub name: &'a str,
+    pub r#type: &'a str,
+    pub subtype: &'a str,
+    pub filename: &'a str,
+    pub size: u64,
+    pub sha256: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub organization: Option<&'a str>,
+}
+
+/// Tags of a project.
+#[derive(Deserialize, Debug)]
+pub struct ProjectTagsDTO {
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+/// Changed fields of a project, the others are left untouched.
+#[derive(Serialize, Debug)]
+pub struct ProjectUpdateDTO<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<&'a str>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -27,133 +100,271 @@ pub struct Project {
     pub project_type: String,
     pub project_subtype: String,
     pub creation_date: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub update_date: Option<String>,
+    /// Number of CVEs found, when reported by the server
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cve_count: Option<u32>,
+    /// Tags of the project, missing with older servers
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
 }
 
 impl Project {
-    pub fn get_table_from_list(list: &[Project]) -> String {
+    /// Names of the serialized fields, for the templates of the list
+    pub const FIELDS: &'static [&'static str] = &[
+        "description",
+        "id",
+        "name",
+        "status",
+        "original_name",
+        "organization_name",
+        "score",
+        "project_type",
+        "project_subtype",
+        "creation_date",
+        "update_date",
+        "cve_count",
+        "tags",
+    ];
+
+    /// Value of `column`, list values joined with `list_delimiter`.
+    pub fn column(&self, column: ProjectColumn, list_delimiter: &str) -> String {
+        match column {
+            ProjectColumn::Id => self.id.to_string(),
+            ProjectColumn::Name => self.name.clone(),
+            ProjectColumn::Type => self.project_type.clone(),
+            ProjectColumn::Subtype => self.project_subtype.clone(),
+            ProjectColumn::Status => self.status.clone(),
+            ProjectColumn::Score => self.score.to_string(),
+            ProjectColumn::CveCount => self
+                .cve_count
+                .map(|count| count.to_string())
+                .unwrap_or_default(),
+            ProjectColumn::Organization => self.organization_name.clone().unwrap_or_default(),
+            ProjectColumn::Created => self.creation_date.clone(),
+            ProjectColumn::Updated => self.update_date.clone().unwrap_or_default(),
+            ProjectColumn::Tags => self.tags.join(list_delimiter),
+            ProjectColumn::Description => self.description.clone().unwrap_or_default(),
+            ProjectColumn::OriginalName => self.original_name.clone(),
+        }
+    }
+
+    pub fn get_table_from_list(list: &[Project], columns: &[ProjectColumn]) -> String {
         let mut table = Table::new();
         // table.max_column_width = 40;
-        table.add_row(Row::from(vec![
-            Cell::new("NAME"),
-            Cell::new("ID"),
-            Cell::new("DESCRIPTION"),
-            Cell::new("ORIGINAL NAME"),
-            Cell::new("SCORE"),
-            Cell::new("TYPE"),
-            Cell::new("SUBTYPE"),
-            Cell::new("STATUS"),
-        ]));
-
-        let rows: Vec<Row> = list
-            .iter()
-            .map(|project| {
-                let desc = project
-                    .description
-                    .as_ref()
-                    .map(|s| s.to_string())
-                    .unwrap_or_default();
-                vec![
-                    Cell::new(&project.name),
-                    Cell::new(project.id),
-                    Cell::new(desc),
-                    Cell::new(&project.original_name),
-                    Cell::new(project.score),
-                    Cell::new(&project.project_type),
-                    Cell::new(&project.project_subtype),
-                    Cell::new(&project.status),
-                ]
-            })
-            .map(Row::from)
-            .collect();
+        table.add_row(Row::from(
+            columns.iter().map(|column| Cell::new(column.title())),
+        ));
 
-        for row in rows {
-            table.add_row(row);
+        for project in list {
+            table.add_row(Row::from(
+                columns
+                    .iter()
+                    .map(|column| Cell::new(project.column(*column, ", "))),
+            ));
         }
 
-        table.to_string()
+        output::render(table)
     }
 }
 
-// Linux/Container Analysis
-#[derive(Debug, Serialize, Deserialize)]
-pub struct LinuxProjectOverview {
-    pub kernel_security: Option<u16>,
-    pub password_hash: u16,
-    pub security_scan: u16,
-    pub cve_check: LinuxProjectOverviewCveCheck,
-    pub code: LinuxProjectOverviewCode,
-    pub binary: LinuxProjectOverviewBinary,
-    // pub project: Project,
-    pub info: LinuxInfo,
-}
+// Project overview
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct LinuxInfo {
-    pub arch: String,
-    pub banner: Option<String>,
+/// Overview of a project of any type, the fields missing with some types
+/// left empty and the unknown ones ignored.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct ProjectOverview {
+    pub project: OverviewProject,
+    pub cve_check: Option<OverviewCveCheck>,
+    pub info: OverviewInfo,
+    /// The overview as received, for the json output
+    #[serde(skip)]
+    pub raw: serde_json::Value,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct OverviewProject {
+    pub name: Option<String>,
+    pub project_type: Option<String>,
+    pub status: Option<String>,
+    pub score: Option<f64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct OverviewCveCheck {
+    pub severity: OverviewSeverity,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct OverviewSeverity {
+    /// Reported by the newer servers only
+    pub critical: Option<u32>,
+    pub high: u32,
+    pub medium: u32,
+    pub low: u32,
+}
+
+/// Facts about the firmware, depending on the type of the project.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct OverviewInfo {
+    pub arch: Option<String>,
+    pub endianness: Option<String>,
+    pub os: Option<String>,
+    pub os_name: Option<String>,
+    pub os_version: Option<String>,
     pub kernel: Option<String>,
     pub kernelc: Option<String>,
     pub libc: Option<String>,
-}
+    pub banner: Option<String>,
+    pub manufacturer: Option<String>,
+    pub dxe_no: Option<u32>,
+    pub pei_no: Option<u32>,
+    pub s3mit: Option<String>,
+    pub functions_no: Option<u32>,
+    pub tasks_no: Option<u32>,
+    pub symbols_no: Option<u32>,
+}
+
+/// Width of the bar of the CVEs by severity
+const SEVERITY_BAR_WIDTH: u32 = 30;
+
+impl ProjectOverview {
+    pub fn from_value(raw: serde_json::Value) -> Result<Self> {
+        let mut overview: Self = serde_json::from_value(raw.clone())
+            .map_err(|err| anyhow!("invalid overview: {}", err))?;
+        overview.raw = raw;
+        Ok(overview)
+    }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct LinuxProjectOverviewCveCheck {
-    pub severity: LinuxProjectOverviewSeverity,
-}
+    /// Summary of the project, colored when printed to a terminal.
+    pub fn get_text_output(&self) -> String {
+        let project = &self.project;
+        let header: Vec<String> = [
+            project
+                .name
+                .as_ref()
+                .map(|name| style(name).bold().to_string()),
+            project.project_type.clone(),
+            project.status.as_ref().map(|status| {
+                let color = match status.to_uppercase().as_str() {
+                    "COMPLETED" | "DONE" | "SUCCESS" => Color::Green,
+                    "FAILED" | "ERROR" => Color::Red,
+                    _ => Color::Yellow,
+                };
+                style(status).fg(color).to_string()
+            }),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+
+        let mut facts = vec![];
+        if let Some(score) = project.score {
+            let color = match score {
+                score if score >= 75.0 => Color::Green,
+                score if score >= 50.0 => Color::Yellow,
+                _ => Color::Red,
+            };
+            facts.push(("Score", style(score).fg(color).bold().to_string()));
+        }
+        if let Some(cve_check) = &self.cve_check {
+            facts.push(("CVEs", cve_check.severity.bar()));
+        }
+        facts.extend(self.info.facts());
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct LinuxProjectOverviewCode {
-    pub vulnerabilities: u16,
-    pub files_affected: u16,
+        let width = facts.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+        let mut lines = vec![];
+        if !header.is_empty() {
+            lines.push(header.join("  "));
+        }
+        lines.extend(
+            facts
+                .iter()
+                .map(|(name, value)| format!("{:width$}  {}", name, value, width = width)),
+        );
+        lines.join("\n")
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct LinuxProjectOverviewBinary {
-    pub severity: LinuxProjectOverviewSeverity,
-}
+impl OverviewSeverity {
+    /// Bar of the CVEs, a segment per severity shaded apart for the terminals
+    /// without colors, followed by their numbers.
+    fn bar(&self) -> String {
+        let severities = [
+            (self.critical, "critical", Severity::Critical),
+            (Some(self.high), "high", Severity::High),
+            (Some(self.medium), "medium", Severity::Medium),
+            (Some(self.low), "low", Severity::Low),
+        ];
+        let total: u32 = severities.iter().filter_map(|(count, ..)| *count).sum();
+        if total == 0 {
+            return "none".to_string();
+        }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct LinuxProjectOverviewSeverity {
-    pub low: u16,
-    pub medium: u16,
-    pub high: u16,
-}
+        let bar: String = severities
+            .iter()
+            .filter_map(|(count, _, severity)| {
+                let count = count.filter(|count| *count > 0)?;
+                // Each severity found gets at least a block
+                let len = (count * SEVERITY_BAR_WIDTH / total).max(1);
+                Some(
+                    severity
+                        .paint(severity.block().repeat(len as usize))
+                        .to_string(),
+                )
+            })
+            .collect();
+        let counts: Vec<String> = severities
+            .iter()
+            .filter_map(|(count, name, ..)| count.map(|count| format!("{} {}", count, name)))
+            .collect();
 
-impl LinuxProjectOverview {
-    pub fn get_text_output(project: &LinuxProjectOverview) -> String {
-        let banner = project
-            .info
-            .banner
-            .as_ref()
-            .map(|s| s.to_string())
-            .unwrap_or_default();
-        let kernel = project
-            .info
-            .kernel
-            .as_ref()
-            .map(|s| s.to_string())
-            .unwrap_or_default();
-        let kernelc = project
-            .info
-            .kernelc
-            .as_ref()
-            .map(|s| s.to_string())
-            .unwrap_or_default();
-        let libc = project
-            .info
-            .libc
-            .as_ref()
-            .map(|s| s.to_string())
-            .unwrap_or_default();
-        let arch = &project.info.arch;
+        format!("{} {}", bar, counts.join(", "))
+    }
+}
 
-        format!(
-            "Architecture: {}\nBanner: {}\nLib C: {}\nKernel version: {}\nKernel compiler: {}",
-            arch, banner, libc, kernel, kernelc
-        )
+impl OverviewInfo {
+    /// Names and values of the facts reported.
+    pub fn facts(&self) -> Vec<(&'static str, String)> {
+        let os = match (&self.os_name, &self.os_version) {
+            (Some(name), Some(version)) => Some(format!("{} {}", name, version)),
+            (name, version) => name.clone().or_else(|| version.clone()),
+        };
+        let arch = match (&self.arch, &self.endianness) {
+            (Some(arch), Some(endianness)) => Some(format!("{} ({})", arch, endianness)),
+            (arch, _) => arch.clone(),
+        };
+        let count = |count: Option<u32>| count.map(|count| count.to_string());
+
+        [
+            ("Architecture", arch),
+            ("OS", os),
+            ("OS version", self.os.clone()),
+            ("Kernel version", self.kernel.clone()),
+            ("Kernel compiler", self.kernelc.clone()),
+            ("Lib C", self.libc.clone()),
+            ("Banner", self.banner.clone()),
+            ("Manufacturer", self.manufacturer.clone()),
+            ("DXE number", count(self.dxe_no)),
+            ("PEI number", count(self.pei_no)),
+            ("S3 mitigation", self.s3mit.clone()),
+            ("Functions", count(self.functions_no)),
+            ("Tasks", count(self.tasks_no)),
+            ("Symbols", count(self.symbols_no)),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| Some((name, value.filter(|value| !value.is_empty())?)))
+        .collect()
     }
 }
 
+// Linux/Container Analysis
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ProjectAnalysis {
     pub(crate) name: String,
@@ -164,8 +375,13 @@ pub struct ProjectAnalysis {
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct LinuxHardeningAnalysis {
+    /// Path of the binary in the firmware
+    #[serde(alias = "path")]
     pub filename: String,
     pub r#type: String,
+    /// Architecture the binary was built for, when the server reports it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub arch: Option<String>,
     pub score: u8,
     pub compiler: Option<String>,
     pub stripped: bool,
@@ -178,7 +394,67 @@ pub struct LinuxHardeningAnalysis {
     pub relro: String,
 }
 
+impl CsvRecord for LinuxHardeningAnalysis {
+    const HEADER: &'static [&'static str] = &[
+        "filename",
+        "type",
+        "score",
+        "canary",
+        "fortify",
+        "nx",
+        "pie",
+        "relro",
+        "execstack",
+        "suid",
+        "stripped",
+        "compiler",
+        "arch",
+    ];
+
+    fn record(&self, _list_delimiter: &str) -> Vec<String> {
+        vec![
+            self.filename.clone(),
+            self.r#type.clone(),
+            self.score.to_string(),
+            self.canary.to_string(),
+            self.fortify.to_string(),
+            self.nx.to_string(),
+            self.pie.clone(),
+            self.relro.clone(),
+            self.execstack.to_string(),
+            self.suid.to_string(),
+            self.stripped.to_string(),
+            self.compiler.clone().unwrap_or_default(),
+            self.arch.clone().unwrap_or_default(),
+        ]
+    }
+}
+
 impl LinuxHardeningAnalysis {
+    /// Whether the binary is position independent.
+    pub fn has_pie(&self) -> bool {
+        enabled(&self.pie)
+    }
+
+    /// Whether the binary has partial or full RELRO.
+    pub fn has_relro(&self) -> bool {
+        enabled(&self.relro)
+    }
+
+    /// Protections the binary was built without.
+    pub fn missing_protections(&self) -> Vec<&'static str> {
+        [
+            ("stack canary", !self.canary),
+            ("NX", !self.nx),
+            ("PIE", !self.has_pie()),
+            ("RELRO", !self.has_relro()),
+            ("non executable stack", self.execstack),
+        ]
+        .into_iter()
+        .filter_map(|(protection, lacking)| lacking.then_some(protection))
+        .collect()
+    }
+
     pub fn get_table_from_list(list: &[LinuxHardeningAnalysis]) -> String {
         let mut table = Table::new();
         // table.max_column_width = 50;
@@ -223,28 +499,109 @@ impl LinuxHardeningAnalysis {
             table.add_row(row);
         }
 
-        table.to_string()
+        output::render(table)
     }
 }
 
+/// Whether a protection reported as text, like `full` or `no`, is there.
+fn enabled(value: &str) -> bool {
+    !matches!(
+        value.trim().to_ascii_lowercase().as_str(),
+        "" | "no" | "none" | "false"
+    )
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct LinuxCveCheckAnalysis {
     pub cveid: String,
     pub severity: String,
     pub summary: String,
     pub vendor: String,
+    /// Affected package
     pub product: String,
     pub version: String,
     pub vector: String,
     pub patch: Option<String>,
+    /// Version of the package fixing the CVE, when known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fixed_version: Option<String>,
     pub references: Option<String>,
-    pub cvss: Option<serde_json::Value>,
+    pub cvss: Option<CveCvss>,
     pub problems: Option<serde_json::Value>,
     pub published_date: Option<String>,
+    /// Fields unknown to this version of the cli, kept in the JSON output
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+/// CVSS of a CVE: the scores by CVSS version, anything else as sent.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum CveCvss {
+    Versions {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        v3: Option<CvssScore>,
+        #[serde(flatten)]
+        extra: serde_json::Map<String, serde_json::Value>,
+    },
+    Other(serde_json::Value),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CvssScore {
+    pub score: f32,
+    /// Vector string, e.g. `CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:H`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vector: Option<String>,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+impl CsvRecord for LinuxCveCheckAnalysis {
+    const HEADER: &'static [&'static str] = &[
+        "cve_id", "severity", "cvss", "package", "version", "fixed_in",
+    ];
+
+    fn record(&self, _list_delimiter: &str) -> Vec<String> {
+        vec![
+            self.cveid.clone(),
+            self.severity.clone(),
+            self.cvss_score()
+                .map(|score| format!("{:.1}", score))
+                .unwrap_or_default(),
+            self.product.clone(),
+            self.version.clone(),
+            self.fixed_version.clone().unwrap_or_default(),
+        ]
+    }
 }
 
 impl LinuxCveCheckAnalysis {
+    /// CVSS v3 score, a bare score being taken as such.
+    pub fn cvss_score(&self) -> Option<f32> {
+        match self.cvss.as_ref()? {
+            CveCvss::Versions { v3, .. } => v3.as_ref().map(|v3| v3.score),
+            CveCvss::Other(score) => score.as_f64().map(|score| score as f32),
+        }
+    }
+
+    /// CVSS v3 vector string.
+    pub fn cvss_vector(&self) -> Option<&str> {
+        match self.cvss.as_ref()? {
+            CveCvss::Versions { v3, .. } => v3.as_ref()?.vector.as_deref(),
+            CveCvss::Other(_) => None,
+        }
+    }
+
     pub fn get_table_from_list(list: &[LinuxCveCheckAnalysis]) -> String {
+        Self::get_table_with_suppressed(list, &[])
+    }
+
+    /// Table of the CVEs, those whose `suppressed` flag is set dimmed.
+    pub fn get_table_with_suppressed(
+        list: &[LinuxCveCheckAnalysis],
+        suppressed: &[bool],
+    ) -> String {
         let mut table = Table::new();
         // table.max_column_width = 30;
         // table.set_max_width_for_column(4, 50);
@@ -253,19 +610,31 @@ impl LinuxCveCheckAnalysis {
             Cell::new("VERSION"),
             Cell::new("CVE ID"),
             Cell::new("SEVERITY"),
+            Cell::new("CVSS"),
             Cell::new("DETAILS"),
         ]));
 
         let rows: Vec<Row> = list
             .iter()
-            .map(|project| {
-                vec![
+            .enumerate()
+            .map(|(i, project)| {
+                let cells = vec![
                     Cell::new(&project.product),
                     Cell::new(&project.version),
                     Cell::new(&project.cveid),
-                    Cell::new(&project.severity),
+                    style::severity_cell(&project.severity),
+                    Cell::new(
+                        project
+                            .cvss_score()
+                            .map(|score| format!("{:.1}", score))
+                            .unwrap_or_default(),
+                    ),
                     Cell::new(format!("{}{}", CVE_DETAILS_BASE_URL, &project.cveid)),
-                ]
+                ];
+                match suppressed.get(i) {
+                    Some(true) => cells.into_iter().map(style::dim).collect(),
+                    _ => cells,
+                }
             })
             .map(Row::from)
             .collect();
@@ -274,7 +643,7 @@ impl LinuxCveCheckAnalysis {
             table.add_row(row);
         }
 
-        table.to_string()
+        output::render(table)
     }
 }
 
@@ -285,6 +654,18 @@ pub struct LinuxSecurityScanAnalysis {
     pub desc: String,
 }
 
+impl CsvRecord for LinuxSecurityScanAnalysis {
+    const HEADER: &'static [&'static str] = &["filename", "type", "description"];
+
+    fn record(&self, list_delimiter: &str) -> Vec<String> {
+        vec![
+            self.filename.clone(),
+            self.r#type.join(list_delimiter),
+            self.desc.clone(),
+        ]
+    }
+}
+
 impl LinuxSecurityScanAnalysis {
     pub fn get_table_from_list(list: &[LinuxSecurityScanAnalysis]) -> String {
         let mut table = Table::new();
@@ -301,76 +682,61 @@ impl LinuxSecurityScanAnalysis {
             table.add_row(row);
         }
 
-        table.to_string()
+        output::render(table)
     }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct LinuxPasswordHashAnalysis {
-    username: String,
+    pub username: String,
     password: String,
+    /// File the hash was found in, when the server reports it
+    #[serde(default, alias = "filename", skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
 }
 
 impl LinuxPasswordHashAnalysis {
-    pub fn get_table_from_list(list: &[LinuxPasswordHashAnalysis]) -> String {
-        let mut table = Table::new();
-        // table.max_column_width = 30;
-        table.add_row(Row::from(vec![
-            Cell::new("USERNAME"),
-            Cell::new("PASSWORD"),
-        ]));
-
-        let rows: Vec<Row> = list
-            .iter()
-            .map(|project| vec![Cell::new(&project.username), Cell::new(&project.password)])
-            .map(Row::from)
-            .collect();
-
-        for row in rows {
-            table.add_row(row);
-        }
-
-        table.to_string()
+    /// Hash of the password, as found in the firmware.
+    pub fn password(&self) -> &str {
+        &self.password
     }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct LinuxCryptoAnalysis {
-    filename: String,
-    r#type: String,
-    subtype: String,
-    pubsz: u16,
-}
-
-impl LinuxCryptoAnalysis {
-    pub fn get_table_from_list(list: &[LinuxCryptoAnalysis]) -> String {
-        let mut table = Table::new();
-        // table.max_column_width = 30;
-        table.add_row(Row::from(vec![
-            Cell::new("NAME"),
-            Cell::new("TYPE"),
-            Cell::new("SUBTYPE"),
-            Cell::new("SIZE"),
-        ]));
-
-        let rows: Vec<Row> = list
-            .iter()
-            .map(|project| {
-                vec![
-                    Cell::new(&project.filename),
-                    Cell::new(&project.r#type),
-                    Cell::new(&project.subtype),
-                    Cell::new(project.pubsz),
-                ]
-            })
-            .map(Row::from)
-            .collect();
-
-        for row in rows {
-            table.add_row(row);
-        }
-
-        table.to_string()
+    pub filename: String,
+    /// Kind of material, e.g. `certificate`, `private key` or `public key`
+    pub r#type: String,
+    /// Algorithm of the key, e.g. `RSA`
+    pub subtype: String,
+    /// Size of the key in bits
+    pub pubsz: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subject: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub issuer: Option<String>,
+    /// Expiry date of a certificate, as reported by the server
+    #[serde(default, alias = "notAfter", skip_serializing_if = "Option::is_none")]
+    pub not_after: Option<String>,
+    /// Whether the key is a known default one, when the server reports it
+    #[serde(
+        default,
+        alias = "knownDefault",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub known_default: Option<bool>,
+}
+
+impl CsvRecord for LinuxCryptoAnalysis {
+    const HEADER: &'static [&'static str] = &["filename", "type", "subtype", "pubsz"];
+
+    fn record(&self, _list_delimiter: &str) -> Vec<String> {
+        vec![
+            self.filename.clone(),
+            self.r#type.clone(),
+            self.subtype.clone(),
+            self.pubsz.to_string(),
+        ]
     }
 }
 
@@ -381,6 +747,14 @@ pub struct LinuxNvramAnalysis {
     name: String,
 }
 
+impl CsvRecord for LinuxNvramAnalysis {
+    const HEADER: &'static [&'static str] = &["executable", "function", "name"];
+
+    fn record(&self, _list_delimiter: &str) -> Vec<String> {
+        vec![self.exe.clone(), self.fun.clone(), self.name.clone()]
+    }
+}
+
 impl LinuxNvramAnalysis {
     pub fn get_table_from_list(list: &[LinuxNvramAnalysis]) -> String {
         let mut table = Table::new();
@@ -407,7 +781,7 @@ impl LinuxNvramAnalysis {
             table.add_row(row);
         }
 
-        table.to_string()
+        output::render(table)
     }
 }
 
@@ -432,6 +806,45 @@ pub struct LinuxStaticCode {
     pub(crate) flaw_type: String,
 }
 
+/// Flaws of the static code analysis, each sent as a JSON string.
+pub fn static_code_flaws(result: serde_json::Value) -> Result<Vec<LinuxStaticCode>> {
+    let analysis_result: Vec<LinuxStaticCodeAnalysis> = serde_json::from_value(result)?;
+    analysis_result
+        .into_iter()
+        .map(|executable_flaw| {
+            let flaw_str = executable_flaw
+                .flaws
+                .as_str()
+                .ok_or_else(|| anyhow!("failed to access flaw string"));
+
+            let flaw_parsed = flaw_str.and_then(|flaw| {
+                serde_json::from_str::<LinuxStaticCodeAnalysisFlaws>(flaw)
+                    .map_err(|_| anyhow!("failed to parse flaw"))
+            });
+
+            flaw_parsed.map(|flaw| LinuxStaticCode {
+                line: flaw.line.trim().to_string(),
+                descr: flaw.descr.trim().to_string(),
+                flaw_type: flaw.flaw_type.trim().to_string(),
+                filename: executable_flaw.filename,
+            })
+        })
+        .collect()
+}
+
+impl CsvRecord for LinuxStaticCode {
+    const HEADER: &'static [&'static str] = &["filename", "line", "type", "description"];
+
+    fn record(&self, _list_delimiter: &str) -> Vec<String> {
+        vec![
+            self.filename.clone(),
+            self.line.clone(),
+            self.flaw_type.clone(),
+            self.descr.clone(),
+        ]
+    }
+}
+
 impl LinuxStaticCode {
     pub fn get_table_from_list(list: &[LinuxStaticCode]) -> String {
         let mut table = Table::new();
@@ -460,41 +873,75 @@ impl LinuxStaticCode {
             table.add_row(row);
         }
 
-        table.to_string()
+        output::render(table)
     }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct LinuxKernelAnalysis {
-    name: String,
-    enabled: bool,
+    /// CONFIG option, e.g. `CONFIG_STRICT_KERNEL_RWX`
+    pub name: String,
+    pub enabled: bool,
+    /// Whether the option should be enabled, when the server reports it
+    #[serde(default, alias = "expected", skip_serializing_if = "Option::is_none")]
+    pub recommended: Option<bool>,
+    /// Group of the option, when the server reports it
+    #[serde(default, alias = "category", skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
 }
 
-impl LinuxKernelAnalysis {
-    pub fn get_table_from_list(list: &[LinuxKernelAnalysis]) -> String {
-        let mut table = Table::new();
-        table.add_row(Row::from(vec![Cell::new("FEATURE"), Cell::new("ENABLED")]));
-
-        let rows: Vec<Row> = list
-            .iter()
-            .map(|project| vec![Cell::new(&project.name), Cell::new(project.enabled)])
-            .map(Row::from)
-            .collect();
-
-        for row in rows {
-            table.add_row(row);
-        }
+impl CsvRecord for LinuxKernelAnalysis {
+    const HEADER: &'static [&'static str] = &["name", "enabled"];
 
-        table.to_string()
+    fn record(&self, _list_delimiter: &str) -> Vec<String> {
+        vec![self.name.clone(), self.enabled.to_string()]
     }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct LinuxSoftwareBOMAnalysis {
-    filename: String,
-    license: Option<String>,
-    occurrences: u16,
-    resolve: String,
+    pub filename: String,
+    pub license: Option<String>,
+    pub occurrences: u16,
+    pub resolve: String,
+    /// Package name, version and url, when identified by the server
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub purl: Option<String>,
+    /// Package manager the package was found by, e.g. `dpkg`, when the
+    /// server reports it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub origin: Option<String>,
+    /// Hex encoded hashes by algorithm, e.g. `sha256`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub hashes: BTreeMap<String, String>,
+}
+
+impl CsvRecord for LinuxSoftwareBOMAnalysis {
+    const HEADER: &'static [&'static str] = &[
+        "filename",
+        "path",
+        "name",
+        "version",
+        "license",
+        "occurrences",
+        "purl",
+    ];
+
+    fn record(&self, _list_delimiter: &str) -> Vec<String> {
+        vec![
+            self.filename.clone(),
+            self.resolve.clone(),
+            self.name.clone().unwrap_or_default(),
+            self.version.clone().unwrap_or_default(),
+            self.license.clone().unwrap_or_default(),
+            self.occurrences.to_string(),
+            self.purl.clone().unwrap_or_default(),
+        ]
+    }
 }
 
 impl LinuxSoftwareBOMAnalysis {
@@ -529,78 +976,12 @@ impl LinuxSoftwareBOMAnalysis {
             table.add_row(row);
         }
 
-        table.to_string()
-    }
-}
-
-// CONTAINER Analysis
-#[derive(Debug, Serialize, Deserialize)]
-pub struct ContainerProjectOverview {
-    pub info: ContainerInfo,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct ContainerInfo {
-    pub arch: String,
-    pub os_name: Option<String>,
-    pub os_version: Option<String>,
-    pub env: Option<serde_json::Value>,
-    pub history: Option<String>,
-}
-
-impl ContainerProjectOverview {
-    pub fn get_text_output(project: &ContainerProjectOverview) -> String {
-        let name = project
-            .info
-            .os_name
-            .as_ref()
-            .map(|s| s.to_string())
-            .unwrap_or_default();
-        let version = project
-            .info
-            .os_version
-            .as_ref()
-            .map(|s| s.to_string())
-            .unwrap_or_default();
-        let arch = &project.info.arch;
-
-        format!(
-            "Name: {}\nVersion: {}\nArchitecture: {}",
-            name, version, arch
-        )
+        output::render(table)
     }
 }
 
 // UEFI Analysis
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct UefiProjectOverview {
-    pub info: UefiInfo,
-    // pub project: Project,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct UefiInfo {
-    pub dxe_no: u32,
-    pub pei_no: u32,
-    pub manufacturer: String,
-    pub s3mit: String,
-}
-
-impl UefiProjectOverview {
-    pub fn get_text_output(project: &UefiProjectOverview) -> String {
-        let manufacturer = &project.info.manufacturer;
-        let dxe_no = &project.info.dxe_no;
-        let pei_no = &project.info.pei_no;
-        let s3mit = &project.info.s3mit;
-
-        format!(
-            "Manufacturer: {}\nDXE number: {}\nPEI number: {}\nS3 mitigation: {}",
-            manufacturer, dxe_no, pei_no, s3mit
-        )
-    }
-}
-
 #[derive(Debug, Serialize, Deserialize)]
 pub struct UefiAccess {
     read: String,
@@ -634,7 +1015,7 @@ impl UefiAccess {
             table.add_row(row);
         }
 
-        table.to_string()
+        output::render(table)
     }
 }
 
@@ -646,8 +1027,8 @@ pub struct UefiIntelBootGuard {
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct UefiIntelBootGuardRsa {
-    name: String,
-    value: String,
+    pub name: String,
+    pub value: String,
 }
 
 impl UefiIntelBootGuardRsa {
@@ -666,7 +1047,7 @@ impl UefiIntelBootGuardRsa {
             table.add_row(row);
         }
 
-        table.to_string()
+        output::render(table)
     }
 }
 
@@ -678,36 +1059,6 @@ pub struct UefiSurface {
     pub guid: Uuid,
 }
 
-impl UefiSurface {
-    pub fn get_table_from_list(list: &[UefiSurface]) -> String {
-        let mut table = Table::new();
-        // table.max_column_width = 40;
-        table.add_row(Row::from(vec![
-            Cell::new("NAME"),
-            Cell::new("TYPE"),
-            Cell::new("GUID"),
-        ]));
-
-        let rows: Vec<Row> = list
-            .iter()
-            .map(|project| {
-                vec![
-                    Cell::new(&project.name),
-                    Cell::new(&project.r#type),
-                    Cell::new(project.guid),
-                ]
-            })
-            .map(Row::from)
-            .collect();
-
-        for row in rows {
-            table.add_row(row);
-        }
-
-        table.to_string()
-    }
-}
-
 #[derive(Debug, Serialize, Deserialize)]
 pub struct UefiSecureBoot {
     pub(crate) certs: UefiSecureBootCerts,
@@ -718,14 +1069,9 @@ pub struct UefiSecureBoot {
 #[serde(rename_all = "UPPERCASE")]
 pub struct UefiSecureBootCerts {
     pub(crate) kek: Vec<UefiSecureBootData>,
-    pub(crate) pk: UefiSecureBootData,
-}
-
-impl UefiSecureBootCerts {
-    pub fn get_table_from_list(list: &[UefiSecureBootData], db: &str) -> String {
-        let table = UefiSecureBootData::get_table_from_list(list, db);
-        table.to_string()
-    }
+    /// Platform key, none in setup mode
+    #[serde(default)]
+    pub(crate) pk: Option<UefiSecureBootData>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -740,99 +1086,48 @@ pub struct UefiSecureBootDatabasesData {
     pub dbx: Vec<UefiSecureBootData>,
 }
 
+/// Certificate, issued by `first` to `second`, or hash of a database.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UefiSecureBootData {
     pub first: String,
     pub second: String,
 }
 
-impl UefiSecureBootData {
-    pub fn get_table_from_list(list: &[UefiSecureBootData], db: &str) -> Table {
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UefiSecurityScan {
+    pub guid: Uuid,
+    pub module: String,
+    /// Vulnerability found in the module
+    pub name: String,
+    /// Advisories of the vulnerability, e.g. `BRLY-2022-010` or a CVE, when
+    /// the server reports them
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub advisories: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub severity: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UefiPeimDxe {
+    pub name: String,
+    pub filetype: String,
+    pub format: String,
+    pub machine: String,
+    pub r#type: String,
+    pub sign: Option<bool>,
+    pub dependencies: Option<Vec<String>>,
+}
+
+impl UefiPeimDxe {
+    pub fn get_table_from_list(list: &[UefiPeimDxe]) -> String {
         let mut table = Table::new();
         // table.max_column_width = 50;
         table.add_row(Row::from(vec![
             Cell::new("NAME"),
-            Cell::new("ISSUED BY"),
-            Cell::new("ISSUED TO"),
-        ]));
-
-        let rows: Vec<Row> = list
-            .iter()
-            .map(|project| {
-                vec![
-                    Cell::new(db),
-                    Cell::new(&project.first),
-                    Cell::new(&project.second),
-                ]
-            })
-            .map(Row::from)
-            .collect();
-
-        for row in rows {
-            table.add_row(row);
-        }
-        table
-        //table.to_string()
-    }
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct UefiSecurityScan {
-    pub guid: Uuid,
-    pub module: String,
-    pub name: String,
-}
-
-impl UefiSecurityScan {
-    pub fn get_table_from_list(list: &[UefiSecurityScan]) -> String {
-        let mut table = Table::new();
-        // table.max_column_width = 50;
-        table.add_row(Row::from(vec![
-            Cell::new("NAME"),
-            Cell::new("MODULE"),
-            Cell::new("GUID"),
-        ]));
-
-        let rows: Vec<Row> = list
-            .iter()
-            .map(|project| {
-                vec![
-                    Cell::new(&project.name),
-                    Cell::new(&project.module),
-                    Cell::new(project.guid),
-                ]
-            })
-            .map(Row::from)
-            .collect();
-
-        for row in rows {
-            table.add_row(row);
-        }
-        table.to_string()
-    }
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct UefiPeimDxe {
-    pub name: String,
-    pub filetype: String,
-    pub format: String,
-    pub machine: String,
-    pub r#type: String,
-    pub sign: Option<bool>,
-    pub dependencies: Option<Vec<String>>,
-}
-
-impl UefiPeimDxe {
-    pub fn get_table_from_list(list: &[UefiPeimDxe]) -> String {
-        let mut table = Table::new();
-        // table.max_column_width = 50;
-        table.add_row(Row::from(vec![
-            Cell::new("NAME"),
-            Cell::new("FILETYPE"),
-            Cell::new("FORMAT"),
-            Cell::new("MACHINE"),
-            Cell::new("TYPE"),
+            Cell::new("FILETYPE"),
+            Cell::new("FORMAT"),
+            Cell::new("MACHINE"),
+            Cell::new("TYPE"),
         ]));
 
         let rows: Vec<Row> = list
@@ -852,53 +1147,12 @@ impl UefiPeimDxe {
         for row in rows {
             table.add_row(row);
         }
-        table.to_string()
+        output::render(table)
     }
 }
 
 // VxWorks Analysis
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct VxworksProjectOverview {
-    pub info: VxworksInfo,
-    // pub project: Project,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct VxworksInfo {
-    pub functions_no: u32,
-    pub symbols_no: u32,
-    pub tasks_no: u32,
-    pub word_size: u32,
-    pub arch: String,
-    pub endianness: String,
-    pub kernel: Option<String>,
-    pub capabilities: Option<serde_json::Value>,
-    pub os: String,
-}
-
-impl VxworksProjectOverview {
-    pub fn get_text_output(project: &VxworksProjectOverview) -> String {
-        let os = &project.info.os;
-        let arch = &project.info.arch;
-        let functions_no = &project.info.functions_no;
-        let tasks_no = &project.info.tasks_no;
-        let symbols_no = &project.info.symbols_no;
-
-        let kernel = project
-            .info
-            .kernel
-            .as_ref()
-            .map(|s| s.to_string())
-            .unwrap_or_default();
-
-        format!(
-            "Architecture: {}\nOS version: {}\nKernel version: {}\nFunctions: {}\nTasks: {}\nSymbols: {}",
-            arch, os, kernel, functions_no, tasks_no, symbols_no
-        )
-    }
-}
-
 #[derive(Debug, Serialize, Deserialize)]
 pub struct VxworksData {
     offset: u32,
@@ -931,7 +1185,7 @@ impl VxworksData {
         for row in rows {
             table.add_row(row);
         }
-        table.to_string()
+        output::render(table)
     }
 }
 
@@ -967,7 +1221,7 @@ impl VxworksTask {
         for row in rows {
             table.add_row(row);
         }
-        table.to_string()
+        output::render(table)
     }
 }
 
@@ -995,16 +1249,366 @@ impl VxworksCapability {
         for row in rows {
             table.add_row(row);
         }
-        table.to_string()
+        output::render(table)
     }
 }
 
 // List projects in personal workspace
-pub async fn list_projects<U: ApiServer>(api_server: &mut U) -> Result<Vec<Project>> {
-    let projects = api_server.list_projects().await?;
+pub async fn list_projects<U: ApiServer>(
+    api_server: &mut U,
+    filter: &ProjectFilter,
+    limit: Option<usize>,
+) -> Result<Vec<Project>> {
+    let mut projects: Vec<Project> = vec![];
+    for_each_project(api_server, filter, limit, |project| projects.push(project)).await?;
     Ok(projects)
 }
 
+/// Call `f` with the projects matching `filter` as their pages are
+/// received, stopping after `limit` projects.
+pub async fn for_each_project<U: ApiServer, F: FnMut(Project)>(
+    api_server: &mut U,
+    filter: &ProjectFilter,
+    limit: Option<usize>,
+    mut f: F,
+) -> Result<()> {
+    let mut count = 0;
+    let mut seen = HashSet::new();
+
+    for page in 1.. {
+        let page_projects = match api_server
+            .list_projects_paged(page, LIST_PAGE_SIZE, filter)
+            .await
+        {
+            Ok(page_projects) => page_projects,
+            // Servers and caches predating pagination only have the whole list
+            Err(ApiServerError::NotFound(_) | ApiServerError::NotCached(_)) if page == 1 => {
+                tracing::debug!("Paginated project list not available, listing all projects");
+                api_server.list_projects().await?
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        let received = page_projects.len();
+        let mut new = 0;
+        for project in page_projects {
+            // Projects created while listing shift the pages, repeating some
+            if !seen.insert(project.id) {
+                continue;
+            }
+            new += 1;
+            // Filtered again in case the server doesn't support the filters
+            if filter.matches(&project) && limit.is_none_or(|limit| count < limit) {
+                f(project);
+                count += 1;
+            }
+        }
+
+        let last_page = received != LIST_PAGE_SIZE as usize || new == 0;
+        if last_page || limit.is_some_and(|limit| count >= limit) {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Minimum length of the ID prefixes resolved to a project
+const MIN_ID_PREFIX_LEN: usize = 6;
+/// Maximum number of matching projects listed for ambiguous references
+const MAX_CANDIDATES_SHOWN: usize = 10;
+
+/// Failure resolving a [ProjectRef] to a project.
+#[derive(Debug)]
+pub enum ResolveError {
+    /// No project has the name or ID prefix
+    NotFound(String),
+    /// Several projects have the name or ID prefix
+    Ambiguous {
+        reference: String,
+        candidates: Vec<(Uuid, String)>,
+    },
+}
+
+impl fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotFound(reference) => write!(
+                f,
+                "No project named `{}` or with an ID starting with it (at least {} characters)",
+                reference, MIN_ID_PREFIX_LEN
+            ),
+            Self::Ambiguous {
+                reference,
+                candidates,
+            } => {
+                write!(f, "`{}` matches several projects, use the ID:", reference)?;
+                for (id, name) in candidates.iter().take(MAX_CANDIDATES_SHOWN) {
+                    write!(f, "\n  {}  {}", id, name)?;
+                }
+                if candidates.len() > MAX_CANDIDATES_SHOWN {
+                    write!(
+                        f,
+                        "\n  ... and {} more",
+                        candidates.len() - MAX_CANDIDATES_SHOWN
+                    )?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ResolveError {}
+
+/// Resolves [ProjectRef]s to project IDs, listing the projects at most once
+/// per invocation.
+#[derive(Debug, Default)]
+pub struct ProjectResolver {
+    projects: Option<Vec<(Uuid, String)>>,
+}
+
+impl ProjectResolver {
+    /// ID of `project`, an ID, a unique ID prefix or an exact name.
+    pub async fn resolve<U: ApiServer>(
+        &mut self,
+        api_server: &mut U,
+        project: &ProjectRef,
+    ) -> Result<Uuid> {
+        let reference = match project {
+            ProjectRef::Id(id) => return Ok(*id),
+            ProjectRef::Reference(reference) => reference,
+        };
+
+        let projects = self.projects(api_server).await?;
+
+        let prefix = reference.to_lowercase();
+        let is_prefix = prefix.len() >= MIN_ID_PREFIX_LEN
+            && prefix.chars().all(|c| c.is_ascii_hexdigit() || c == '-');
+        let candidates: Vec<_> = projects
+            .iter()
+            .filter(|(id, name)| {
+                name == reference || (is_prefix && id.to_string().starts_with(&prefix))
+            })
+            .cloned()
+            .collect();
+
+        match candidates.as_slice() {
+            [(id, _)] => {
+                tracing::debug!("Project `{}` resolved to {}", reference, id);
+                Ok(*id)
+            }
+            [] => Err(ResolveError::NotFound(reference.clone()).into()),
+            _ => Err(ResolveError::Ambiguous {
+                reference: reference.clone(),
+                candidates,
+            }
+            .into()),
+        }
+    }
+
+    /// Name of the project `id`.
+    pub async fn name<U: ApiServer>(&mut self, api_server: &mut U, id: Uuid) -> Result<String> {
+        let projects = self.projects(api_server).await?;
+        match projects.iter().find(|(project_id, _)| *project_id == id) {
+            Some((_, name)) => Ok(name.clone()),
+            None => Err(ResolveError::NotFound(id.to_string()).into()),
+        }
+    }
+
+    async fn projects<U: ApiServer>(&mut self, api_server: &mut U) -> Result<&[(Uuid, String)]> {
+        if self.projects.is_none() {
+            let projects = list_projects(api_server, &ProjectFilter::default(), None).await?;
+            self.projects = Some(projects.into_iter().map(|p| (p.id, p.name)).collect());
+        }
+        Ok(self.projects.as_deref().unwrap_or_default())
+    }
+
+    /// ID of `project`, else of the project picked from the list of the
+    /// account, newest first, when `prompt` allows it.
+    pub async fn resolve_or_pick<U: ApiServer>(
+        &mut self,
+        api_server: &mut U,
+        project: Option<&ProjectRef>,
+        prompt: Prompt,
+    ) -> Result<Uuid> {
+        if let Some(project) = project {
+            return self.resolve(api_server, project).await;
+        }
+
+        const QUESTION: &str = "Select a project";
+        const REMEDY: &str = "pass the project with --id";
+        // Fail before listing the projects when they can't be shown
+        prompt.check_select(QUESTION, REMEDY)?;
+
+        let mut projects = list_projects(api_server, &ProjectFilter::default(), None).await?;
+        if projects.is_empty() {
+            bail!("No project found, create one with `cosmo create`");
+        }
+        sort_projects(&mut projects, ProjectSort::Created, true);
+
+        let name_width = projects.iter().map(|p| p.name.chars().count()).max();
+        let name_width = name_width.unwrap_or(0);
+        let type_width = projects.iter().map(|p| p.project_type.len()).max();
+        let type_width = type_width.unwrap_or(0);
+        let items: Vec<_> = projects
+            .iter()
+            .map(|project| {
+                let created = creation_date(&project.creation_date)
+                    .map_or_else(|| project.creation_date.clone(), |day| day.to_string());
+                format!(
+                    "{:name_width$}  {:type_width$}  {}  {}",
+                    project.name, project.project_type, created, project.status
+                )
+            })
+            .collect();
+
+        let selected = prompt.select(QUESTION, &items, REMEDY)?;
+        let project = &projects[selected];
+        tracing::debug!("Project {} picked", project.id);
+        Ok(project.id)
+    }
+}
+
+/// Sort `projects` by `sort`, ties by name then id so that the order is
+/// the same across runs.
+pub fn sort_projects(projects: &mut [Project], sort: ProjectSort, reverse: bool) {
+    projects.sort_by(|a, b| {
+        let ordering = match sort {
+            ProjectSort::Name => Ordering::Equal,
+            ProjectSort::Created => a.creation_date.cmp(&b.creation_date),
+            ProjectSort::Updated => {
+                let updated = |p: &Project| {
+                    p.update_date
+                        .clone()
+                        .unwrap_or_else(|| p.creation_date.clone())
+                };
+                updated(a).cmp(&updated(b))
+            }
+            ProjectSort::Score => a.score.total_cmp(&b.score),
+            ProjectSort::CveCount => a.cve_count.cmp(&b.cve_count),
+        };
+        let ordering = ordering
+            .then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase()))
+            .then_with(|| a.id.cmp(&b.id));
+
+        if reverse {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    });
+}
+
+impl ProjectFilter {
+    /// Query parameters asking the server to filter the list.
+    pub(crate) fn query(&self) -> Vec<(&'static str, String)> {
+        let date = |date: Option<NaiveDate>| date.map(|date| date.format("%Y-%m-%d").to_string());
+
+        [
+            ("name", self.name.clone()),
+            ("type", self.fw_type.clone()),
+            ("subtype", self.fw_subtype.clone()),
+            ("since", date(self.since)),
+            ("until", date(self.until)),
+            ("status", self.status.clone()),
+            ("organization_id", self.organization.clone()),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| value.map(|value| (name, value)))
+        .chain(self.tags.iter().map(|tag| ("tag", tag.clone())))
+        .collect()
+    }
+
+    /// Whether `project` matches all the filters.
+    pub fn matches(&self, project: &Project) -> bool {
+        let equal = |filter: &Option<String>, value: &str| {
+            filter
+                .as_ref()
+                .is_none_or(|filter| filter.eq_ignore_ascii_case(value))
+        };
+        let created = creation_date(&project.creation_date);
+
+        self.name
+            .as_ref()
+            .is_none_or(|name| project.name.to_lowercase().contains(&name.to_lowercase()))
+            && equal(&self.fw_type, &project.project_type)
+            && equal(&self.fw_subtype, &project.project_subtype)
+            && equal(&self.status, &project.status)
+            // Unknown with the servers leaving out the name of the organization
+            && self
+                .organization_name
+                .as_ref()
+                .zip(project.organization_name.as_ref())
+                .is_none_or(|(name, project_name)| name.eq_ignore_ascii_case(project_name))
+            && self
+                .since
+                .is_none_or(|since| created.is_some_and(|created| created >= since))
+            && self
+                .until
+                .is_none_or(|until| created.is_some_and(|created| created <= until))
+            && self.tags.iter().all(|tag| {
+                project
+                    .tags
+                    .iter()
+                    .any(|project_tag| project_tag.eq_ignore_ascii_case(tag))
+            })
+    }
+}
+
+impl CveFilter {
+    pub fn is_empty(&self) -> bool {
+        self.severity.is_empty() && self.min_cvss.is_none() && self.package.is_none()
+    }
+
+    /// Whether `cve` matches all the filters.
+    pub fn matches(&self, cve: &LinuxCveCheckAnalysis) -> bool {
+        (self.severity.is_empty()
+            || self
+                .severity
+                .iter()
+                .any(|severity| cve.severity.eq_ignore_ascii_case(severity.as_str())))
+            && self
+                .min_cvss
+                .is_none_or(|min| cve.cvss_score().is_some_and(|score| score >= min))
+            && self
+                .package
+                .as_ref()
+                .is_none_or(|package| cve.product.eq_ignore_ascii_case(package))
+    }
+}
+
+/// Sort the CVEs by `sort`, then by ID.
+pub fn sort_cves(cves: &mut [LinuxCveCheckAnalysis], sort: CveSort) {
+    cves.sort_by(|a, b| {
+        let ordering = match sort {
+            // Highest first, unscored last
+            CveSort::Cvss => b
+                .cvss_score()
+                .unwrap_or(-1.0)
+                .total_cmp(&a.cvss_score().unwrap_or(-1.0)),
+            CveSort::Package => a.product.to_lowercase().cmp(&b.product.to_lowercase()),
+            CveSort::Id => Ordering::Equal,
+        };
+        ordering.then_with(|| a.cveid.cmp(&b.cveid))
+    });
+}
+
+/// Time of a creation date like `2023-10-24T12:00:00`, in UTC.
+fn creation_time(date: &str) -> Option<NaiveDateTime> {
+    DateTime::parse_from_rfc3339(date)
+        .map(|date| date.naive_utc())
+        .or_else(|_| NaiveDateTime::parse_from_str(date, "%Y-%m-%dT%H:%M:%S%.f"))
+        .ok()
+        .or_else(|| creation_date(date).and_then(|day| day.and_hms_opt(0, 0, 0)))
+}
+
+/// Day of a creation date like `2023-10-24T12:00:00`.
+fn creation_date(date: &str) -> Option<NaiveDate> {
+    date.get(..10)
+        .and_then(|day| NaiveDate::parse_from_str(day, "%Y-%m-%d").ok())
+}
+
 // Project overview
 pub async fn overview<U: ApiServer>(
     api_server: &mut U,
@@ -1018,16 +1622,74 @@ pub async fn overview<U: ApiServer>(
 pub async fn report<U: ApiServer>(
     api_server: &mut U,
     project_id: Uuid,
-    savepath: String,
-) -> Result<String> {
-    let report_path = Path::new(&savepath);
+    savepath: &str,
+    force: bool,
+    wait: Option<(Duration, Duration)>,
+) -> Result<u64> {
+    let report_path = Path::new(savepath);
+
+    if report_path.exists() && !force {
+        return Err(anyhow!(
+            "File {} already exists, use --force to overwrite it",
+            report_path.display()
+        ));
+    }
+
+    // Not found until generated, polled when waiting
+    let Some((poll_interval, timeout)) = wait else {
+        return match api_server.report_pdf(&project_id, report_path).await {
+            Err(err @ ApiServerError::NotFound(_)) => Err(anyhow::Error::new(err)
+                .context("Report not generated yet, run with --wait to wait until it's ready")),
+            res => Ok(res?),
+        };
+    };
+
+    let spinner = wait_spinner();
+    spinner.set_message("Waiting for the report");
+    let deadline = Instant::now() + timeout;
+
+    let outcome = loop {
+        match api_server.report_pdf(&project_id, report_path).await {
+            Err(ApiServerError::NotFound(_)) => {}
+            res => break res.map_err(anyhow::Error::new),
+        }
+        tracing::debug!("Report of project {} not generated yet", project_id);
+
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            break Err(WaitError::ReportTimeout {
+                project_id,
+                timeout,
+            }
+            .into());
+        }
+        tokio::time::sleep(poll_interval.min(remaining)).await;
+    };
+
+    spinner.finish_and_clear();
+    outcome
+}
+
+// Download the firmware of a project
+pub async fn download_firmware<U: ApiServer>(
+    api_server: &mut U,
+    project_id: Uuid,
+    savepath: &str,
+    force: bool,
+) -> Result<u64> {
+    let firmware_path = Path::new(savepath);
 
-    if report_path.exists() {
-        return Err(anyhow!("File {} already exists", report_path.display()));
+    if firmware_path.exists() && !force {
+        return Err(anyhow!(
+            "File {} already exists, use --force to overwrite it",
+            firmware_path.display()
+        ));
     }
-    api_server.report(&project_id, report_path).await?;
+    let size = api_server
+        .fetch_firmware(&project_id, firmware_path)
+        .await?;
 
-    Ok(savepath)
+    Ok(size)
 }
 
 // Analysis result
@@ -1044,18 +1706,534 @@ pub async fn analysis<U: ApiServer>(
     Ok(res)
 }
 
+/// Number of results requested per page when fetching all of them
+const ANALYSIS_PAGE_SIZE: i32 = 100;
+
+// All the results of an analysis, from all its pages
+pub async fn analysis_results<U: ApiServer>(
+    api_server: &U,
+    project_id: Uuid,
+    analysis: &Analysis,
+) -> Result<Vec<serde_json::Value>> {
+    let mut results = vec![];
+    let mut seen = HashSet::new();
+
+    for page in 0.. {
+        let res = api_server
+            .analysis(&project_id, analysis, page, ANALYSIS_PAGE_SIZE)
+            .await?;
+        if let Some(err) = res.error {
+            bail!("Analysis {} error: {}", analysis, err);
+        }
+        let page_results = match res.result {
+            Some(serde_json::Value::Array(page_results)) => page_results,
+            Some(_) => bail!("Analysis {} result is not a list", analysis),
+            None => vec![],
+        };
+
+        let received = page_results.len();
+        let mut new = 0;
+        for result in page_results {
+            if seen.insert(result.to_string()) {
+                new += 1;
+                results.push(result);
+            }
+        }
+
+        // Servers ignoring the pagination send the same results again
+        if received < ANALYSIS_PAGE_SIZE as usize || new == 0 {
+            break;
+        }
+    }
+
+    Ok(results)
+}
+
+/// Maximum number of analyses fetched concurrently
+const ANALYSES_CONCURRENCY: usize = 4;
+
+// Results of several analyses, fetched concurrently
+pub async fn analyses<U: ApiServer + Sync>(
+    api_server: &U,
+    project_id: Uuid,
+    analyses: &[Analysis],
+    page: i32,
+    per_page: i32,
+) -> Vec<(Analysis, Result<ProjectAnalysis>)> {
+    let mut results: Vec<_> = analyses_stream(api_server, project_id, analyses, page, per_page)
+        .collect()
+        .await;
+
+    // Keep the order of the request
+    results.sort_by_key(|(analysis, _)| analyses.iter().position(|a| a == analysis));
+    results
+}
+
+/// Results of `analyses` of a project, fetched concurrently, in the order
+/// they are received.
+pub fn analyses_stream<'a, U: ApiServer + Sync>(
+    api_server: &'a U,
+    project_id: Uuid,
+    analyses: &'a [Analysis],
+    page: i32,
+    per_page: i32,
+) -> impl Stream<Item = (Analysis, Result<ProjectAnalysis>)> + 'a {
+    stream::iter(analyses)
+        .map(move |analysis| async move {
+            let res = api_server
+                .analysis(&project_id, analysis, page, per_page)
+                .await
+                .map_err(anyhow::Error::from);
+            (analysis.clone(), res)
+        })
+        .buffer_unordered(ANALYSES_CONCURRENCY)
+}
+
+/// Status of a project as sent by the server.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectStatusDTO {
+    pub status: String,
+    #[serde(default)]
+    pub stage: Option<String>,
+    #[serde(default)]
+    pub progress: Option<u8>,
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+/// State of the analysis of a project.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "ProjectStatusDTO", into = "ProjectStatusDTO")]
+pub enum ProjectStatus {
+    Queued,
+    Extracting,
+    Analyzing {
+        stage: Option<String>,
+        progress: Option<u8>,
+    },
+    Completed,
+    Failed {
+        reason: String,
+    },
+    /// State unknown to this version of the cli
+    Other(String),
+}
+
+impl From<ProjectStatusDTO> for ProjectStatus {
+    fn from(dto: ProjectStatusDTO) -> Self {
+        match dto.status.to_uppercase().as_str() {
+            "QUEUED" | "PENDING" => Self::Queued,
+            "EXTRACTING" | "UNPACKING" => Self::Extracting,
+            "ANALYZING" | "RUNNING" | "IN_PROGRESS" => Self::Analyzing {
+                stage: dto.stage,
+                progress: dto.progress,
+            },
+            "COMPLETED" | "DONE" | "SUCCESS" => Self::Completed,
+            "FAILED" | "ERROR" => Self::Failed {
+                reason: dto
+                    .error
+                    .unwrap_or_else(|| "no reason reported by the server".to_string()),
+            },
+            _ => Self::Other(dto.status),
+        }
+    }
+}
+
+impl From<ProjectStatus> for ProjectStatusDTO {
+    fn from(status: ProjectStatus) -> Self {
+        let dto = |status: &str| ProjectStatusDTO {
+            status: status.to_string(),
+            stage: None,
+            progress: None,
+            error: None,
+        };
+
+        match status {
+            ProjectStatus::Queued => dto("QUEUED"),
+            ProjectStatus::Extracting => dto("EXTRACTING"),
+            ProjectStatus::Analyzing { stage, progress } => ProjectStatusDTO {
+                stage,
+                progress,
+                ..dto("ANALYZING")
+            },
+            ProjectStatus::Completed => dto("COMPLETED"),
+            ProjectStatus::Failed { reason } => ProjectStatusDTO {
+                error: Some(reason),
+                ..dto("FAILED")
+            },
+            ProjectStatus::Other(status) => dto(&status),
+        }
+    }
+}
+
+impl fmt::Display for ProjectStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Queued => write!(f, "queued"),
+            Self::Extracting => write!(f, "extracting"),
+            Self::Analyzing { stage, progress } => {
+                write!(f, "analyzing")?;
+                match (stage, progress) {
+                    (Some(stage), Some(progress)) => write!(f, " ({}, {}%)", stage, progress),
+                    (Some(stage), None) => write!(f, " ({})", stage),
+                    (None, Some(progress)) => write!(f, " ({}%)", progress),
+                    (None, None) => Ok(()),
+                }
+            }
+            Self::Completed => write!(f, "completed"),
+            Self::Failed { reason } => write!(f, "failed: {}", reason),
+            Self::Other(status) => write!(f, "{}", status.to_lowercase()),
+        }
+    }
+}
+
+// Status of the analysis of a project
+pub async fn status<U: ApiServer>(api_server: &mut U, project_id: Uuid) -> Result<ProjectStatus> {
+    let status = api_server.status(&project_id).await?;
+    Ok(status)
+}
+
+impl ProjectStatus {
+    /// Whether the analysis is still to complete.
+    pub fn is_running(&self) -> bool {
+        matches!(
+            self,
+            Self::Queued | Self::Extracting | Self::Analyzing { .. }
+        )
+    }
+}
+
+// Queue a new analysis of a project, unless one is running and not `force`d
+pub async fn rescan<U: ApiServer>(
+    api_server: &mut U,
+    project_id: Uuid,
+    force: bool,
+) -> Result<RescanJob> {
+    if !force {
+        let status = api_server.status(&project_id).await?;
+        if status.is_running() {
+            bail!(
+                "An analysis of project {} is already {}, pass --force to rescan anyway",
+                project_id,
+                status
+            );
+        }
+    }
+
+    let job = api_server.rescan(&project_id).await?;
+    Ok(job)
+}
+
+/// Failure waiting for the analysis of a project.
+#[derive(Debug)]
+pub enum WaitError {
+    /// The analysis failed, with the reason reported by the server
+    Failed { project_id: Uuid, reason: String },
+    /// The analysis didn't complete in time
+    Timeout { project_id: Uuid, timeout: Duration },
+    /// The PDF report wasn't generated in time
+    ReportTimeout { project_id: Uuid, timeout: Duration },
+}
+
+impl fmt::Display for WaitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Failed { project_id, reason } => {
+                write!(f, "Analysis of project {} failed: {}", project_id, reason)
+            }
+            Self::Timeout {
+                project_id,
+                timeout,
+            } => write!(
+                f,
+                "Analysis of project {} still running after {}s, check it later with `cosmo overview --id {}`",
+                project_id,
+                timeout.as_secs(),
+                project_id
+            ),
+            Self::ReportTimeout {
+                project_id,
+                timeout,
+            } => write!(
+                f,
+                "Report of project {} not generated after {}s",
+                project_id,
+                timeout.as_secs()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for WaitError {}
+
+/// Spinner shown on stderr while waiting, hidden when not a terminal or
+/// when quiet.
+pub(crate) fn wait_spinner() -> ProgressBar {
+    let spinner = if std::io::stderr().is_terminal() && tracing::enabled!(tracing::Level::INFO) {
+        ProgressBar::new_spinner().with_style(
+            ProgressStyle::with_template("[{elapsed_precise}] {spinner} {msg}")
+                .expect("valid progress template"),
+        )
+    } else {
+        ProgressBar::hidden()
+    };
+    spinner.enable_steady_tick(Duration::from_millis(100));
+    spinner
+}
+
+// Wait for the analysis of a project
+pub async fn wait_analysis<U: ApiServer>(
+    api_server: &mut U,
+    project_id: Uuid,
+    poll_interval: Duration,
+    timeout: Duration,
+) -> Result<serde_json::Value> {
+    let spinner = wait_spinner();
+    let deadline = Instant::now() + timeout;
+    let mut last_status = None;
+
+    let outcome: Result<()> = loop {
+        let status = match api_server.status(&project_id).await {
+            Ok(status) => status,
+            Err(err) => break Err(err.into()),
+        };
+
+        match status {
+            ProjectStatus::Completed => break Ok(()),
+            ProjectStatus::Failed { reason } => {
+                break Err(WaitError::Failed { project_id, reason }.into())
+            }
+            _ => {}
+        }
+
+        if last_status.as_ref() != Some(&status) {
+            tracing::debug!("Project {} status: {}", project_id, status);
+            spinner.set_message(format!("Analysis {}", status));
+            last_status = Some(status);
+        }
+
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            break Err(WaitError::Timeout {
+                project_id,
+                timeout,
+            }
+            .into());
+        }
+        tokio::time::sleep(poll_interval.min(remaining)).await;
+    };
+
+    spinner.finish_and_clear();
+    outcome?;
+
+    let overview = api_server.overview(&project_id).await?;
+    Ok(overview)
+}
+
+/// Change of the status of a project seen by `project watch`.
+#[derive(Debug, Clone, Serialize)]
+pub struct StatusChange {
+    pub time: DateTime<Utc>,
+    pub project_id: Uuid,
+    #[serde(flatten)]
+    pub status: ProjectStatus,
+}
+
+impl fmt::Display for StatusChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}  {}",
+            self.time.format("%Y-%m-%d %H:%M:%S UTC"),
+            self.status
+        )
+    }
+}
+
+/// Follow the analysis of a project until it completes or fails, calling
+/// `on_change` on each change of its status but the final one, returned.
+/// The changes pushed by the server are followed when it offers them, and
+/// else the status is checked every `poll_interval`.
+pub async fn watch<U: ApiServer>(
+    api_server: &mut U,
+    project_id: Uuid,
+    poll_interval: Duration,
+    timeout: Duration,
+    mut on_change: impl FnMut(&StatusChange),
+) -> Result<StatusChange> {
+    let deadline = Instant::now() + timeout;
+    let mut status = api_server.status(&project_id).await?;
+    let mut last_status = None;
+
+    let mut events = None;
+    if status.is_running() {
+        events = match api_server.status_events(&project_id, timeout).await {
+            Ok(events) => events,
+            Err(e) => {
+                tracing::debug!("Status events not available: {}", e);
+                None
+            }
+        };
+        if events.is_some() {
+            tracing::debug!("Following the status events of project {}", project_id);
+        }
+    }
+
+    loop {
+        if last_status.as_ref() != Some(&status) {
+            let change = StatusChange {
+                time: Utc::now(),
+                project_id,
+                status: status.clone(),
+            };
+            if matches!(
+                status,
+                ProjectStatus::Completed | ProjectStatus::Failed { .. }
+            ) {
+                return Ok(change);
+            }
+            on_change(&change);
+            last_status = Some(status.clone());
+        }
+
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(WaitError::Timeout {
+                project_id,
+                timeout,
+            }
+            .into());
+        }
+        status = match events.as_mut() {
+            Some(stream) => match tokio::time::timeout(remaining, stream.next()).await {
+                Ok(Some(Ok(status))) => status,
+                Ok(Some(Err(e))) => {
+                    tracing::debug!("Status events interrupted: {}, polling instead", e);
+                    events = None;
+                    api_server.status(&project_id).await?
+                }
+                Ok(None) => {
+                    tracing::debug!("Status events ended, polling instead");
+                    events = None;
+                    api_server.status(&project_id).await?
+                }
+                Err(_) => continue,
+            },
+            None => {
+                tokio::time::sleep(poll_interval.min(remaining)).await;
+                api_server.status(&project_id).await?
+            }
+        };
+    }
+}
+
 // Delete a project
 pub async fn delete<U: ApiServer>(api_server: &mut U, project_id: Uuid) -> Result<()> {
     api_server.delete(&project_id).await?;
     Ok(())
 }
 
+// Rename a project or change its description
+pub async fn update<U: ApiServer>(
+    api_server: &U,
+    project_id: Uuid,
+    name: Option<&str>,
+    description: Option<&str>,
+) -> Result<()> {
+    match api_server.update(&project_id, name, description).await {
+        Ok(()) => Ok(()),
+        // The server answers with its raw body, rather explain the conflict
+        Err(ApiServerError::Conflict(response)) if name.is_some() => {
+            let mut message = format!(
+                "Another project is already named \"{}\", choose a different name",
+                name.unwrap_or_default()
+            );
+            if let Some(request_id) = &response.request_id {
+                message.push_str(&format!(" (request id {})", request_id));
+            }
+            Err(anyhow!(message))
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+// Add and remove tags of a project, returning its tags
+pub async fn tag<U: ApiServer>(
+    api_server: &U,
+    project_id: Uuid,
+    add: &[String],
+    remove: &[String],
+) -> Result<Vec<String>> {
+    let tags = api_server.tag(&project_id, add, remove).await?;
+    Ok(tags)
+}
+
+/// Maximum number of projects deleted concurrently
+const DELETE_CONCURRENCY: usize = 4;
+
+// Delete several projects concurrently, attempting all of them
+pub async fn delete_many<U: ApiServer + Sync>(
+    api_server: &U,
+    project_ids: &[Uuid],
+) -> Vec<(Uuid, Result<()>)> {
+    let mut results: Vec<_> = stream::iter(project_ids.iter().enumerate())
+        .map(|(i, project_id)| async move {
+            let res = api_server
+                .delete(project_id)
+                .await
+                .map_err(anyhow::Error::from);
+            (i, *project_id, res)
+        })
+        .buffer_unordered(DELETE_CONCURRENCY)
+        .collect()
+        .await;
+
+    // Keep the order of the request
+    results.sort_by_key(|(i, _, _)| *i);
+    results
+        .into_iter()
+        .map(|(_, project_id, res)| (project_id, res))
+        .collect()
+}
+
+// Projects whose name starts with `name_prefix` created longer than
+// `older_than` ago
+pub async fn select_projects<U: ApiServer>(
+    api_server: &mut U,
+    name_prefix: Option<&str>,
+    older_than: Option<Duration>,
+) -> Result<Vec<Project>> {
+    let cutoff = match older_than {
+        Some(age) => Some(
+            chrono::Duration::from_std(age)
+                .ok()
+                .and_then(|age| Utc::now().naive_utc().checked_sub_signed(age))
+                .ok_or_else(|| anyhow!("Age too large: {}s", age.as_secs()))?,
+        ),
+        None => None,
+    };
+
+    let projects = list_projects(api_server, &ProjectFilter::default(), None).await?;
+
+    Ok(projects
+        .into_iter()
+        .filter(|project| {
+            name_prefix.is_none_or(|prefix| project.name.starts_with(prefix))
+                && cutoff.is_none_or(|cutoff| {
+                    creation_time(&project.creation_date).is_some_and(|created| created < cutoff)
+                })
+        })
+        .collect())
+}
+
 #[derive(Debug)]
 pub struct ProjectCreated {
     pub id: Uuid,
+    pub sha256: String,
 }
 
 // Create a new project
+#[allow(clippy::too_many_arguments)]
 pub async fn create<U: ApiServer>(
     fw_filepath: &str,
     fw_type: &str,
@@ -1063,32 +2241,38 @@ pub async fn create<U: ApiServer>(
     name: &str,
     description: Option<&str>,
     organization: Option<&str>,
-    api_server: &mut U,
+    tags: &[String],
+    options: &UploadOptions,
+    api_server: &U,
 ) -> Result<ProjectCreated> {
-    let fw_file = Path::new(fw_filepath);
+    // Read from stdin while uploading, the size being checked then
+    if fw_filepath != "-" {
+        let fw_file = Path::new(fw_filepath);
 
-    if !fw_file.exists() {
-        return Err(anyhow!("File not exists: {}", fw_filepath));
-    }
+        if !fw_file.exists() {
+            return Err(anyhow!("File not exists: {}", fw_filepath));
+        }
 
-    if !fw_file.is_file() {
-        return Err(anyhow!("Not a file: {}", fw_filepath));
-    }
+        if !fw_file.is_file() {
+            return Err(anyhow!("Not a file: {}", fw_filepath));
+        }
 
-    let fw_file = File::open(fw_file).map_err(|_| anyhow!("Error opening file {}", fw_filepath))?;
+        let fw_file =
+            File::open(fw_file).map_err(|_| anyhow!("Error opening file {}", fw_filepath))?;
 
-    let fw_file_metadata = fw_file
-        .metadata()
-        .map_err(|_| anyhow!("Error accessing file metadata {}", fw_filepath))?;
+        let fw_file_metadata = fw_file
+            .metadata()
+            .map_err(|_| anyhow!("Error accessing file metadata {}", fw_filepath))?;
 
-    if fw_file_metadata.len() as usize > FILE_SIZE_LIMIT {
-        return Err(anyhow!(
-            "File size exceeds maximum file size of {} bytes",
-            FILE_SIZE_LIMIT
-        ));
+        if fw_file_metadata.len() as usize > FILE_SIZE_LIMIT {
+            return Err(anyhow!(
+                "File size exceeds maximum file size of {} bytes",
+                FILE_SIZE_LIMIT
+            ));
+        }
     }
 
-    let project_id = api_server
+    let created = api_server
         .create(
             fw_filepath,
             fw_type,
@@ -1096,8 +2280,271 @@ pub async fn create<U: ApiServer>(
             name,
             description,
             organization,
+            tags,
+            options,
         )
-        .await?;
+        .await;
+    match created {
+        Ok(created) => Ok(created),
+        Err(e) => Err(usage_service::explain_quota(api_server, e).await),
+    }
+}
+
+/// Outcome of a check of `create --dry-run`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    Passed,
+    Failed,
+    /// Not run, an earlier check having failed or there being nothing to check
+    Skipped,
+}
+
+/// Check of `create --dry-run` and what it found.
+#[derive(Debug, Serialize)]
+pub struct DryRunCheck {
+    pub check: &'static str,
+    pub status: CheckStatus,
+    pub detail: String,
+}
+
+/// Outcome of `create --dry-run`, everything checked without uploading.
+#[derive(Debug, Serialize)]
+pub struct CreateDryRun {
+    pub file: String,
+    pub size: Option<u64>,
+    pub sha256: Option<String>,
+    pub passed: bool,
+    pub checks: Vec<DryRunCheck>,
+    /// Exit code of the first failed check
+    #[serde(skip)]
+    pub exit_code: Option<ExitCode>,
+}
+
+impl CreateDryRun {
+    fn push(&mut self, check: &'static str, status: CheckStatus, detail: impl Into<String>) {
+        self.checks.push(DryRunCheck {
+            check,
+            status,
+            detail: detail.into(),
+        });
+    }
+
+    fn fail(&mut self, check: &'static str, detail: impl Into<String>, exit_code: ExitCode) {
+        self.push(check, CheckStatus::Failed, detail);
+        self.passed = false;
+        self.exit_code.get_or_insert(exit_code);
+    }
+
+    fn skip(&mut self, check: &'static str, detail: impl Into<String>) {
+        self.push(check, CheckStatus::Skipped, detail);
+    }
+
+    pub fn get_text_output(&self) -> String {
+        let mut lines: Vec<String> = self
+            .checks
+            .iter()
+            .map(|check| {
+                let outcome = match check.status {
+                    CheckStatus::Passed => "PASS",
+                    CheckStatus::Failed => "FAIL",
+                    CheckStatus::Skipped => "SKIP",
+                };
+                format!("{}  {}: {}", outcome, check.check, check.detail)
+            })
+            .collect();
+
+        lines.push(if self.passed {
+            format!("Dry run passed, {} would be uploaded", self.file)
+        } else {
+            let failed = self
+                .checks
+                .iter()
+                .filter(|check| check.status == CheckStatus::Failed)
+                .count();
+            format!(
+                "Dry run failed: {} of {} checks failed",
+                failed,
+                self.checks.len()
+            )
+        });
+        lines.join("\n")
+    }
+}
+
+/// Check everything `create` would need without uploading the firmware:
+/// the file, the type, the name, the api key, the organization and, when the
+/// server can, the upload itself. All the checks are reported, not only the
+/// first failure.
+#[allow(clippy::too_many_arguments)]
+pub async fn create_dry_run<U: ApiServer>(
+    fw_filepath: &str,
+    fw_type: Option<&str>,
+    fw_subtype: &str,
+    name: &str,
+    organization: Option<&str>,
+    allow_unknown_type: bool,
+    api_server: &mut U,
+) -> CreateDryRun {
+    let mut dry_run = CreateDryRun {
+        file: fw_filepath.to_string(),
+        size: None,
+        sha256: None,
+        passed: true,
+        checks: vec![],
+        exit_code: None,
+    };
+
+    match check_file(Path::new(fw_filepath)) {
+        Ok(size) => {
+            dry_run.size = Some(size);
+            dry_run.push("file", CheckStatus::Passed, format!("{} bytes", size));
+        }
+        Err(detail) => dry_run.fail("file", detail, ExitCode::Usage),
+    }
+
+    match dry_run.size {
+        Some(_) => match crate::api::file_sha256(Path::new(fw_filepath)).await {
+            Ok(sha256) => {
+                dry_run.push("sha256", CheckStatus::Passed, sha256.clone());
+                dry_run.sha256 = Some(sha256);
+            }
+            Err(err) => dry_run.fail("sha256", format!("{:#}", err), ExitCode::Failure),
+        },
+        None => dry_run.skip("sha256", "the file check failed"),
+    }
+
+    match fw_type {
+        Some(fw_type) => match cli::parse_firmware(fw_type, fw_subtype) {
+            Ok((fw_type, fw_subtype)) => dry_run.push(
+                "type",
+                CheckStatus::Passed,
+                format!("{fw_type} {fw_subtype}"),
+            ),
+            Err(detail) if allow_unknown_type => {
+                dry_run.skip("type", format!("{detail}. Left to the server"))
+            }
+            Err(detail) => dry_run.fail("type", detail, ExitCode::Usage),
+        },
+        None => dry_run.fail(
+            "type",
+            "no firmware type, pass --type or set `type` in the configuration file",
+            ExitCode::Usage,
+        ),
+    }
+
+    match check_name(name) {
+        Ok(()) => dry_run.push("name", CheckStatus::Passed, name),
+        Err(detail) => dry_run.fail("name", detail, ExitCode::Usage),
+    }
+
+    let authenticated = match api_server.ping().await {
+        Ok(report) => {
+            dry_run.push(
+                "auth",
+                CheckStatus::Passed,
+                format!(
+                    "api key accepted by {} in {} ms",
+                    report.address, report.authentication_ms
+                ),
+            );
+            true
+        }
+        Err(err) => {
+            let exit_code = ExitCode::from_api_error(&err);
+            dry_run.fail("auth", err.to_string(), exit_code);
+            false
+        }
+    };
+
+    let mut organization_id = None;
+    match organization {
+        Some(_) if !authenticated => dry_run.skip("organization", "the api key was refused"),
+        Some(organization) => {
+            match crate::services::organization_service::resolve(api_server, organization).await {
+                Ok(resolved) => {
+                    dry_run.push(
+                        "organization",
+                        CheckStatus::Passed,
+                        format!("{} ({})", resolved.name, resolved.id),
+                    );
+                    organization_id = Some(resolved.id.to_string());
+                }
+                Err(err) => {
+                    let exit_code = ExitCode::from_error(&err);
+                    dry_run.fail("organization", format!("{:#}", err), exit_code);
+                }
+            }
+        }
+        None => dry_run.skip("organization", "none given, the built-in one"),
+    }
+
+    match (&dry_run.sha256, fw_type) {
+        _ if !authenticated => dry_run.skip("server", "the api key was refused"),
+        (Some(sha256), Some(fw_type)) => {
+            let validation = UploadValidationDTO {
+                name,
+                r#type: fw_type,
+                subtype: fw_subtype,
+                filename: Path::new(fw_filepath)
+                    .file_name()
+                    .and_then(|filename| filename.to_str())
+                    .unwrap_or(fw_filepath),
+                size: dry_run.size.unwrap_or_default(),
+                sha256,
+                organization: organization_id.as_deref(),
+            };
+            match api_server.validate_upload(&validation).await {
+                Ok(true) => dry_run.push("server", CheckStatus::Passed, "upload accepted"),
+                Ok(false) => dry_run.skip("server", "no validation offered by the server"),
+                Err(err) => {
+                    let exit_code = ExitCode::from_api_error(&err);
+                    dry_run.fail("server", err.to_string(), exit_code);
+                }
+            }
+        }
+        _ => dry_run.skip("server", "the file or the type is invalid"),
+    }
+
+    dry_run
+}
+
+/// Size of the firmware at `path`, once checked that it can be uploaded.
+fn check_file(path: &Path) -> Result<u64, String> {
+    if !path.exists() {
+        return Err(format!("{} doesn't exist", path.display()));
+    }
+    if !path.is_file() {
+        return Err(format!("{} is not a file", path.display()));
+    }
+    let size = File::open(path)
+        .and_then(|file| file.metadata())
+        .map_err(|err| format!("{} can't be read: {}", path.display(), err))?
+        .len();
+    if size == 0 {
+        return Err(format!("{} is empty", path.display()));
+    }
+    if size as usize > FILE_SIZE_LIMIT {
+        return Err(format!(
+            "{} bytes, more than the maximum of {} bytes",
+            size, FILE_SIZE_LIMIT
+        ));
+    }
+    Ok(size)
+}
 
-    Ok(ProjectCreated { id: project_id })
+/// Whether `name` is a valid project name: 1 to [MAX_NAME_LEN] characters,
+/// none of them a control character.
+pub(crate) fn check_name(name: &str) -> Result<(), String> {
+    let len = name.chars().count();
+    if name.trim().is_empty() || len > MAX_NAME_LEN {
+        return Err(format!(
+            "{} characters, expected 1 to {}",
+            len, MAX_NAME_LEN
+        ));
+    }
+    if name.chars().any(char::is_control) {
+        return Err("control characters are not allowed".to_string());
+    }
+    Ok(())
 }
@@ -0,0 +1,594 @@
+use std::path::Path;
+
+use anyhow::{anyhow, bail, Result};
+use clap::ValueEnum;
+use serde::de::DeserializeOwned;
+use uuid::Uuid;
+
+use crate::{
+    api::ApiServer,
+    cli::{Analysis, ReportFormat},
+    output::{CsvRecord, Template},
+    services::project_service::{
+        self, LinuxCryptoAnalysis, LinuxCveCheckAnalysis, LinuxHardeningAnalysis,
+        LinuxKernelAnalysis, LinuxNvramAnalysis, LinuxPasswordHashAnalysis,
+        LinuxSecurityScanAnalysis, LinuxSoftwareBOMAnalysis, ProjectOverview,
+    },
+};
+
+/// Analyses of the report when none is given
+pub const REPORT_ANALYSES: [Analysis; 3] = [
+    Analysis::CveCheck,
+    Analysis::Hardening,
+    Analysis::PasswordHash,
+];
+
+/// Template of the Markdown report, overridden with `--template`
+const DEFAULT_TEMPLATE: &str = "# Security report of {{.name}}
+
+{{.summary}}
+
+## Overview
+
+{{.overview}}
+
+{{.analyses}}
+";
+
+/// Fields of the templates, the analyses by name having their own section
+const TEMPLATE_FIELDS: &[&str] = &[
+    "name",
+    "id",
+    "type",
+    "status",
+    "score",
+    "date",
+    "summary",
+    "overview",
+    "analyses",
+    "hardening",
+    "cve-check",
+    "security-scan",
+    "password-hash",
+    "crypto",
+    "nvram",
+    "kernel",
+    "software-bom",
+    "static-code",
+];
+
+/// Style of the HTML report, inline for the file to be self-contained
+const HTML_STYLE: &str = r#"
+body { font-family: -apple-system, "Segoe UI", Helvetica, Arial, sans-serif; margin: 2em auto; max-width: 70em; padding: 0 1em; color: #1f2328; }
+h1 { font-size: 1.6em; border-bottom: 1px solid #d0d7de; padding-bottom: .3em; }
+details { border: 1px solid #d0d7de; border-radius: 6px; margin: 1em 0; padding: 0 1em; }
+summary { cursor: pointer; font-weight: 600; font-size: 1.2em; padding: .6em 0; }
+table { border-collapse: collapse; margin-bottom: 1em; width: 100%; font-size: .9em; }
+th, td { border: 1px solid #d0d7de; padding: .3em .6em; text-align: left; vertical-align: top; }
+th { background: #f6f8fa; }
+table.sortable th { cursor: pointer; user-select: none; }
+table.sortable th[aria-sort=ascending]::after { content: " \25B2"; }
+table.sortable th[aria-sort=descending]::after { content: " \25BC"; }
+.badge { border-radius: 1em; color: #fff; display: inline-block; font-size: .85em; font-weight: 600; padding: .1em .6em; background: #6e7781; }
+.critical { background: #8250df; }
+.high { background: #cf222e; }
+.medium { background: #bf8700; }
+.low { background: #0969da; }
+.count { color: #656d76; font-weight: normal; }
+.warning { background: #fff8c5; border: 1px solid #d4a72c; border-radius: 6px; padding: .6em 1em; }
+footer { color: #656d76; font-size: .85em; margin-top: 2em; }
+"#;
+
+/// Sorting of the tables with the `sortable` class, by the `data-sort` of
+/// the cells or their text, numbers by value
+const HTML_SCRIPT: &str = r#"
+document.querySelectorAll("table.sortable").forEach(function (table) {
+  table.querySelectorAll("th").forEach(function (th, column) {
+    th.addEventListener("click", function () {
+      var ascending = th.getAttribute("aria-sort") !== "ascending";
+      table.querySelectorAll("th").forEach(function (other) { other.removeAttribute("aria-sort"); });
+      th.setAttribute("aria-sort", ascending ? "ascending" : "descending");
+      var key = function (row) {
+        var cell = row.cells[column];
+        return cell.dataset.sort !== undefined ? cell.dataset.sort : cell.textContent.trim();
+      };
+      var tbody = table.tBodies[0];
+      Array.from(tbody.rows).sort(function (a, b) {
+        var x = key(a), y = key(b);
+        var order = x !== "" && y !== "" && !isNaN(x) && !isNaN(y)
+          ? Number(x) - Number(y)
+          : x.localeCompare(y);
+        return ascending ? order : -order;
+      }).forEach(function (row) { tbody.appendChild(row); });
+    });
+  });
+});
+"#;
+
+/// Report of a project, its overview and a section per analysis.
+struct Report {
+    project_id: Uuid,
+    overview: ProjectOverview,
+    sections: Vec<Section>,
+}
+
+/// Section of an analysis in the report.
+struct Section {
+    analysis: Analysis,
+    content: Content,
+    /// Number of CVEs by severity, for the summary of the cve-check analysis
+    cves: Option<Vec<(String, usize)>>,
+}
+
+enum Content {
+    Table {
+        header: Vec<String>,
+        rows: Vec<Vec<String>>,
+    },
+    Empty,
+    /// The analysis couldn't be fetched
+    Warning(String),
+}
+
+// Save the `format` report of the `analyses` of a project to `savepath`
+pub async fn save<U: ApiServer>(
+    api_server: &mut U,
+    project_id: Uuid,
+    savepath: String,
+    format: ReportFormat,
+    analyses: &[Analysis],
+    template: Option<&Path>,
+    force: bool,
+) -> Result<String> {
+    let report_path = Path::new(&savepath);
+    if report_path.exists() && !force {
+        bail!(
+            "File {} already exists, use --force to overwrite it",
+            report_path.display()
+        );
+    }
+
+    // The template is checked before fetching anything
+    let template = match template {
+        Some(path) => std::fs::read_to_string(path)
+            .map_err(|err| anyhow!("error reading template {}: {}", path.display(), err))?,
+        None => DEFAULT_TEMPLATE.to_string(),
+    };
+    let template = Template::parse(&template, TEMPLATE_FIELDS).map_err(|err| anyhow!(err))?;
+
+    let report = fetch(api_server, project_id, analyses).await?;
+    let document = match format {
+        ReportFormat::Markdown => template.render(&report.fields()),
+        ReportFormat::Html => report.html(),
+        ReportFormat::Pdf => unreachable!("PDF reports are generated by the server"),
+    };
+    std::fs::write(report_path, document).map_err(|err| {
+        anyhow!(
+            "Error writing data to file: {}. Reason: {}",
+            report_path.display(),
+            err
+        )
+    })?;
+
+    Ok(savepath)
+}
+
+/// Fetch the overview and the `analyses` of a project, those failing having
+/// a warning in place of their results.
+async fn fetch<U: ApiServer>(
+    api_server: &mut U,
+    project_id: Uuid,
+    analyses: &[Analysis],
+) -> Result<Report> {
+    let overview =
+        ProjectOverview::from_value(project_service::overview(api_server, project_id).await?)?;
+
+    let mut sections = vec![];
+    for analysis in analyses {
+        let section = match project_service::analysis_results(api_server, project_id, analysis)
+            .await
+            .and_then(|results| section(analysis, results))
+        {
+            Ok(section) => section,
+            Err(err) => {
+                tracing::warn!("Analysis {} not in the report: {:#}", analysis, err);
+                Section {
+                    analysis: analysis.clone(),
+                    content: Content::Warning(format!("{:#}", err)),
+                    cves: None,
+                }
+            }
+        };
+        sections.push(section);
+    }
+
+    Ok(Report {
+        project_id,
+        overview,
+        sections,
+    })
+}
+
+impl Report {
+    fn name(&self) -> String {
+        self.overview
+            .project
+            .name
+            .clone()
+            .unwrap_or_else(|| self.project_id.to_string())
+    }
+
+    /// The CVEs found by the analysis, those of the overview otherwise.
+    fn cves(&self) -> Option<Vec<(String, usize)>> {
+        self.sections
+            .iter()
+            .find_map(|section| section.cves.clone())
+            .or_else(|| {
+                let severity = &self.overview.cve_check.as_ref()?.severity;
+                let mut cves = vec![];
+                if let Some(critical) = severity.critical {
+                    cves.push(("critical".to_string(), critical as usize));
+                }
+                cves.push(("high".to_string(), severity.high as usize));
+                cves.push(("medium".to_string(), severity.medium as usize));
+                cves.push(("low".to_string(), severity.low as usize));
+                Some(cves)
+            })
+    }
+
+    /// Properties of the project and facts about the firmware.
+    fn overview_rows(&self) -> Vec<Vec<String>> {
+        let project = &self.overview.project;
+        let mut rows = vec![];
+        rows.extend(project.project_type.clone().map(|value| ("Type", value)));
+        rows.extend(project.status.clone().map(|value| ("Status", value)));
+        rows.extend(project.score.map(|score| ("Score", score.to_string())));
+        rows.extend(self.overview.info.facts());
+
+        rows.into_iter()
+            .map(|(name, value)| vec![name.to_string(), value])
+            .collect()
+    }
+
+    /// Values of the fields of the Markdown template.
+    fn fields(&self) -> serde_json::Value {
+        let project = &self.overview.project;
+        let sections: Vec<(&Analysis, String)> = self
+            .sections
+            .iter()
+            .map(|section| (&section.analysis, section.markdown()))
+            .collect();
+
+        let mut fields = serde_json::json!({
+            "name": self.name(),
+            "id": self.project_id.to_string(),
+            "type": project.project_type,
+            "status": project.status,
+            "score": project.score.map(|score| score.to_string()),
+            "date": date(),
+            "summary": self.markdown_summary(),
+            "overview": markdown_table(&["property", "value"], &self.overview_rows()),
+            "analyses": sections
+                .iter()
+                .map(|(_, markdown)| markdown.as_str())
+                .collect::<Vec<_>>()
+                .join("\n\n"),
+        });
+        for (analysis, markdown) in sections {
+            if let Some(name) = analysis.to_possible_value() {
+                fields[name.get_name()] = markdown.into();
+            }
+        }
+        fields
+    }
+
+    /// Executive summary: the score and the CVEs by severity.
+    fn markdown_summary(&self) -> String {
+        let mut summary = match self.overview.project.score {
+            Some(score) => format!("Security score **{}**", score),
+            None => "No security score reported".to_string(),
+        };
+        if let Some(cves) = self.cves() {
+            let total: usize = cves.iter().map(|(_, count)| count).sum();
+            let counts: Vec<String> = cves
+                .iter()
+                .filter(|(_, count)| *count > 0)
+                .map(|(severity, count)| format!("{} {}", count, severity))
+                .collect();
+            summary.push_str(&format!(", {} CVEs", total));
+            if !counts.is_empty() {
+                summary.push_str(&format!(" ({})", counts.join(", ")));
+            }
+        }
+        summary + "."
+    }
+
+    /// Single HTML file, styled and scripted inline to be opened offline.
+    fn html(&self) -> String {
+        let title = format!("Security report of {}", self.name());
+
+        let mut summary = match self.overview.project.score {
+            Some(score) => format!("Security score <strong>{}</strong>", score),
+            None => "No security score reported".to_string(),
+        };
+        if let Some(cves) = self.cves() {
+            let total: usize = cves.iter().map(|(_, count)| count).sum();
+            summary.push_str(&format!(", {} CVEs", total));
+            for (severity, count) in cves.iter().filter(|(_, count)| *count > 0) {
+                summary.push_str(&format!(
+                    " {}",
+                    badge(severity, &format!("{} {}", count, severity))
+                ));
+            }
+        }
+
+        let mut body = vec![
+            format!("<h1>{}</h1>", escape(&title)),
+            format!("<p class=\"summary\">{}</p>", summary),
+            html_details(
+                "Overview",
+                None,
+                &html_table(&["property", "value"], &self.overview_rows(), false),
+            ),
+        ];
+        body.extend(self.sections.iter().map(Section::html));
+        body.push(format!(
+            "<footer>Generated by {} {} on {} for project {}</footer>",
+            env!("CARGO_PKG_NAME"),
+            crate::version(),
+            date(),
+            self.project_id
+        ));
+
+        format!(
+            "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+             <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+             <title>{}</title>\n<style>{}</style>\n</head>\n<body>\n{}\n<script>{}</script>\n\
+             </body>\n</html>\n",
+            escape(&title),
+            HTML_STYLE,
+            body.join("\n"),
+            HTML_SCRIPT
+        )
+    }
+}
+
+impl Section {
+    fn markdown(&self) -> String {
+        let content = match &self.content {
+            Content::Table { header, rows } => {
+                let header: Vec<&str> = header.iter().map(String::as_str).collect();
+                markdown_table(&header, rows)
+            }
+            Content::Empty => "No findings.".to_string(),
+            Content::Warning(err) => format!("> **Warning:** analysis not available: {}", err),
+        };
+        format!("## {}\n\n{}", title(&self.analysis), content)
+    }
+
+    /// Collapsible section, the CVEs sortable by column.
+    fn html(&self) -> String {
+        let (count, content) = match &self.content {
+            Content::Table { header, rows } => {
+                let header: Vec<&str> = header.iter().map(String::as_str).collect();
+                let sortable = self.analysis == Analysis::CveCheck;
+                (Some(rows.len()), html_table(&header, rows, sortable))
+            }
+            Content::Empty => (Some(0), "<p>No findings.</p>".to_string()),
+            Content::Warning(err) => (
+                None,
+                format!(
+                    "<p class=\"warning\"><strong>Warning:</strong> analysis not available: {}</p>",
+                    escape(err)
+                ),
+            ),
+        };
+        html_details(title(&self.analysis), count, &content)
+    }
+}
+
+/// Section of `analysis` with its `results`, as a table.
+fn section(analysis: &Analysis, results: Vec<serde_json::Value>) -> Result<Section> {
+    let results = serde_json::Value::Array(results);
+    let mut cves = None;
+
+    let content = match analysis {
+        Analysis::CveCheck => {
+            let mut an: Vec<LinuxCveCheckAnalysis> = serde_json::from_value(results)?;
+            sort_by_severity(&mut an);
+            let mut counts: Vec<(String, usize)> = vec![];
+            for cve in &an {
+                let severity = cve.severity.to_lowercase();
+                match counts.iter_mut().find(|(name, _)| *name == severity) {
+                    Some((_, count)) => *count += 1,
+                    None => counts.push((severity, 1)),
+                }
+            }
+            cves = Some(counts);
+            records(&an)
+        }
+        Analysis::Hardening => records(&parse::<LinuxHardeningAnalysis>(results)?),
+        Analysis::SecurityScan => records(&parse::<LinuxSecurityScanAnalysis>(results)?),
+        // Only the users, the hashes don't belong in a shared document
+        Analysis::PasswordHash => {
+            let an: Vec<LinuxPasswordHashAnalysis> = serde_json::from_value(results)?;
+            match an.is_empty() {
+                true => Content::Empty,
+                false => Content::Table {
+                    header: vec!["username".to_string()],
+                    rows: an.iter().map(|hash| vec![hash.username.clone()]).collect(),
+                },
+            }
+        }
+        Analysis::Crypto => records(&parse::<LinuxCryptoAnalysis>(results)?),
+        Analysis::Nvram => records(&parse::<LinuxNvramAnalysis>(results)?),
+        Analysis::Kernel => records(&parse::<LinuxKernelAnalysis>(results)?),
+        Analysis::SoftwareBOM => records(&parse::<LinuxSoftwareBOMAnalysis>(results)?),
+        Analysis::StaticCode => records(&project_service::static_code_flaws(results)?),
+        _ => bail!("no table for the {} analysis in the report", analysis),
+    };
+
+    Ok(Section {
+        analysis: analysis.clone(),
+        content,
+        cves,
+    })
+}
+
+fn parse<T: DeserializeOwned>(results: serde_json::Value) -> Result<Vec<T>> {
+    Ok(serde_json::from_value(results)?)
+}
+
+/// Most severe CVEs first, by CVSS score within a severity.
+fn sort_by_severity(cves: &mut [LinuxCveCheckAnalysis]) {
+    cves.sort_by(|a, b| {
+        severity_rank(&a.severity)
+            .cmp(&severity_rank(&b.severity))
+            .then_with(|| {
+                b.cvss_score()
+                    .unwrap_or(-1.0)
+                    .total_cmp(&a.cvss_score().unwrap_or(-1.0))
+            })
+            .then_with(|| a.cveid.cmp(&b.cveid))
+    });
+}
+
+/// Rank of a severity, the most severe first.
+fn severity_rank(severity: &str) -> u8 {
+    match severity.to_lowercase().as_str() {
+        "critical" => 0,
+        "high" => 1,
+        "medium" => 2,
+        "low" => 3,
+        _ => 4,
+    }
+}
+
+/// Table of `records`, with the columns of their CSV output.
+fn records<T: CsvRecord>(records: &[T]) -> Content {
+    if records.is_empty() {
+        return Content::Empty;
+    }
+    Content::Table {
+        header: T::HEADER.iter().map(|name| name.to_string()).collect(),
+        rows: records.iter().map(|record| record.record(", ")).collect(),
+    }
+}
+
+/// Markdown table of `rows` under `header`.
+fn markdown_table(header: &[&str], rows: &[Vec<String>]) -> String {
+    let line = |cells: Vec<String>| format!("| {} |", cells.join(" | "));
+
+    let mut lines = vec![
+        line(header.iter().map(|name| markdown_cell(name)).collect()),
+        line(header.iter().map(|_| "---".to_string()).collect()),
+    ];
+    lines.extend(
+        rows.iter()
+            .map(|row| line(row.iter().map(|field| markdown_cell(field)).collect())),
+    );
+    lines.join("\n")
+}
+
+/// Escape `field` for a table cell, where pipes end the cell and line breaks
+/// the row.
+fn markdown_cell(field: &str) -> String {
+    field
+        .trim()
+        .replace('|', "\\|")
+        .replace("\r\n", "<br>")
+        .replace('\n', "<br>")
+}
+
+/// HTML table of `rows` under `header`, the severities as badges.
+fn html_table(header: &[&str], rows: &[Vec<String>], sortable: bool) -> String {
+    let severity = header.iter().position(|name| *name == "severity");
+
+    let mut html = match sortable {
+        true => "<table class=\"sortable\">\n<thead><tr>".to_string(),
+        false => "<table>\n<thead><tr>".to_string(),
+    };
+    for name in header {
+        html.push_str(&format!("<th>{}</th>", escape(name)));
+    }
+    html.push_str("</tr></thead>\n<tbody>\n");
+    for row in rows {
+        html.push_str("<tr>");
+        for (i, field) in row.iter().enumerate() {
+            if Some(i) == severity {
+                html.push_str(&format!(
+                    "<td data-sort=\"{}\">{}</td>",
+                    severity_rank(field),
+                    badge(field, field)
+                ));
+            } else {
+                html.push_str(&format!("<td>{}</td>", escape(field)));
+            }
+        }
+        html.push_str("</tr>\n");
+    }
+    html.push_str("</tbody>\n</table>");
+    html
+}
+
+/// Collapsible section, open by default, with the number of its findings.
+fn html_details(title: &str, count: Option<usize>, content: &str) -> String {
+    let count = count
+        .map(|count| format!(" <span class=\"count\">({})</span>", count))
+        .unwrap_or_default();
+    format!(
+        "<details open>\n<summary>{}{}</summary>\n{}\n</details>",
+        escape(title),
+        count,
+        content
+    )
+}
+
+/// Badge of `text`, colored by `severity`.
+fn badge(severity: &str, text: &str) -> String {
+    let class = match severity_rank(severity) {
+        0 => " critical",
+        1 => " high",
+        2 => " medium",
+        3 => " low",
+        _ => "",
+    };
+    format!("<span class=\"badge{}\">{}</span>", class, escape(text))
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+fn date() -> String {
+    chrono::Utc::now().format("%Y-%m-%d").to_string()
+}
+
+/// Heading of the section of `analysis`.
+fn title(analysis: &Analysis) -> &'static str {
+    match analysis {
+        Analysis::Hardening => "Hardening",
+        Analysis::CveCheck => "CVEs",
+        Analysis::SecurityScan => "Security scan",
+        Analysis::PasswordHash => "Password hashes",
+        Analysis::Crypto => "Crypto",
+        Analysis::Nvram => "NVRAM",
+        Analysis::Kernel => "Kernel",
+        Analysis::SoftwareBOM => "Software BOM",
+        Analysis::StaticCode => "Static code",
+        Analysis::Access => "Access",
+        Analysis::IntelBootGuard => "Intel Boot Guard",
+        Analysis::Surface => "Surface",
+        Analysis::SecureBoot => "Secure Boot",
+        Analysis::UefiSecurityScan => "UEFI security scan",
+        Analysis::PeimDxe => "PEIM/DXE",
+        Analysis::Functions => "Functions",
+        Analysis::Symbols => "Symbols",
+        Analysis::Tasks => "Tasks",
+        Analysis::Capabilities => "Capabilities",
+    }
+}
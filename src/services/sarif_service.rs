@@ -0,0 +1,313 @@
+use anyhow::{bail, Result};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{
+    api::ApiServer,
+    cli::{Analysis, CveFilter},
+    services::project_service::{
+        self, LinuxCveCheckAnalysis, LinuxHardeningAnalysis, LinuxPasswordHashAnalysis,
+    },
+};
+
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+const SARIF_VERSION: &str = "2.1.0";
+const COSMO_URI: &str = "https://cosmo.exein.io";
+
+/// Code scanning needs a file for every result, the firmware itself stands
+/// for the findings without one
+const FIRMWARE_URI: &str = "firmware";
+
+/// Analyses whose findings are mapped to SARIF results
+pub const SARIF_ANALYSES: [Analysis; 3] = [
+    Analysis::CveCheck,
+    Analysis::Hardening,
+    Analysis::PasswordHash,
+];
+
+/// Rules of the results, one per finding type: `(id, name, description)`
+const RULES: [(&str, &str, &str); 3] = [
+    (
+        "cve",
+        "KnownVulnerability",
+        "Package affected by a known vulnerability",
+    ),
+    (
+        "hardening",
+        "MissingHardening",
+        "Binary built without some of the hardening protections",
+    ),
+    (
+        "hardcoded-credentials",
+        "HardcodedCredentials",
+        "Password hash of a user stored in the firmware",
+    ),
+];
+
+/// SARIF 2.1.0 log of the findings of a project, only the parts filled from
+/// the analyses.
+#[derive(Debug, Serialize)]
+pub struct SarifLog {
+    #[serde(rename = "$schema")]
+    schema: &'static str,
+    version: &'static str,
+    runs: Vec<SarifRun>,
+}
+
+#[derive(Debug, Serialize)]
+struct SarifRun {
+    tool: SarifTool,
+    results: Vec<SarifResult>,
+    properties: SarifRunProperties,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifRunProperties {
+    project_id: Uuid,
+}
+
+#[derive(Debug, Serialize)]
+struct SarifTool {
+    driver: SarifDriver,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifDriver {
+    name: &'static str,
+    version: String,
+    information_uri: &'static str,
+    rules: Vec<SarifRule>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifRule {
+    id: &'static str,
+    name: &'static str,
+    short_description: SarifMessage,
+    help_uri: &'static str,
+}
+
+#[derive(Debug, Serialize)]
+struct SarifMessage {
+    text: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifResult {
+    rule_id: &'static str,
+    rule_index: usize,
+    level: &'static str,
+    message: SarifMessage,
+    locations: Vec<SarifLocation>,
+    #[serde(skip_serializing_if = "serde_json::Map::is_empty")]
+    properties: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifLocation {
+    physical_location: SarifPhysicalLocation,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    logical_locations: Vec<SarifLogicalLocation>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifPhysicalLocation {
+    artifact_location: SarifArtifactLocation,
+}
+
+#[derive(Debug, Serialize)]
+struct SarifArtifactLocation {
+    uri: String,
+}
+
+#[derive(Debug, Serialize)]
+struct SarifLogicalLocation {
+    name: String,
+    kind: &'static str,
+}
+
+// Findings of the `analyses` of a project, CVEs filtered by `cve_filter`
+pub async fn findings<U: ApiServer>(
+    api_server: &U,
+    project_id: Uuid,
+    analyses: &[Analysis],
+    cve_filter: &CveFilter,
+) -> Result<SarifLog> {
+    let analyses: Vec<&Analysis> = analyses
+        .iter()
+        .filter(|analysis| {
+            matches!(
+                analysis,
+                Analysis::CveCheck | Analysis::Hardening | Analysis::PasswordHash
+            )
+        })
+        .collect();
+    if analyses.is_empty() {
+        bail!("SARIF output only covers the cve-check, hardening and password-hash analyses");
+    }
+
+    let mut results = vec![];
+    for analysis in analyses {
+        let found = project_service::analysis_results(api_server, project_id, analysis).await?;
+        let found = serde_json::Value::Array(found);
+        match analysis {
+            Analysis::CveCheck => results.extend(
+                serde_json::from_value::<Vec<LinuxCveCheckAnalysis>>(found)?
+                    .iter()
+                    .filter(|cve| cve_filter.matches(cve))
+                    .map(cve_result),
+            ),
+            Analysis::Hardening => results.extend(
+                serde_json::from_value::<Vec<LinuxHardeningAnalysis>>(found)?
+                    .iter()
+                    .filter_map(hardening_result),
+            ),
+            Analysis::PasswordHash => results.extend(
+                serde_json::from_value::<Vec<LinuxPasswordHashAnalysis>>(found)?
+                    .iter()
+                    .map(credentials_result),
+            ),
+            _ => unreachable!("analysis without SARIF results"),
+        }
+    }
+
+    Ok(SarifLog {
+        schema: SARIF_SCHEMA,
+        version: SARIF_VERSION,
+        runs: vec![SarifRun {
+            tool: SarifTool {
+                driver: SarifDriver {
+                    name: env!("CARGO_PKG_NAME"),
+                    version: crate::version().to_string(),
+                    information_uri: COSMO_URI,
+                    rules: RULES
+                        .iter()
+                        .map(|(id, name, description)| SarifRule {
+                            id,
+                            name,
+                            short_description: SarifMessage {
+                                text: description.to_string(),
+                            },
+                            help_uri: COSMO_URI,
+                        })
+                        .collect(),
+                },
+            },
+            results,
+            properties: SarifRunProperties { project_id },
+        }],
+    })
+}
+
+fn cve_result(cve: &LinuxCveCheckAnalysis) -> SarifResult {
+    let level = match cve.severity.to_ascii_lowercase().as_str() {
+        "critical" | "high" => "error",
+        "low" => "note",
+        _ => "warning",
+    };
+
+    let mut properties = serde_json::Map::new();
+    properties.insert("severity".to_string(), cve.severity.clone().into());
+    if let Some(score) = cve.cvss_score() {
+        properties.insert("cvss".to_string(), score.into());
+    }
+
+    result(
+        "cve",
+        level,
+        format!(
+            "{} in {} {}: {}",
+            cve.cveid, cve.product, cve.version, cve.summary
+        ),
+        None,
+        Some(SarifLogicalLocation {
+            name: cve.product.clone(),
+            kind: "module",
+        }),
+        properties,
+    )
+}
+
+/// Result of a binary lacking any of the protections, if so.
+fn hardening_result(binary: &LinuxHardeningAnalysis) -> Option<SarifResult> {
+    let lacking = binary.missing_protections();
+    if lacking.is_empty() {
+        return None;
+    }
+
+    let mut properties = serde_json::Map::new();
+    properties.insert("score".to_string(), binary.score.into());
+
+    Some(result(
+        "hardening",
+        "warning",
+        format!("{} built without {}", binary.filename, lacking.join(", ")),
+        Some(&binary.filename),
+        None,
+        properties,
+    ))
+}
+
+fn credentials_result(credentials: &LinuxPasswordHashAnalysis) -> SarifResult {
+    // The hash itself is left out of the log
+    result(
+        "hardcoded-credentials",
+        "error",
+        format!("Password hash of user {} found", credentials.username),
+        None,
+        Some(SarifLogicalLocation {
+            name: credentials.username.clone(),
+            kind: "member",
+        }),
+        serde_json::Map::new(),
+    )
+}
+
+fn result(
+    rule_id: &'static str,
+    level: &'static str,
+    message: String,
+    path: Option<&str>,
+    logical_location: Option<SarifLogicalLocation>,
+    properties: serde_json::Map<String, serde_json::Value>,
+) -> SarifResult {
+    SarifResult {
+        rule_id,
+        rule_index: RULES
+            .iter()
+            .position(|(id, _, _)| *id == rule_id)
+            .expect("rule of the result"),
+        level,
+        message: SarifMessage { text: message },
+        locations: vec![SarifLocation {
+            physical_location: SarifPhysicalLocation {
+                artifact_location: SarifArtifactLocation {
+                    uri: path
+                        .map(|path| uri_encode(path.trim_start_matches('/')))
+                        .filter(|uri| !uri.is_empty())
+                        .unwrap_or_else(|| FIRMWARE_URI.to_string()),
+                },
+            },
+            logical_locations: logical_location.into_iter().collect(),
+        }],
+        properties,
+    }
+}
+
+/// Percent-encode a path of the firmware as a relative uri.
+fn uri_encode(path: &str) -> String {
+    path.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'.' | b'-' | b'_' | b'~' | b'/' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
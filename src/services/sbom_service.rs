@@ -0,0 +1,569 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use chrono::{SecondsFormat, Utc};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{
+    api::ApiServer,
+    cli::{Analysis, SbomFormat},
+    services::project_service::{self, LinuxSoftwareBOMAnalysis},
+};
+
+const CYCLONEDX_SPEC_VERSION: &str = "1.5";
+const SPDX_VERSION: &str = "SPDX-2.3";
+const SPDX_NAMESPACE_BASE: &str = "https://cosmo.exein.io/spdx";
+const NOASSERTION: &str = "NOASSERTION";
+
+/// Software BOM of a project, rendered in the requested format.
+#[derive(Debug)]
+pub struct Sbom {
+    pub document: String,
+    /// Number of packages of the BOM, the firmware excluded
+    pub packages: usize,
+}
+
+// Software BOM of a project
+pub async fn export<U: ApiServer>(
+    api_server: &U,
+    project_id: Uuid,
+    format: SbomFormat,
+) -> Result<Sbom> {
+    let results =
+        project_service::analysis_results(api_server, project_id, &Analysis::SoftwareBOM).await?;
+    let packages: Vec<Package> =
+        serde_json::from_value::<Vec<LinuxSoftwareBOMAnalysis>>(serde_json::Value::Array(results))?
+            .iter()
+            .map(Package::from)
+            .collect();
+
+    let document = match format {
+        SbomFormat::CyclonedxJson => {
+            serde_json::to_string_pretty(&cyclonedx(project_id, &packages))?
+        }
+        SbomFormat::SpdxJson => serde_json::to_string_pretty(&spdx(project_id, &packages))?,
+        SbomFormat::SpdxTagValue => spdx(project_id, &packages).tag_value(),
+    };
+
+    Ok(Sbom {
+        document,
+        packages: packages.len(),
+    })
+}
+
+/// Package of the software BOM analysis, with what can be derived of it.
+struct Package {
+    name: String,
+    /// Unknown versions are left out, the package is still listed
+    version: Option<String>,
+    purl: Option<String>,
+    license: Option<String>,
+    /// Well formed hashes of a known algorithm, e.g. `("SHA-256", hex)`
+    hashes: Vec<(&'static str, String)>,
+}
+
+impl From<&LinuxSoftwareBOMAnalysis> for Package {
+    fn from(package: &LinuxSoftwareBOMAnalysis) -> Self {
+        let name = package
+            .name
+            .clone()
+            .unwrap_or_else(|| package.filename.clone());
+        let version = package
+            .version
+            .clone()
+            .or_else(|| soname_version(&package.resolve))
+            .or_else(|| soname_version(&package.filename));
+        let purl = package.purl.clone().or_else(|| {
+            version.as_ref().map(|version| {
+                format!(
+                    "pkg:generic/{}@{}",
+                    purl_encode(&name),
+                    purl_encode(version)
+                )
+            })
+        });
+
+        Self {
+            name,
+            version,
+            purl,
+            license: package
+                .license
+                .as_deref()
+                .map(str::trim)
+                .filter(|license| !license.is_empty())
+                .map(str::to_string),
+            hashes: hashes(&package.hashes),
+        }
+    }
+}
+
+/// CycloneDX BOM, only the parts filled from the analysis.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CycloneDxBom {
+    bom_format: &'static str,
+    spec_version: &'static str,
+    serial_number: String,
+    version: u32,
+    metadata: CycloneDxMetadata,
+    components: Vec<CycloneDxComponent>,
+}
+
+#[derive(Debug, Serialize)]
+struct CycloneDxMetadata {
+    timestamp: String,
+    tools: CycloneDxTools,
+    component: CycloneDxComponent,
+}
+
+#[derive(Debug, Serialize)]
+struct CycloneDxTools {
+    components: Vec<CycloneDxComponent>,
+}
+
+#[derive(Debug, Serialize)]
+struct CycloneDxComponent {
+    r#type: &'static str,
+    #[serde(rename = "bom-ref", skip_serializing_if = "Option::is_none")]
+    bom_ref: Option<String>,
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    purl: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    licenses: Vec<CycloneDxLicenseChoice>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    hashes: Vec<CycloneDxHash>,
+}
+
+#[derive(Debug, Serialize)]
+struct CycloneDxLicenseChoice {
+    license: CycloneDxLicense,
+}
+
+#[derive(Debug, Serialize)]
+struct CycloneDxLicense {
+    name: String,
+}
+
+#[derive(Debug, Serialize)]
+struct CycloneDxHash {
+    alg: &'static str,
+    content: String,
+}
+
+fn cyclonedx(project_id: Uuid, packages: &[Package]) -> CycloneDxBom {
+    let components = packages
+        .iter()
+        .enumerate()
+        .map(|(i, package)| CycloneDxComponent {
+            r#type: "library",
+            bom_ref: Some(format!("component-{}", i + 1)),
+            name: package.name.clone(),
+            version: package.version.clone(),
+            purl: package.purl.clone(),
+            licenses: package
+                .license
+                .iter()
+                .map(|license| CycloneDxLicenseChoice {
+                    license: CycloneDxLicense {
+                        name: license.clone(),
+                    },
+                })
+                .collect(),
+            hashes: package
+                .hashes
+                .iter()
+                .map(|(alg, content)| CycloneDxHash {
+                    alg,
+                    content: content.clone(),
+                })
+                .collect(),
+        })
+        .collect();
+
+    CycloneDxBom {
+        bom_format: "CycloneDX",
+        spec_version: CYCLONEDX_SPEC_VERSION,
+        serial_number: format!("urn:uuid:{}", Uuid::new_v4()),
+        version: 1,
+        metadata: CycloneDxMetadata {
+            timestamp: timestamp(),
+            tools: CycloneDxTools {
+                components: vec![CycloneDxComponent {
+                    r#type: "application",
+                    bom_ref: None,
+                    name: env!("CARGO_PKG_NAME").to_string(),
+                    version: Some(crate::version().to_string()),
+                    purl: None,
+                    licenses: vec![],
+                    hashes: vec![],
+                }],
+            },
+            component: CycloneDxComponent {
+                r#type: "firmware",
+                bom_ref: Some(project_id.to_string()),
+                name: project_id.to_string(),
+                version: None,
+                purl: None,
+                licenses: vec![],
+                hashes: vec![],
+            },
+        },
+        components,
+    }
+}
+
+/// SPDX document, only the parts filled from the analysis.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SpdxDocument {
+    spdx_version: &'static str,
+    data_license: &'static str,
+    #[serde(rename = "SPDXID")]
+    spdx_id: &'static str,
+    name: String,
+    document_namespace: String,
+    creation_info: SpdxCreationInfo,
+    packages: Vec<SpdxPackage>,
+    relationships: Vec<SpdxRelationship>,
+}
+
+#[derive(Debug, Serialize)]
+struct SpdxCreationInfo {
+    created: String,
+    creators: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SpdxPackage {
+    #[serde(rename = "SPDXID")]
+    spdx_id: String,
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    version_info: Option<String>,
+    download_location: &'static str,
+    files_analyzed: bool,
+    license_concluded: &'static str,
+    license_declared: &'static str,
+    copyright_text: &'static str,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    checksums: Vec<SpdxChecksum>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    external_refs: Vec<SpdxExternalRef>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    primary_package_purpose: Option<&'static str>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SpdxChecksum {
+    algorithm: String,
+    checksum_value: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SpdxExternalRef {
+    reference_category: &'static str,
+    reference_type: &'static str,
+    reference_locator: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SpdxRelationship {
+    spdx_element_id: String,
+    relationship_type: &'static str,
+    related_spdx_element: String,
+}
+
+fn spdx(project_id: Uuid, packages: &[Package]) -> SpdxDocument {
+    let firmware_id = "SPDXRef-firmware".to_string();
+
+    let mut spdx_packages = vec![SpdxPackage {
+        spdx_id: firmware_id.clone(),
+        name: project_id.to_string(),
+        version_info: None,
+        download_location: NOASSERTION,
+        files_analyzed: false,
+        license_concluded: NOASSERTION,
+        license_declared: NOASSERTION,
+        copyright_text: NOASSERTION,
+        checksums: vec![],
+        external_refs: vec![],
+        primary_package_purpose: Some("FIRMWARE"),
+    }];
+    let mut relationships = vec![SpdxRelationship {
+        spdx_element_id: "SPDXRef-DOCUMENT".to_string(),
+        relationship_type: "DESCRIBES",
+        related_spdx_element: firmware_id.clone(),
+    }];
+
+    for (i, package) in packages.iter().enumerate() {
+        let spdx_id = format!("SPDXRef-Package-{}", i + 1);
+        relationships.push(SpdxRelationship {
+            spdx_element_id: firmware_id.clone(),
+            relationship_type: "CONTAINS",
+            related_spdx_element: spdx_id.clone(),
+        });
+        spdx_packages.push(SpdxPackage {
+            spdx_id,
+            name: package.name.clone(),
+            version_info: package.version.clone(),
+            download_location: NOASSERTION,
+            files_analyzed: false,
+            license_concluded: NOASSERTION,
+            license_declared: package
+                .license
+                .as_deref()
+                .and_then(spdx_license_id)
+                .unwrap_or(NOASSERTION),
+            copyright_text: NOASSERTION,
+            checksums: package
+                .hashes
+                .iter()
+                .map(|(alg, content)| SpdxChecksum {
+                    algorithm: alg.replace('-', ""),
+                    checksum_value: content.clone(),
+                })
+                .collect(),
+            external_refs: package
+                .purl
+                .iter()
+                .map(|purl| SpdxExternalRef {
+                    reference_category: "PACKAGE-MANAGER",
+                    reference_type: "purl",
+                    reference_locator: purl.clone(),
+                })
+                .collect(),
+            primary_package_purpose: None,
+        });
+    }
+
+    SpdxDocument {
+        spdx_version: SPDX_VERSION,
+        data_license: "CC0-1.0",
+        spdx_id: "SPDXRef-DOCUMENT",
+        name: format!("cosmo-{}", project_id),
+        // Unique for every document of the project
+        document_namespace: format!("{}/{}-{}", SPDX_NAMESPACE_BASE, project_id, Uuid::new_v4()),
+        creation_info: SpdxCreationInfo {
+            created: timestamp(),
+            creators: vec![format!(
+                "Tool: {}-{}",
+                env!("CARGO_PKG_NAME"),
+                crate::version()
+            )],
+        },
+        packages: spdx_packages,
+        relationships,
+    }
+}
+
+impl SpdxDocument {
+    /// The document in the tag-value format.
+    fn tag_value(&self) -> String {
+        // Values are single line, free text would need <text> tags
+        let value = |s: &str| s.replace(['\r', '\n'], " ");
+
+        let mut lines = vec![
+            format!("SPDXVersion: {}", self.spdx_version),
+            format!("DataLicense: {}", self.data_license),
+            format!("SPDXID: {}", self.spdx_id),
+            format!("DocumentName: {}", value(&self.name)),
+            format!("DocumentNamespace: {}", self.document_namespace),
+        ];
+        for creator in &self.creation_info.creators {
+            lines.push(format!("Creator: {}", value(creator)));
+        }
+        lines.push(format!("Created: {}", self.creation_info.created));
+
+        for package in &self.packages {
+            lines.push(String::new());
+            lines.push(format!("PackageName: {}", value(&package.name)));
+            lines.push(format!("SPDXID: {}", package.spdx_id));
+            if let Some(version) = &package.version_info {
+                lines.push(format!("PackageVersion: {}", value(version)));
+            }
+            lines.push(format!(
+                "PackageDownloadLocation: {}",
+                package.download_location
+            ));
+            lines.push(format!("FilesAnalyzed: {}", package.files_analyzed));
+            for checksum in &package.checksums {
+                lines.push(format!(
+                    "PackageChecksum: {}: {}",
+                    checksum.algorithm, checksum.checksum_value
+                ));
+            }
+            lines.push(format!(
+                "PackageLicenseConcluded: {}",
+                package.license_concluded
+            ));
+            lines.push(format!(
+                "PackageLicenseDeclared: {}",
+                package.license_declared
+            ));
+            lines.push(format!("PackageCopyrightText: {}", package.copyright_text));
+            for external_ref in &package.external_refs {
+                lines.push(format!(
+                    "ExternalRef: {} {} {}",
+                    external_ref.reference_category,
+                    external_ref.reference_type,
+                    value(&external_ref.reference_locator)
+                ));
+            }
+            if let Some(purpose) = package.primary_package_purpose {
+                lines.push(format!("PrimaryPackagePurpose: {}", purpose));
+            }
+        }
+
+        lines.push(String::new());
+        for relationship in &self.relationships {
+            lines.push(format!(
+                "Relationship: {} {} {}",
+                relationship.spdx_element_id,
+                relationship.relationship_type,
+                relationship.related_spdx_element
+            ));
+        }
+
+        lines.join("\n")
+    }
+}
+
+/// SPDX license id of a license name like `Apache 2.0` or `GPLv2`, when
+/// recognized.
+fn spdx_license_id(license: &str) -> Option<&'static str> {
+    const IDS: [&str; 27] = [
+        "0BSD",
+        "AGPL-3.0-only",
+        "AGPL-3.0-or-later",
+        "Apache-2.0",
+        "Artistic-2.0",
+        "BSD-2-Clause",
+        "BSD-3-Clause",
+        "BSL-1.0",
+        "CC0-1.0",
+        "curl",
+        "EPL-2.0",
+        "GPL-2.0-only",
+        "GPL-2.0-or-later",
+        "GPL-3.0-only",
+        "GPL-3.0-or-later",
+        "ISC",
+        "LGPL-2.0-only",
+        "LGPL-2.1-only",
+        "LGPL-2.1-or-later",
+        "LGPL-3.0-only",
+        "LGPL-3.0-or-later",
+        "MIT",
+        "MPL-2.0",
+        "OpenSSL",
+        "Python-2.0",
+        "Unlicense",
+        "Zlib",
+    ];
+
+    // e.g. `GNU GPL v2+` and `gpl-2.0-or-later` both give `gpl2+`
+    let key = license
+        .to_lowercase()
+        .replace("license", "")
+        .replace("version", "")
+        .replace("gnu", "")
+        .replace("-or-later", "+")
+        .replace("orlater", "+")
+        .replace("-only", "")
+        .replace(
+            |c: char| !(c.is_ascii_alphanumeric() || matches!(c, '.' | '+')),
+            "",
+        )
+        .replace("gplv", "gpl")
+        .replace(".0", "");
+
+    let id = match key.as_str() {
+        "gpl2" => "GPL-2.0-only",
+        "gpl2+" => "GPL-2.0-or-later",
+        "gpl3" => "GPL-3.0-only",
+        "gpl3+" => "GPL-3.0-or-later",
+        "lgpl2" => "LGPL-2.0-only",
+        "lgpl2.1" => "LGPL-2.1-only",
+        "lgpl2.1+" => "LGPL-2.1-or-later",
+        "lgpl3" => "LGPL-3.0-only",
+        "lgpl3+" => "LGPL-3.0-or-later",
+        "agpl3" => "AGPL-3.0-only",
+        "agpl3+" => "AGPL-3.0-or-later",
+        "apache2" | "apache" => "Apache-2.0",
+        "bsd2clause" | "simplifiedbsd" => "BSD-2-Clause",
+        "bsd3clause" | "newbsd" | "revisedbsd" => "BSD-3-Clause",
+        "mpl2" => "MPL-2.0",
+        "boost" | "bsl1" => "BSL-1.0",
+        "cc01" | "cc0" | "publicdomaincc0" => "CC0-1.0",
+        "python2" | "psf" => "Python-2.0",
+        "epl2" => "EPL-2.0",
+        "artistic2" => "Artistic-2.0",
+        "mitx11" | "expat" => "MIT",
+        _ => {
+            return IDS
+                .iter()
+                .find(|id| id.eq_ignore_ascii_case(license.trim()))
+                .or_else(|| {
+                    IDS.iter().find(|id| {
+                        id.to_lowercase().replace(['-', '.'], "") == key.replace('.', "")
+                    })
+                })
+                .copied()
+        }
+    };
+    Some(id)
+}
+
+fn timestamp() -> String {
+    Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/// Version of a shared library from its name, e.g. `1.1` for `libssl.so.1.1`.
+pub fn soname_version(filename: &str) -> Option<String> {
+    let (_, version) = filename.rsplit('/').next()?.split_once(".so.")?;
+    version
+        .split('.')
+        .all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit()))
+        .then(|| version.to_string())
+}
+
+/// Hashes of the server with a known algorithm and a well formed content.
+fn hashes(hashes: &BTreeMap<String, String>) -> Vec<(&'static str, String)> {
+    hashes
+        .iter()
+        .filter_map(|(alg, content)| {
+            let (alg, len) = match alg.to_ascii_lowercase().replace('-', "").as_str() {
+                "md5" => ("MD5", 32),
+                "sha1" => ("SHA-1", 40),
+                "sha256" => ("SHA-256", 64),
+                "sha384" => ("SHA-384", 96),
+                "sha512" => ("SHA-512", 128),
+                _ => return None,
+            };
+            let content = content.trim().to_ascii_lowercase();
+            (content.len() == len && content.chars().all(|c| c.is_ascii_hexdigit()))
+                .then_some((alg, content))
+        })
+        .collect()
+}
+
+/// Percent-encode a name or version of a package url.
+fn purl_encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'.' | b'-' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
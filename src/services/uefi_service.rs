@@ -0,0 +1,409 @@
+//! UEFI analyses: the state of Secure Boot and Intel Boot Guard, the modules
+//! with known vulnerabilities and a summary of the attack surface.
+
+use comfy_table::{Cell, CellAlignment, Row, Table};
+use console::style;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{
+    output,
+    services::project_service::{
+        UefiIntelBootGuard, UefiIntelBootGuardRsa, UefiSecureBoot, UefiSecureBootData,
+        UefiSecurityScan, UefiSurface,
+    },
+    style::{severity_cell, Severity},
+};
+
+/// Found in the names of the test keys of the reference firmwares, never
+/// meant to be shipped
+const TEST_KEY_MARKERS: [&str; 2] = ["DO NOT TRUST", "DO NOT SHIP"];
+
+/// Problem of a UEFI analysis and its severity.
+#[derive(Debug, Serialize)]
+pub struct UefiIssue {
+    pub severity: &'static str,
+    pub issue: String,
+}
+
+impl UefiIssue {
+    fn new(severity: Severity, issue: &str) -> Self {
+        UefiIssue {
+            severity: severity.as_str(),
+            issue: issue.to_string(),
+        }
+    }
+
+    fn line(&self) -> String {
+        let severity = Severity::parse(self.severity).unwrap_or(Severity::Low);
+        format!(
+            "{}  {}",
+            severity.paint(format!("{:<8}", self.severity.to_uppercase())),
+            self.issue
+        )
+    }
+}
+
+/// Certificate of a Secure Boot database.
+#[derive(Debug, Serialize)]
+pub struct SecureBootCertificate {
+    /// `PK`, `KEK`, `db` or `dbx`
+    pub database: &'static str,
+    pub issued_by: String,
+    pub issued_to: String,
+    /// Whether it is a test key of a reference firmware
+    pub test_key: bool,
+}
+
+impl SecureBootCertificate {
+    fn new(database: &'static str, data: &UefiSecureBootData) -> Self {
+        SecureBootCertificate {
+            database,
+            issued_by: data.first.clone(),
+            issued_to: data.second.clone(),
+            test_key: is_test_key(&data.first) || is_test_key(&data.second),
+        }
+    }
+}
+
+/// Keys and databases of Secure Boot, with the problems making it
+/// bypassable.
+#[derive(Debug, Serialize)]
+pub struct SecureBootReport {
+    /// Owner of the platform key, none in setup mode
+    pub platform_key: Option<String>,
+    pub certificates: Vec<SecureBootCertificate>,
+    /// Hashes of the images allowed by db
+    pub db_hashes: usize,
+    /// Hashes of the images revoked by dbx
+    pub dbx_hashes: usize,
+    pub issues: Vec<UefiIssue>,
+}
+
+impl SecureBootReport {
+    pub fn new(found: &UefiSecureBoot) -> Self {
+        let pk = found
+            .certs
+            .pk
+            .as_ref()
+            .filter(|pk| !pk.first.trim().is_empty() || !pk.second.trim().is_empty());
+        let databases = &found.databases;
+
+        let mut certificates: Vec<SecureBootCertificate> = vec![];
+        certificates.extend(pk.map(|pk| SecureBootCertificate::new("PK", pk)));
+        for (database, list) in [
+            ("KEK", &found.certs.kek),
+            ("db", &databases.certs.db),
+            ("dbx", &databases.certs.dbx),
+        ] {
+            certificates.extend(
+                list.iter()
+                    .map(|data| SecureBootCertificate::new(database, data)),
+            );
+        }
+
+        let mut issues = vec![];
+        match pk {
+            None => issues.push(UefiIssue::new(
+                Severity::Critical,
+                "No platform key, Secure Boot is in setup mode and not enforced",
+            )),
+            Some(pk) if is_test_key(&pk.first) || is_test_key(&pk.second) => issues
+                .push(UefiIssue::new(
+                Severity::Critical,
+                "The platform key is a test key, anyone with its private part can sign boot images",
+            )),
+            Some(_) => {}
+        }
+        if certificates
+            .iter()
+            .any(|certificate| certificate.database != "PK" && certificate.test_key)
+        {
+            issues.push(UefiIssue::new(
+                Severity::High,
+                "Test certificates in KEK, db or dbx",
+            ));
+        }
+        if databases.certs.dbx.is_empty() && databases.hashes.dbx.is_empty() {
+            issues.push(UefiIssue::new(
+                Severity::High,
+                "dbx is empty, the revoked boot loaders are not blocked",
+            ));
+        }
+
+        SecureBootReport {
+            platform_key: pk.map(|pk| pk.second.clone()),
+            certificates,
+            db_hashes: databases.hashes.db.len(),
+            dbx_hashes: databases.hashes.dbx.len(),
+            issues,
+        }
+    }
+
+    pub fn get_text_output(&self) -> String {
+        let count = |database: &str| {
+            self.certificates
+                .iter()
+                .filter(|certificate| certificate.database == database)
+                .count()
+        };
+        let mut lines = vec![
+            format!(
+                "Platform key  {}",
+                self.platform_key.as_deref().unwrap_or("none")
+            ),
+            format!("KEK           {} certificates", count("KEK")),
+            format!(
+                "db            {} certificates, {} hashes",
+                count("db"),
+                self.db_hashes
+            ),
+            format!(
+                "dbx           {} certificates, {} hashes",
+                count("dbx"),
+                self.dbx_hashes
+            ),
+            String::new(),
+        ];
+        match self.issues.is_empty() {
+            true => lines.push("No Secure Boot issue found".to_string()),
+            false => lines.extend(self.issues.iter().map(UefiIssue::line)),
+        }
+
+        if !self.certificates.is_empty() {
+            let mut table = Table::new();
+            table.set_header(Row::from(vec![
+                Cell::new("DATABASE"),
+                Cell::new("ISSUED BY"),
+                Cell::new("ISSUED TO"),
+            ]));
+            for certificate in &self.certificates {
+                let cell = |text: &str| match certificate.test_key {
+                    true => Severity::Critical.cell(text),
+                    false => Cell::new(text),
+                };
+                table.add_row(Row::from(vec![
+                    Cell::new(certificate.database),
+                    cell(&certificate.issued_by),
+                    cell(&certificate.issued_to),
+                ]));
+            }
+            lines.push(String::new());
+            lines.push(output::render(table));
+        }
+        lines.join("\n")
+    }
+}
+
+/// Module with known vulnerabilities, found by the UEFI security scan.
+#[derive(Debug, Serialize)]
+pub struct VulnerableModule {
+    pub module: String,
+    pub guid: Uuid,
+    pub vulnerabilities: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub advisories: Vec<String>,
+    /// Severity of the most severe vulnerability, when reported
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub severity: Option<String>,
+}
+
+impl VulnerableModule {
+    /// The findings `found` grouped by module, the most severe first.
+    pub fn from_findings(found: &[UefiSecurityScan]) -> Vec<Self> {
+        let mut modules: Vec<VulnerableModule> = vec![];
+        for finding in found {
+            let module = match modules
+                .iter_mut()
+                .find(|module| module.guid == finding.guid && module.module == finding.module)
+            {
+                Some(module) => module,
+                None => {
+                    modules.push(VulnerableModule {
+                        module: finding.module.clone(),
+                        guid: finding.guid,
+                        vulnerabilities: vec![],
+                        advisories: vec![],
+                        severity: None,
+                    });
+                    modules.last_mut().unwrap()
+                }
+            };
+            if !module.vulnerabilities.contains(&finding.name) {
+                module.vulnerabilities.push(finding.name.clone());
+            }
+            for advisory in &finding.advisories {
+                if !module.advisories.contains(advisory) {
+                    module.advisories.push(advisory.clone());
+                }
+            }
+            if rank(finding.severity.as_deref()) < rank(module.severity.as_deref()) {
+                module.severity = finding.severity.clone();
+            }
+        }
+
+        modules.sort_by(|a, b| {
+            rank(a.severity.as_deref())
+                .cmp(&rank(b.severity.as_deref()))
+                .then_with(|| a.module.cmp(&b.module))
+        });
+        modules
+    }
+
+    pub fn get_table_from_list(list: &[VulnerableModule]) -> String {
+        if list.is_empty() {
+            return "No vulnerable module found".to_string();
+        }
+        let advisories = list.iter().any(|module| !module.advisories.is_empty());
+        let severity = list.iter().any(|module| module.severity.is_some());
+
+        let mut table = Table::new();
+        let mut header = vec![
+            Cell::new("MODULE"),
+            Cell::new("GUID"),
+            Cell::new("VULNERABILITIES"),
+        ];
+        if advisories {
+            header.push(Cell::new("ADVISORIES"));
+        }
+        if severity {
+            header.push(Cell::new("SEVERITY"));
+        }
+        table.set_header(Row::from(header));
+
+        for module in list {
+            let mut row = vec![
+                Cell::new(&module.module),
+                Cell::new(module.guid),
+                Cell::new(module.vulnerabilities.join("\n")),
+            ];
+            if advisories {
+                row.push(Cell::new(module.advisories.join("\n")));
+            }
+            if severity {
+                row.push(match &module.severity {
+                    Some(severity) => severity_cell(severity),
+                    None => Cell::new("-"),
+                });
+            }
+            table.add_row(Row::from(row));
+        }
+
+        let findings: usize = list.iter().map(|module| module.vulnerabilities.len()).sum();
+        format!(
+            "{}\n{} modules with {} vulnerabilities",
+            output::render(table),
+            list.len(),
+            findings
+        )
+    }
+}
+
+/// Entries of the attack surface counted by type.
+#[derive(Debug, Serialize)]
+pub struct SurfaceCount {
+    pub r#type: String,
+    pub count: usize,
+}
+
+/// Attack surface of the firmware: the entries reachable from the operating
+/// system, like SMI handlers, and their count by type.
+#[derive(Debug, Serialize)]
+pub struct SurfaceReport {
+    /// The most frequent type first
+    pub summary: Vec<SurfaceCount>,
+    pub entries: Vec<UefiSurface>,
+}
+
+impl SurfaceReport {
+    pub fn new(mut entries: Vec<UefiSurface>) -> Self {
+        let mut summary: Vec<SurfaceCount> = vec![];
+        for entry in &entries {
+            match summary
+                .iter_mut()
+                .find(|count| count.r#type == entry.r#type)
+            {
+                Some(count) => count.count += 1,
+                None => summary.push(SurfaceCount {
+                    r#type: entry.r#type.clone(),
+                    count: 1,
+                }),
+            }
+        }
+        summary.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.r#type.cmp(&b.r#type)));
+        entries.sort_by(|a, b| a.r#type.cmp(&b.r#type).then_with(|| a.name.cmp(&b.name)));
+
+        SurfaceReport { summary, entries }
+    }
+
+    pub fn get_text_output(&self) -> String {
+        let mut summary = Table::new();
+        summary.set_header(Row::from(vec![Cell::new("TYPE"), Cell::new("ENTRIES")]));
+        for count in &self.summary {
+            summary.add_row(Row::from(vec![
+                Cell::new(&count.r#type),
+                Cell::new(count.count).set_alignment(CellAlignment::Right),
+            ]));
+        }
+
+        let mut table = Table::new();
+        table.set_header(Row::from(vec![
+            Cell::new("NAME"),
+            Cell::new("TYPE"),
+            Cell::new("GUID"),
+        ]));
+        for entry in &self.entries {
+            table.add_row(Row::from(vec![
+                Cell::new(&entry.name),
+                Cell::new(&entry.r#type),
+                Cell::new(entry.guid),
+            ]));
+        }
+
+        format!(
+            "{}\n{}\n{} entries of {} types",
+            output::render(summary),
+            output::render(table),
+            self.entries.len(),
+            self.summary.len()
+        )
+    }
+}
+
+impl UefiIntelBootGuard {
+    /// Whether Boot Guard is provisioned, with an ACM or keys.
+    pub fn is_present(&self) -> bool {
+        !self.acm.trim().is_empty() || !self.rsa.is_empty()
+    }
+
+    pub fn get_text_output(&self) -> String {
+        if !self.is_present() {
+            return "Intel Boot Guard not provisioned".to_string();
+        }
+        let mut lines = vec![format!(
+            "Intel Boot Guard ACM {}",
+            style(self.acm.trim()).bold()
+        )];
+        if !self.rsa.is_empty() {
+            lines.push(UefiIntelBootGuardRsa::get_table_from_list(&self.rsa));
+        }
+        lines.join("\n")
+    }
+}
+
+/// Whether a name of a certificate is the one of a test key.
+fn is_test_key(name: &str) -> bool {
+    let name = name.to_uppercase();
+    TEST_KEY_MARKERS.iter().any(|marker| name.contains(marker))
+}
+
+/// Rank of a severity, the most severe first and the unknown ones last.
+fn rank(severity: Option<&str>) -> usize {
+    match severity.and_then(Severity::parse) {
+        Some(Severity::Critical) => 0,
+        Some(Severity::High) => 1,
+        Some(Severity::Medium) => 2,
+        Some(Severity::Low) => 3,
+        None => 4,
+    }
+}
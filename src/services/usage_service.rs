@@ -0,0 +1,117 @@
+//! Usage of the account against the limits of its plan, `cosmo usage`, and
+//! shown when `create` is refused for a reached quota.
+
+use std::fmt;
+
+use comfy_table::{Cell, Row, Table};
+use indicatif::HumanBytes;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::{ApiServer, ApiServerError},
+    output,
+};
+
+/// Usage of the account, as reported by the server.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Usage {
+    pub projects: Quota,
+    /// Total size of the analyzed firmwares, in bytes
+    pub storage: Quota,
+    /// Scans started since the first day of the month
+    #[serde(alias = "scans")]
+    pub scans_this_month: Quota,
+}
+
+/// Amount used of a resource, and its limit when the plan has one.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Quota {
+    pub used: u64,
+    #[serde(default)]
+    pub limit: Option<u64>,
+}
+
+/// Refusal of a request because a quota of the account is reached, with
+/// the usage when it could be fetched.
+#[derive(Debug)]
+pub struct QuotaExceeded {
+    pub error: ApiServerError,
+    pub usage: Option<Usage>,
+}
+
+impl Quota {
+    /// Whether nothing more can be used.
+    fn reached(&self) -> bool {
+        self.limit.is_some_and(|limit| self.used >= limit)
+    }
+}
+
+impl Usage {
+    pub fn get_text_output(&self) -> String {
+        let mut table = Table::new();
+        table.set_header(Row::from(vec![
+            Cell::new("RESOURCE"),
+            Cell::new("USED"),
+            Cell::new("LIMIT"),
+            Cell::new("USED %"),
+        ]));
+        for (resource, quota, bytes) in [
+            ("projects", &self.projects, false),
+            ("storage", &self.storage, true),
+            ("scans this month", &self.scans_this_month, false),
+        ] {
+            let amount = |amount: u64| match bytes {
+                true => HumanBytes(amount).to_string(),
+                false => amount.to_string(),
+            };
+            let percent = match quota.limit {
+                Some(limit) if limit > 0 => format!("{}%", quota.used * 100 / limit),
+                _ => "-".to_string(),
+            };
+            let resource = match quota.reached() {
+                true => format!("{} (reached)", resource),
+                false => resource.to_string(),
+            };
+            table.add_row(Row::from(vec![
+                Cell::new(resource),
+                Cell::new(amount(quota.used)),
+                Cell::new(quota.limit.map_or("unlimited".to_string(), amount)),
+                Cell::new(percent),
+            ]));
+        }
+
+        output::render(table)
+    }
+}
+
+/// `error` of a request, with the usage of the account added when it was
+/// refused for a reached quota.
+pub async fn explain_quota<U: ApiServer>(api_server: &U, error: ApiServerError) -> anyhow::Error {
+    if !error
+        .response()
+        .is_some_and(|response| response.is_quota_exceeded())
+    {
+        return error.into();
+    }
+    let usage = match api_server.usage().await {
+        Ok(usage) => Some(usage),
+        Err(e) => {
+            tracing::debug!("Usage of the account not available: {}", e);
+            None
+        }
+    };
+    QuotaExceeded { error, usage }.into()
+}
+
+impl fmt::Display for QuotaExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Quota of the account reached: {}", self.error)?;
+        match &self.usage {
+            Some(usage) => write!(f, "\n{}", usage.get_text_output()),
+            None => write!(f, ", check it with `cosmo usage`"),
+        }
+    }
+}
+
+impl std::error::Error for QuotaExceeded {}
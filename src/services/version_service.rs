@@ -0,0 +1,148 @@
+use serde::Serialize;
+
+use crate::{
+    api::{ApiServer, ServerVersion, SUPPORTED_API_VERSIONS},
+    output::CommandOutput,
+};
+
+/// Versions of the cli and of the server, as asked by the support tickets.
+#[derive(Debug, Serialize)]
+pub struct VersionReport {
+    pub cli: CliVersion,
+    /// The server, unless it couldn't be reached
+    pub server: Option<ServerReport>,
+    /// Why the server is missing
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+/// Version and build of this binary.
+#[derive(Debug, Serialize)]
+pub struct CliVersion {
+    pub version: &'static str,
+    /// Commit built, unless built outside of a git checkout
+    pub git_sha: Option<&'static str>,
+    /// Date of the build, `YYYY-MM-DD` in UTC
+    pub build_date: &'static str,
+    /// Target triple of the build
+    pub target: &'static str,
+    /// Versions of the api supported by the cli
+    pub api_versions: Vec<u32>,
+}
+
+/// Versions of the server and their compatibility with the cli.
+#[derive(Debug, Serialize)]
+pub struct ServerReport {
+    pub address: String,
+    #[serde(flatten)]
+    pub versions: ServerVersion,
+    /// Newest version of the api supported by both, the one used
+    pub api_version: Option<u32>,
+    /// Whether the server offers a version of the api supported by the cli
+    pub compatible: bool,
+}
+
+impl CliVersion {
+    fn current() -> Self {
+        Self {
+            version: crate::version(),
+            git_sha: Some(env!("COSMO_GIT_SHA")).filter(|sha| !sha.is_empty()),
+            build_date: env!("COSMO_BUILD_DATE"),
+            target: env!("COSMO_TARGET"),
+            api_versions: SUPPORTED_API_VERSIONS.to_vec(),
+        }
+    }
+}
+
+/// Versions of the cli and of the server behind `api_server`. A server
+/// that can't be reached only leaves a note.
+pub async fn version<U: ApiServer>(api_server: &U) -> VersionReport {
+    let cli = CliVersion::current();
+    match api_server.server_version().await {
+        Ok(versions) => {
+            let api_version = versions
+                .api_versions
+                .iter()
+                .filter(|version| cli.api_versions.contains(version))
+                .max()
+                .copied();
+            VersionReport {
+                cli,
+                server: Some(ServerReport {
+                    address: api_server.address().to_string(),
+                    versions,
+                    api_version,
+                    compatible: api_version.is_some(),
+                }),
+                note: None,
+            }
+        }
+        Err(err) => {
+            tracing::debug!("Server version not available: {:?}", err);
+            VersionReport {
+                cli,
+                server: None,
+                note: Some(format!(
+                    "server version not available, {}: {}",
+                    api_server.address(),
+                    err
+                )),
+            }
+        }
+    }
+}
+
+fn api_versions(versions: &[u32]) -> String {
+    versions
+        .iter()
+        .map(|version| format!("v{}", version))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+impl CommandOutput for VersionReport {
+    fn text(&self) -> String {
+        let cli = &self.cli;
+        let mut text = format!(
+            "Cli: {}\nCommit: {}\nBuilt: {}\nTarget: {}\nCli api: {}",
+            cli.version,
+            cli.git_sha.unwrap_or("-"),
+            cli.build_date,
+            cli.target,
+            api_versions(&cli.api_versions)
+        );
+
+        match &self.server {
+            Some(server) => {
+                let compatibility = match server.api_version {
+                    Some(version) if server.versions.deprecated_api_versions.contains(&version) => {
+                        format!("using v{}, deprecated by the server", version)
+                    }
+                    Some(version) => format!("using v{}", version),
+                    None => "incompatible, update with `cosmo self-update`".to_string(),
+                };
+                text.push_str(&format!(
+                    "\nServer: {}\nServer version: {}\nServer api: {} ({})",
+                    server.address,
+                    server.versions.version.as_deref().unwrap_or("-"),
+                    api_versions(&server.versions.api_versions),
+                    compatibility
+                ));
+            }
+            None => {
+                if let Some(note) = &self.note {
+                    text.push_str(&format!("\nNote: {}", note));
+                }
+            }
+        }
+        text
+    }
+
+    fn json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    fn value(&self) -> Option<String> {
+        Some(self.cli.version.to_string())
+    }
+}
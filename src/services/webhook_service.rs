@@ -0,0 +1,148 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use comfy_table::{Cell, Row, Table};
+use serde::{Deserialize, Serialize};
+use url::Url;
+use uuid::Uuid;
+
+use crate::{api::ApiServer, output};
+
+/// Event of an analysis delivered to a webhook.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookEvent {
+    AnalysisCompleted,
+    AnalysisFailed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookData {
+    pub id: Uuid,
+    pub url: String,
+    /// Events delivered, as named by the server
+    pub events: Vec<String>,
+    /// Project whose analyses are delivered, all of them when not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project_id: Option<Uuid>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub creation_date: Option<DateTime<Utc>>,
+    /// Last delivery, when the server reports it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_delivery: Option<WebhookDelivery>,
+}
+
+/// Delivery of an event to a webhook.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookDelivery {
+    /// Outcome, e.g. `succeeded` or `failed`
+    pub status: String,
+    /// Http status code of the answer of the webhook
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status_code: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub date: Option<DateTime<Utc>>,
+}
+
+/// Body of the request registering a webhook.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookCreateDTO<'a> {
+    pub url: &'a Url,
+    pub events: &'a [WebhookEvent],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub project_id: Option<&'a Uuid>,
+    /// Key signing the deliveries
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret: Option<&'a str>,
+}
+
+impl WebhookData {
+    pub fn get_table_from_list(list: &[WebhookData]) -> String {
+        let delivered = list.iter().any(|webhook| webhook.last_delivery.is_some());
+
+        let mut table = Table::new();
+        let mut header = vec![
+            Cell::new("ID"),
+            Cell::new("URL"),
+            Cell::new("EVENTS"),
+            Cell::new("PROJECT"),
+        ];
+        if delivered {
+            header.push(Cell::new("LAST DELIVERY"));
+        }
+        table.set_header(Row::from(header));
+
+        for webhook in list {
+            let mut row = vec![
+                Cell::new(webhook.id),
+                Cell::new(&webhook.url),
+                Cell::new(webhook.events.join(", ")),
+                Cell::new(
+                    webhook
+                        .project_id
+                        .map_or("all".to_string(), |id| id.to_string()),
+                ),
+            ];
+            if delivered {
+                row.push(Cell::new(
+                    webhook
+                        .last_delivery
+                        .as_ref()
+                        .map_or("-".to_string(), WebhookDelivery::summary),
+                ));
+            }
+            table.add_row(Row::from(row));
+        }
+
+        output::render(table)
+    }
+}
+
+impl WebhookDelivery {
+    /// Outcome with its status code and date, e.g. `failed (500) on
+    /// 2024-01-31 10:00:00 UTC`.
+    fn summary(&self) -> String {
+        let mut summary = self.status.to_lowercase();
+        if let Some(status_code) = self.status_code {
+            summary.push_str(&format!(" ({})", status_code));
+        }
+        if let Some(date) = self.date {
+            summary.push_str(&format!(" on {}", date.format("%Y-%m-%d %H:%M:%S UTC")));
+        }
+        summary
+    }
+}
+
+// List the webhooks, only the ones of `project` when given
+pub async fn list<U: ApiServer>(api_server: &U, project: Option<Uuid>) -> Result<Vec<WebhookData>> {
+    let webhooks = api_server.webhooks(project.as_ref()).await?;
+    Ok(webhooks)
+}
+
+// Register a webhook called on `events` of the analyses of `project`, or of
+// all of them
+pub async fn create<U: ApiServer>(
+    api_server: &U,
+    url: &Url,
+    events: &[WebhookEvent],
+    project: Option<Uuid>,
+    secret: Option<&str>,
+) -> Result<WebhookData> {
+    let webhook = WebhookCreateDTO {
+        url,
+        events,
+        project_id: project.as_ref(),
+        secret,
+    };
+    let webhook = api_server.webhook_create(&webhook).await?;
+    Ok(webhook)
+}
+
+// Remove a webhook
+pub async fn delete<U: ApiServer>(api_server: &U, id: Uuid) -> Result<()> {
+    api_server.webhook_delete(&id).await?;
+    Ok(())
+}
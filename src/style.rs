@@ -0,0 +1,146 @@
+//! Colors and characters of the output for humans, shared by the tables and
+//! the summaries so that a severity looks the same everywhere.
+
+use std::{
+    io::IsTerminal,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use comfy_table::{presets, Attribute, Cell, Table};
+use console::{style, Color, StyledObject};
+
+use crate::cli::ColorChoice;
+
+/// Set by `--ascii` or a dumb terminal
+static ASCII: AtomicBool = AtomicBool::new(false);
+
+/// Apply `--color` and `--ascii` to everything printed afterwards.
+///
+/// With `auto` the colors are left to the detection of the terminal, which
+/// follows `NO_COLOR`, `CLICOLOR` and `CLICOLOR_FORCE`.
+pub fn init(color: ColorChoice, ascii: bool) {
+    match color {
+        ColorChoice::Auto => {}
+        ColorChoice::Always | ColorChoice::Never => {
+            let enabled = color == ColorChoice::Always;
+            console::set_colors_enabled(enabled);
+            console::set_colors_enabled_stderr(enabled);
+        }
+    }
+
+    let dumb = std::env::var_os("TERM").is_some_and(|term| term == "dumb");
+    ASCII.store(ascii || dumb, Ordering::Relaxed);
+}
+
+/// Whether only ASCII characters are printed.
+pub fn ascii() -> bool {
+    ASCII.load(Ordering::Relaxed)
+}
+
+/// `unicode`, or `ascii` with `--ascii`.
+pub fn glyph(unicode: &'static str, ascii: &'static str) -> &'static str {
+    if self::ascii() {
+        ascii
+    } else {
+        unicode
+    }
+}
+
+/// Arrow between a base and a target.
+pub fn arrow() -> &'static str {
+    glyph("→", "->")
+}
+
+/// Draw the borders of `table`, with box drawing characters on a terminal,
+/// and style its cells whenever the colors are enabled.
+pub fn table(table: &mut Table) {
+    if !ascii() && std::io::stdout().is_terminal() {
+        table.load_preset(presets::UTF8_FULL);
+    }
+    if console::colors_enabled() {
+        table.enforce_styling();
+    }
+}
+
+/// Severity of a CVE, as colored in the lists, the analyses and the diffs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Critical,
+    High,
+    Medium,
+    Low,
+}
+
+impl Severity {
+    /// The severity named `name`, in any case.
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "critical" => Some(Self::Critical),
+            "high" => Some(Self::High),
+            "medium" => Some(Self::Medium),
+            "low" => Some(Self::Low),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Critical => "critical",
+            Self::High => "high",
+            Self::Medium => "medium",
+            Self::Low => "low",
+        }
+    }
+
+    /// Block of the bars, shaded apart for the terminals without colors.
+    pub fn block(self) -> &'static str {
+        match self {
+            Self::Critical => glyph("█", "#"),
+            Self::High => glyph("▓", "="),
+            Self::Medium => glyph("▒", "-"),
+            Self::Low => glyph("░", "."),
+        }
+    }
+
+    /// `text` in the color of the severity: critical red, high orange,
+    /// medium yellow and low dim.
+    pub fn paint<D>(self, text: D) -> StyledObject<D> {
+        match self {
+            Self::Critical => style(text).fg(Color::Red),
+            Self::High => style(text).fg(Color::Color256(208)),
+            Self::Medium => style(text).fg(Color::Yellow),
+            Self::Low => style(text).dim(),
+        }
+    }
+
+    /// Cell of a table in the color of the severity.
+    pub fn cell(self, text: &str) -> Cell {
+        if !console::colors_enabled() {
+            return Cell::new(text);
+        }
+        match self {
+            Self::Critical => Cell::new(text).fg(comfy_table::Color::Red),
+            Self::High => Cell::new(text).fg(comfy_table::Color::AnsiValue(208)),
+            Self::Medium => Cell::new(text).fg(comfy_table::Color::Yellow),
+            Self::Low => Cell::new(text).add_attribute(Attribute::Dim),
+        }
+    }
+}
+
+/// Cell of a table in the color of the severity `name`, plain for an
+/// unknown one.
+pub fn severity_cell(name: &str) -> Cell {
+    match Severity::parse(name) {
+        Some(severity) => severity.cell(name),
+        None => Cell::new(name),
+    }
+}
+
+/// `cell` dimmed, when the colors are enabled.
+pub fn dim(cell: Cell) -> Cell {
+    if console::colors_enabled() {
+        cell.add_attribute(Attribute::Dim)
+    } else {
+        cell
+    }
+}
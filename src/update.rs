@@ -0,0 +1,320 @@
+//! Notice of the newer versions of the cli, and `self-update` replacing the
+//! running binary with the one of the latest release.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::{DateTime, Utc};
+use openssl::{
+    pkey::{Id, PKey},
+    sign::Verifier,
+};
+use semver::Version;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::{self, ApiServer, LatestCliVersion, PlainClient},
+    output::CommandOutput,
+};
+
+/// File of the time of the last check, in the cache directory
+const STATE_DIR: &str = "cosmo-cli";
+const STATE_FILE: &str = "update-check.json";
+/// Time between two checks
+const CHECK_INTERVAL_HOURS: i64 = 24;
+/// Maximum time spent checking, after the command is done
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+/// Ed25519 public key of the releases, in base64, embedded at build time by
+/// the release workflow. Builds without it can't `self-update`
+const RELEASE_PUBLIC_KEY: Option<&str> = option_env!("COSMO_RELEASE_PUBLIC_KEY");
+
+/// Paths of the binaries installed by package managers, updating them
+/// instead of `self-update`
+const PACKAGE_MANAGERS: [(&str, &str); 9] = [
+    ("/nix/store/", "Nix"),
+    ("/snap/", "snap"),
+    ("/cellar/", "Homebrew"),
+    ("/opt/homebrew/", "Homebrew"),
+    ("/home/linuxbrew/", "Homebrew"),
+    ("/scoop/apps/", "Scoop"),
+    ("/chocolatey/", "Chocolatey"),
+    ("/winget/packages/", "winget"),
+    ("/.cargo/bin/", "cargo install"),
+];
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CheckState {
+    checked: Option<DateTime<Utc>>,
+}
+
+/// Tell on stderr when a newer version exists, checking at most once a day.
+///
+/// Failures are only logged at debug level, the command being done.
+pub async fn notify<U: ApiServer>(api_server: &U) {
+    let Some(path) = state_path() else {
+        return;
+    };
+    let state: CheckState = fs::read(&path)
+        .ok()
+        .and_then(|state| serde_json::from_slice(&state).ok())
+        .unwrap_or_default();
+    let now = Utc::now();
+    if state
+        .checked
+        .is_some_and(|checked| now - checked < chrono::Duration::hours(CHECK_INTERVAL_HOURS))
+    {
+        return;
+    }
+
+    // Saved before checking, so that a failing server isn't asked each time
+    let state = CheckState { checked: Some(now) };
+    let saved = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|()| fs::write(&path, serde_json::to_vec(&state).unwrap_or_default()));
+    if let Err(err) = saved {
+        tracing::debug!("Update check not saved in {}: {}", path.display(), err);
+    }
+
+    match tokio::time::timeout(CHECK_TIMEOUT, api_server.updates_check()).await {
+        Ok(Ok(latest)) if is_newer(&latest) => tracing::info!("{}", notice(&latest)),
+        Ok(Ok(_)) => {}
+        Ok(Err(err)) => tracing::debug!("Updates not checked: {}", err),
+        Err(_) => tracing::debug!("Updates not checked: the server is too slow"),
+    }
+}
+
+fn state_path() -> Option<PathBuf> {
+    dirs::cache_dir().map(|dir| dir.join(STATE_DIR).join(STATE_FILE))
+}
+
+fn is_newer(latest: &LatestCliVersion) -> bool {
+    Version::parse(crate::version()).is_ok_and(|current| current < latest.version)
+}
+
+/// One line notice of the `latest` version, with the first line of its
+/// changelog if any.
+fn notice(latest: &LatestCliVersion) -> String {
+    let summary = latest
+        .changelog
+        .lines()
+        .map(|line| line.trim_start_matches(['#', '-', '*', ' ']).trim())
+        .find(|line| !line.is_empty());
+    let notice = format!(
+        "cosmo {} is available, you have {}",
+        latest.version,
+        crate::version()
+    );
+    match summary {
+        Some(summary) => format!("{notice}: {summary}. Update with `cosmo self-update`"),
+        None => format!("{notice}. Update with `cosmo self-update`"),
+    }
+}
+
+/// Outcome of `self-update`.
+#[derive(Debug, Serialize)]
+pub struct SelfUpdate {
+    pub current: String,
+    pub latest: String,
+    /// Whether the latest version is newer than this one
+    pub available: bool,
+    /// Whether the binary was replaced
+    pub updated: bool,
+    /// The binary replaced
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+}
+
+impl CommandOutput for SelfUpdate {
+    fn text(&self) -> String {
+        if self.updated {
+            format!("Updated cosmo from {} to {}", self.current, self.latest)
+        } else if self.available {
+            format!(
+                "cosmo {} is available, you have {}",
+                self.latest, self.current
+            )
+        } else {
+            format!("cosmo {} is up to date", self.current)
+        }
+    }
+
+    fn json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+/// Replace the running binary with the one of the latest release for this
+/// platform, once its signature verified with the key of the releases. With
+/// `check` only tell whether there is a newer version.
+///
+/// Binaries installed by a package manager are left to it, and builds
+/// without the key of the releases can only check.
+pub async fn self_update<U: ApiServer>(
+    api_server: &U,
+    plain_client: &PlainClient,
+    check: bool,
+) -> Result<SelfUpdate> {
+    let exe = std::env::current_exe()
+        .and_then(fs::canonicalize)
+        .context("error finding the running binary")?;
+    if !check {
+        if let Some(manager) = package_manager(&exe) {
+            bail!(
+                "{} was installed with {}, update it with {} instead",
+                exe.display(),
+                manager,
+                manager
+            );
+        }
+    }
+
+    let latest = api_server.updates_check().await?;
+    let mut outcome = SelfUpdate {
+        current: crate::version().to_string(),
+        latest: latest.version.to_string(),
+        available: is_newer(&latest),
+        updated: false,
+        path: None,
+    };
+    if check || !outcome.available {
+        return Ok(outcome);
+    }
+
+    let public_key = RELEASE_PUBLIC_KEY.context(
+        "this build of cosmo has no release key to verify the binaries with, update it the way it was installed",
+    )?;
+    let platform = platform();
+    let artifact = latest
+        .artifacts
+        .iter()
+        .find(|artifact| artifact.platform == platform)
+        .ok_or_else(|| anyhow!("no binary of cosmo {} for {}", latest.version, platform))?;
+    if artifact.url.scheme() != "https" {
+        bail!(
+            "the binary of cosmo {} is not served over https: {}",
+            latest.version,
+            artifact.url
+        );
+    }
+
+    // Next to the binary, to be renamed over it
+    let dir = exe.parent().context("error finding the running binary")?;
+    let download = dir.join(format!(
+        ".{}.update",
+        exe.file_name().unwrap_or_default().to_string_lossy()
+    ));
+    let replaced = async {
+        plain_client
+            .download(&artifact.url, &download)
+            .await
+            .with_context(|| format!("error downloading cosmo {}", latest.version))?;
+        verify(
+            &download,
+            &artifact.sha256,
+            artifact.signature.as_deref(),
+            public_key,
+        )
+        .await?;
+        replace(&exe, &download).with_context(|| format!("error replacing {}", exe.display()))
+    }
+    .await;
+    if replaced.is_err() {
+        let _ = fs::remove_file(&download);
+    }
+    replaced?;
+
+    outcome.updated = true;
+    outcome.path = Some(exe);
+    Ok(outcome)
+}
+
+/// Platform of the release binaries, e.g. `linux-x86_64`.
+fn platform() -> String {
+    format!("{}-{}", std::env::consts::OS, std::env::consts::ARCH)
+}
+
+/// Package manager that installed `exe`, if known.
+fn package_manager(exe: &Path) -> Option<&'static str> {
+    let path = exe.to_string_lossy().replace('\\', "/").to_lowercase();
+    if let Some((_, manager)) = PACKAGE_MANAGERS.iter().find(|(dir, _)| path.contains(dir)) {
+        return Some(manager);
+    }
+
+    // Binaries of the distribution packages, /usr/local being left to the users
+    let system = ["/usr/", "/bin/", "/sbin/"]
+        .iter()
+        .any(|dir| path.starts_with(dir))
+        && !path.starts_with("/usr/local/");
+    (cfg!(target_os = "linux") && system).then_some("the system package manager")
+}
+
+/// Check the signature of the binary at `path` with `public_key`, the
+/// SHA-256 only telling a corrupted download apart from a forged one: both
+/// come from the same server.
+async fn verify(
+    path: &Path,
+    sha256: &str,
+    signature: Option<&str>,
+    public_key: &str,
+) -> Result<()> {
+    let actual = api::file_sha256(path).await?;
+    if !actual.eq_ignore_ascii_case(sha256.trim()) {
+        bail!(
+            "checksum mismatch of the downloaded binary: expected {}, got {}",
+            sha256,
+            actual
+        );
+    }
+
+    let signature = signature.context("the release binary is not signed")?;
+    let public_key = PKey::public_key_from_raw_bytes(&STANDARD.decode(public_key)?, Id::ED25519)
+        .context("invalid release key in this build")?;
+    let signature = STANDARD
+        .decode(signature.trim())
+        .context("invalid signature of the release binary")?;
+    let binary = fs::read(path)?;
+    if !Verifier::new_without_digest(&public_key)?.verify_oneshot(&signature, &binary)? {
+        bail!("invalid signature of the release binary");
+    }
+    Ok(())
+}
+
+/// Rename `new` over `exe`, keeping its permissions.
+#[cfg(unix)]
+fn replace(exe: &Path, new: &Path) -> io::Result<()> {
+    fs::set_permissions(new, fs::metadata(exe)?.permissions())?;
+    fs::rename(new, exe)
+}
+
+/// Rename `new` over `exe`. A running binary can't be replaced on Windows
+/// but can be renamed: it is moved aside first, and removed by the next run.
+#[cfg(windows)]
+fn replace(exe: &Path, new: &Path) -> io::Result<()> {
+    let old = replaced_path(exe);
+    let _ = fs::remove_file(&old);
+    fs::rename(exe, &old)?;
+    if let Err(err) = fs::rename(new, exe) {
+        let _ = fs::rename(&old, exe);
+        return Err(err);
+    }
+    Ok(())
+}
+
+/// Remove the binary moved aside by the last update, if any.
+pub fn remove_replaced() {
+    if let Ok(exe) = std::env::current_exe() {
+        let _ = fs::remove_file(replaced_path(&exe));
+    }
+}
+
+fn replaced_path(exe: &Path) -> PathBuf {
+    let mut path = exe.as_os_str().to_owned();
+    path.push(".old");
+    PathBuf::from(path)
+}
@@ -0,0 +1,50 @@
+//! Discovery of the api version: cached between invocations, and sent once
+//! by `ping`.
+
+mod common;
+
+use common::{test_dir, Server};
+
+fn versions_requests(server: &Server) -> usize {
+    server
+        .requests()
+        .iter()
+        .filter(|request| request.path == "/api/versions")
+        .count()
+}
+
+#[test]
+fn api_version_is_cached_between_invocations() {
+    let dir = test_dir("api-versions-cached");
+    let server = Server::start();
+
+    for _ in 0..2 {
+        let output = common::cosmo_cached_at(&dir, &server.url)
+            .arg("list")
+            .output()
+            .expect("run cosmo");
+        assert!(
+            output.status.success(),
+            "list failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    assert_eq!(versions_requests(&server), 1);
+}
+
+#[test]
+fn ping_discovers_the_api_version_without_retries() {
+    let dir = test_dir("api-versions-ping");
+    let server = Server::with(|request| {
+        (request.path == "/api/versions").then(|| (503, r#"{"detail":"busy"}"#.to_string()))
+    });
+
+    let output = common::cosmo_at(&dir, &server.url)
+        .arg("ping")
+        .output()
+        .expect("run cosmo");
+
+    assert!(!output.status.success());
+    assert_eq!(versions_requests(&server), 1);
+}
@@ -0,0 +1,186 @@
+//! `apikey --action rotate`: the old key is deleted only once the new one
+//! works, and the new key takes the place of the saved one.
+
+mod common;
+
+use std::{path::Path, process::Output};
+
+use common::{test_dir, Request, Response, Server, API_KEY};
+
+/// Api key saved before the rotation, `API_KEY` being the new one.
+const OLD_API_KEY: &str = "1e2f3a4b-5c6d-4e7f-8a9b-0c1d2e3f4a5b";
+
+fn saved_config(dir: &Path) -> String {
+    std::fs::read_to_string(dir.join("config")).expect("config file")
+}
+
+/// Answer of a server rotating the key: the new key and the old one are
+/// listed with the new key, and the old one is deleted by its id.
+fn rotation(request: &Request) -> Option<Response> {
+    let listed = format!(
+        r#"[{{"apiKey":"{OLD_API_KEY}","creationDate":"2026-01-01T12:00:00Z"}},{{"apiKey":"{API_KEY}","creationDate":"2026-10-15T12:00:00Z"}}]"#
+    );
+    match (request.method.as_str(), request.header("x-api-key")) {
+        ("GET", Some(API_KEY)) if request.path == "/api/v1/api_key" => Some((200, listed)),
+        ("DELETE", Some(API_KEY)) => Some((200, "{}".to_string())),
+        _ => None,
+    }
+}
+
+/// Answer of a server holding a single api key: creating one with a key
+/// fails as it is already present.
+fn single_key(request: &Request) -> Option<Response> {
+    (request.method == "POST" && request.header("x-api-key").is_some())
+        .then(|| (400, r#"{"detail":"API key already present!"}"#.to_string()))
+}
+
+/// `apikey --action rotate` with `args` and the api key saved in the
+/// configuration file.
+fn rotate(dir: &Path, server: &Server, args: &[&str]) -> Output {
+    std::fs::write(
+        dir.join("config"),
+        format!("[default]\napi_key={OLD_API_KEY}\n"),
+    )
+    .expect("config file");
+
+    common::cosmo_at(dir, &server.url)
+        .args(["--retries", "0"])
+        .args(args)
+        .args(["apikey", "--action", "rotate"])
+        .env_remove("COSMO_API_KEY")
+        .output()
+        .expect("run cosmo")
+}
+
+fn request(method: &str, path: &str, api_key: &str) -> (String, String, Option<String>) {
+    (
+        method.to_string(),
+        path.to_string(),
+        Some(api_key.to_string()),
+    )
+}
+
+/// Method, path and api key of the requests to the api keys.
+fn apikey_requests(server: &Server) -> Vec<(String, String, Option<String>)> {
+    server
+        .requests()
+        .into_iter()
+        .filter(|request| request.path.starts_with("/api/v1/api_key"))
+        .map(|request| {
+            let api_key = request.header("x-api-key").map(str::to_string);
+            (request.method, request.path, api_key)
+        })
+        .collect()
+}
+
+#[test]
+fn old_key_is_deleted_by_its_id_once_the_new_one_works() {
+    let dir = test_dir("apikey-rotate");
+    let server = Server::with(rotation);
+
+    let output = rotate(&dir, &server, &["-o", "json"]);
+
+    assert_eq!(
+        output.status.code(),
+        Some(0),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let rotated: serde_json::Value = serde_json::from_slice(&output.stdout).expect("json");
+    assert_eq!(rotated["apiKey"], API_KEY);
+    assert_eq!(rotated["savedIn"], "config_file");
+    assert_eq!(
+        apikey_requests(&server),
+        [
+            request("POST", "/api/v1/api_key", OLD_API_KEY),
+            request("GET", "/api/v1/api_key", API_KEY),
+            request("DELETE", &format!("/api/v1/api_key/{OLD_API_KEY}"), API_KEY),
+        ]
+    );
+    let config = saved_config(&dir);
+    assert!(config.contains(API_KEY), "new api key not saved: {config}");
+    assert!(!config.contains(OLD_API_KEY), "old api key kept: {config}");
+}
+
+#[test]
+fn old_key_is_kept_when_the_creation_fails() {
+    let dir = test_dir("apikey-rotate-create-fails");
+    let server = Server::with(|request| {
+        (request.method == "POST").then(|| (500, r#"{"detail":"database down"}"#.to_string()))
+    });
+
+    let output = rotate(&dir, &server, &[]);
+
+    assert_eq!(output.status.code(), Some(5));
+    assert!(output.stdout.is_empty());
+    // Never deleted
+    assert_eq!(
+        apikey_requests(&server),
+        [request("POST", "/api/v1/api_key", OLD_API_KEY)]
+    );
+    assert!(saved_config(&dir).contains(OLD_API_KEY));
+}
+
+#[test]
+fn old_key_is_kept_when_the_new_one_is_rejected() {
+    let dir = test_dir("apikey-rotate-new-rejected");
+    let server = Server::with(|request| {
+        (request.method == "GET" && request.path == "/api/v1/api_key")
+            .then(|| (401, r#"{"detail":"invalid api key"}"#.to_string()))
+    });
+
+    let output = rotate(&dir, &server, &[]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(
+        stderr.contains("the previous one is kept and still valid"),
+        "no error: {stderr}"
+    );
+    assert_eq!(
+        apikey_requests(&server),
+        [
+            request("POST", "/api/v1/api_key", OLD_API_KEY),
+            request("GET", "/api/v1/api_key", API_KEY),
+        ]
+    );
+    assert!(saved_config(&dir).contains(OLD_API_KEY));
+}
+
+#[test]
+fn single_key_is_kept_with_a_clear_error() {
+    let dir = test_dir("apikey-rotate-single-key");
+    let server = Server::with(single_key);
+
+    let output = rotate(&dir, &server, &[]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(
+        stderr.contains("allows a single api key per account"),
+        "no error: {stderr}"
+    );
+    assert!(stderr.contains("The api key is unchanged"), "{stderr}");
+    assert_eq!(
+        apikey_requests(&server),
+        [request("POST", "/api/v1/api_key", OLD_API_KEY)]
+    );
+    assert!(saved_config(&dir).contains(OLD_API_KEY));
+}
+
+#[test]
+fn key_given_in_the_environment_is_not_saved() {
+    let dir = test_dir("apikey-rotate-env");
+    let server = Server::with(rotation);
+
+    let output = common::cosmo_at(&dir, &server.url)
+        .args(["apikey", "--action", "rotate"])
+        .output()
+        .expect("run cosmo");
+
+    assert_eq!(output.status.code(), Some(0));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains(API_KEY), "new api key not shown: {stdout}");
+    assert!(!stdout.contains("saved in"), "{stdout}");
+    assert!(!dir.join("config").exists());
+}
@@ -0,0 +1,286 @@
+//! Fake api server and isolated runs of cosmo, shared by the tests.
+
+#![allow(dead_code)]
+
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    path::{Path, PathBuf},
+    process::Command,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+use serde_json::{json, Value};
+
+pub const PROJECT_ID: &str = "4c1f1a2e-9f6b-4d2e-8d44-0b7a5b2c9e11";
+pub const API_KEY: &str = "9a1f1a2e-9f6b-4d2e-8d44-0b7a5b2c9e12";
+pub const ORGANIZATION_ID: &str = "0b7a5b2c-9f6b-4d2e-8d44-4c1f1a2e9e13";
+
+/// Request received by the fake server.
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub method: String,
+    /// Without the query
+    pub path: String,
+    pub query: String,
+    /// Names in lowercase
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Status and json body of an answer of the fake server.
+pub type Response = (u16, String);
+
+type Handler = dyn Fn(&Request) -> Option<Response> + Send + Sync;
+
+/// Answer of the fake server to `method` on `path`.
+fn respond(method: &str, path: &str) -> Response {
+    let organizations = format!(
+        r#"[{{"id":"{ORGANIZATION_ID}","name":"personal","description":"","built_in":true}}]"#
+    );
+    let project = format!(r#"{{"id":"{PROJECT_ID}","sha256":null}}"#);
+    let api_key = format!(r#"{{"apiKey":"{API_KEY}","creationDate":"2026-10-15T12:00:00Z"}}"#);
+
+    match (method, path) {
+        ("GET", "/api/versions") => (200, r#"{"versions":[1]}"#.to_string()),
+        ("GET", "/api/v1/organizations") => (200, organizations),
+        ("POST", p) if p == format!("/api/v1/organizations/{ORGANIZATION_ID}/projects") => {
+            (200, project)
+        }
+        ("POST", "/api/v1/api_key") => (200, api_key),
+        ("GET", p) if p == format!("/api/v1/projects/{PROJECT_ID}/status") => (
+            200,
+            r#"{"status":"RUNNING","stage":"cve-check","progress":40}"#.to_string(),
+        ),
+        _ => (404, r#"{"detail":"not found"}"#.to_string()),
+    }
+}
+
+/// Read the next request of `reader`, body included, none when the client
+/// closed the connection.
+fn read_request(reader: &mut impl BufRead) -> Option<Request> {
+    let mut request_line = String::new();
+    if reader.read_line(&mut request_line).ok()? == 0 {
+        return None;
+    }
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let target = parts.next().unwrap_or_default();
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+
+    let mut headers = vec![];
+    loop {
+        let mut header = String::new();
+        reader.read_line(&mut header).ok()?;
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        let (name, value) = header.split_once(':').unwrap_or_default();
+        headers.push((name.to_lowercase(), value.trim().to_string()));
+    }
+
+    let mut request = Request {
+        method,
+        path: path.to_string(),
+        query: query.to_string(),
+        headers,
+        body: vec![],
+    };
+
+    if request
+        .header("transfer-encoding")
+        .is_some_and(|value| value.eq_ignore_ascii_case("chunked"))
+    {
+        loop {
+            let mut size = String::new();
+            reader.read_line(&mut size).ok()?;
+            let size = usize::from_str_radix(size.trim(), 16).ok()?;
+            // The chunk and its CRLF
+            let mut chunk = vec![0; size + 2];
+            reader.read_exact(&mut chunk).ok()?;
+            if size == 0 {
+                break;
+            }
+            request.body.extend_from_slice(&chunk[..size]);
+        }
+    } else {
+        let content_length = request
+            .header("content-length")
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(0);
+        request.body = vec![0; content_length];
+        reader.read_exact(&mut request.body).ok()?;
+    }
+
+    Some(request)
+}
+
+/// Answer the requests of a connection until the client closes it.
+fn handle(stream: TcpStream, handler: &Handler, requests: &Mutex<Vec<Request>>) {
+    let mut reader = BufReader::new(stream.try_clone().expect("clone stream"));
+    let mut stream = stream;
+
+    while let Some(request) = read_request(&mut reader) {
+        requests.lock().unwrap().push(request.clone());
+        let (status, body) =
+            handler(&request).unwrap_or_else(|| respond(&request.method, &request.path));
+        let written = write!(
+            stream,
+            "HTTP/1.1 {status} X\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{body}",
+            body.len()
+        );
+        if written.is_err() {
+            break;
+        }
+    }
+}
+
+/// Fake api server answering in the background, keeping the connections
+/// alive between requests.
+pub struct Server {
+    pub url: String,
+    connections: Arc<AtomicUsize>,
+    requests: Arc<Mutex<Vec<Request>>>,
+}
+
+impl Server {
+    /// Server with the default answers.
+    pub fn start() -> Self {
+        Self::with(|_| None)
+    }
+
+    /// Server answering with `handler`, or the default answer when it
+    /// returns none.
+    pub fn with(handler: impl Fn(&Request) -> Option<Response> + Send + Sync + 'static) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind");
+        let port = listener.local_addr().expect("address").port();
+        let handler: Arc<Handler> = Arc::new(handler);
+        let connections = Arc::new(AtomicUsize::new(0));
+        let requests = Arc::new(Mutex::new(vec![]));
+
+        {
+            let connections = connections.clone();
+            let requests = requests.clone();
+            thread::spawn(move || {
+                for stream in listener.incoming().flatten() {
+                    connections.fetch_add(1, Ordering::SeqCst);
+                    let handler = handler.clone();
+                    let requests = requests.clone();
+                    thread::spawn(move || handle(stream, handler.as_ref(), &requests));
+                }
+            });
+        }
+
+        Server {
+            url: format!("http://127.0.0.1:{port}"),
+            connections,
+            requests,
+        }
+    }
+
+    /// Connections accepted so far.
+    pub fn connections(&self) -> usize {
+        self.connections.load(Ordering::SeqCst)
+    }
+
+    /// Requests received so far, in order.
+    pub fn requests(&self) -> Vec<Request> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+/// Answer to a request of the results of an analysis of the project, by
+/// `results` of the name of the analysis, in a single page.
+pub fn analysis_response(
+    request: &Request,
+    results: fn(&str) -> Option<Value>,
+) -> Option<Response> {
+    let prefix = format!("/api/v1/projects/{PROJECT_ID}/analysis/");
+    let results = results(request.path.strip_prefix(&prefix)?)?;
+    let result = match request.query.contains("page=0") {
+        true => results,
+        false => json!([]),
+    };
+    let analysis = json!({
+        "name": "analysis",
+        "fw_type": "linux",
+        "error": null,
+        "result": result,
+    });
+    Some((200, analysis.to_string()))
+}
+
+/// Fake server with the results of the analyses of the project, by the
+/// name of the analysis.
+pub fn analysis_server(results: fn(&str) -> Option<Value>) -> Server {
+    Server::with(move |request| analysis_response(request, results))
+}
+
+/// Command running cosmo against a fake server, away from the configuration
+/// and cache of the user.
+pub fn cosmo(dir: &Path) -> Command {
+    cosmo_at(dir, &Server::start().url)
+}
+
+/// Command running cosmo against the api server at `url`, away from the
+/// configuration and cache of the user.
+pub fn cosmo_at(dir: &Path, url: &str) -> Command {
+    let mut command = cosmo_cached_at(dir, url);
+    command.arg("--no-cache");
+    command
+}
+
+/// Command running cosmo against the api server at `url`, with a cache of
+/// the responses in `dir`.
+pub fn cosmo_cached_at(dir: &Path, url: &str) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_cosmo"));
+    command
+        .args(["--api-server", url, "--non-interactive"])
+        .env("COSMO_API_KEY", "test")
+        .env("COSMO_CONFIG_FILE", dir.join("config"))
+        .env("HOME", dir)
+        .env("XDG_CACHE_HOME", dir.join("cache"))
+        .env_remove("COSMO_OUTPUT")
+        .env_remove("COSMO_PROFILE")
+        .env_remove("COSMO_LOG")
+        .env_remove("RUST_LOG")
+        .current_dir(dir);
+    for var in PROXY_ENV_VARS {
+        command.env_remove(var);
+    }
+    command
+}
+
+/// Proxy variables of the environment, honored by cosmo.
+const PROXY_ENV_VARS: [&str; 8] = [
+    "HTTP_PROXY",
+    "http_proxy",
+    "HTTPS_PROXY",
+    "https_proxy",
+    "ALL_PROXY",
+    "all_proxy",
+    "NO_PROXY",
+    "no_proxy",
+];
+
+/// Temporary directory of a test, emptied first.
+pub fn test_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("cosmo-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).expect("test dir");
+    dir
+}
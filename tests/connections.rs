@@ -0,0 +1,39 @@
+//! The http client shared by the requests of a command.
+
+mod common;
+
+use common::{test_dir, Server, PROJECT_ID};
+
+#[test]
+fn requests_reuse_the_connection() {
+    let dir = test_dir("connections");
+    std::fs::write(dir.join("firmware.bin"), b"firmware").expect("firmware");
+    let server = Server::start();
+
+    let output = common::cosmo_at(&dir, &server.url)
+        .args([
+            "-q",
+            "create",
+            "--file",
+            "firmware.bin",
+            "--type",
+            "linux",
+            "--name",
+            "router",
+        ])
+        .output()
+        .expect("run cosmo");
+
+    assert!(
+        output.status.success(),
+        "cosmo failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        format!("{PROJECT_ID}\n")
+    );
+    let requests = server.requests();
+    assert!(requests.len() > 1, "requests: {requests:?}");
+    assert_eq!(server.connections(), 1);
+}
@@ -0,0 +1,96 @@
+//! `setup --encrypt-session`: the api key is saved encrypted with a key
+//! derived with Argon2id from `COSMO_SESSION_KEY`.
+
+mod common;
+
+use std::{path::Path, process::Output};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+use common::{test_dir, Server, API_KEY};
+
+const SESSION_KEY: &str = "correct horse battery staple";
+
+/// Save the api key encrypted, returning the value in the configuration
+/// file.
+fn setup(dir: &Path, server: &Server) -> String {
+    let output = common::cosmo_at(dir, &server.url)
+        .args(["--encrypt-session", "setup"])
+        .env("COSMO_API_KEY", API_KEY)
+        .env("COSMO_SESSION_KEY", SESSION_KEY)
+        .output()
+        .expect("run cosmo");
+    assert!(
+        output.status.success(),
+        "setup failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let config = std::fs::read_to_string(dir.join("config")).expect("config file");
+    config
+        .lines()
+        .find_map(|line| line.strip_prefix("api_key="))
+        .expect("api key entry")
+        .to_string()
+}
+
+fn list(dir: &Path, server: &Server, session_key: &str) -> Output {
+    common::cosmo_at(dir, &server.url)
+        .arg("list")
+        .env_remove("COSMO_API_KEY")
+        .env("COSMO_SESSION_KEY", session_key)
+        .output()
+        .expect("run cosmo")
+}
+
+#[test]
+fn api_key_is_saved_with_its_argon2id_parameters() {
+    let dir = test_dir("encrypt-session");
+    let server = Server::start();
+
+    let saved = setup(&dir, &server);
+
+    assert!(!saved.contains(API_KEY), "api key in clear: {saved}");
+    let data = STANDARD
+        .decode(saved.strip_prefix("encrypted:").expect("encrypted value"))
+        .expect("base64");
+    // Format version, then memory, iterations and parallelism
+    assert_eq!(data[0], 1);
+    let param = |index: usize| {
+        let start = 1 + index * 4;
+        u32::from_le_bytes(data[start..start + 4].try_into().unwrap())
+    };
+    assert_eq!((param(0), param(1), param(2)), (19 * 1024, 2, 1));
+
+    let output = list(&dir, &server, SESSION_KEY);
+    assert!(
+        output.status.success(),
+        "list failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let requests = server.requests();
+    let listed = requests.last().expect("list request");
+    assert_eq!(listed.header("x-api-key"), Some(API_KEY));
+}
+
+#[test]
+fn wrong_session_key_is_reported() {
+    let dir = test_dir("encrypt-session-wrong");
+    let server = Server::start();
+    setup(&dir, &server);
+
+    let output = list(&dir, &server, "wrong");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert!(!output.status.success());
+    assert!(
+        stderr.contains(
+            "cannot decrypt session, re-login with `cosmo setup` or check COSMO_SESSION_KEY"
+        ),
+        "no error: {stderr}"
+    );
+    assert!(server
+        .requests()
+        .iter()
+        .all(|request| request.method != "GET" || !request.path.contains("projects")));
+}
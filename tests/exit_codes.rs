@@ -0,0 +1,102 @@
+//! Exit codes by class of failure, relied upon by scripts.
+
+mod common;
+
+use std::{net::TcpListener, path::Path, process::Output};
+
+use common::{test_dir, Server};
+
+/// Exit code of `list` against a server failing it with `status` and the
+/// error message `detail`.
+fn exit_code(name: &str, status: u16, detail: &str) -> Option<i32> {
+    let dir = test_dir(name);
+    let body = format!(r#"{{"detail":"{detail}"}}"#);
+    let server = Server::with(move |request| {
+        (request.path != "/api/versions").then(|| (status, body.clone()))
+    });
+
+    list(&dir, &server.url).status.code()
+}
+
+/// Url of a port nothing listens on anymore.
+fn unreachable_url() -> String {
+    let port = TcpListener::bind("127.0.0.1:0")
+        .and_then(|listener| listener.local_addr())
+        .expect("free port")
+        .port();
+    format!("http://127.0.0.1:{port}")
+}
+
+/// `list` against the api server at `url`, failing at once.
+fn list(dir: &Path, url: &str) -> Output {
+    common::cosmo_at(dir, url)
+        .args(["--retries", "0", "--no-rate-limit-wait", "list"])
+        .output()
+        .expect("run cosmo")
+}
+
+#[test]
+fn success_exits_with_0() {
+    let dir = test_dir("exit-success");
+    let server = Server::with(|request| {
+        (request.path == "/api/v1/projects").then(|| (200, "[]".to_string()))
+    });
+
+    assert_eq!(list(&dir, &server.url).status.code(), Some(0));
+}
+
+#[test]
+fn usage_errors_exit_with_2() {
+    let dir = test_dir("exit-usage");
+
+    let output = common::cosmo(&dir)
+        .args(["list", "--no-such-flag"])
+        .output()
+        .expect("run cosmo");
+
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn rejected_api_keys_exit_with_3() {
+    assert_eq!(exit_code("exit-401", 401, "Invalid api key"), Some(3));
+}
+
+#[test]
+fn other_authentication_failures_have_their_own_codes() {
+    assert_eq!(exit_code("exit-expired", 401, "Api key expired"), Some(12));
+    assert_eq!(exit_code("exit-403", 403, "Forbidden"), Some(13));
+    assert_eq!(exit_code("exit-423", 423, "Account locked"), Some(14));
+}
+
+#[test]
+fn missing_resources_exit_with_4() {
+    assert_eq!(exit_code("exit-404", 404, "failure"), Some(4));
+}
+
+#[test]
+fn server_errors_exit_with_5() {
+    assert_eq!(exit_code("exit-409", 409, "failure"), Some(5));
+    assert_eq!(exit_code("exit-429", 429, "failure"), Some(5));
+    assert_eq!(exit_code("exit-500", 500, "failure"), Some(5));
+    assert_eq!(exit_code("exit-503", 503, "failure"), Some(5));
+}
+
+#[test]
+fn unreachable_servers_exit_with_6() {
+    let dir = test_dir("exit-unreachable");
+    let output = list(&dir, &unreachable_url());
+
+    assert_eq!(output.status.code(), Some(6));
+}
+
+#[test]
+fn ping_tells_connection_failures_apart() {
+    let dir = test_dir("exit-ping");
+    let output = common::cosmo_at(&dir, &unreachable_url())
+        .arg("ping")
+        .output()
+        .expect("run cosmo");
+
+    assert_eq!(output.status.code(), Some(9));
+}
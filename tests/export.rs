@@ -0,0 +1,478 @@
+//! Exports of the analyses in standard formats, checked against their
+//! schemas.
+
+mod common;
+
+use std::path::{Path, PathBuf};
+
+use jsonschema::{Retrieve, Uri};
+use serde_json::{json, Value};
+
+use common::{analysis_server, test_dir, PROJECT_ID};
+
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/schemas")
+        .join(name)
+}
+
+fn read_json(path: &Path) -> Value {
+    let content = std::fs::read_to_string(path).expect("json file");
+    serde_json::from_str(&content).expect("json")
+}
+
+/// Schemas referenced by the vendored ones, found next to them.
+struct Fixtures;
+
+impl Retrieve for Fixtures {
+    fn retrieve(
+        &self,
+        uri: &Uri<String>,
+    ) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+        let name = uri.path().as_str().rsplit('/').next().unwrap_or_default();
+        Ok(read_json(&fixture(name)))
+    }
+}
+
+/// Check `document` against the vendored schema `name`, formats included.
+fn assert_valid(name: &str, document: &Value) {
+    let validator = jsonschema::options()
+        .with_retriever(Fixtures)
+        .should_validate_formats(true)
+        .build(&read_json(&fixture(name)))
+        .expect("schema");
+
+    let errors: Vec<String> = validator
+        .iter_errors(document)
+        .map(|error| format!("{} at {}", error, error.instance_path()))
+        .collect();
+    assert!(errors.is_empty(), "not valid against {name}: {errors:#?}");
+}
+
+/// Packages found by the software BOM analysis.
+fn packages() -> Value {
+    json!([
+        {
+            "filename": "libssl.so.3",
+            "resolve": "/usr/lib/libssl.so.3",
+            "occurrences": 2,
+            "name": "openssl",
+            "version": "3.0.11",
+            "purl": "pkg:deb/debian/openssl@3.0.11",
+            "license": "Apache-2.0",
+            "hashes": {
+                "sha256": "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08",
+                "md5": "not a hash"
+            }
+        },
+        {
+            "filename": "libz.so.1.2.13",
+            "resolve": "/lib/libz.so.1.2.13",
+            "occurrences": 1,
+            "license": "zlib License"
+        },
+        {
+            "filename": "busybox",
+            "resolve": "/bin/busybox",
+            "occurrences": 1,
+            "name": "busybox",
+            "license": "GPLv2"
+        },
+        {
+            "filename": "libfoo",
+            "resolve": "/usr/lib/libfoo",
+            "occurrences": 1,
+            "name": "lib foo++",
+            "version": "1.0+git 2",
+            "license": null
+        }
+    ])
+}
+
+/// Software BOM of the project in `format`.
+fn sbom(name: &str, format: &str) -> String {
+    let dir = test_dir(name);
+    let server = analysis_server(|analysis| (analysis == "SoftwareBOM").then(packages));
+
+    let output = common::cosmo_at(&dir, &server.url)
+        .args(["project", "sbom", PROJECT_ID, "--format", format])
+        .output()
+        .expect("run cosmo");
+
+    assert!(
+        output.status.success(),
+        "cosmo failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).expect("utf-8 BOM")
+}
+
+#[test]
+fn cyclonedx_bom_is_valid() {
+    let bom: Value =
+        serde_json::from_str(&sbom("export-cyclonedx", "cyclonedx-json")).expect("json");
+
+    assert_valid("bom-1.5.schema.json", &bom);
+    assert_eq!(bom["specVersion"], "1.5");
+}
+
+#[test]
+fn cyclonedx_bom_lists_the_components_without_version() {
+    let bom: Value =
+        serde_json::from_str(&sbom("export-cyclonedx-versions", "cyclonedx-json")).expect("json");
+
+    let components: Vec<(&str, Option<&str>)> = bom["components"]
+        .as_array()
+        .expect("components")
+        .iter()
+        .map(|component| {
+            (
+                component["name"].as_str().expect("name"),
+                component["version"].as_str(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        components,
+        [
+            ("openssl", Some("3.0.11")),
+            // Version of the soname
+            ("libz.so.1.2.13", Some("1.2.13")),
+            ("busybox", None),
+            ("lib foo++", Some("1.0+git 2")),
+        ]
+    );
+    // Without a version there is nothing to make a purl of
+    assert!(bom["components"][2].get("purl").is_none());
+    assert_eq!(
+        bom["components"][3]["purl"],
+        "pkg:generic/lib%20foo%2B%2B@1.0%2Bgit%202"
+    );
+    // The malformed md5 is left out
+    assert_eq!(
+        bom["components"][0]["hashes"],
+        json!([{
+            "alg": "SHA-256",
+            "content": "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08"
+        }])
+    );
+}
+
+/// Package of an SPDX document, as compared between its formats.
+#[derive(Debug, Default, PartialEq)]
+struct SpdxPackage {
+    spdx_id: String,
+    name: String,
+    version: Option<String>,
+    license_declared: String,
+    checksums: Vec<String>,
+    purls: Vec<String>,
+}
+
+/// Packages and relationships of an SPDX JSON document.
+fn spdx_json_packages(document: &Value) -> (Vec<SpdxPackage>, Vec<String>) {
+    let packages = document["packages"]
+        .as_array()
+        .expect("packages")
+        .iter()
+        .map(|package| SpdxPackage {
+            spdx_id: package["SPDXID"].as_str().expect("SPDXID").to_string(),
+            name: package["name"].as_str().expect("name").to_string(),
+            version: package["versionInfo"].as_str().map(str::to_string),
+            license_declared: package["licenseDeclared"]
+                .as_str()
+                .expect("licenseDeclared")
+                .to_string(),
+            checksums: package["checksums"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .map(|checksum| {
+                    format!(
+                        "{}: {}",
+                        checksum["algorithm"].as_str().expect("algorithm"),
+                        checksum["checksumValue"].as_str().expect("checksumValue")
+                    )
+                })
+                .collect(),
+            purls: package["externalRefs"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .map(|external_ref| {
+                    external_ref["referenceLocator"]
+                        .as_str()
+                        .expect("referenceLocator")
+                        .to_string()
+                })
+                .collect(),
+        })
+        .collect();
+    let relationships = document["relationships"]
+        .as_array()
+        .expect("relationships")
+        .iter()
+        .map(|relationship| {
+            format!(
+                "{} {} {}",
+                relationship["spdxElementId"]
+                    .as_str()
+                    .expect("spdxElementId"),
+                relationship["relationshipType"]
+                    .as_str()
+                    .expect("relationshipType"),
+                relationship["relatedSpdxElement"]
+                    .as_str()
+                    .expect("relatedSpdxElement")
+            )
+        })
+        .collect();
+    (packages, relationships)
+}
+
+/// Packages and relationships of an SPDX tag-value document.
+fn spdx_tag_value_packages(document: &str) -> (Vec<SpdxPackage>, Vec<String>) {
+    let mut packages: Vec<SpdxPackage> = vec![];
+    let mut relationships = vec![];
+    for line in document.lines() {
+        let Some((tag, value)) = line.split_once(": ") else {
+            continue;
+        };
+        let value = value.to_string();
+        match tag {
+            "PackageName" => packages.push(SpdxPackage {
+                name: value,
+                ..Default::default()
+            }),
+            "Relationship" => relationships.push(value),
+            _ => {
+                let Some(package) = packages.last_mut() else {
+                    continue;
+                };
+                match tag {
+                    "SPDXID" => package.spdx_id = value,
+                    "PackageVersion" => package.version = Some(value),
+                    "PackageLicenseDeclared" => package.license_declared = value,
+                    "PackageChecksum" => package.checksums.push(value),
+                    "ExternalRef" => package.purls.extend(
+                        value
+                            .strip_prefix("PACKAGE-MANAGER purl ")
+                            .map(str::to_string),
+                    ),
+                    _ => {}
+                }
+            }
+        }
+    }
+    (packages, relationships)
+}
+
+#[test]
+fn spdx_document_is_valid() {
+    let document: Value = serde_json::from_str(&sbom("export-spdx", "spdx-json")).expect("json");
+
+    assert_valid("spdx-2.3.schema.json", &document);
+    assert_eq!(document["spdxVersion"], "SPDX-2.3");
+}
+
+#[test]
+fn spdx_formats_describe_the_same_packages() {
+    let json: Value = serde_json::from_str(&sbom("export-spdx-json", "spdx-json")).expect("json");
+    let tag_value = sbom("export-spdx-tag-value", "spdx-tag-value");
+
+    let (packages, relationships) = spdx_json_packages(&json);
+    assert_eq!(
+        (packages, relationships),
+        spdx_tag_value_packages(&tag_value)
+    );
+}
+
+#[test]
+fn spdx_packages_have_the_analysis_data() {
+    let json: Value =
+        serde_json::from_str(&sbom("export-spdx-packages", "spdx-json")).expect("json");
+
+    let (packages, relationships) = spdx_json_packages(&json);
+    let firmware = &packages[0];
+    assert_eq!(firmware.spdx_id, "SPDXRef-firmware");
+    assert_eq!(firmware.name, PROJECT_ID);
+    assert_eq!(
+        packages[1..]
+            .iter()
+            .map(|package| (
+                package.name.as_str(),
+                package.version.as_deref(),
+                package.license_declared.as_str()
+            ))
+            .collect::<Vec<_>>(),
+        [
+            ("openssl", Some("3.0.11"), "Apache-2.0"),
+            ("libz.so.1.2.13", Some("1.2.13"), "Zlib"),
+            ("busybox", None, "GPL-2.0-only"),
+            ("lib foo++", Some("1.0+git 2"), "NOASSERTION"),
+        ]
+    );
+    assert_eq!(
+        packages[1].checksums,
+        ["SHA256: 9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08"]
+    );
+    assert_eq!(packages[1].purls, ["pkg:deb/debian/openssl@3.0.11"]);
+    assert_eq!(
+        relationships,
+        [
+            "SPDXRef-DOCUMENT DESCRIBES SPDXRef-firmware",
+            "SPDXRef-firmware CONTAINS SPDXRef-Package-1",
+            "SPDXRef-firmware CONTAINS SPDXRef-Package-2",
+            "SPDXRef-firmware CONTAINS SPDXRef-Package-3",
+            "SPDXRef-firmware CONTAINS SPDXRef-Package-4",
+        ]
+    );
+}
+
+/// Findings of the analyses mapped to SARIF results.
+fn findings(analysis: &str) -> Option<Value> {
+    let findings = match analysis {
+        "CveCheck" => json!([
+            {
+                "cveid": "CVE-2023-5678",
+                "severity": "medium",
+                "summary": "Excessive time spent in DH key generation",
+                "vendor": "openssl",
+                "product": "openssl",
+                "version": "3.0.11",
+                "vector": "NETWORK",
+                "patch": null,
+                "references": null,
+                "cvss": {"v3": {"score": 5.3}},
+                "problems": null,
+                "published_date": "2023-11-06"
+            },
+            {
+                "cveid": "CVE-2022-48174",
+                "severity": "critical",
+                "summary": "Stack overflow in ash",
+                "vendor": "busybox",
+                "product": "busybox",
+                "version": "1.35.0",
+                "vector": "NETWORK",
+                "patch": null,
+                "references": null,
+                "cvss": null,
+                "problems": null,
+                "published_date": null
+            }
+        ]),
+        "Hardening" => json!([
+            {
+                "filename": "/usr/bin/web server",
+                "type": "ELF",
+                "score": 2,
+                "compiler": null,
+                "stripped": true,
+                "suid": false,
+                "execstack": true,
+                "canary": false,
+                "fortify": false,
+                "nx": true,
+                "pie": "no",
+                "relro": "partial"
+            },
+            {
+                "filename": "/bin/busybox",
+                "type": "ELF",
+                "score": 5,
+                "compiler": "GCC",
+                "stripped": true,
+                "suid": false,
+                "execstack": false,
+                "canary": true,
+                "fortify": true,
+                "nx": true,
+                "pie": "yes",
+                "relro": "full"
+            }
+        ]),
+        "PasswordHash" => json!([
+            {"username": "root", "password": "$1$abc$def", "file": "/etc/shadow"}
+        ]),
+        _ => return None,
+    };
+    Some(findings)
+}
+
+/// SARIF log of the findings of the project.
+fn sarif(name: &str) -> Value {
+    let dir = test_dir(name);
+    let server = analysis_server(findings);
+
+    let output = common::cosmo_at(&dir, &server.url)
+        .args([
+            "-o",
+            "sarif",
+            "analysis",
+            "--id",
+            PROJECT_ID,
+            "--analyses",
+            "cve-check,hardening,password-hash",
+        ])
+        .output()
+        .expect("run cosmo");
+
+    assert!(
+        output.status.success(),
+        "cosmo failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    serde_json::from_slice(&output.stdout).expect("json log")
+}
+
+#[test]
+fn sarif_log_is_valid() {
+    let log = sarif("export-sarif");
+
+    assert_valid("sarif-2.1.0.schema.json", &log);
+    assert_eq!(log["version"], "2.1.0");
+}
+
+#[test]
+fn sarif_results_point_at_their_rules_and_files() {
+    let log = sarif("export-sarif-results");
+
+    let run = &log["runs"][0];
+    let rules: Vec<&str> = run["tool"]["driver"]["rules"]
+        .as_array()
+        .expect("rules")
+        .iter()
+        .map(|rule| rule["id"].as_str().expect("rule id"))
+        .collect();
+    let results: Vec<(&str, &str, &str)> = run["results"]
+        .as_array()
+        .expect("results")
+        .iter()
+        .map(|result| {
+            let rule_id = result["ruleId"].as_str().expect("ruleId");
+            let rule_index = result["ruleIndex"].as_u64().expect("ruleIndex") as usize;
+            assert_eq!(rules[rule_index], rule_id);
+            (
+                rule_id,
+                result["level"].as_str().expect("level"),
+                result["locations"][0]["physicalLocation"]["artifactLocation"]["uri"]
+                    .as_str()
+                    .expect("uri"),
+            )
+        })
+        .collect();
+
+    assert_eq!(
+        results,
+        [
+            ("cve", "warning", "firmware"),
+            ("cve", "error", "firmware"),
+            // Only the binary lacking protections
+            ("hardening", "warning", "usr/bin/web%20server"),
+            ("hardcoded-credentials", "error", "firmware"),
+        ]
+    );
+    // The hash is never written
+    assert!(!log.to_string().contains("$1$abc$def"));
+}
@@ -0,0 +1,109 @@
+//! Ctrl-C aborting a command and cleaning up the project it created.
+
+#![cfg(unix)]
+
+mod common;
+
+use std::{
+    process::{Child, Output, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use common::{test_dir, Server, PROJECT_ID};
+
+/// Fake server stalling on the status of the project, with the project
+/// deletion succeeding.
+fn stalling_server() -> Server {
+    Server::with(|request| {
+        if request.path == format!("/api/v1/projects/{PROJECT_ID}/status") {
+            thread::sleep(Duration::from_secs(20));
+        }
+        (request.method == "DELETE").then(|| (200, "{}".to_string()))
+    })
+}
+
+/// cosmo creating a project and waiting for its analysis.
+fn create_and_wait(server: &Server, name: &str, args: &[&str]) -> Child {
+    let dir = test_dir(name);
+    std::fs::write(dir.join("firmware.bin"), b"firmware").expect("firmware");
+    common::cosmo_at(&dir, &server.url)
+        .args([
+            "create",
+            "--file",
+            "firmware.bin",
+            "--type",
+            "linux",
+            "--name",
+            "router",
+            "--wait",
+        ])
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("run cosmo")
+}
+
+/// Send SIGINT to `child` once it is waiting for `server`.
+fn interrupt(child: Child, server: &Server) -> Output {
+    let start = Instant::now();
+    while !server
+        .requests()
+        .iter()
+        .any(|request| request.path.ends_with("/status"))
+    {
+        assert!(
+            start.elapsed() < Duration::from_secs(10),
+            "no status request"
+        );
+        thread::sleep(Duration::from_millis(50));
+    }
+
+    let killed = std::process::Command::new("kill")
+        .args(["-INT", &child.id().to_string()])
+        .status()
+        .expect("kill");
+    assert!(killed.success());
+    child.wait_with_output().expect("run cosmo")
+}
+
+fn deletions(server: &Server) -> Vec<String> {
+    server
+        .requests()
+        .into_iter()
+        .filter(|request| request.method == "DELETE")
+        .map(|request| request.path)
+        .collect()
+}
+
+#[test]
+fn interrupted_create_deletes_the_project_with_cleanup_on_abort() {
+    let server = stalling_server();
+    let child = create_and_wait(&server, "interrupt-cleanup", &["--cleanup-on-abort"]);
+
+    let output = interrupt(child, &server);
+
+    assert_eq!(output.status.code(), Some(130));
+    assert_eq!(
+        deletions(&server),
+        [format!("/api/v1/projects/{PROJECT_ID}")]
+    );
+}
+
+#[test]
+fn interrupted_create_keeps_the_project_without_asking() {
+    let server = stalling_server();
+    let child = create_and_wait(&server, "interrupt-keep", &[]);
+
+    let output = interrupt(child, &server);
+
+    assert_eq!(output.status.code(), Some(130));
+    assert!(deletions(&server).is_empty());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains(&format!("cosmo delete --id {PROJECT_ID}")),
+        "stderr: {stderr}"
+    );
+}
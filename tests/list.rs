@@ -0,0 +1,105 @@
+//! Projects listed page by page.
+
+mod common;
+
+use common::{test_dir, Request, Server};
+
+/// Projects of the fake server, on three pages of 100
+const PROJECTS: usize = 250;
+
+fn project_id(i: usize) -> String {
+    format!("00000000-0000-4000-8000-{i:012}")
+}
+
+/// Value of the parameter `name` of the query of `request`.
+fn param(request: &Request, name: &str) -> Option<usize> {
+    request
+        .query
+        .split('&')
+        .filter_map(|param| param.split_once('='))
+        .find(|(param, _)| *param == name)
+        .and_then(|(_, value)| value.parse().ok())
+}
+
+/// Fake server with the projects on pages of the requested size.
+fn server() -> Server {
+    Server::with(|request| {
+        if request.path != "/api/v1/projects" {
+            return None;
+        }
+        let page = param(request, "page").expect("page");
+        let per_page = param(request, "per_page").expect("per_page");
+        let projects: Vec<String> = ((page - 1) * per_page..page * per_page)
+            .filter(|i| *i < PROJECTS)
+            .map(|i| {
+                format!(
+                    r#"{{"id":"{}","name":"project-{i}","description":null,"status":"SUCCESS","original_name":"firmware.bin","organization_name":"personal","score":5.0,"project_type":"LINUX","project_subtype":"generic","creation_date":"2026-10-15T12:00:00"}}"#,
+                    project_id(i)
+                )
+            })
+            .collect();
+        Some((200, format!("[{}]", projects.join(","))))
+    })
+}
+
+/// Ids of the projects listed by `list` with `args`, and the pages
+/// requested.
+fn list(name: &str, args: &[&str]) -> (Vec<String>, Vec<(usize, usize)>) {
+    let dir = test_dir(name);
+    let server = server();
+
+    let output = common::cosmo_at(&dir, &server.url)
+        .args(["-o", "json", "list"])
+        .args(args)
+        .output()
+        .expect("run cosmo");
+
+    assert!(
+        output.status.success(),
+        "cosmo failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let projects: Vec<serde_json::Value> =
+        serde_json::from_slice(&output.stdout).expect("json output");
+    let ids = projects
+        .iter()
+        .map(|project| project["id"].as_str().expect("id").to_string())
+        .collect();
+    let pages = server
+        .requests()
+        .iter()
+        .filter(|request| request.path == "/api/v1/projects")
+        .map(|request| {
+            (
+                param(request, "page").expect("page"),
+                param(request, "per_page").expect("per_page"),
+            )
+        })
+        .collect();
+    (ids, pages)
+}
+
+#[test]
+fn all_pages_are_listed_in_order() {
+    let (ids, pages) = list("list-pages", &[]);
+
+    assert_eq!(ids, (0..PROJECTS).map(project_id).collect::<Vec<_>>());
+    // The last page is the first not full
+    assert_eq!(pages, [(1, 100), (2, 100), (3, 100)]);
+}
+
+#[test]
+fn limit_stops_at_the_page_reaching_it() {
+    let (ids, pages) = list("list-limit", &["--limit", "150"]);
+
+    assert_eq!(ids, (0..150).map(project_id).collect::<Vec<_>>());
+    assert_eq!(pages, [(1, 100), (2, 100)]);
+}
+
+#[test]
+fn limit_within_the_first_page_requests_only_it() {
+    let (ids, pages) = list("list-limit-first", &["--limit", "100"]);
+
+    assert_eq!(ids, (0..100).map(project_id).collect::<Vec<_>>());
+    assert_eq!(pages, [(1, 100)]);
+}
@@ -0,0 +1,197 @@
+//! Filtering and formats of the diagnostics, and the secrets never written.
+
+mod common;
+
+use std::{
+    io::Write,
+    sync::{Arc, Mutex},
+};
+
+use cosmo_cli::{
+    cli::LogFormat,
+    logging::{self, LogFilter, Logger, REDACTED},
+};
+use tracing::{level_filters::LevelFilter, Level};
+
+use common::{test_dir, API_KEY};
+
+/// Output of a logger, read back by the tests.
+#[derive(Clone, Default)]
+struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Buffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Buffer {
+    fn text(&self) -> String {
+        String::from_utf8(self.0.lock().unwrap().clone()).expect("utf-8 logs")
+    }
+}
+
+/// What `emit` logs at trace level in `format`.
+fn logs(format: LogFormat, emit: impl FnOnce()) -> String {
+    let buffer = Buffer::default();
+    // The events of the tests have their own target
+    let mut filter = LogFilter::new(LevelFilter::TRACE);
+    filter.parse("trace").unwrap();
+    let logger = Logger::new(filter, format, false, buffer.clone());
+    tracing::subscriber::with_default(logger, emit);
+    buffer.text()
+}
+
+#[test]
+fn dependencies_log_at_most_at_info() {
+    let filter = LogFilter::new(LevelFilter::TRACE);
+
+    assert!(filter.enabled("cosmo_cli::api", &Level::TRACE));
+    assert!(filter.enabled("cosmo", &Level::TRACE));
+    assert!(filter.enabled("hyper::proto", &Level::INFO));
+    assert!(!filter.enabled("hyper::proto", &Level::DEBUG));
+    // Not a module of the cli
+    assert!(!filter.enabled("cosmos", &Level::DEBUG));
+}
+
+#[test]
+fn directives_set_the_levels_by_target() {
+    let mut filter = LogFilter::new(LevelFilter::WARN);
+    filter.parse("reqwest=trace, cosmo_cli::api=debug").unwrap();
+
+    assert!(filter.enabled("reqwest::connect", &Level::TRACE));
+    assert!(filter.enabled("cosmo_cli::api::http_server", &Level::DEBUG));
+    assert!(!filter.enabled("cosmo_cli::services", &Level::INFO));
+    assert!(!filter.enabled("hyper", &Level::INFO));
+
+    filter.parse("debug").unwrap();
+    assert!(filter.enabled("hyper", &Level::DEBUG));
+    assert!(!filter.enabled("cosmo_cli::api", &Level::TRACE));
+}
+
+#[test]
+fn invalid_directives_are_not_applied() {
+    let mut filter = LogFilter::new(LevelFilter::INFO);
+
+    assert!(filter.parse("reqwest=trace,cosmo=loud").is_err());
+    assert!(filter.parse("=debug").is_err());
+    assert!(!filter.enabled("reqwest", &Level::DEBUG));
+}
+
+#[test]
+fn text_lines_have_the_level_message_and_fields() {
+    let logs = logs(LogFormat::Text, || {
+        tracing::warn!(project = "router", "Analysis {} failed", "cve-check");
+    });
+
+    assert_eq!(logs, "[WARN ] Analysis cve-check failed project=router\n");
+}
+
+#[test]
+fn json_lines_have_the_fields_of_the_spans() {
+    let logs = logs(LogFormat::Json, || {
+        let span = tracing::debug_span!(
+            "request",
+            path = "/api/v1/projects",
+            duration_ms = tracing::field::Empty
+        );
+        let _entered = span.enter();
+        span.record("duration_ms", 12);
+        tracing::debug!(status = 200, "<-- 200");
+    });
+
+    let event: serde_json::Value = serde_json::from_str(&logs).expect("one json object");
+    assert_eq!(event["level"], "DEBUG");
+    assert_eq!(event["fields"]["message"], "<-- 200");
+    assert_eq!(event["fields"]["status"], 200);
+    assert_eq!(event["spans"][0]["name"], "request");
+    assert_eq!(event["spans"][0]["path"], "/api/v1/projects");
+    assert_eq!(event["spans"][0]["duration_ms"], 12);
+}
+
+#[test]
+fn fields_holding_secrets_are_redacted() {
+    for format in [LogFormat::Text, LogFormat::Json] {
+        let logs = logs(format, || {
+            let span = tracing::info_span!("login", password = "hunter2-span");
+            let _entered = span.enter();
+            tracing::info!(
+                api_key = "key-in-field",
+                token = %"token-in-field",
+                passphrase = ?"passphrase-in-field",
+                x_api_key = "header-in-field",
+                user = "alice",
+                "Signing in"
+            );
+        });
+
+        for secret in [
+            "hunter2-span",
+            "key-in-field",
+            "token-in-field",
+            "passphrase-in-field",
+            "header-in-field",
+        ] {
+            assert!(!logs.contains(secret), "{secret} written in {logs}");
+        }
+        assert!(logs.contains(REDACTED));
+        assert!(logs.contains("alice"));
+    }
+}
+
+#[test]
+fn registered_secrets_are_redacted_from_the_messages() {
+    logging::redact("registered-secret-value");
+
+    let logs = logs(LogFormat::Text, || {
+        tracing::error!("Request to https://example.com/?key=registered-secret-value failed");
+        tracing::debug!(url = "https://registered-secret-value@example.com");
+    });
+
+    assert!(!logs.contains("registered-secret-value"));
+    assert_eq!(logs.matches(REDACTED).count(), 2);
+}
+
+#[test]
+fn secrets_are_redacted_from_json_bodies() {
+    let body = r#"{"apiKey":"abc","items":[{"name":"x","client_secret":"def"}],"expires":null}"#;
+
+    let redacted = logging::redact_json(body);
+
+    assert!(!redacted.contains("abc") && !redacted.contains("def"));
+    assert!(redacted.contains(r#""name":"x""#));
+    assert!(redacted.contains(r#""expires":null"#));
+    assert_eq!(logging::redact_json("not json"), "not json");
+}
+
+#[test]
+fn api_keys_never_appear_in_the_traces() {
+    let dir = test_dir("logging-trace");
+    let used_key = "used-api-key-5f0c2d";
+
+    for format in ["text", "json"] {
+        let output = common::cosmo(&dir)
+            .args(["--log-level", "trace", "--log-format", format])
+            .args(["apikey", "--action", "create"])
+            .env("COSMO_API_KEY", used_key)
+            .output()
+            .expect("run cosmo");
+
+        assert!(output.status.success());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        // The request was traced, with its headers and body
+        assert!(stderr.contains("/api/v1/api_key"), "no trace in {stderr}");
+        assert!(
+            !stderr.contains(used_key),
+            "api key in use written in {stderr}"
+        );
+        assert!(
+            !stderr.contains(API_KEY),
+            "api key created written in {stderr}"
+        );
+    }
+}
@@ -0,0 +1,85 @@
+//! `logout --revoke`: the api key is forgotten locally even when the server
+//! fails to revoke it.
+
+mod common;
+
+use std::{path::Path, process::Output};
+
+use common::{test_dir, Server, API_KEY};
+
+/// Configuration file with the api key of the default profile.
+fn save_api_key(dir: &Path) {
+    std::fs::write(
+        dir.join("config"),
+        format!("[default]\napi_key={API_KEY}\n"),
+    )
+    .expect("config file");
+}
+
+fn saved_config(dir: &Path) -> String {
+    std::fs::read_to_string(dir.join("config")).expect("config file")
+}
+
+/// `logout --revoke` with the saved api key, against a server answering the
+/// revocation with `status`.
+fn logout(name: &str, status: u16) -> (Output, String, Server) {
+    let dir = test_dir(name);
+    save_api_key(&dir);
+    let server = Server::with(move |request| {
+        (request.method == "DELETE" && request.path == "/api/v1/api_key")
+            .then(|| (status, r#"{"detail":"revocation failed"}"#.to_string()))
+    });
+
+    let output = common::cosmo_at(&dir, &server.url)
+        .args(["--retries", "0", "logout", "--revoke"])
+        .env_remove("COSMO_API_KEY")
+        .output()
+        .expect("run cosmo");
+    (output, saved_config(&dir), server)
+}
+
+#[test]
+fn revoked_key_is_forgotten() {
+    let (output, config, server) = logout("logout-revoke", 200);
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout).trim(),
+        "Api key revoked and removed from profile default"
+    );
+    assert!(!config.contains(API_KEY), "api key still saved: {config}");
+
+    let requests = server.requests();
+    let revocation = requests
+        .iter()
+        .find(|request| request.method == "DELETE")
+        .expect("revocation request");
+    assert_eq!(revocation.header("x-api-key"), Some(API_KEY));
+}
+
+#[test]
+fn key_is_forgotten_when_the_revocation_fails() {
+    let (output, config, _server) = logout("logout-revoke-failure", 500);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    // The code of the failure, a server error
+    assert_eq!(output.status.code(), Some(5), "stderr: {stderr}");
+    assert!(
+        stderr.contains("Could not revoke the api key, removing it anyway"),
+        "no warning: {stderr}"
+    );
+    assert!(
+        stderr.contains("error revoking the api key"),
+        "no error: {stderr}"
+    );
+    assert!(output.stdout.is_empty());
+    assert!(!config.contains(API_KEY), "api key still saved: {config}");
+}
+
+#[test]
+fn key_is_forgotten_when_the_revocation_is_denied() {
+    let (output, config, _server) = logout("logout-revoke-denied", 403);
+
+    assert_eq!(output.status.code(), Some(13));
+    assert!(!config.contains(API_KEY), "api key still saved: {config}");
+}
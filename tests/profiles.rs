@@ -0,0 +1,64 @@
+//! `profile` and `config` subcommands, answered from the configuration file
+//! without the server.
+
+mod common;
+
+use common::test_dir;
+
+#[test]
+fn config_set_writes_the_section_of_the_profile() {
+    let dir = test_dir("config-set");
+    std::fs::write(
+        dir.join("config"),
+        "[default]\napi_key=key\n\n[profile.staging]\napi_key=staging-key\n",
+    )
+    .expect("config file");
+
+    let output = common::cosmo(&dir)
+        .args(["--profile", "staging", "config", "set", "timeout", "90"])
+        .output()
+        .expect("run cosmo");
+
+    assert!(
+        output.status.success(),
+        "config set failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let config = std::fs::read_to_string(dir.join("config")).expect("config file");
+    assert!(
+        config.ends_with("[profile.staging]\napi_key=staging-key\ntimeout=90\n"),
+        "{config}"
+    );
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(dir.join("config"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+}
+
+#[test]
+fn profile_list_shows_the_default_one() {
+    let dir = test_dir("profile-list");
+    std::fs::write(
+        dir.join("config"),
+        "[default]\nprofile=staging\n\n[profile.staging]\napi_server=https://staging.example.com\n",
+    )
+    .expect("config file");
+
+    let output = common::cosmo(&dir)
+        .args(["--output", "json", "profile", "list"])
+        .output()
+        .expect("run cosmo");
+
+    assert!(output.status.success());
+    let profiles: serde_json::Value = serde_json::from_slice(&output.stdout).expect("json");
+    assert_eq!(profiles[0]["name"], "default");
+    assert_eq!(profiles[0]["default"], false);
+    assert_eq!(profiles[1]["name"], "staging");
+    assert_eq!(profiles[1]["default"], true);
+    assert_eq!(profiles[1]["api_server"], "https://staging.example.com/");
+}
@@ -0,0 +1,85 @@
+//! The single values printed with `--quiet`, relied upon by scripts.
+
+mod common;
+
+use std::{path::Path, process::Output};
+
+use common::{test_dir, API_KEY, ORGANIZATION_ID, PROJECT_ID};
+
+/// Run cosmo quietly with `args` against a fake server.
+fn cosmo(dir: &Path, args: &[&str]) -> Output {
+    common::cosmo(dir)
+        .arg("-q")
+        .args(args)
+        .output()
+        .expect("run cosmo")
+}
+
+fn stdout(output: &Output) -> String {
+    assert!(
+        output.status.success(),
+        "cosmo failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout.clone()).expect("utf-8 stdout")
+}
+
+#[test]
+fn create_prints_only_the_project_id() {
+    let dir = test_dir("create");
+    std::fs::write(dir.join("firmware.bin"), b"firmware").expect("firmware");
+
+    let output = cosmo(
+        &dir,
+        &[
+            "create",
+            "--file",
+            "firmware.bin",
+            "--type",
+            "linux",
+            "--name",
+            "router",
+        ],
+    );
+
+    assert_eq!(stdout(&output), format!("{PROJECT_ID}\n"));
+}
+
+#[test]
+fn apikey_create_prints_only_the_key() {
+    let dir = test_dir("apikey");
+
+    let output = cosmo(&dir, &["apikey", "--action", "create"]);
+
+    assert_eq!(stdout(&output), format!("{API_KEY}\n"));
+}
+
+#[test]
+fn status_prints_only_the_state() {
+    let dir = test_dir("status");
+
+    let output = cosmo(&dir, &["project", "status", "--id", PROJECT_ID]);
+
+    assert_eq!(stdout(&output), "ANALYZING\n");
+}
+
+#[test]
+fn json_output_wins_over_quiet() {
+    let dir = test_dir("json");
+
+    let output = cosmo(&dir, &["-o", "json", "apikey", "--action", "create"]);
+
+    let json: serde_json::Value = serde_json::from_str(&stdout(&output)).expect("json output");
+    assert_eq!(json["apiKey"], API_KEY);
+}
+
+#[test]
+fn errors_go_to_stderr() {
+    let dir = test_dir("error");
+
+    let output = cosmo(&dir, &["project", "status", "--id", ORGANIZATION_ID]);
+
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+    assert!(!output.stderr.is_empty());
+}
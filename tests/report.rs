@@ -0,0 +1,155 @@
+//! HTML report of a project, compared with the golden file
+//! `tests/fixtures/report/report.html`.
+//!
+//! Run with `COSMO_UPDATE_GOLDEN=1` to rewrite the golden file after a
+//! change of the report, and review its diff.
+
+mod common;
+
+use std::path::Path;
+
+use serde_json::{json, Value};
+
+use common::{analysis_response, test_dir, Server, PROJECT_ID};
+
+/// Overview of the project, its name to be escaped.
+fn overview() -> Value {
+    json!({
+        "project": {
+            "name": "Router <R7000> & co",
+            "project_type": "LINUX",
+            "status": "SUCCESS",
+            "score": 62.5
+        },
+        "cve_check": {
+            "severity": {"critical": 1, "high": 0, "medium": 1, "low": 0}
+        },
+        "info": {
+            "arch": "arm",
+            "endianness": "little",
+            "os_name": "OpenWrt",
+            "os_version": "22.03.5",
+            "kernel": "5.10.176",
+            "libc": "musl 1.2.3"
+        }
+    })
+}
+
+/// Findings of the analyses of the report, the crypto one not available.
+fn findings(analysis: &str) -> Option<Value> {
+    let findings = match analysis {
+        "CveCheck" => json!([
+            {
+                "cveid": "CVE-2023-5678",
+                "severity": "medium",
+                "summary": "Excessive time spent in DH key generation",
+                "vendor": "openssl",
+                "product": "openssl",
+                "version": "3.0.11",
+                "vector": "NETWORK",
+                "cvss": {"v3": {"score": 5.3}}
+            },
+            {
+                "cveid": "CVE-2022-48174",
+                "severity": "critical",
+                "summary": "Stack overflow in ash",
+                "vendor": "busybox",
+                "product": "busybox",
+                "version": "1.35.0",
+                "vector": "NETWORK",
+                "cvss": {"v3": {"score": 9.8}}
+            }
+        ]),
+        "Hardening" => json!([
+            {
+                "filename": "/usr/sbin/httpd",
+                "type": "ELF",
+                "score": 2,
+                "compiler": null,
+                "stripped": true,
+                "suid": false,
+                "execstack": true,
+                "canary": false,
+                "fortify": false,
+                "nx": true,
+                "pie": "no",
+                "relro": "partial"
+            }
+        ]),
+        "PasswordHash" => json!([]),
+        _ => return None,
+    };
+    Some(findings)
+}
+
+/// The report with the version of cosmo and the date of the footer, and
+/// the ids of the requests, replaced: the only parts changing between runs.
+fn normalize(report: &str) -> String {
+    let mut lines: Vec<String> = vec![];
+    for line in report.lines() {
+        let mut line = match line.strip_prefix("<footer>Generated by ") {
+            Some(footer) => {
+                let (name, footer) = footer.split_once(' ').expect("footer name");
+                let (_, project) = footer.split_once(" for project ").expect("footer project");
+                format!("<footer>Generated by {name} VERSION on DATE for project {project}")
+            }
+            None => line.to_string(),
+        };
+        if let Some(start) = line.find("request id ") {
+            let start = start + "request id ".len();
+            // A uuid
+            line.replace_range(start..start + 36, "REQUEST_ID");
+        }
+        lines.push(line);
+    }
+    lines.join("\n") + "\n"
+}
+
+#[test]
+fn html_report_matches_the_golden_file() {
+    let dir = test_dir("report-html");
+    let server = Server::with(|request| {
+        if request.path == format!("/api/v1/projects/{PROJECT_ID}/overview") {
+            return Some((200, overview().to_string()));
+        }
+        if request.path == format!("/api/v1/projects/{PROJECT_ID}/analysis/Crypto") {
+            return Some((500, r#"{"detail":"analysis failed"}"#.to_string()));
+        }
+        analysis_response(request, findings)
+    });
+
+    let output = common::cosmo_at(&dir, &server.url)
+        .args([
+            "report",
+            "--id",
+            PROJECT_ID,
+            "--format",
+            "html",
+            "--analyses",
+            "cve-check,hardening,password-hash,crypto",
+            "-f",
+            "report.html",
+        ])
+        .output()
+        .expect("run cosmo");
+    assert!(
+        output.status.success(),
+        "cosmo failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let report = std::fs::read_to_string(dir.join("report.html")).expect("report");
+    let report = normalize(&report);
+    let golden = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/report/report.html");
+    if std::env::var_os("COSMO_UPDATE_GOLDEN").is_some() {
+        std::fs::create_dir_all(golden.parent().unwrap()).expect("fixtures dir");
+        std::fs::write(&golden, &report).expect("golden file");
+    }
+
+    let expected = std::fs::read_to_string(&golden).expect("golden file");
+    // Line by line, for a readable failure
+    for (number, (line, expected)) in report.lines().zip(expected.lines()).enumerate() {
+        assert_eq!(line, expected, "line {} of the report", number + 1);
+    }
+    assert_eq!(report.lines().count(), expected.lines().count());
+}
@@ -0,0 +1,71 @@
+//! `self-update`: a binary is never installed unless signed with the key of
+//! the releases, which the builds of the tests don't have.
+
+mod common;
+
+use std::process::Output;
+
+use common::{test_dir, Server};
+
+/// Server announcing a newer release, with an unsigned binary for this
+/// platform.
+fn release_server() -> Server {
+    Server::with(|request| match request.path.as_str() {
+        "/api/updates_check" => {
+            let platform = format!("{}-{}", std::env::consts::OS, std::env::consts::ARCH);
+            let latest = serde_json::json!({
+                "version": "99.0.0",
+                "changelog": "- faster uploads",
+                "artifacts": [{
+                    "platform": platform,
+                    "url": "https://releases.example.com/cosmo",
+                    "sha256": "0000000000000000000000000000000000000000000000000000000000000000"
+                }]
+            });
+            Some((200, latest.to_string()))
+        }
+        _ => None,
+    })
+}
+
+fn self_update(name: &str, args: &[&str]) -> (Output, Server) {
+    let dir = test_dir(name);
+    let server = release_server();
+    let output = common::cosmo_at(&dir, &server.url)
+        .arg("self-update")
+        .args(args)
+        .output()
+        .expect("run cosmo");
+    (output, server)
+}
+
+#[test]
+fn update_is_refused_without_the_release_key() {
+    let (output, server) = self_update("self-update", &[]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert_eq!(output.status.code(), Some(1), "stderr: {stderr}");
+    assert!(
+        stderr.contains("no release key to verify the binaries with"),
+        "no error: {stderr}"
+    );
+    // Nothing downloaded
+    assert!(server
+        .requests()
+        .iter()
+        .all(|request| request.path == "/api/updates_check"));
+}
+
+#[test]
+fn check_works_without_the_release_key() {
+    let (output, _server) = self_update("self-update-check", &["--check"]);
+
+    assert_eq!(
+        output.status.code(),
+        Some(0),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("cosmo 99.0.0 is available"), "{stdout}");
+}
@@ -0,0 +1,184 @@
+//! Firmware uploads, streamed from the disk and compressed.
+
+mod common;
+
+use std::{
+    fs::File,
+    io::Read,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use flate2::read::GzDecoder;
+
+use common::{test_dir, Request, Server, ORGANIZATION_ID, PROJECT_ID};
+
+/// Size of the uploaded firmware, sparse on the disk
+const FIRMWARE_SIZE: u64 = 128 * 1024 * 1024;
+
+/// Peak resident memory of the process `pid`, in bytes.
+#[cfg(target_os = "linux")]
+fn peak_rss(pid: u32) -> u64 {
+    let status = std::fs::read_to_string(format!("/proc/{pid}/status")).expect("process status");
+    let kb: u64 = status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmHWM:"))
+        .and_then(|value| value.trim().strip_suffix("kB"))
+        .and_then(|value| value.trim().parse().ok())
+        .expect("VmHWM");
+    kb * 1024
+}
+
+// The memory of cosmo is read from /proc
+#[cfg(target_os = "linux")]
+#[test]
+fn uploads_are_not_buffered_in_memory() {
+    let dir = test_dir("upload-stream");
+    File::create(dir.join("firmware.bin"))
+        .and_then(|file| file.set_len(FIRMWARE_SIZE))
+        .expect("firmware");
+
+    // Measured once the whole body is received, cosmo waiting for the answer
+    let pid = Arc::new(AtomicU32::new(0));
+    let peak = Arc::new(Mutex::new(None));
+    let server = {
+        let pid = pid.clone();
+        let peak = peak.clone();
+        Server::with(move |request| {
+            if request.path == format!("/api/v1/organizations/{ORGANIZATION_ID}/projects") {
+                assert!(request.body.len() as u64 > FIRMWARE_SIZE);
+                *peak.lock().unwrap() = Some(peak_rss(pid.load(Ordering::SeqCst)));
+            }
+            None
+        })
+    };
+
+    let child = common::cosmo_at(&dir, &server.url)
+        .args([
+            "-q",
+            "create",
+            "--file",
+            "firmware.bin",
+            "--type",
+            "linux",
+            "--name",
+            "router",
+        ])
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .expect("run cosmo");
+    pid.store(child.id(), Ordering::SeqCst);
+    let output = child.wait_with_output().expect("run cosmo");
+
+    assert!(
+        output.status.success(),
+        "cosmo failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        format!("{PROJECT_ID}\n")
+    );
+    let peak = peak.lock().unwrap().expect("upload received");
+    assert!(
+        peak < FIRMWARE_SIZE / 2,
+        "peak memory of {peak} bytes uploading {FIRMWARE_SIZE} bytes"
+    );
+}
+
+/// Headers and content of the part of the multipart `request` with the
+/// file.
+fn file_part(request: &Request) -> (String, Vec<u8>) {
+    let boundary = request
+        .header("content-type")
+        .and_then(|value| value.split_once("boundary="))
+        .map(|(_, boundary)| format!("\r\n--{}", boundary.trim_matches('"')))
+        .expect("multipart boundary");
+    // The body starts with the boundary, without the line break
+    let body = [b"\r\n".as_slice(), &request.body].concat();
+
+    split(&body, boundary.as_bytes())
+        .into_iter()
+        .find_map(|part| {
+            let end = part.windows(4).position(|w| w == b"\r\n\r\n")?;
+            let headers = String::from_utf8_lossy(&part[..end]).to_lowercase();
+            headers
+                .contains("filename=")
+                .then(|| (headers, part[end + 4..].to_vec()))
+        })
+        .expect("file part")
+}
+
+/// Parts of `data` between the occurrences of `separator`.
+fn split<'a>(data: &'a [u8], separator: &[u8]) -> Vec<&'a [u8]> {
+    let mut parts = vec![];
+    let mut start = 0;
+    let mut i = 0;
+    while i + separator.len() <= data.len() {
+        if &data[i..i + separator.len()] == separator {
+            parts.push(&data[start..i]);
+            i += separator.len();
+            start = i;
+        } else {
+            i += 1;
+        }
+    }
+    parts.push(&data[start..]);
+    parts
+}
+
+#[test]
+fn compressed_uploads_decompress_to_the_firmware() {
+    let dir = test_dir("upload-compress");
+    // Compressible, not trivially
+    let firmware: Vec<u8> = (0..4 * 1024 * 1024u32)
+        .map(|i| (i % 251) as u8 ^ (i / 4096) as u8)
+        .collect();
+    std::fs::write(dir.join("firmware.bin"), &firmware).expect("firmware");
+
+    let received = Arc::new(Mutex::new(None));
+    let server = {
+        let received = received.clone();
+        Server::with(move |request| {
+            if request.path == format!("/api/v1/organizations/{ORGANIZATION_ID}/projects") {
+                *received.lock().unwrap() = Some(file_part(request));
+            }
+            None
+        })
+    };
+
+    let output = common::cosmo_at(&dir, &server.url)
+        .args([
+            "-q",
+            "create",
+            "--file",
+            "firmware.bin",
+            "--type",
+            "linux",
+            "--name",
+            "router",
+            "--compress",
+        ])
+        .output()
+        .expect("run cosmo");
+
+    assert!(
+        output.status.success(),
+        "cosmo failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let (headers, compressed) = received.lock().unwrap().take().expect("upload received");
+    assert!(
+        headers.contains("content-encoding: gzip"),
+        "headers: {headers}"
+    );
+    assert!(compressed.len() < firmware.len());
+    let mut decompressed = vec![];
+    GzDecoder::new(compressed.as_slice())
+        .read_to_end(&mut decompressed)
+        .expect("gzip part");
+    assert!(decompressed == firmware, "decompressed part differs");
+}